@@ -193,12 +193,23 @@ impl PeakNative {
                 peak_theme::ThemeTone::Light,
             ),
             active_downloads: std::collections::HashSet::new(),
-            assistant: None,
+            #[cfg(feature = "native")]
+            models: crate::app::assistant_models(),
+            loaded_model_id: None,
+            #[cfg(feature = "native")]
+            assistant_tools: crate::app::assistant_tools(),
+            #[cfg(feature = "native")]
+            permission_requests: Default::default(),
             active_model_id: None,
+            remote_model: crate::app::remote_model(),
             pending_chat: None,
+            speak_reply: false,
             ai_input_text: String::new(),
         };
 
+        // The server in the `[llm]` settings is the model to chat with
+        shell.inspector.active_model = shell.remote_model.clone();
+
         // --- Register Modular Apps ---
 
         // NOTE: Browser removed - using Firefox via `opener::open` instead
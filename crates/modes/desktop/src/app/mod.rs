@@ -20,6 +20,7 @@ pub mod state;
 mod update;
 mod view;
 mod view_desktop;
+mod voice;
 mod window_handling;
 
 // Re-export public types
@@ -104,9 +105,24 @@ pub struct PeakNative {
     pub scanned_apps: Vec<MediaItem>,
     pub tokens: peak_theme::ThemeTokens,
     pub active_downloads: std::collections::HashSet<String>,
-    pub assistant: Option<peak_intelligence::brain::Assistant>,
+    /// Runs the active model, restarting it when it crashes
+    #[cfg(feature = "native")]
+    pub models: peak_intelligence::brain::Manager,
+    /// The model handed to `models`, once it booted
+    pub loaded_model_id: Option<String>,
+    /// Tools the assistant may call, shared by every reply
+    #[cfg(feature = "native")]
+    pub assistant_tools: std::sync::Arc<peak_intelligence::mcp::Registry>,
+    /// Tool calls waiting for the user to allow them, oldest first
+    #[cfg(feature = "native")]
+    pub permission_requests: std::collections::VecDeque<peak_intelligence::policy::Confirmation>,
     pub active_model_id: Option<String>,
+    /// The model of the server set up in the `[llm]` settings, which answers
+    /// instead of a local one
+    pub remote_model: Option<String>,
     pub pending_chat: Option<String>,
+    /// Speak the reply to `pending_chat`, which was asked out loud
+    pub speak_reply: bool,
     pub ai_input_text: String,
 }
 
@@ -161,71 +177,189 @@ impl PeakNative {
 
         // 2. Chat Reply Subscription
         if let Some(prompt) = &self.pending_chat {
-            if let Some(assistant) = &self.assistant {
-                let history = self.inspector.chat_history.clone();
-                let system_prompt = self.get_ai_system_prompt();
-                subs.push(reply_subscription(
-                    assistant.clone(),
-                    system_prompt,
-                    history,
-                    prompt.clone(),
-                ));
-            } else if let Some(model_id) = &self.active_model_id {
-                subs.push(boot_subscription(model_id.clone()));
+            match &self.active_model_id {
+                #[cfg(feature = "native")]
+                // A server set up in the `[llm]` settings needs no local model
+                active
+                    if self.remote_model.is_some()
+                        || active.is_some() && self.loaded_model_id == *active =>
+                {
+                    let history = self.inspector.chat_history.clone();
+                    let summary = self.inspector.summary.clone();
+                    let system_prompt = self.get_ai_system_prompt();
+                    subs.push(reply_subscription(
+                        self.models.clone(),
+                        self.assistant_tools.clone(),
+                        system_prompt,
+                        history,
+                        summary,
+                        prompt.clone(),
+                    ));
+                }
+                Some(model_id) => subs.push(boot_subscription(
+                    #[cfg(feature = "native")]
+                    self.models.clone(),
+                    model_id.clone(),
+                )),
+                None => {}
             }
         }
 
+        // 3. Crashes and safety shutdowns of the running model
+        #[cfg(feature = "native")]
+        subs.push(models_subscription(self.models.clone()));
+
         iced::Subscription::batch(subs)
     }
 }
 
 // --- Subscription Data Wrappers ---
 
+#[cfg(feature = "native")]
 struct ChatSubscriptionData {
     id: String,
-    assistant: peak_intelligence::brain::Assistant,
+    models: peak_intelligence::brain::Manager,
+    tools: std::sync::Arc<peak_intelligence::mcp::Registry>,
     system_prompt: String,
     history: Vec<(String, String)>,
+    summary: Option<(usize, String)>,
     prompt: String,
 }
 
+#[cfg(feature = "native")]
 impl std::hash::Hash for ChatSubscriptionData {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.id.hash(state);
     }
 }
 
+#[cfg(feature = "native")]
 impl PartialEq for ChatSubscriptionData {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
     }
 }
 
+#[cfg(feature = "native")]
 impl Eq for ChatSubscriptionData {}
 
+#[cfg(feature = "native")]
 fn chat_stream(data: &ChatSubscriptionData) -> impl iced::futures::Stream<Item = Message> {
     use iced::futures::StreamExt;
     use peak_intelligence::brain::assistant::Message as LLMMessage;
 
-    let assistant = data.assistant.clone();
+    let models = data.models.clone();
+    let tools = data.tools.clone();
     let system_prompt = data.system_prompt.clone();
-    let history = data.history.clone();
+    let mut history = data.history.clone();
+    let summary = data.summary.clone();
     let prompt = data.prompt.clone();
 
     iced::stream::channel(
         100,
         move |mut output: iced::futures::channel::mpsc::Sender<Message>| async move {
-            let messages = history
-                .into_iter()
-                .map(|(role, content)| match role.as_str() {
-                    "user" => LLMMessage::User(content),
-                    "assistant" => LLMMessage::Assistant(content),
-                    _ => LLMMessage::System(content),
+            use peak_intelligence::brain::assistant::Assistant;
+            use peak_intelligence::brain::manager::Role;
+            use peak_intelligence::brain::{chat, context};
+
+            // A local model is restarted by the manager if it crashed or sat idle
+            let assistant = match Assistant::configured().await {
+                Some(assistant) => Ok(assistant),
+                None => models.get(Role::Answer).await,
+            };
+
+            let assistant = match assistant {
+                Ok(assistant) => assistant,
+                Err(e) => {
+                    let _ = output.send(Message::AssistantBooted(Err(e))).await;
+                    return;
+                }
+            };
+
+            // The Inspector already shows the prompt; it is sent separately
+            if history.last() == Some(&("user".to_string(), prompt.clone())) {
+                let _ = history.pop();
+            }
+
+            // Excerpts of the documents chosen in Settings, if any match,
+            // quoted right before the prompt
+            let documents = chat::documents(&assistant, &prompt).await;
+
+            let system_prompt = if documents.is_some() {
+                format!("{}\n\n{}", system_prompt, chat::CITATIONS)
+            } else {
+                system_prompt
+            };
+
+            let (covered, mut summary) = match summary {
+                Some((covered, summary)) => (covered, Some(summary)),
+                None => (0, None),
+            };
+
+            let entries: Vec<(usize, LLMMessage)> = history
+                .iter()
+                .enumerate()
+                .skip(covered)
+                // Tool activity is only shown; the model sees what the tools returned
+                .filter(|(_, (role, _))| role != "tool")
+                .map(|(i, (role, content))| {
+                    let message = match role.as_str() {
+                        "user" => LLMMessage::User(content.clone()),
+                        "assistant" => LLMMessage::Assistant(content.clone()),
+                        "tool_result" => LLMMessage::tool_output(content),
+                        _ => LLMMessage::System(content.clone()),
+                    };
+
+                    (i, message)
                 })
                 .collect();
 
-            let append = vec![LLMMessage::User(prompt)];
-            let mut stream = Box::pin(assistant.reply(system_prompt, messages, append));
+            let append: Vec<_> = documents
+                .into_iter()
+                .chain([LLMMessage::User(prompt)])
+                .collect();
+
+            // The prompt is measured too, as the one turn that is always kept
+            let turns: Vec<_> = entries
+                .iter()
+                .map(|(_, message)| vec![message.clone()])
+                .chain([append.clone()])
+                .collect();
+
+            let mut folded = 0;
+
+            match context::fit(&assistant, &system_prompt, summary.as_deref(), &turns).await {
+                Ok(context::Fit {
+                    folded: n,
+                    summary: Some(content),
+                }) => {
+                    let covered = entries.get(n).map_or(history.len(), |(i, _)| *i);
+
+                    let _ = output
+                        .send(Message::AssistantSummarized(covered, content.clone()))
+                        .await;
+
+                    summary = Some(content);
+                    folded = n;
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("Could not summarize the conversation: {}", e),
+            }
+
+            let messages = summary
+                .as_deref()
+                .map(context::summary_message)
+                .into_iter()
+                .chain(entries.into_iter().skip(folded).map(|(_, message)| message))
+                .collect();
+
+            let mut stream = {
+                let (toolbox, requests) = assistant_toolbox(tools.clone(), assistant.name());
+
+                forward_confirmations(requests, tools.policy().cloned(), output.clone());
+
+                Box::pin(assistant.reply_with_tools(system_prompt, messages, append, toolbox))
+            };
 
             while let Some((reply, token)) = stream.next().await {
                 let _ = output.send(Message::AssistantReply(reply, token)).await;
@@ -240,17 +374,96 @@ fn chat_stream(data: &ChatSubscriptionData) -> impl iced::futures::Stream<Item =
     )
 }
 
+/// The built-in tools the Inspector assistant may use on its own.
+///
+/// Built once, so terminals, remembered grants and the audit log are shared
+/// by every reply.
+#[cfg(feature = "native")]
+pub(crate) fn assistant_tools() -> std::sync::Arc<peak_intelligence::mcp::Registry> {
+    use peak_intelligence::audit;
+    use peak_intelligence::policy::Policy;
+    use peak_intelligence::terminal::TerminalManager;
+    use peak_intelligence::tools::builtin;
+    use std::sync::Arc;
+
+    let mut registry = builtin::registry(Arc::new(TerminalManager::new()));
+    registry.set_policy(Arc::new(Policy::load()));
+
+    match audit::Log::open_default() {
+        Ok(log) => registry.set_audit(Arc::new(log)),
+        Err(e) => log::warn!("Audit log unavailable: {}", e),
+    }
+
+    Arc::new(registry)
+}
+
+/// The tools of one reply, called on behalf of `model`, and the
+/// notifications they send, including their permission prompts.
+#[cfg(feature = "native")]
+fn assistant_toolbox(
+    tools: std::sync::Arc<peak_intelligence::mcp::Registry>,
+    model: &str,
+) -> (
+    peak_intelligence::agent::Toolbox,
+    tokio::sync::mpsc::Receiver<String>,
+) {
+    use peak_intelligence::agent::Toolbox;
+    use peak_intelligence::mcp::Context;
+    use peak_intelligence::policy::Caller;
+
+    let (notifications, requests) = tokio::sync::mpsc::channel(16);
+
+    // "Allow this session" lasts until the desktop restarts
+    let context = Context::new(notifications).with_caller(Caller {
+        app: Some("desktop".to_string()),
+        model: Some(model.to_string()),
+        session: Some(format!("desktop-{}", std::process::id())),
+    });
+
+    (Toolbox::new(tools, context), requests)
+}
+
+/// Shows the permission prompts of a reply's tools until the reply is done.
+#[cfg(feature = "native")]
+fn forward_confirmations(
+    mut requests: tokio::sync::mpsc::Receiver<String>,
+    policy: Option<std::sync::Arc<peak_intelligence::policy::Policy>>,
+    mut output: iced::futures::channel::mpsc::Sender<Message>,
+) {
+    use peak_intelligence::policy::Confirmation;
+
+    // Other notifications, e.g. terminal output, are drained so tools never block
+    tokio::spawn(async move {
+        while let Some(request) = requests.recv().await {
+            let Some(policy) = &policy else {
+                continue;
+            };
+
+            if let Some(confirmation) = Confirmation::parse(&request, policy) {
+                let _ = output
+                    .send(Message::PermissionRequested(confirmation))
+                    .await;
+            }
+        }
+    });
+}
+
+#[cfg(feature = "native")]
 fn reply_subscription(
-    assistant: peak_intelligence::brain::Assistant,
+    models: peak_intelligence::brain::Manager,
+    tools: std::sync::Arc<peak_intelligence::mcp::Registry>,
     system_prompt: String,
     history: Vec<(String, String)>,
+    summary: Option<(usize, String)>,
     prompt: String,
 ) -> iced::Subscription<Message> {
     let data = ChatSubscriptionData {
         id: format!("chat-{}", prompt),
-        assistant,
+        models,
+        tools,
         system_prompt,
         history,
+        summary,
         prompt,
     };
 
@@ -327,31 +540,17 @@ fn download_stream(data: &DownloadSubscriptionData) -> impl iced::futures::Strea
                 return;
             };
 
-            // Pick best available quantization (Q4_K_M is a good default for Apple Silicon)
-            let file = files.values().flatten().find(|f| {
-                f.name.contains("Q4_K_M")
-                    || f.name.contains("Q4_0")
-                    || f.name.contains("block_medium")
-            });
-
-            let file = match file {
-                Some(f) => f.clone(),
-                None => {
-                    // Fallback to first file
-                    if let Some(first) = files.values().flatten().next() {
-                        first.clone()
-                    } else {
-                        let _ = output
-                            .send(Message::Settings(
-                                peak_apps::settings::SettingsMessage::ModelDownloadFailed(
-                                    id.clone(),
-                                    "No GGUF file found".into(),
-                                ),
-                            ))
-                            .await;
-                        return;
-                    }
-                }
+            // Pick the quantization that fits this machine best
+            let Some(file) = pick_file(&files) else {
+                let _ = output
+                    .send(Message::Settings(
+                        peak_apps::settings::SettingsMessage::ModelDownloadFailed(
+                            id.clone(),
+                            "No GGUF file found".into(),
+                        ),
+                    ))
+                    .await;
+                return;
             };
 
             let directory = peak_intelligence::brain::model::Directory::default();
@@ -447,6 +646,36 @@ fn download_stream(data: &DownloadSubscriptionData) -> impl iced::futures::Strea
     )
 }
 
+/// The file of a model that fits this machine best. The first part of a
+/// split model stands for all of its parts.
+fn pick_file(
+    files: &peak_intelligence::brain::model::Files,
+) -> Option<peak_intelligence::brain::model::File> {
+    let candidates = files
+        .values()
+        .flatten()
+        .filter(|file| !file.name.contains("-of-") || file.name.contains("-00001-of-"))
+        .cloned();
+
+    #[cfg(feature = "native")]
+    {
+        use peak_intelligence::brain::assistant::Backend;
+        use peak_intelligence::brain::hardware::Hardware;
+
+        Hardware::detect(Backend::Cpu)
+            .recommend(candidates)
+            .into_iter()
+            .next()
+            .map(|recommendation| recommendation.file)
+    }
+
+    #[cfg(not(feature = "native"))]
+    {
+        let mut candidates = candidates;
+        candidates.next()
+    }
+}
+
 fn download_model_subscription(id: String) -> iced::Subscription<Message> {
     let data = DownloadSubscriptionData { id: id.clone() };
     iced::Subscription::run_with(data, download_stream)
@@ -482,6 +711,8 @@ impl PeakNative {
 
 struct BootSubscriptionData {
     id: String,
+    #[cfg(feature = "native")]
+    models: peak_intelligence::brain::Manager,
     model_id: String,
 }
 
@@ -499,16 +730,18 @@ impl PartialEq for BootSubscriptionData {
 
 impl Eq for BootSubscriptionData {}
 
+#[cfg(feature = "native")]
 fn boot_stream(data: &BootSubscriptionData) -> impl iced::futures::Stream<Item = Message> {
-    use iced::futures::StreamExt;
+    use peak_intelligence::brain::manager::Role;
     use peak_intelligence::brain::model::{File, Model};
+    let models = data.models.clone();
     let model_id = data.model_id.clone();
 
     iced::stream::channel(
         100,
         move |mut output: iced::futures::channel::mpsc::Sender<Message>| async move {
             // 1. Resolve Model/File (Simplified version of download resolution)
-            let Ok(models) = Model::search(model_id.clone()).await else {
+            let Ok(models_found) = Model::search(model_id.clone()).await else {
                 let _ = output
                     .send(Message::AssistantBooted(Err(
                         peak_intelligence::brain::Error::DockerFailed("Model Search Failed"),
@@ -516,7 +749,7 @@ fn boot_stream(data: &BootSubscriptionData) -> impl iced::futures::Stream<Item =
                     .await;
                 return;
             };
-            let Some(model) = models.first() else {
+            let Some(model) = models_found.first() else {
                 let _ = output
                     .send(Message::AssistantBooted(Err(
                         peak_intelligence::brain::Error::DockerFailed("Model Not Found"),
@@ -534,62 +767,124 @@ fn boot_stream(data: &BootSubscriptionData) -> impl iced::futures::Stream<Item =
             };
 
             // Pick best file (same logic as download)
-            let file = files.values().flatten().find(|f| {
-                f.name.contains("Q4_K_M")
-                    || f.name.contains("Q4_0")
-                    || f.name.contains("block_medium")
-            });
-            let file = match file {
-                Some(f) => f.clone(),
-                None => {
-                    if let Some(first) = files.values().flatten().next() {
-                        first.clone()
-                    } else {
-                        let _ = output
-                            .send(Message::AssistantBooted(Err(
-                                peak_intelligence::brain::Error::DockerFailed("No File Found"),
-                            )))
-                            .await;
-                        return;
-                    }
-                }
+            let Some(file) = pick_file(&files) else {
+                let _ = output
+                    .send(Message::AssistantBooted(Err(
+                        peak_intelligence::brain::Error::DockerFailed("No File Found"),
+                    )))
+                    .await;
+                return;
             };
 
-            let directory = peak_intelligence::brain::model::Directory::default();
-            let backend = peak_intelligence::brain::assistant::Backend::Cpu;
-
-            let mut straw = Box::pin(peak_intelligence::brain::Assistant::boot(
-                directory, file, backend,
-            ));
-
-            while let Some(event) = straw.next().await {
-                match event {
-                    peak_intelligence::brain::assistant::BootEvent::Progressed { .. } => {}
-                    peak_intelligence::brain::assistant::BootEvent::Logged(_) => {}
-                }
-            }
+            // Swaps out the previous model, if any
+            let result = models.load(Role::Answer, file).await;
 
-            // Result should be the Assistant
-            match straw.await {
-                Ok(assistant) => {
-                    let _ = output.send(Message::AssistantBooted(Ok(assistant))).await;
-                }
-                Err(e) => {
-                    let _ = output.send(Message::AssistantBooted(Err(e))).await;
-                }
-            }
+            let _ = output
+                .send(Message::AssistantBooted(result.map(|_| model_id)))
+                .await;
         },
     )
 }
 
-fn boot_subscription(model_id: String) -> iced::Subscription<Message> {
+#[cfg(not(feature = "native"))]
+fn boot_stream(_data: &BootSubscriptionData) -> impl iced::futures::Stream<Item = Message> {
+    iced::futures::stream::once(async {
+        Message::AssistantBooted(Err(peak_intelligence::brain::Error::NoExecutorAvailable))
+    })
+}
+
+fn boot_subscription(
+    #[cfg(feature = "native")] models: peak_intelligence::brain::Manager,
+    model_id: String,
+) -> iced::Subscription<Message> {
     let data = BootSubscriptionData {
         id: format!("boot-{}", model_id),
+        #[cfg(feature = "native")]
+        models,
         model_id,
     };
     iced::Subscription::run_with(data, boot_stream)
 }
 
+#[cfg(feature = "native")]
+struct ModelsSubscriptionData(peak_intelligence::brain::Manager);
+
+#[cfg(feature = "native")]
+impl std::hash::Hash for ModelsSubscriptionData {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        "models".hash(state);
+    }
+}
+
+#[cfg(feature = "native")]
+impl PartialEq for ModelsSubscriptionData {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "native")]
+impl Eq for ModelsSubscriptionData {}
+
+#[cfg(feature = "native")]
+fn models_stream(data: &ModelsSubscriptionData) -> impl iced::futures::Stream<Item = Message> {
+    use tokio::sync::broadcast::error::RecvError;
+
+    let mut events = data.0.subscribe();
+
+    iced::stream::channel(
+        100,
+        move |mut output: iced::futures::channel::mpsc::Sender<Message>| async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        let _ = output.send(Message::Models(event)).await;
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        },
+    )
+}
+
+#[cfg(feature = "native")]
+fn models_subscription(models: peak_intelligence::brain::Manager) -> iced::Subscription<Message> {
+    iced::Subscription::run_with(ModelsSubscriptionData(models), models_stream)
+}
+
+/// The model of the server in the `[llm]` section of the settings, if any.
+#[cfg(feature = "native")]
+pub(crate) fn remote_model() -> Option<String> {
+    match peak_intelligence::brain::Settings::fetch() {
+        Ok(settings) => settings.llm.map(|llm| llm.model),
+        Err(_) => None,
+    }
+}
+
+#[cfg(not(feature = "native"))]
+pub(crate) fn remote_model() -> Option<String> {
+    None
+}
+
+/// The manager of the assistant's models.
+///
+/// Must be called from within the app, which runs in a Tokio runtime.
+#[cfg(feature = "native")]
+pub(crate) fn assistant_models() -> peak_intelligence::brain::Manager {
+    use peak_intelligence::brain::assistant::Backend;
+    use peak_intelligence::brain::manager::{Llama, Manager, Settings};
+    use peak_intelligence::brain::model::Directory;
+
+    Manager::new(
+        Llama {
+            directory: Directory::default(),
+            backend: Backend::Cpu,
+        },
+        Settings::default(),
+    )
+}
+
 impl PeakApp for PeakNative {
     type Message = Message;
     type Flags = PeakNativeFlags;
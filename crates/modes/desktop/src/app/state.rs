@@ -71,11 +71,20 @@ pub enum Message {
     SubmitLogin,
     FactoryReset,
     ToggleAppGrid,
-    AssistantBooted(Result<peak_intelligence::brain::Assistant, peak_intelligence::brain::Error>),
+    /// The active model is running, or could not be started
+    AssistantBooted(Result<String, peak_intelligence::brain::Error>),
+    #[cfg(feature = "native")]
+    Models(peak_intelligence::brain::manager::Event),
+    /// A tool call of the assistant is waiting for the user to allow it
+    #[cfg(feature = "native")]
+    PermissionRequested(peak_intelligence::policy::Confirmation),
+    #[cfg(feature = "native")]
+    PermissionAnswered(peak_intelligence::policy::Decision),
     AssistantReply(
         peak_intelligence::brain::assistant::Reply,
         peak_intelligence::brain::assistant::Token,
     ),
+    AssistantSummarized(usize, String),
     AssistantFinished,
     ConsoleCategory(peak_shell::console::category_bar::CategoryBarMessage),
     ConsoleGame(peak_shell::console::game_rail::GameRailMessage),
@@ -742,6 +742,18 @@ impl PeakNative {
                     }
                 }
 
+                if let crate::components::inspector::InspectorMessage::Voice(
+                    peak_intelligence::voice::hands_free::Event::Command(intent),
+                ) = &msg
+                {
+                    let command = self.handle_intent(intent.clone());
+
+                    return Task::batch([
+                        command,
+                        self.inspector.update(msg).map(Message::Inspector),
+                    ]);
+                }
+
                 self.inspector.update(msg).map(Message::Inspector)
             }
             Message::ToggleInspector => {
@@ -805,6 +817,40 @@ impl PeakNative {
                     peak_apps::settings::SettingsMessage::ModelDownloadFailed(id, _) => {
                         self.active_downloads.remove(id);
                     }
+                    #[cfg(feature = "native")]
+                    peak_apps::settings::SettingsMessage::ModelDownloadComplete(_) => {
+                        return Task::batch(vec![
+                            self.forward_to_app(
+                                AppId::Settings,
+                                Message::Settings(settings_msg.clone()),
+                            ),
+                            Task::perform(load_model_specs(), |specs| {
+                                Message::Settings(
+                                    peak_apps::settings::SettingsMessage::ModelSpecsLoaded(specs),
+                                )
+                            }),
+                        ]);
+                    }
+                    peak_apps::settings::SettingsMessage::ModelRemove(id) => {
+                        // The desktop owns the active model; stop running it first
+                        if self.active_model_id.as_ref() == Some(id) {
+                            self.active_model_id = None;
+                            self.loaded_model_id = None;
+
+                            #[cfg(feature = "native")]
+                            self.models
+                                .unload(peak_intelligence::brain::manager::Role::Answer);
+                        }
+
+                        #[cfg(feature = "native")]
+                        return Task::batch(vec![
+                            self.forward_to_app(
+                                AppId::Settings,
+                                Message::Settings(settings_msg.clone()),
+                            ),
+                            Task::future(remove_model(id.clone())).discard(),
+                        ]);
+                    }
                     peak_apps::settings::SettingsMessage::ModelActivate(id) => {
                         // Set active model in main state
                         self.active_model_id = Some(id.clone());
@@ -845,6 +891,85 @@ impl PeakNative {
                     peak_apps::settings::SettingsMessage::ShellStyleChanged(style) => {
                         self.shell_style = *style;
                     }
+                    #[cfg(feature = "native")]
+                    peak_apps::settings::SettingsMessage::TabChanged(
+                        peak_apps::settings::SettingsTab::Privacy,
+                    )
+                    | peak_apps::settings::SettingsMessage::AuditRefresh => {
+                        return Task::batch(vec![
+                            self.forward_to_app(
+                                AppId::Settings,
+                                Message::Settings(settings_msg.clone()),
+                            ),
+                            Task::perform(load_audit_log(), |(entries, integrity)| {
+                                Message::Settings(
+                                    peak_apps::settings::SettingsMessage::AuditLoaded(
+                                        entries, integrity,
+                                    ),
+                                )
+                            }),
+                        ]);
+                    }
+                    #[cfg(feature = "native")]
+                    peak_apps::settings::SettingsMessage::TabChanged(
+                        peak_apps::settings::SettingsTab::Intelligence,
+                    )
+                    | peak_apps::settings::SettingsMessage::DocumentFolderPicked(_)
+                    | peak_apps::settings::SettingsMessage::DocumentFolderRemove(_) => {
+                        use peak_apps::settings::SettingsMessage;
+                        use std::path::PathBuf;
+
+                        let models = self.models.clone();
+
+                        let indexing = match settings_msg.clone() {
+                            SettingsMessage::DocumentFolderPicked(Some(folder)) => Task::perform(
+                                index_documents(Some(models), move |index| {
+                                    index.add_folder(folder)
+                                }),
+                                |(folders, status)| {
+                                    Message::Settings(SettingsMessage::DocumentsIndexed(
+                                        folders, status,
+                                    ))
+                                },
+                            ),
+                            SettingsMessage::DocumentFolderRemove(folder) => Task::perform(
+                                index_documents(None, move |index| {
+                                    let _ = index.remove_folder(&PathBuf::from(folder));
+                                }),
+                                |(folders, status)| {
+                                    Message::Settings(SettingsMessage::DocumentsIndexed(
+                                        folders, status,
+                                    ))
+                                },
+                            ),
+                            // Only shows the folders; indexing happens at boot
+                            _ => {
+                                Task::perform(index_documents(None, |_| {}), |(folders, status)| {
+                                    Message::Settings(SettingsMessage::DocumentsIndexed(
+                                        folders, status,
+                                    ))
+                                })
+                            }
+                        };
+
+                        let specs = match &settings_msg {
+                            SettingsMessage::TabChanged(_) => {
+                                Task::perform(load_model_specs(), |specs| {
+                                    Message::Settings(SettingsMessage::ModelSpecsLoaded(specs))
+                                })
+                            }
+                            _ => Task::none(),
+                        };
+
+                        return Task::batch(vec![
+                            self.forward_to_app(
+                                AppId::Settings,
+                                Message::Settings(settings_msg.clone()),
+                            ),
+                            indexing,
+                            specs,
+                        ]);
+                    }
                     _ => {}
                 }
 
@@ -1164,6 +1289,9 @@ impl PeakNative {
                             // Search mode - launch app
                             self.show_omnibar = false;
                             Task::done(Message::DockInteraction(dock::DockMessage::Launch(app_id)))
+                        } else if let Some(path) = self.omnibar.get_selected_file() {
+                            // Search mode - open file
+                            Task::done(Message::Omnibar(OmnibarMessage::OpenFile(path)))
                         } else if let Some(apk_name) = self.omnibar.get_selected_apk() {
                             // Install mode - select APK
                             Task::done(Message::Omnibar(
@@ -1184,6 +1312,14 @@ impl PeakNative {
                         self.show_omnibar = false;
                         Task::done(Message::DockInteraction(dock::DockMessage::Launch(app_id)))
                     }
+                    OmnibarMessage::OpenFile(path) => {
+                        self.show_omnibar = false;
+                        #[cfg(not(target_arch = "wasm32"))]
+                        let _ = opener::open(&path);
+                        #[cfg(target_arch = "wasm32")]
+                        let _ = path;
+                        Task::none()
+                    }
                     OmnibarMessage::SelectMenuItem(_item) => {
                         // Menu items handled in omnibar.update()
                         Task::none()
@@ -1383,12 +1519,25 @@ impl PeakNative {
             // Message::Browser removed - using external Firefox
             Message::AssistantBooted(result) => {
                 match result {
-                    Ok(assistant) => {
-                        self.assistant = Some(assistant);
+                    Ok(model_id) => {
+                        self.loaded_model_id = Some(model_id);
                         self.alert = Some((
                             "Assistant Ready".into(),
                             "Peak Intelligence is now active.".into(),
                         ));
+
+                        // Catch up with documents changed since the last run
+                        #[cfg(feature = "native")]
+                        return Task::perform(
+                            index_documents(Some(self.models.clone()), |_| {}),
+                            |(folders, status)| {
+                                Message::Settings(
+                                    peak_apps::settings::SettingsMessage::DocumentsIndexed(
+                                        folders, status,
+                                    ),
+                                )
+                            },
+                        );
                     }
                     Err(e) => {
                         let (title, message) = match e {
@@ -1403,20 +1552,93 @@ impl PeakNative {
 
                         self.alert = Some((title, message));
                         self.pending_chat = None;
+                        self.speak_reply = false;
                     }
                 }
                 Task::none()
             }
+            #[cfg(feature = "native")]
+            Message::Models(event) => {
+                use peak_intelligence::brain::manager::Event;
+                use peak_intelligence::brain::Error;
+
+                match event {
+                    Event::Crashed { retry_in, .. } => {
+                        self.alert = Some((
+                            "Assistant Crashed".into(),
+                            format!(
+                                "Peak Intelligence restarts in {} seconds.",
+                                retry_in.as_secs().max(1)
+                            ),
+                        ));
+                    }
+                    // Boot failures are reported by AssistantBooted
+                    Event::Failed {
+                        error: Error::ResourceLimitExceeded(msg),
+                        ..
+                    } => {
+                        self.alert = Some(("Safety Shutdown".into(), msg));
+                    }
+                    _ => {}
+                }
+
+                Task::none()
+            }
+            #[cfg(feature = "native")]
+            Message::PermissionRequested(confirmation) => {
+                self.permission_requests.push_back(confirmation);
+                Task::none()
+            }
+            #[cfg(feature = "native")]
+            Message::PermissionAnswered(decision) => {
+                if let Some(confirmation) = self.permission_requests.pop_front() {
+                    confirmation.answer(decision);
+                }
+
+                Task::none()
+            }
             Message::AssistantReply(_reply, token) => {
                 // Check if last message is assistant, if so append, else push
                 // Inspector stores history as Vec<(Role, Content)>
+                use peak_intelligence::brain::assistant::Token;
 
                 let content_chunk = match token {
-                    peak_intelligence::brain::assistant::Token::Talking(s) => s,
-                    peak_intelligence::brain::assistant::Token::Reasoning(s) => s, // Treat reasoning as text for now
+                    Token::Talking(s) => s,
+                    Token::Reasoning(s) => s, // Treat reasoning as text for now
+                    Token::ToolCalled { activity, .. } => {
+                        self.inspector
+                            .chat_history
+                            .push(("tool".to_string(), activity));
+                        String::new()
+                    }
+                    Token::ToolFinished {
+                        name,
+                        output,
+                        is_error,
+                        ..
+                    } => {
+                        if is_error {
+                            if let Some((role, content)) = self.inspector.chat_history.last_mut() {
+                                if role == "tool" {
+                                    *content =
+                                        format!("{} (failed)", content.trim_end_matches("..."));
+                                }
+                            }
+                        }
+
+                        // Not shown, but kept for the model's next turns
+                        let outcome = if is_error { "failed" } else { "returned" };
+                        self.inspector.chat_history.push((
+                            "tool_result".to_string(),
+                            format!("{name} {outcome}: {output}"),
+                        ));
+                        String::new()
+                    }
                 };
 
-                if let Some((role, content)) = self.inspector.chat_history.last_mut() {
+                if content_chunk.is_empty() {
+                    // Tool rows are already in place
+                } else if let Some((role, content)) = self.inspector.chat_history.last_mut() {
                     if role == "assistant" {
                         content.push_str(&content_chunk);
                     } else {
@@ -1437,6 +1659,10 @@ impl PeakNative {
                     iced::widget::scrollable::AbsoluteOffset { x: 0.0, y: 10000.0 },
                 )
             }
+            Message::AssistantSummarized(covered, summary) => {
+                self.inspector.summary = Some((covered, summary));
+                Task::none()
+            }
             Message::AssistantFinished => {
                 let mut tasks = Vec::new();
                 if let Some((role, content)) = self.inspector.chat_history.last_mut() {
@@ -1509,7 +1735,18 @@ impl PeakNative {
                         *content = content.trim().to_string();
                     }
                 }
+
+                // Asked out loud, so answered out loud
+                if self.speak_reply {
+                    if let Some((role, content)) = self.inspector.chat_history.last() {
+                        if role == "assistant" {
+                            tasks.push(self.speak(content.clone()));
+                        }
+                    }
+                }
+
                 self.pending_chat = None;
+                self.speak_reply = false;
                 Task::batch(tasks)
             }
             Message::ConsoleCategory(msg) => {
@@ -1556,3 +1793,159 @@ impl PeakNative {
         }
     }
 }
+
+/// Applies `change` to the document folders and, if the assistant is
+/// running, indexes them with it, for Settings > Intelligence.
+///
+/// Returns the folders and a line about the index.
+#[cfg(feature = "native")]
+async fn index_documents(
+    models: Option<peak_intelligence::brain::Manager>,
+    change: impl FnOnce(&mut peak_intelligence::brain::index::Index) + Send + 'static,
+) -> (Vec<String>, String) {
+    use peak_intelligence::brain::assistant::Assistant;
+    use peak_intelligence::brain::index::Index;
+    use peak_intelligence::brain::manager::{Role, State};
+
+    // Indexing alone is no reason to boot a model; a remote one is always up
+    let assistant = match (Assistant::configured().await, models) {
+        (Some(assistant), _) => Some(assistant),
+        (None, Some(models))
+            if matches!(
+                models.states().get(&Role::Answer),
+                Some(State::Ready { .. })
+            ) =>
+        {
+            models.get(Role::Answer).await.ok()
+        }
+        _ => None,
+    };
+
+    let mut index = match Index::open_default() {
+        Ok(index) => index,
+        Err(e) => return (Vec::new(), format!("Document index unavailable: {}", e)),
+    };
+
+    change(&mut index);
+
+    let folders = index
+        .folders()
+        .iter()
+        .map(|folder| folder.display().to_string())
+        .collect();
+
+    let status = match assistant {
+        Some(assistant) if !index.folders().is_empty() => match index.update(&assistant).await {
+            Ok(report) if report.failed > 0 => format!(
+                "{} files indexed, {} could not be read",
+                index.len(),
+                report.failed
+            ),
+            Ok(_) => format!("{} files indexed", index.len()),
+            Err(e) => format!("Could not index documents: {}", e),
+        },
+        _ => match index.save() {
+            Ok(()) if index.is_empty() => "Indexed when the assistant starts.".to_string(),
+            Ok(()) => format!("{} files indexed", index.len()),
+            Err(e) => format!("Could not save document folders: {}", e),
+        },
+    };
+
+    (folders, status)
+}
+
+/// Deletes the downloaded files of a model from the library.
+#[cfg(feature = "native")]
+async fn remove_model(id: String) {
+    use peak_intelligence::brain::model::{Directory, Library};
+
+    let mut library = match Library::scan(Directory::default()).await {
+        Ok(library) => library,
+        Err(e) => {
+            log::warn!("Could not read the model library: {}", e);
+            return;
+        }
+    };
+
+    let files: Vec<_> = library
+        .files()
+        .iter()
+        .filter(|file| file.model.0 == id)
+        .cloned()
+        .collect();
+
+    for file in files {
+        if let Err(e) = library.remove(&file).await {
+            log::warn!("Could not remove {}: {}", file.name, e);
+        }
+    }
+}
+
+/// Reads what the GGUF headers in the model library say about each
+/// downloaded model, for Settings > Intelligence.
+#[cfg(feature = "native")]
+async fn load_model_specs() -> Vec<(String, String)> {
+    use peak_intelligence::brain::model::{Directory, Library};
+
+    let library = match Library::scan(Directory::default()).await {
+        Ok(library) => library,
+        Err(e) => {
+            log::warn!("Could not read the model library: {}", e);
+            return Vec::new();
+        }
+    };
+
+    library
+        .files()
+        .iter()
+        .filter_map(|file| Some((file.model.0.clone(), file.metadata.as_ref()?.to_string())))
+        .collect()
+}
+
+/// Reads the most recent intelligence tool calls for Settings > Privacy.
+#[cfg(feature = "native")]
+async fn load_audit_log() -> (Vec<peak_apps::settings::AuditEntryInfo>, String) {
+    use peak_intelligence::audit::{Integrity, Log, Query};
+
+    let log = match Log::open_default() {
+        Ok(log) => log,
+        Err(e) => return (Vec::new(), format!("Audit log unavailable: {}", e)),
+    };
+
+    let integrity = match log.verify() {
+        Ok(Integrity::Intact { entries }) => format!("Log intact · {} entries", entries),
+        Ok(Integrity::Tampered { line, reason }) => {
+            format!("Tampering detected at entry {}: {}", line, reason)
+        }
+        Err(e) => format!("Could not verify audit log: {}", e),
+    };
+
+    let query = Query {
+        limit: Some(200),
+        ..Query::default()
+    };
+
+    let entries = log
+        .query(&query)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| peak_apps::settings::AuditEntryInfo {
+            timestamp: entry
+                .timestamp
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+            caller: entry
+                .caller
+                .model
+                .or(entry.caller.app)
+                .unwrap_or_else(|| "Unknown".into()),
+            tool: entry.tool,
+            arguments: entry.arguments.to_string(),
+            status: entry.status.to_string(),
+            duration_ms: entry.duration_ms,
+        })
+        .collect();
+
+    (entries, integrity)
+}
@@ -17,6 +17,14 @@ impl PeakNative {
             AppState::Desktop => self.view_desktop(),
         };
 
+        #[cfg(feature = "native")]
+        let content = match self.permission_requests.front() {
+            Some(confirmation) => {
+                iced::widget::stack![content, self.view_permission(confirmation)].into()
+            }
+            None => content,
+        };
+
         if let Some((title, body)) = &self.alert {
             iced::widget::stack![
                 content,
@@ -47,6 +55,85 @@ impl PeakNative {
         }
     }
 
+    /// Asks the user whether the assistant may make a tool call.
+    #[cfg(feature = "native")]
+    fn view_permission(
+        &self,
+        confirmation: &peak_intelligence::policy::Confirmation,
+    ) -> Element<'_, Message> {
+        use iced::widget::{button, column, container, row, text};
+        use iced::Length;
+        use peak_intelligence::policy::Decision;
+
+        let is_light = matches!(self.theme, peak_core::Theme::Light);
+        let (background, foreground) = if is_light {
+            (iced::Color::WHITE, iced::Color::BLACK)
+        } else {
+            (iced::Color::from_rgb8(40, 40, 40), iced::Color::WHITE)
+        };
+
+        let caller = confirmation
+            .caller
+            .model
+            .clone()
+            .unwrap_or_else(|| "The assistant".to_string());
+
+        // Arguments arrive redacted by the policy
+        let arguments = serde_json::to_string_pretty(&confirmation.arguments).unwrap_or_default();
+
+        let answer = |label: &'static str, decision: Decision| {
+            button(text(label).size(13.0))
+                .on_press(Message::PermissionAnswered(decision))
+                .padding([6.0, 12.0])
+        };
+
+        let dialog = container(
+            column![
+                text(format!("{} wants to run {}", caller, confirmation.tool))
+                    .size(16.0)
+                    .color(foreground),
+                text(confirmation.description.clone())
+                    .size(13.0)
+                    .color(foreground),
+                text(arguments)
+                    .size(12.0)
+                    .font(iced::Font::MONOSPACE)
+                    .color(foreground),
+                row![
+                    answer("Deny", Decision::Deny).style(button::danger),
+                    answer("Allow Once", Decision::AllowOnce).style(button::secondary),
+                    answer("Allow This Session", Decision::AllowSession).style(button::secondary),
+                    answer("Always Allow", Decision::AllowAlways).style(button::primary),
+                ]
+                .spacing(8.0),
+            ]
+            .spacing(12.0),
+        )
+        .padding(20.0)
+        .max_width(480.0)
+        .style(move |_| container::Style {
+            background: Some(background.into()),
+            border: iced::Border {
+                radius: 12.0.into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        container(dialog)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .align_x(iced::alignment::Horizontal::Center)
+            .align_y(iced::alignment::Vertical::Center)
+            .style(|_| container::Style {
+                background: Some(iced::Background::Color(iced::Color::from_rgba(
+                    0.0, 0.0, 0.0, 0.5,
+                ))),
+                ..Default::default()
+            })
+            .into()
+    }
+
     fn view_login(&self) -> Element<'_, Message> {
         use crate::components::login::LoginView;
         use peak_ui::core::Context;
@@ -0,0 +1,119 @@
+// Hands-free voice commands
+
+use super::{Message, PeakNative};
+use iced::Task;
+use peak_core::registry::{AppId, AppInfo, ShellMode};
+use peak_intelligence::voice::Intent;
+
+impl PeakNative {
+    /// Carries out a spoken command and says what is happening.
+    pub(crate) fn handle_intent(&mut self, intent: Intent) -> Task<Message> {
+        match intent {
+            Intent::OpenApp(name) => match app_named(&name) {
+                Some(app_id) => Task::batch([
+                    Task::done(Message::DockInteraction(
+                        peak_shell::dock::DockMessage::Launch(app_id),
+                    )),
+                    self.speak(format!("Opening {}.", AppInfo::get_info(app_id).name)),
+                ]),
+                // Not an app after all, e.g. "open the pod bay doors"
+                None => self.ask(format!("Open {name}")),
+            },
+            Intent::SwitchMode(name) => match mode_named(&name) {
+                Some(mode) => Task::batch([
+                    Task::done(Message::SwitchMode(mode)),
+                    self.speak(format!("Switching to {mode} mode.")),
+                ]),
+                None => self.speak(format!("There is no {name} mode.")),
+            },
+            Intent::Ask(question) => self.ask(question),
+        }
+    }
+
+    /// Asks the assistant, speaking the reply once it is finished.
+    fn ask(&mut self, question: String) -> Task<Message> {
+        // Nothing would answer, and nothing would say so
+        if self.active_model_id.is_none() && self.remote_model.is_none() {
+            self.pending_chat = None;
+            self.speak_reply = false;
+
+            return self.speak(
+                "No model is loaded. Choose one in Settings, under Intelligence.".to_string(),
+            );
+        }
+
+        self.inspector
+            .chat_history
+            .push(("user".to_string(), question.clone()));
+        self.pending_chat = Some(question);
+        self.speak_reply = true;
+
+        Task::none()
+    }
+
+    /// Says `text` out loud, with hands-free listening paused until it is
+    /// done.
+    pub(crate) fn speak(&self, text: String) -> Task<Message> {
+        #[cfg(feature = "voice")]
+        {
+            use crate::components::inspector::SPEAKING;
+            use std::sync::atomic::Ordering;
+
+            Task::future(async move {
+                let _ = SPEAKING.fetch_add(1, Ordering::SeqCst);
+
+                let mut manager = peak_intelligence::voice::VOICE.lock().await;
+
+                match manager.synthesize(&text, "en_US-lessac-medium").await {
+                    Ok(_) => manager.finish_speaking().await,
+                    Err(e) => log::warn!("Failed to speak: {}", e),
+                }
+
+                let _ = SPEAKING.fetch_sub(1, Ordering::SeqCst);
+            })
+            .discard()
+        }
+        #[cfg(not(feature = "voice"))]
+        {
+            let _ = text;
+            Task::none()
+        }
+    }
+}
+
+/// The app called `name`, by its title or its id, e.g. "jukebox" or
+/// "file manager".
+fn app_named(name: &str) -> Option<AppId> {
+    let name = squash(name);
+
+    AppInfo::all().into_iter().map(|info| info.id).find(|id| {
+        squash(AppInfo::get_info(*id).name) == name
+            || squash(id.metadata().name) == name
+            || squash(&id.to_string()) == name
+    })
+}
+
+/// The mode called `name`, including what people call them out loud.
+fn mode_named(name: &str) -> Option<ShellMode> {
+    Some(match squash(name).as_str() {
+        "desktop" | "computer" => ShellMode::Desktop,
+        "auto" | "car" | "driving" => ShellMode::Auto,
+        "console" | "game" | "gaming" => ShellMode::Console,
+        "fireplace" => ShellMode::Fireplace,
+        "kiosk" => ShellMode::Kiosk,
+        "mobile" | "phone" => ShellMode::Mobile,
+        "robot" => ShellMode::Robot,
+        "server" => ShellMode::Server,
+        "home" | "smarthome" | "kitchen" => ShellMode::SmartHome,
+        "tv" | "television" => ShellMode::TV,
+        _ => return None,
+    })
+}
+
+/// Lowercase, without spaces or dashes.
+fn squash(name: &str) -> String {
+    name.chars()
+        .filter(|character| character.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
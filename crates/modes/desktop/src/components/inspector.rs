@@ -21,6 +21,8 @@ pub enum InspectorMessage {
     InputChanged(String),
     SubmitMessage,
     SetVoiceEnabled(bool),
+    Voice(peak_intelligence::voice::hands_free::Event),
+    VoiceFailed(String),
 }
 
 pub struct Inspector {
@@ -28,8 +30,14 @@ pub struct Inspector {
     pub view_state: InspectorViewState,
     pub input_content: String,
     pub chat_history: Vec<(String, String)>, // (Role, Content)
+    /// Summary standing in for the first `n` entries of `chat_history`
+    pub summary: Option<(usize, String)>,
     pub active_model: Option<String>,
     pub available_models: Vec<String>,
+    /// Waiting for "Hey Peak", hands-free
+    pub is_listening: bool,
+    /// What the microphone heard, while a command is being spoken
+    pub voice_status: Option<String>,
 }
 
 impl Inspector {
@@ -42,14 +50,28 @@ impl Inspector {
                 "system".to_string(),
                 "Inspector initialized. Ready for query.".to_string(),
             )],
+            summary: None,
             active_model: None,
             available_models: vec![],
+            is_listening: false,
+            voice_status: None,
         }
     }
 
     pub fn update(&mut self, message: InspectorMessage) -> Task<InspectorMessage> {
         match message {
-            InspectorMessage::ToggleVoice => {}
+            InspectorMessage::ToggleVoice => {
+                #[cfg(feature = "voice")]
+                {
+                    self.is_listening = !self.is_listening;
+                    self.voice_status = None;
+                }
+                #[cfg(not(feature = "voice"))]
+                self.chat_history.push((
+                    "system".to_string(),
+                    "Voice is not available in this build.".to_string(),
+                ));
+            }
             InspectorMessage::SwitchView(state) => {
                 self.view_state = state;
             }
@@ -70,6 +92,22 @@ impl Inspector {
                 self.available_models = models;
             }
             InspectorMessage::SetVoiceEnabled(_) => {}
+            InspectorMessage::Voice(event) => {
+                use peak_intelligence::voice::hands_free::Event;
+
+                // Commands are carried out by the shell
+                self.voice_status = match event {
+                    Event::Woke => Some("Listening...".to_string()),
+                    Event::Heard(text) => Some(text),
+                    Event::Command(_) | Event::Dozed => None,
+                };
+            }
+            InspectorMessage::VoiceFailed(error) => {
+                self.is_listening = false;
+                self.voice_status = None;
+                self.chat_history
+                    .push(("system".to_string(), format!("Voice failed: {error}")));
+            }
             InspectorMessage::InputChanged(content) => {
                 self.input_content = content;
             }
@@ -90,6 +128,11 @@ impl Inspector {
     }
 
     pub fn subscription(&self) -> iced::Subscription<InspectorMessage> {
+        #[cfg(feature = "voice")]
+        if self.is_listening {
+            return iced::Subscription::run(hands_free);
+        }
+
         iced::Subscription::none()
     }
 
@@ -99,6 +142,8 @@ impl Inspector {
             column(
                 self.chat_history
                     .iter()
+                    // Tool results are for the model
+                    .filter(|(role, _)| role != "tool_result")
                     .map(|(role, msg)| {
                         let is_user = role == "user";
                         let bubble = container(text(msg.clone()).style(move |_| text::Style {
@@ -184,9 +229,23 @@ impl Inspector {
             }
         };
 
+        let icon_color = format!(
+            "#{:02X}{:02X}{:02X}",
+            (tokens.colors.text_primary.r * 255.0) as u8,
+            (tokens.colors.text_primary.g * 255.0) as u8,
+            (tokens.colors.text_primary.b * 255.0) as u8
+        );
+        let is_listening = self.is_listening;
+
+        let placeholder = match &self.voice_status {
+            Some(status) => status.as_str(),
+            None if self.is_listening => "Say \"Hey Peak\"...",
+            None => "Ask Peak...",
+        };
+
         let input_area: Element<'_, InspectorMessage> = container(
             column![
-                text_input("Ask Peak...", &self.input_content)
+                text_input(placeholder, &self.input_content)
                     .on_input(InspectorMessage::InputChanged)
                     .on_submit(InspectorMessage::SubmitMessage)
                     .padding(10)
@@ -205,17 +264,32 @@ impl Inspector {
                     model_selector,
                     iced::widget::Space::new().width(Length::Fill),
                     button(
-                        iced::widget::svg(peak_core::icons::get_ui_icon(
-                            "arrow_up",
-                            &format!(
-                                "#{:02X}{:02X}{:02X}",
-                                (tokens.colors.text_primary.r * 255.0) as u8,
-                                (tokens.colors.text_primary.g * 255.0) as u8,
-                                (tokens.colors.text_primary.b * 255.0) as u8
-                            )
-                        ))
-                        .width(16)
-                        .height(16)
+                        iced::widget::svg(peak_core::icons::get_ui_icon("microphone", &icon_color))
+                            .width(16)
+                            .height(16)
+                    )
+                    .on_press(InspectorMessage::ToggleVoice)
+                    .padding(8)
+                    .style(move |_theme, _status| {
+                        iced::widget::button::Style {
+                            background: Some(if is_listening {
+                                iced::Color::from_rgb(0.8, 0.2, 0.2).into()
+                            } else {
+                                iced::Color::from_rgba(0.5, 0.5, 0.5, 0.2).into()
+                            }),
+                            border: iced::Border {
+                                radius: 100.0.into(),
+                                ..Default::default()
+                            },
+                            text_color: iced::Color::WHITE,
+                            shadow: iced::Shadow::default(),
+                            snap: false,
+                        }
+                    }),
+                    button(
+                        iced::widget::svg(peak_core::icons::get_ui_icon("arrow_up", &icon_color))
+                            .width(16)
+                            .height(16)
                     )
                     .on_press(InspectorMessage::SubmitMessage)
                     .padding(8)
@@ -271,3 +345,81 @@ impl Inspector {
             .into()
     }
 }
+
+/// Replies being spoken out loud. [`hands_free`] ignores the microphone
+/// meanwhile, so it does not hear itself.
+#[cfg(feature = "voice")]
+pub(crate) static SPEAKING: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Listens for "Hey Peak" and the command after it, until the subscription
+/// is dropped.
+#[cfg(feature = "voice")]
+fn hands_free() -> impl iced::futures::Stream<Item = InspectorMessage> {
+    use iced::futures::{SinkExt, StreamExt};
+    use peak_intelligence::voice::{hands_free, VOICE};
+
+    iced::stream::channel(
+        100,
+        |mut output: iced::futures::channel::mpsc::Sender<InspectorMessage>| async move {
+            let hands_free = {
+                let mut manager = VOICE.lock().await;
+
+                match manager.init_whisper("tiny.en").await {
+                    Ok(()) => manager.hands_free(hands_free::Settings::default()),
+                    Err(error) => Err(error),
+                }
+            };
+
+            let mut hands_free = match hands_free {
+                Ok(hands_free) => hands_free,
+                Err(error) => {
+                    let _ = output
+                        .send(InspectorMessage::VoiceFailed(error.to_string()))
+                        .await;
+                    return;
+                }
+            };
+
+            let (sender, mut messages) = iced::futures::channel::mpsc::unbounded();
+
+            // The microphone and Whisper both block, so they get a thread
+            std::thread::spawn(move || {
+                let microphone = match crate::recorder::Microphone::start() {
+                    Ok(microphone) => microphone,
+                    Err(error) => {
+                        let _ =
+                            sender.unbounded_send(InspectorMessage::VoiceFailed(error.to_string()));
+                        return;
+                    }
+                };
+
+                while !sender.is_closed() {
+                    let events = microphone.next().and_then(|samples| {
+                        if SPEAKING.load(std::sync::atomic::Ordering::SeqCst) > 0 {
+                            return Ok(Vec::new());
+                        }
+
+                        hands_free.push(&samples)
+                    });
+
+                    match events {
+                        Ok(events) => {
+                            for event in events {
+                                let _ = sender.unbounded_send(InspectorMessage::Voice(event));
+                            }
+                        }
+                        Err(error) => {
+                            let _ = sender
+                                .unbounded_send(InspectorMessage::VoiceFailed(error.to_string()));
+                            return;
+                        }
+                    }
+                }
+            });
+
+            while let Some(message) = messages.next().await {
+                let _ = output.send(message).await;
+            }
+        },
+    )
+}
@@ -1,6 +1,7 @@
 use iced::widget::{button, column, container, row, text, text_input};
 use iced::{Alignment, Background, Color, Element, Length};
 use peak_core::registry::{AppId, AppInfo};
+use std::path::PathBuf;
 
 // Menu items for the default view
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -72,11 +73,12 @@ pub enum OmnibarMode {
     Install, // APK package search
 }
 
-// Search results for local apps
+// Search results for local apps and files
 #[derive(Debug, Clone)]
 pub struct SearchResult {
     pub name: String,
     pub app_id: Option<AppId>,
+    pub path: Option<PathBuf>,
 }
 
 // APK package result
@@ -93,10 +95,13 @@ pub enum OmnibarMessage {
     SelectMenuItem(MenuItem),
     SelectApp(AppId),
     SelectApk(String),
+    OpenFile(PathBuf),
     NavigateUp,
     NavigateDown,
     Cancel,
     ApkResults(Vec<ApkPackage>),
+    // Files matching the query, found in the background
+    FileResults(String, Vec<SearchResult>),
 }
 
 pub struct Omnibar {
@@ -106,6 +111,8 @@ pub struct Omnibar {
     search_results: Vec<SearchResult>,
     apk_results: Vec<ApkPackage>,
     selected_index: usize,
+    // The running file search; dropping it stops the walk
+    file_search: Option<iced::task::Handle>,
 }
 
 impl Omnibar {
@@ -117,6 +124,7 @@ impl Omnibar {
             search_results: Vec::new(),
             apk_results: Vec::new(),
             selected_index: 0,
+            file_search: None,
         }
     }
 
@@ -128,6 +136,14 @@ impl Omnibar {
         }
     }
 
+    pub fn get_selected_file(&self) -> Option<PathBuf> {
+        if self.mode == OmnibarMode::Search {
+            self.search_results.get(self.selected_index)?.path.clone()
+        } else {
+            None
+        }
+    }
+
     pub fn get_selected_apk(&self) -> Option<String> {
         if self.mode == OmnibarMode::Install {
             self.apk_results
@@ -150,6 +166,7 @@ impl Omnibar {
         match message {
             OmnibarMessage::QueryChanged(new_query) => {
                 self.query = new_query;
+                self.file_search = None;
 
                 if self.query.is_empty() {
                     // Return to menu mode
@@ -178,12 +195,26 @@ impl Omnibar {
                                 hits.push(SearchResult {
                                     name: app.name.to_string(),
                                     app_id: Some(app.id),
+                                    path: None,
                                 });
                             }
                         }
 
                         self.search_results = hits;
                         self.selected_index = 0;
+
+                        // Then files in the home folder
+                        if trimmed.len() > 2 {
+                            let query = trimmed.clone();
+                            let (task, handle) =
+                                iced::Task::perform(search_files(query.clone()), move |files| {
+                                    OmnibarMessage::FileResults(query.clone(), files)
+                                })
+                                .abortable();
+
+                            self.file_search = Some(handle.abort_on_drop());
+                            return task;
+                        }
                     }
                     OmnibarMode::Install => {
                         // Search APK packages
@@ -201,6 +232,13 @@ impl Omnibar {
             OmnibarMessage::ApkResults(results) => {
                 self.apk_results = results;
             }
+            OmnibarMessage::FileResults(query, files) => {
+                // Ignore results for a query that has since changed
+                if self.mode == OmnibarMode::Search && query == self.query.to_lowercase() {
+                    self.file_search = None;
+                    self.search_results.extend(files);
+                }
+            }
             OmnibarMessage::SelectMenuItem(item) => {
                 if item == MenuItem::Install {
                     // Enter install mode
@@ -376,7 +414,7 @@ impl Omnibar {
         if self.search_results.is_empty() {
             let mut text_color = tokens.colors.text_primary;
             text_color.a = 0.4;
-            container(text("No apps found").size(14).color(text_color))
+            container(text("No apps or files found").size(14).color(text_color))
                 .padding(20)
                 .width(Length::Fill)
                 .center_x(Length::Fill)
@@ -418,6 +456,8 @@ impl Omnibar {
 
         let msg = if let Some(app_id) = result.app_id {
             OmnibarMessage::SelectApp(app_id)
+        } else if let Some(path) = &result.path {
+            OmnibarMessage::OpenFile(path.clone())
         } else {
             OmnibarMessage::Submit
         };
@@ -548,6 +588,47 @@ impl Omnibar {
     }
 }
 
+async fn search_files(query: String) -> Vec<SearchResult> {
+    #[cfg(feature = "native")]
+    {
+        use peak_intelligence::tools::search::{self, Match};
+
+        let Some(home) = dirs::home_dir() else {
+            return Vec::new();
+        };
+
+        let mut options = search::Options::new(home, query);
+        options.max_depth = Some(4);
+        options.max_results = 8;
+
+        let Ok(mut matches) = search::stream(options) else {
+            return Vec::new();
+        };
+
+        let mut results = Vec::new();
+
+        while let Some(found) = matches.recv().await {
+            if let Match::Name { path, .. } = found {
+                results.push(SearchResult {
+                    name: path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    app_id: None,
+                    path: Some(path),
+                });
+            }
+        }
+
+        results
+    }
+    #[cfg(not(feature = "native"))]
+    {
+        let _ = query;
+        Vec::new()
+    }
+}
+
 async fn search_apk_packages(query: String) -> Vec<ApkPackage> {
     #[cfg(not(target_arch = "wasm32"))]
     {
@@ -1,51 +1,140 @@
 #[cfg(feature = "voice")]
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 #[cfg(feature = "voice")]
-use std::sync::{Arc, Mutex};
+use peak_intelligence::voice::audio::{self, Format};
+#[cfg(feature = "voice")]
+use std::sync::{mpsc, Arc, Mutex};
 
 #[cfg(feature = "voice")]
 pub struct Recorder {
     _stream: cpal::Stream,
     data: Arc<Mutex<Vec<f32>>>,
+    format: Format,
 }
 
 #[cfg(feature = "voice")]
 impl Recorder {
     pub fn start() -> anyhow::Result<Self> {
-        let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .ok_or_else(|| anyhow::anyhow!("No input device found"))?;
-
-        let config = device.default_input_config()?;
         let data = Arc::new(Mutex::new(Vec::new()));
-        let data_clone = data.clone();
-
-        let stream = match config.sample_format() {
-            cpal::SampleFormat::F32 => device.build_input_stream(
-                &config.into(),
-                move |samples: &[f32], _| {
-                    let mut data = data_clone.lock().unwrap();
-                    data.extend_from_slice(samples);
-                },
-                |err| eprintln!("Audio record error: {}", err),
-                None,
-            )?,
-            _ => return Err(anyhow::anyhow!("Unsupported sample format")),
-        };
-
-        stream
-            .play()
-            .map_err(|e| anyhow::anyhow!("Failed to play stream: {:?}", e))?;
+        let recorded = data.clone();
+
+        let (stream, format) = open(move |samples| {
+            recorded.lock().unwrap().extend_from_slice(samples);
+        })?;
 
         Ok(Self {
             _stream: stream,
             data,
+            format,
         })
     }
 
+    /// Stops recording and returns the speech as 16 kHz mono, which is what
+    /// `intelligence/stt` expects.
     pub fn stop(self) -> Vec<f32> {
         let data = self.data.lock().unwrap();
-        data.clone()
+
+        audio::normalize(data.as_slice(), self.format)
+    }
+}
+
+/// Hears everything for as long as it lives, e.g. to wait for the wake word.
+#[cfg(feature = "voice")]
+pub struct Microphone {
+    _stream: cpal::Stream,
+    chunks: mpsc::Receiver<Vec<f32>>,
+    format: Format,
+}
+
+#[cfg(feature = "voice")]
+impl Microphone {
+    pub fn start() -> anyhow::Result<Self> {
+        let (sender, chunks) = mpsc::channel();
+
+        let (stream, format) = open(move |samples| {
+            let _ = sender.send(samples.to_vec());
+        })?;
+
+        Ok(Self {
+            _stream: stream,
+            chunks,
+            format,
+        })
+    }
+
+    /// Waits for the next 16 kHz mono audio, about a quarter of a second.
+    pub fn next(&self) -> anyhow::Result<Vec<f32>> {
+        let mut samples = Vec::new();
+        let quarter = self.format.sample_rate as usize / 4 * usize::from(self.format.channels);
+
+        // Resampled in larger pieces, so the filter sees enough around them
+        while samples.len() < quarter {
+            samples.extend(self.chunks.recv()?);
+        }
+
+        Ok(audio::resample(
+            &audio::to_mono(&samples, self.format.channels),
+            self.format.sample_rate,
+            peak_intelligence::voice::SAMPLE_RATE,
+        ))
     }
 }
+
+/// Records from the default input device, converting samples to `f32`.
+#[cfg(feature = "voice")]
+fn open(
+    on_samples: impl FnMut(&[f32]) + Send + 'static,
+) -> anyhow::Result<(cpal::Stream, Format)> {
+    let host = cpal::default_host();
+    let device = host
+        .default_input_device()
+        .ok_or_else(|| anyhow::anyhow!("No input device found"))?;
+
+    let config = device.default_input_config()?;
+    let format = Format {
+        sample_rate: config.sample_rate().0,
+        channels: config.channels(),
+    };
+
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => build::<f32>(&device, &config.into(), on_samples)?,
+        cpal::SampleFormat::I16 => build::<i16>(&device, &config.into(), on_samples)?,
+        cpal::SampleFormat::U16 => build::<u16>(&device, &config.into(), on_samples)?,
+        sample_format => {
+            return Err(anyhow::anyhow!(
+                "Unsupported sample format: {sample_format}"
+            ))
+        }
+    };
+
+    stream
+        .play()
+        .map_err(|e| anyhow::anyhow!("Failed to play stream: {:?}", e))?;
+
+    Ok((stream, format))
+}
+
+/// Records interleaved samples, converted to `f32`.
+#[cfg(feature = "voice")]
+fn build<S>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut on_samples: impl FnMut(&[f32]) + Send + 'static,
+) -> anyhow::Result<cpal::Stream>
+where
+    S: cpal::SizedSample + audio::Sample,
+{
+    let mut buffer = Vec::new();
+
+    Ok(device.build_input_stream(
+        config,
+        move |samples: &[S], _| {
+            buffer.clear();
+            buffer.extend(samples.iter().map(|sample| sample.to_f32()));
+
+            on_samples(&buffer);
+        },
+        |err| eprintln!("Audio record error: {}", err),
+        None,
+    )?)
+}
@@ -96,12 +96,14 @@ pub fn view_card<'a>(item: &'a MediaItem) -> Element<'a, LibraryMessage> {
         MediaStatus::Ready => Color::from_rgb(0.0, 1.0, 0.8), // Cyan
         MediaStatus::Running => Color::from_rgb(0.0, 1.0, 0.0), // Green
         MediaStatus::Updating(_) => Color::from_rgb(1.0, 0.8, 0.0), // Amber
+        MediaStatus::Broken => Color::from_rgb(1.0, 0.3, 0.3), // Red
     };
 
     let status_text = match item.status {
         MediaStatus::Ready => "READY".to_string(),
         MediaStatus::Running => "RUNNING".to_string(),
         MediaStatus::Updating(p) => format!("UPDATING {:.0}%", p * 100.0),
+        MediaStatus::Broken => "NEEDS REPAIR".to_string(),
     };
 
     // Card Content: Image OR Placeholder
@@ -1,4 +1,6 @@
-pub use peak_core::apps::settings::{SettingsApp, SettingsMessage, SettingsTab, ThemeMode};
+pub use peak_core::apps::settings::{
+    AuditEntryInfo, SettingsApp, SettingsMessage, SettingsTab, ThemeMode,
+};
 use peak_ui::prelude::*;
 
 // --- Pure PeakUI View Implementation ---
@@ -47,6 +49,10 @@ trait SettingsHelpers {
         &self,
         context: &peak_ui::core::Context,
     ) -> B::AnyView<SettingsMessage>;
+    fn view_privacy<B: peak_ui::core::Backend>(
+        &self,
+        context: &peak_ui::core::Context,
+    ) -> B::AnyView<SettingsMessage>;
     fn view_modes<B: peak_ui::core::Backend>(
         &self,
         context: &peak_ui::core::Context,
@@ -107,6 +113,12 @@ where
                 SettingsMessage::TabChanged(SettingsTab::Focus),
                 app.current_tab == SettingsTab::Focus,
             )
+            .item(
+                "Privacy",
+                "shield",
+                SettingsMessage::TabChanged(SettingsTab::Privacy),
+                app.current_tab == SettingsTab::Privacy,
+            )
             .item(
                 "Intelligence",
                 "sparkles",
@@ -257,6 +269,7 @@ impl SettingsHelpers for SettingsApp {
             SettingsTab::WiFi => self.view_wifi::<B>(context),
             SettingsTab::Bluetooth => self.view_bluetooth::<B>(context),
             SettingsTab::Sound => self.view_sound::<B>(context),
+            SettingsTab::Privacy => self.view_privacy::<B>(context),
             SettingsTab::Intelligence => self.view_intelligence::<B>(context),
             SettingsTab::Modes => self.view_modes::<B>(context),
             _ => B::text(
@@ -773,7 +786,24 @@ impl SettingsHelpers for SettingsApp {
                                             Alignment::Start,
                                             context,
                                         ),
-                                    ],
+                                    ]
+                                    .into_iter()
+                                    // Read from the downloaded file, so it is exact
+                                    .chain(m.specs.as_ref().map(|specs| {
+                                        B::text(
+                                            specs.clone(),
+                                            11.0,
+                                            None,
+                                            false,
+                                            false,
+                                            None,
+                                            None,
+                                            Length::Shrink,
+                                            Alignment::Start,
+                                            context,
+                                        )
+                                    }))
+                                    .collect(),
                                     2.0,
                                     iced::Padding::default(),
                                     Length::Shrink,
@@ -869,6 +899,120 @@ impl SettingsHelpers for SettingsApp {
             ),
         );
 
+        // --- Documents Card ---
+        let folder_rows = app.document_folders.iter().map(|folder| {
+            B::hstack(
+                vec![
+                    B::text(
+                        folder.clone(),
+                        13.0,
+                        None,
+                        false,
+                        false,
+                        None,
+                        None,
+                        Length::Shrink,
+                        Alignment::Start,
+                        context,
+                    ),
+                    B::space(Length::Fill, Length::Shrink),
+                    B::button(
+                        B::text(
+                            "Remove".into(),
+                            13.0,
+                            None,
+                            false,
+                            false,
+                            None,
+                            None,
+                            Length::Shrink,
+                            Alignment::Center,
+                            context,
+                        ),
+                        Some(SettingsMessage::DocumentFolderRemove(folder.clone())),
+                        peak_ui::modifiers::Variant::Ghost,
+                        peak_ui::modifiers::Intent::Neutral,
+                        iced::Length::Shrink,
+                        true, // is_compact
+                        context,
+                    ),
+                ],
+                0.0,
+                iced::Padding::default(),
+                Length::Fill,
+                Length::Shrink,
+                Alignment::Center,
+                Alignment::Center,
+                1.0,
+            )
+        });
+
+        let documents_status = app.documents_status.clone().unwrap_or_else(|| {
+            if app.document_folders.is_empty() {
+                "Add folders so the assistant can answer from your notes and code.".into()
+            } else {
+                "Indexed when the assistant starts.".into()
+            }
+        });
+
+        let documents_card = self.card::<B>(
+            context,
+            B::vstack(
+                folder_rows
+                    .chain([B::hstack(
+                        vec![
+                            B::text(
+                                documents_status,
+                                12.0,
+                                None,
+                                false,
+                                false,
+                                None,
+                                None,
+                                Length::Fill,
+                                Alignment::Start,
+                                context,
+                            ),
+                            B::button(
+                                B::text(
+                                    "Add Folder…".into(),
+                                    13.0,
+                                    None,
+                                    false,
+                                    false,
+                                    None,
+                                    None,
+                                    Length::Shrink,
+                                    Alignment::Center,
+                                    context,
+                                ),
+                                Some(SettingsMessage::DocumentFolderAdd),
+                                peak_ui::modifiers::Variant::Soft,
+                                peak_ui::modifiers::Intent::Neutral,
+                                iced::Length::Shrink,
+                                false, // is_compact
+                                context,
+                            ),
+                        ],
+                        8.0,
+                        iced::Padding::default(),
+                        Length::Fill,
+                        Length::Shrink,
+                        Alignment::Center,
+                        Alignment::Center,
+                        1.0,
+                    )])
+                    .collect(),
+                8.0,
+                iced::Padding::default(),
+                Length::Fill,
+                Length::Shrink,
+                Alignment::Start,
+                Alignment::Start,
+                1.0,
+            ),
+        );
+
         // --- Preferences Card ---
         let prefs_card = self.card::<B>(
             context,
@@ -936,6 +1080,20 @@ impl SettingsHelpers for SettingsApp {
                 ),
                 cloud_card,
                 B::space(Length::Shrink, Length::Fixed(16.0)),
+                B::text(
+                    "Documents".into(),
+                    14.0,
+                    None,
+                    true,
+                    false,
+                    None,
+                    None,
+                    Length::Shrink,
+                    Alignment::Start,
+                    context,
+                ),
+                documents_card,
+                B::space(Length::Shrink, Length::Fixed(16.0)),
                 B::text(
                     "Preferences".into(),
                     14.0,
@@ -960,6 +1118,220 @@ impl SettingsHelpers for SettingsApp {
         )
     }
 
+    fn view_privacy<B: Backend>(
+        &self,
+        context: &peak_ui::core::Context,
+    ) -> B::AnyView<SettingsMessage> {
+        let app = self;
+
+        let filter_button = |label: &str, filter: Option<&str>| {
+            let selected = app.audit_filter.as_deref() == filter;
+
+            B::button(
+                B::text(
+                    label.into(),
+                    13.0,
+                    None,
+                    false,
+                    false,
+                    None,
+                    None,
+                    Length::Shrink,
+                    Alignment::Center,
+                    context,
+                ),
+                Some(SettingsMessage::AuditFilterChanged(
+                    filter.map(str::to_string),
+                )),
+                if selected {
+                    peak_ui::modifiers::Variant::Soft
+                } else {
+                    peak_ui::modifiers::Variant::Ghost
+                },
+                peak_ui::modifiers::Intent::Neutral,
+                iced::Length::Shrink,
+                true, // is_compact
+                context,
+            )
+        };
+
+        // --- Integrity Card ---
+        let integrity_card = self.card::<B>(
+            context,
+            self.labeled_row::<B>(
+                context,
+                app.audit_integrity
+                    .as_deref()
+                    .unwrap_or("Audit log not loaded yet"),
+                B::button(
+                    B::text(
+                        "Refresh".into(),
+                        13.0,
+                        None,
+                        false,
+                        false,
+                        None,
+                        None,
+                        Length::Shrink,
+                        Alignment::Center,
+                        context,
+                    ),
+                    Some(SettingsMessage::AuditRefresh),
+                    peak_ui::modifiers::Variant::Ghost,
+                    peak_ui::modifiers::Intent::Neutral,
+                    iced::Length::Shrink,
+                    false, // is_compact
+                    context,
+                ),
+            ),
+        );
+
+        // --- Activity Card ---
+        let entries: Vec<_> = app
+            .audit_entries
+            .iter()
+            .filter(|entry| {
+                app.audit_filter
+                    .as_ref()
+                    .is_none_or(|status| &entry.status == status)
+            })
+            .map(|entry| {
+                B::hstack(
+                    vec![
+                        B::vstack(
+                            vec![
+                                B::text(
+                                    format!("{} · {}", entry.tool, entry.caller),
+                                    14.0,
+                                    None,
+                                    true,
+                                    false,
+                                    None,
+                                    None,
+                                    Length::Shrink,
+                                    Alignment::Start,
+                                    context,
+                                ),
+                                B::text(
+                                    entry.arguments.clone(),
+                                    12.0,
+                                    None,
+                                    false,
+                                    false,
+                                    None,
+                                    None,
+                                    Length::Fill,
+                                    Alignment::Start,
+                                    context,
+                                ),
+                            ],
+                            2.0,
+                            iced::Padding::default(),
+                            Length::Fill,
+                            Length::Shrink,
+                            Alignment::Start,
+                            Alignment::Start,
+                            1.0,
+                        ),
+                        B::text(
+                            format!(
+                                "{} · {} ms · {}",
+                                entry.status, entry.duration_ms, entry.timestamp
+                            ),
+                            12.0,
+                            None,
+                            false,
+                            false,
+                            None,
+                            None,
+                            Length::Shrink,
+                            Alignment::End,
+                            context,
+                        ),
+                    ],
+                    12.0,
+                    iced::Padding::default(),
+                    Length::Fill,
+                    Length::Shrink,
+                    Alignment::Center,
+                    Alignment::Center,
+                    1.0,
+                )
+            })
+            .collect();
+
+        let activity = if entries.is_empty() {
+            vec![B::text(
+                "The assistant has not used any tools yet.".into(),
+                13.0,
+                None,
+                false,
+                false,
+                None,
+                None,
+                Length::Shrink,
+                Alignment::Start,
+                context,
+            )]
+        } else {
+            entries
+        };
+
+        let activity_card = self.card::<B>(
+            context,
+            B::vstack(
+                activity,
+                12.0,
+                iced::Padding::default(),
+                Length::Fill,
+                Length::Shrink,
+                Alignment::Start,
+                Alignment::Start,
+                1.0,
+            ),
+        );
+
+        B::vstack(
+            vec![
+                B::text(
+                    "Assistant Activity".into(),
+                    14.0,
+                    None,
+                    true,
+                    false,
+                    None,
+                    None,
+                    Length::Shrink,
+                    Alignment::Start,
+                    context,
+                ),
+                integrity_card,
+                B::hstack(
+                    vec![
+                        filter_button("All", None),
+                        filter_button("Denied", Some("denied")),
+                        filter_button("Failed", Some("error")),
+                    ],
+                    8.0,
+                    iced::Padding::default(),
+                    Length::Shrink,
+                    Length::Shrink,
+                    Alignment::Start,
+                    Alignment::Center,
+                    1.0,
+                ),
+                activity_card,
+            ],
+            12.0,
+            iced::Padding::default(),
+            Length::Fill,
+            Length::Shrink,
+            Alignment::Start,
+            Alignment::Start,
+            1.0,
+        )
+    }
+
     fn view_modes<B: Backend>(
         &self,
         context: &peak_ui::core::Context,
@@ -1192,6 +1564,20 @@ impl PeakApp for DesktopSettingsApp {
         message: Self::Message,
         context: &dyn ShellContext,
     ) -> iced::Task<Self::Message> {
+        #[cfg(feature = "native")]
+        if let SettingsMessage::DocumentFolderAdd = message {
+            return iced::Task::perform(
+                async {
+                    rfd::AsyncFileDialog::new()
+                        .set_title("Choose a folder of documents")
+                        .pick_folder()
+                        .await
+                        .map(|handle| handle.path().display().to_string())
+                },
+                SettingsMessage::DocumentFolderPicked,
+            );
+        }
+
         self.app.update(message, context)
     }
 
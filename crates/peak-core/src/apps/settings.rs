@@ -39,6 +39,9 @@ pub struct ModelInfo {
     pub id: String,
     pub description: String,
     pub size_estimate: String,
+    /// What the GGUF header of the downloaded file says, e.g.
+    /// "llama, 8B parameters, Q4_K_M, 128K context".
+    pub specs: Option<String>,
     pub min_ram_gb: u8,
     pub is_downloaded: bool,
     pub is_active: bool,
@@ -46,6 +49,17 @@ pub struct ModelInfo {
     pub last_error: Option<String>,
 }
 
+/// A tool invocation from the intelligence audit log, shown under Privacy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntryInfo {
+    pub timestamp: String,
+    pub caller: String,
+    pub tool: String,
+    pub arguments: String, // Secrets already redacted
+    pub status: String,
+    pub duration_ms: u64,
+}
+
 #[derive(Debug, Clone)]
 pub enum SettingsMessage {
     ThemeChanged(ThemeMode),
@@ -59,6 +73,8 @@ pub enum SettingsMessage {
     ModelDownload(String),
     ModelDownloadProgress(String, f32),
     ModelDownloadComplete(String),
+    /// The specs of every downloaded model, by model id
+    ModelSpecsLoaded(Vec<(String, String)>),
     ModelDownloadFailed(String, String),
     ModelDownloadCancel(String),
     ModelRemove(String),
@@ -71,6 +87,14 @@ pub enum SettingsMessage {
     ShellStyleChanged(crate::registry::ShellStyle),
     OpenRouterKeyChanged(String),
     ToggleCloudIntelligence(bool),
+    DocumentFolderAdd,
+    DocumentFolderPicked(Option<String>),
+    DocumentFolderRemove(String),
+    DocumentsIndexed(Vec<String>, String),
+    // Privacy
+    AuditRefresh,
+    AuditLoaded(Vec<AuditEntryInfo>, String),
+    AuditFilterChanged(Option<String>),
 }
 
 #[derive(Debug, Clone)]
@@ -93,6 +117,13 @@ pub struct SettingsApp {
     pub current_shell_style: crate::registry::ShellStyle,
     pub openrouter_key: String,
     pub cloud_intelligence_enabled: bool,
+    /// Folders whose documents the assistant may quote
+    pub document_folders: Vec<String>,
+    pub documents_status: Option<String>,
+    // Privacy
+    pub audit_entries: Vec<AuditEntryInfo>,
+    pub audit_integrity: Option<String>,
+    pub audit_filter: Option<String>, // None = all statuses
 }
 
 impl Default for SettingsApp {
@@ -118,6 +149,7 @@ impl SettingsApp {
                     id: "bartowski/Llama-3.2-3B-Instruct-GGUF".into(),
                     description: "Best balance of speed and intelligence.".into(),
                     size_estimate: "~2.4 GB".into(),
+                    specs: None,
                     min_ram_gb: 8,
                     is_downloaded: false,
                     is_active: false,
@@ -129,6 +161,7 @@ impl SettingsApp {
                     id: "bartowski/google_gemma-3-4b-it-GGUF".into(),
                     description: "Multimodal, large context (128k), efficient.".into(),
                     size_estimate: "~3.0 GB".into(),
+                    specs: None,
                     min_ram_gb: 8,
                     is_downloaded: false,
                     is_active: false,
@@ -140,6 +173,7 @@ impl SettingsApp {
                     id: "Qwen/Qwen2.5-7B-Instruct-GGUF".into(),
                     description: "Superior coding and reasoning capabilities.".into(),
                     size_estimate: "~4.5 GB".into(),
+                    specs: None,
                     min_ram_gb: 16,
                     is_downloaded: false,
                     is_active: false,
@@ -151,6 +185,7 @@ impl SettingsApp {
                     id: "bartowski/mistralai_Ministral-3-8B-Reasoning-2512-GGUF".into(),
                     description: "Strong edge model with vision support.".into(),
                     size_estimate: "~5.5 GB".into(),
+                    specs: None,
                     min_ram_gb: 16,
                     is_downloaded: false,
                     is_active: false,
@@ -166,6 +201,11 @@ impl SettingsApp {
             current_shell_style: crate::registry::ShellStyle::default(),
             openrouter_key: String::new(),
             cloud_intelligence_enabled: false,
+            document_folders: Vec::new(),
+            documents_status: None,
+            audit_entries: Vec::new(),
+            audit_integrity: None,
+            audit_filter: None,
         }
     }
 }
@@ -230,6 +270,18 @@ impl PeakApp for SettingsApp {
                     }
                 }
             }
+            SettingsMessage::ModelSpecsLoaded(specs) => {
+                for model in self
+                    .recommended_models
+                    .iter_mut()
+                    .chain(self.custom_models.iter_mut())
+                {
+                    model.specs = specs
+                        .iter()
+                        .find(|(id, _)| *id == model.id)
+                        .map(|(_, specs)| specs.clone());
+                }
+            }
             SettingsMessage::ModelDownloadFailed(id, err) => {
                 for model in self
                     .recommended_models
@@ -257,6 +309,15 @@ impl PeakApp for SettingsApp {
             }
             SettingsMessage::ModelRemove(id) => {
                 self.custom_models.retain(|m| m.id != id);
+
+                // Recommended models stay listed, without their files
+                for model in self.recommended_models.iter_mut() {
+                    if model.id == id {
+                        model.is_downloaded = false;
+                        model.is_active = false;
+                        model.specs = None;
+                    }
+                }
             }
             SettingsMessage::ModelActivate(id) => {
                 // Deactivate all first
@@ -292,6 +353,7 @@ impl PeakApp for SettingsApp {
                             id: id.clone(),
                             description: "Custom Model".into(),
                             size_estimate: "Unknown".into(),
+                            specs: None,
                             min_ram_gb: 0,
                             is_downloaded: false,
                             is_active: false,
@@ -321,6 +383,35 @@ impl PeakApp for SettingsApp {
             SettingsMessage::ToggleCloudIntelligence(enabled) => {
                 self.cloud_intelligence_enabled = enabled;
             }
+            SettingsMessage::DocumentFolderAdd => {
+                // Picked by DesktopSettingsApp
+            }
+            SettingsMessage::DocumentFolderPicked(folder) => {
+                // Indexed by PeakNative
+                if let Some(folder) = folder {
+                    if !self.document_folders.contains(&folder) {
+                        self.document_folders.push(folder);
+                    }
+                }
+            }
+            SettingsMessage::DocumentFolderRemove(folder) => {
+                self.document_folders.retain(|existing| *existing != folder);
+            }
+            SettingsMessage::DocumentsIndexed(folders, status) => {
+                // Without folders, the view explains what they are for
+                self.documents_status = (!folders.is_empty()).then_some(status);
+                self.document_folders = folders;
+            }
+            SettingsMessage::AuditRefresh => {
+                // Loaded by PeakNative
+            }
+            SettingsMessage::AuditLoaded(entries, integrity) => {
+                self.audit_entries = entries;
+                self.audit_integrity = Some(integrity);
+            }
+            SettingsMessage::AuditFilterChanged(filter) => {
+                self.audit_filter = filter;
+            }
         }
         Task::none()
     }
@@ -1,86 +1,61 @@
 #[cfg(feature = "native")]
-use regex::Regex;
-#[cfg(feature = "native")]
-use std::fs;
-use std::path::PathBuf;
+pub use peak_intelligence::steam::{InstallState, SteamGame};
 
+// The Steam reader needs a filesystem; other builds never find any games
+#[cfg(not(feature = "native"))]
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 pub struct SteamGame {
     pub app_id: String,
     pub name: String,
-    pub install_dir: PathBuf,
+    pub install_dir: std::path::PathBuf,
+    pub size_gb: f32,
+    pub last_played: u64,
+    pub state: InstallState,
+    pub is_shortcut: bool,
 }
 
-pub struct SteamScanner;
-
-impl SteamScanner {
-    // 1. Locate the Steam Directory based on OS
-    #[cfg(feature = "native")]
-    fn locate_steam_dir() -> Option<PathBuf> {
-        let home = dirs::home_dir()?;
-
-        // This is a rough heuristic. A production app might check registry (Windows) or more paths.
-        let paths = vec![
-            // MacOS
-            home.join("Library/Application Support/Steam/steamapps"),
-            // Linux
-            home.join(".local/share/Steam/steamapps"),
-            home.join(".steam/steam/steamapps"),
-            // Windows
-            PathBuf::from(r"C:\Program Files (x86)\Steam\steamapps"),
-        ];
+#[cfg(not(feature = "native"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstallState {
+    Installed,
+    Updating(f32),
+    UpdateRequired,
+    Incomplete,
+}
 
-        paths.into_iter().find(|p| p.exists())
+#[cfg(not(feature = "native"))]
+impl SteamGame {
+    pub fn launch_uri(&self) -> String {
+        if self.is_shortcut {
+            format!("steam://rungameid/{}", self.app_id)
+        } else {
+            format!("steam://run/{}", self.app_id)
+        }
     }
+}
 
-    // 2. Scan for Manifest Files
-    pub fn scan() -> Vec<SteamGame> {
-        #[allow(unused_mut)]
-        let mut games = Vec::new();
+pub struct SteamScanner;
 
+impl SteamScanner {
+    // 1. Scan every library folder, and the games added from outside of Steam
+    pub fn scan() -> Vec<SteamGame> {
         #[cfg(feature = "native")]
-        if let Some(steam_apps) = Self::locate_steam_dir() {
-            println!("✅ Found Steam Library at: {:?}", steam_apps);
-
-            // Regex to grab "appid" and "name" from the VDF file
-            let re_id = Regex::new(r#""appid"\s+"(\d+)""#).unwrap();
-            let re_name = Regex::new(r#""name"\s+"([^"]+)""#).unwrap();
+        {
+            let Some(steam) = peak_intelligence::steam::locate() else {
+                println!("⚠️ Could not locate Steam directory.");
+                return Vec::new();
+            };
 
-            if let Ok(entries) = fs::read_dir(steam_apps) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    // Look for appmanifest_12345.acf
-                    if let Some(ext) = path.extension() {
-                        if ext == "acf" {
-                            if let Ok(content) = fs::read_to_string(&path) {
-                                // Extract Data
-                                let id = re_id.captures(&content).map(|c| c[1].to_string());
-                                let name = re_name.captures(&content).map(|c| c[1].to_string());
+            println!("✅ Found Steam at: {:?}", steam);
 
-                                if let (Some(id), Some(name)) = (id, name) {
-                                    // Filter out "Steamworks Common Redistributables" (App ID 228980)
-                                    if id != "228980" {
-                                        games.push(SteamGame {
-                                            app_id: id,
-                                            name,
-                                            install_dir: path.clone(),
-                                        });
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        } else {
-            println!("⚠️ Could not locate Steam directory.");
+            peak_intelligence::steam::scan(&steam)
         }
 
-        games
+        #[cfg(not(feature = "native"))]
+        Vec::new()
     }
 
-    // 3. Launch Protocol
+    // 2. Launch Protocol
     #[allow(dead_code)]
     pub fn launch(app_id: &str) {
         #[cfg(feature = "native")]
@@ -27,9 +27,10 @@ pub enum MediaStatus {
     #[allow(dead_code)]
     Running,
     Updating(f32), // Progress 0.0 - 1.0
+    Broken,        // Files missing or being removed
 }
 
-use crate::integrations::steam::SteamScanner;
+use crate::integrations::steam::{InstallState, SteamScanner};
 
 impl MediaItem {
     pub fn scan_system() -> Vec<Self> {
@@ -38,17 +39,28 @@ impl MediaItem {
         // 1. Scan Steam
         let steam_games = SteamScanner::scan();
         for s in steam_games {
+            let launch_command = s.launch_uri();
+
             library.push(MediaItem {
                 id: s.app_id.clone(),
+                // Official Steam CDN URL format; games from outside the store have no art there
+                cover_image: if s.is_shortcut {
+                    String::new()
+                } else {
+                    format!(
+                        "https://steamcdn-a.akamaihd.net/steam/apps/{}/library_600x900.jpg",
+                        s.app_id
+                    )
+                },
                 title: s.name,
-                // Official Steam CDN URL format
-                cover_image: format!(
-                    "https://steamcdn-a.akamaihd.net/steam/apps/{}/library_600x900.jpg",
-                    s.app_id
-                ),
-                launch_command: s.app_id,
+                launch_command,
                 kind: MediaKind::Game,
-                status: MediaStatus::Ready,
+                status: match s.state {
+                    InstallState::Updating(progress) => MediaStatus::Updating(progress),
+                    InstallState::UpdateRequired => MediaStatus::Updating(0.0),
+                    InstallState::Installed => MediaStatus::Ready,
+                    InstallState::Incomplete => MediaStatus::Broken,
+                },
                 image_handle: None,
             });
         }
@@ -0,0 +1,384 @@
+//! Lets a model act by calling the tools of a [`Registry`].
+//!
+//! [`run`] sends the tool schemas along with the conversation, executes
+//! every call the model makes, feeds the results back and asks again, until
+//! the model answers without calling anything or the step limit is reached.
+use crate::llm::{Chunk, LlmClient, Message, ToolCall, ToolDefinition};
+use crate::mcp::{CallError, Context, Registry};
+use crate::policy::Risk;
+
+use futures::Stream;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Rounds of tool calls allowed before the model has to answer.
+pub const MAX_STEPS: usize = 8;
+
+/// Tool output longer than this is cut before it is fed back to the model.
+const MAX_OUTPUT: usize = 16 * 1024;
+
+/// Longest tool name OpenAI accepts.
+const MAX_NAME: usize = 64;
+
+/// The tools a model may use, and how to run them.
+#[derive(Debug, Clone)]
+pub struct Toolbox {
+    registry: Arc<Registry>,
+    context: Context,
+    max_risk: Risk,
+    max_steps: usize,
+    /// Names as seen by the model, mapped to registry names.
+    names: BTreeMap<String, String>,
+}
+
+impl Toolbox {
+    pub fn new(registry: Arc<Registry>, context: Context) -> Self {
+        let mut toolbox = Self {
+            registry,
+            context,
+            max_risk: Risk::Destructive,
+            max_steps: MAX_STEPS,
+            names: BTreeMap::new(),
+        };
+
+        toolbox.index();
+        toolbox
+    }
+
+    /// Hides tools riskier than `risk` from the model.
+    ///
+    /// Useful when nobody is around to answer the confirmations the
+    /// [`Policy`](crate::policy::Policy) asks for destructive tools.
+    pub fn max_risk(mut self, risk: Risk) -> Self {
+        self.max_risk = risk;
+        self.index();
+        self
+    }
+
+    pub fn max_steps(mut self, steps: usize) -> Self {
+        self.max_steps = steps;
+        self
+    }
+
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.names
+            .iter()
+            .filter_map(|(alias, name)| {
+                let tool = self.registry.get(name)?;
+
+                Some(ToolDefinition {
+                    name: alias.clone(),
+                    description: tool.description().to_owned(),
+                    parameters: tool.input_schema(),
+                })
+            })
+            .collect()
+    }
+
+    /// Describes what a call is about to do, e.g. "Listing processes...".
+    pub fn activity(&self, call: &ToolCall) -> String {
+        self.names
+            .get(&call.name)
+            .and_then(|name| self.registry.get(name))
+            .map(|tool| tool.activity(&call.arguments))
+            .unwrap_or_else(|| format!("Running {}...", call.name))
+    }
+
+    /// Runs a call and returns the text to feed back to the model.
+    pub async fn call(&self, call: &ToolCall) -> Outcome {
+        let Some(name) = self.names.get(&call.name) else {
+            return Outcome {
+                output: format!("Unknown tool: {}", call.name),
+                is_error: true,
+            };
+        };
+
+        match self
+            .registry
+            .call(name, Some(call.arguments.clone()), &self.context)
+            .await
+        {
+            Ok(result) => Outcome {
+                output: truncate(
+                    result
+                        .content
+                        .into_iter()
+                        .map(|content| content.text)
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+                is_error: result.is_error == Some(true),
+            },
+            // The model gets what it got wrong and the schema, to fix the call
+            Err(error @ CallError::InvalidArguments(_)) => Outcome {
+                output: format!(
+                    "{error}. Expected arguments matching: {}",
+                    self.registry
+                        .get(name)
+                        .map(|tool| tool.input_schema())
+                        .unwrap_or_default()
+                ),
+                is_error: true,
+            },
+            Err(error) => Outcome {
+                output: error.to_string(),
+                is_error: true,
+            },
+        }
+    }
+
+    fn index(&mut self) {
+        self.names.clear();
+
+        let mut tools = self.registry.list().tools;
+
+        // Names that are valid already keep them
+        tools.sort_by_key(|tool| sanitize(&tool.name) != tool.name);
+
+        for tool in tools {
+            let Some(handler) = self.registry.get(&tool.name) else {
+                continue;
+            };
+
+            let risk = match self.registry.policy() {
+                Some(policy) => policy.risk(handler.as_ref()),
+                None => handler.risk(),
+            };
+
+            if risk > self.max_risk {
+                continue;
+            }
+
+            let alias = sanitize(&tool.name);
+            let mut unique = alias.clone();
+            let mut n = 2;
+
+            while self.names.contains_key(&unique) {
+                let suffix = format!("_{n}");
+                unique = format!(
+                    "{}{suffix}",
+                    &alias[..alias.len().min(MAX_NAME - suffix.len())]
+                );
+                n += 1;
+            }
+
+            let _ = self.names.insert(unique, tool.name);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub output: String,
+    pub is_error: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Content(String),
+    ToolCalled { call: ToolCall, activity: String },
+    ToolFinished { call: ToolCall, outcome: Outcome },
+}
+
+/// Streams a reply, running tools in between as the model asks for them.
+///
+/// The last allowed step is sent without tools, so the model has to answer
+/// with what it found so far.
+pub fn run(
+    client: LlmClient,
+    mut messages: Vec<Message>,
+    toolbox: Toolbox,
+) -> impl Stream<Item = Result<Event, String>> {
+    async_stream::stream! {
+        use futures::StreamExt;
+
+        let definitions = toolbox.definitions();
+
+        for step in 0..=toolbox.max_steps {
+            let tools = if step < toolbox.max_steps {
+                definitions.as_slice()
+            } else {
+                &[]
+            };
+
+            let stream = match client.chat_stream_with_tools(&messages, tools).await {
+                Ok(stream) => stream,
+                Err(error) => {
+                    yield Err(error);
+                    return;
+                }
+            };
+
+            let mut stream = Box::pin(stream);
+            let mut content = String::new();
+            let mut calls = Vec::new();
+
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(Chunk::Content(token)) => {
+                        content.push_str(&token);
+                        yield Ok(Event::Content(token));
+                    }
+                    Ok(Chunk::ToolCall(call)) => calls.push(call),
+                    Err(error) => {
+                        yield Err(error);
+                        return;
+                    }
+                }
+            }
+
+            if calls.is_empty() {
+                return;
+            }
+
+            messages.push(Message {
+                tool_calls: calls.clone(),
+                ..Message::new("assistant", content)
+            });
+
+            for call in calls {
+                yield Ok(Event::ToolCalled {
+                    activity: toolbox.activity(&call),
+                    call: call.clone(),
+                });
+
+                let outcome = toolbox.call(&call).await;
+
+                messages.push(Message::tool_result(&call, outcome.output.clone()));
+
+                yield Ok(Event::ToolFinished { call, outcome });
+            }
+        }
+    }
+}
+
+/// Makes a tool name acceptable to OpenAI: letters, digits, `_` and `-`,
+/// at most 64 characters. Namespaced MCP tools like `git/status` become
+/// `git_status`.
+fn sanitize(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_NAME)
+        .collect();
+
+    if name.is_empty() {
+        "tool".to_owned()
+    } else {
+        name
+    }
+}
+
+fn truncate(mut output: String) -> String {
+    if output.len() > MAX_OUTPUT {
+        let mut end = MAX_OUTPUT;
+
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        output.truncate(end);
+        output.push_str("\n[output truncated]");
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::ToolHandler;
+
+    use futures::future::BoxFuture;
+    use futures::FutureExt;
+    use serde_json::{json, Value};
+
+    struct Echo(&'static str, Risk);
+
+    impl ToolHandler for Echo {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn description(&self) -> &str {
+            "Echoes its arguments."
+        }
+
+        fn input_schema(&self) -> Value {
+            json!({
+                "type": "object",
+                "properties": { "short": { "type": "boolean" } }
+            })
+        }
+
+        fn risk(&self) -> Risk {
+            self.1
+        }
+
+        fn call<'a>(
+            &'a self,
+            arguments: Value,
+            _context: &'a Context,
+        ) -> BoxFuture<'a, anyhow::Result<Value>> {
+            async move { Ok(arguments) }.boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_toolbox_sanitizes_and_filters() {
+        let mut registry = Registry::new();
+        registry.register(Echo("git/status", Risk::Safe));
+        registry.register(Echo("git_status", Risk::Safe));
+        registry.register(Echo("rm", Risk::Destructive));
+
+        let (sender, _receiver) = tokio::sync::mpsc::channel(1);
+        let toolbox = Toolbox::new(Arc::new(registry), Context::new(sender)).max_risk(Risk::Safe);
+
+        let names: Vec<_> = toolbox
+            .definitions()
+            .into_iter()
+            .map(|definition| definition.name)
+            .collect();
+        assert_eq!(names, ["git_status", "git_status_2"]);
+
+        let outcome = toolbox
+            .call(&ToolCall {
+                id: "call_1".to_owned(),
+                name: "git_status".to_owned(),
+                arguments: json!({ "short": true }),
+            })
+            .await;
+        assert_eq!(outcome.output, r#"{"short":true}"#);
+        assert!(!outcome.is_error);
+
+        // Malformed arguments are kept as text and fail validation
+        let outcome = toolbox
+            .call(&ToolCall {
+                id: "call_2".to_owned(),
+                name: "git_status".to_owned(),
+                arguments: json!("{\"short\": tr"),
+            })
+            .await;
+        assert!(outcome.is_error);
+        assert!(outcome
+            .output
+            .starts_with("Invalid arguments: must be of type \"object\", got string."));
+        assert!(outcome.output.contains(r#""short":{"type":"boolean"}"#));
+
+        let outcome = toolbox
+            .call(&ToolCall {
+                id: "call_3".to_owned(),
+                name: "rm".to_owned(),
+                arguments: json!({}),
+            })
+            .await;
+        assert!(outcome.is_error);
+    }
+}
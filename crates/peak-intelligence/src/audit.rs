@@ -0,0 +1,448 @@
+//! Append-only audit log of tool invocations.
+//!
+//! Every `tools/call` is written as one JSON line to `audit.jsonl` in the
+//! data directory. Each entry stores the SHA-256 hash of the previous entry
+//! and its own hash, so editing or removing an entry breaks the chain from
+//! that point on and [`Log::verify`] reports where.
+//!
+//! The chain cannot tell whether the newest entries were cut off; a
+//! truncated log still verifies, it just ends earlier.
+//!
+//! Several processes may write to the same log; appends take a lock on the
+//! file and continue from its last entry. Arguments are stored as the
+//! tool's [`ToolHandler::audit_summary`] leaves them: secrets are redacted,
+//! and contents such as file bodies, keystrokes or audio are reduced to their
+//! size, but the command that was run or the process that was killed is kept.
+use crate::brain::{directory, Error};
+#[cfg(doc)]
+use crate::mcp::ToolHandler;
+use crate::policy::Caller;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Hash of the (imaginary) entry before the first one.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Error,
+    Denied,
+    InvalidArguments,
+    NotFound,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ok => "ok",
+            Self::Error => "error",
+            Self::Denied => "denied",
+            Self::InvalidArguments => "invalid_arguments",
+            Self::NotFound => "not_found",
+        })
+    }
+}
+
+/// What happened during a call, as reported by the registry.
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub caller: Caller,
+    pub tool: String,
+    /// Arguments as [`ToolHandler::audit_summary`] leaves them.
+    pub arguments: Value,
+    pub status: Status,
+    pub message: Option<String>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub sequence: u64,
+    pub timestamp: DateTime<Utc>,
+    pub caller: Caller,
+    pub tool: String,
+    pub arguments: Value,
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub duration_ms: u64,
+    pub previous: String,
+    pub hash: String,
+}
+
+impl Entry {
+    fn digest(&self) -> String {
+        let mut unsigned = self.clone();
+        unsigned.hash = String::new();
+
+        let bytes = serde_json::to_vec(&unsigned).expect("entries serialize to JSON");
+
+        Sha256::digest(&bytes)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
+
+/// Filters for [`Log::query`]. Results are returned newest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Query {
+    pub tool: Option<String>,
+    pub app: Option<String>,
+    pub model: Option<String>,
+    pub status: Option<Status>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl Query {
+    fn matches(&self, entry: &Entry) -> bool {
+        self.tool.as_ref().is_none_or(|tool| &entry.tool == tool)
+            && self
+                .app
+                .as_ref()
+                .is_none_or(|app| entry.caller.app.as_ref() == Some(app))
+            && self
+                .model
+                .as_ref()
+                .is_none_or(|model| entry.caller.model.as_ref() == Some(model))
+            && self.status.is_none_or(|status| entry.status == status)
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.until.is_none_or(|until| entry.timestamp <= until)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum Integrity {
+    Intact { entries: u64 },
+    Tampered { line: usize, reason: String },
+}
+
+pub struct Log {
+    path: PathBuf,
+}
+
+impl Log {
+    /// Opens the log in the data directory.
+    pub fn open_default() -> Result<Self, Error> {
+        Self::open(directory::data().join("audit.jsonl"))
+    }
+
+    /// Opens a log. If its last entry is corrupt, the log can still be read
+    /// and verified, but appending fails until it is repaired.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let log = Self { path: path.into() };
+
+        if let Some(mut file) = log.file(false)? {
+            file.lock_shared()?;
+
+            if let Err(error) = log.last(&mut file) {
+                log::error!("Audit log cannot be appended to: {error}");
+            }
+        }
+
+        Ok(log)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, record: Record) -> Result<Entry, Error> {
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+
+        let mut file = self.file(true)?.expect("created log file");
+
+        // Other processes append too; continue from whatever is last now
+        file.lock()?;
+
+        let (sequence, previous) = match self.last(&mut file)? {
+            Some(last) => (last.sequence + 1, last.hash),
+            None => (0, GENESIS.to_owned()),
+        };
+
+        let mut entry = Entry {
+            sequence,
+            timestamp: Utc::now(),
+            caller: record.caller,
+            tool: record.tool,
+            arguments: record.arguments,
+            status: record.status,
+            message: record.message,
+            duration_ms: record.duration.as_millis() as u64,
+            previous,
+            hash: String::new(),
+        };
+
+        entry.hash = entry.digest();
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+
+        file.write_all(&line)?;
+        file.flush()?;
+
+        Ok(entry)
+    }
+
+    pub fn query(&self, query: &Query) -> Result<Vec<Entry>, Error> {
+        Ok(self
+            .read()?
+            .into_iter()
+            .rev()
+            .filter_map(Result::ok)
+            .filter(|entry| query.matches(entry))
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect())
+    }
+
+    /// Walks the whole chain and reports the first broken link.
+    pub fn verify(&self) -> Result<Integrity, Error> {
+        let mut previous = GENESIS.to_owned();
+        let mut entries = 0;
+
+        for (i, entry) in self.read()?.into_iter().enumerate() {
+            let line = i + 1;
+
+            let tampered = |reason: &str| {
+                Ok(Integrity::Tampered {
+                    line,
+                    reason: reason.to_owned(),
+                })
+            };
+
+            let Ok(entry) = entry else {
+                return tampered("entry is not valid JSON");
+            };
+
+            if entry.sequence != entries {
+                return tampered("sequence number is out of order");
+            }
+
+            if entry.previous != previous {
+                return tampered("entry does not follow the previous one");
+            }
+
+            if entry.digest() != entry.hash {
+                return tampered("entry was modified");
+            }
+
+            previous = entry.hash;
+            entries += 1;
+        }
+
+        Ok(Integrity::Intact { entries })
+    }
+
+    /// Opens the log file for appending, creating it if asked to.
+    fn file(&self, create: bool) -> Result<Option<File>, Error> {
+        let mut options = OpenOptions::new();
+        let _ = options.read(true).append(true).create(create);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+
+            let _ = options.mode(0o600);
+        }
+
+        match options.open(&self.path) {
+            Ok(file) => Ok(Some(file)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// The last entry of a locked log file.
+    fn last(&self, file: &mut File) -> Result<Option<Entry>, Error> {
+        const CHUNK: u64 = 4096;
+
+        let mut end = file.seek(SeekFrom::End(0))?;
+        let mut tail = Vec::new();
+
+        // Read backwards until the start of the last line
+        let line = loop {
+            let start = end.saturating_sub(CHUNK);
+            let mut chunk = vec![0; (end - start) as usize];
+
+            let _ = file.seek(SeekFrom::Start(start))?;
+            file.read_exact(&mut chunk)?;
+
+            chunk.extend_from_slice(&tail);
+            tail = chunk;
+            end = start;
+
+            let trimmed = tail.trim_ascii_end();
+
+            if let Some(newline) = trimmed.iter().rposition(|byte| *byte == b'\n') {
+                break &trimmed[newline + 1..];
+            }
+
+            if start == 0 {
+                break trimmed;
+            }
+        };
+
+        if line.is_empty() {
+            return Ok(None);
+        }
+
+        serde_json::from_slice(line).map(Some).map_err(|error| {
+            Error::from(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the last entry of {} is corrupt, so the chain cannot continue: {error}",
+                    self.path.display()
+                ),
+            ))
+        })
+    }
+
+    fn read(&self) -> Result<Vec<Result<Entry, serde_json::Error>>, Error> {
+        let Some(file) = self.file(false)? else {
+            return Ok(Vec::new());
+        };
+
+        // Appends in progress finish first
+        file.lock_shared()?;
+
+        let mut entries = Vec::new();
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(&line));
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+/// What the log keeps of the arguments of a call, unless the tool says
+/// otherwise in [`ToolHandler::audit_summary`].
+///
+/// Secrets are redacted and the top-level `contents` fields are replaced by
+/// their size: strings by their length and arrays by their number of items.
+/// Everything else is kept, so the log can tell what a call did.
+pub fn summarize(arguments: &Value, contents: &[&str]) -> Value {
+    let mut summary = crate::policy::redact(arguments);
+
+    if let Value::Object(object) = &mut summary {
+        for (key, value) in object.iter_mut() {
+            if !contents.contains(&key.as_str()) {
+                continue;
+            }
+
+            *value = match value {
+                Value::String(string) => Value::String(format!("<{} bytes>", string.len())),
+                Value::Array(items) => Value::String(format!("<{} items>", items.len())),
+                _ => continue,
+            };
+        }
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(tool: &str, status: Status) -> Record {
+        Record {
+            caller: Caller {
+                app: Some("desktop".to_owned()),
+                ..Caller::default()
+            },
+            tool: tool.to_owned(),
+            arguments: json!({ "path": "/tmp/notes.txt" }),
+            status,
+            message: None,
+            duration: Duration::from_millis(3),
+        }
+    }
+
+    #[test]
+    fn test_chain_detects_tampering() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+
+        let log = Log::open(&path).unwrap();
+        let _ = log.append(record("read_file", Status::Ok)).unwrap();
+
+        // A second writer, e.g. the desktop next to the daemon, continues the chain
+        let other = Log::open(&path).unwrap();
+        let _ = other.append(record("write_file", Status::Denied)).unwrap();
+        let _ = log.append(record("kill_process", Status::Error)).unwrap();
+
+        assert_eq!(log.verify().unwrap(), Integrity::Intact { entries: 3 });
+
+        let denied = log
+            .query(&Query {
+                status: Some(Status::Denied),
+                ..Query::default()
+            })
+            .unwrap();
+        assert_eq!(denied.len(), 1);
+        assert_eq!(denied[0].tool, "write_file");
+
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::write(&path, contents.replace("write_file", "read_dir")).unwrap();
+
+        assert!(matches!(
+            log.verify().unwrap(),
+            Integrity::Tampered { line: 2, .. }
+        ));
+
+        // A corrupt last entry stops the chain instead of restarting it
+        fs::write(&path, format!("{contents}{{\"sequence\": 3,\n")).unwrap();
+
+        let log = Log::open(&path).unwrap();
+        assert!(log.append(record("read_file", Status::Ok)).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 4);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let arguments = json!({
+            "path": "/home/user/notes.txt",
+            "content": "Dear diary",
+            "password": "hunter2",
+            "audio": [0.1, 0.2, 0.3],
+            "options": { "append": true, "limit": 10, "api_key": "sk-123" },
+        });
+
+        assert_eq!(
+            summarize(&arguments, &["content", "audio"]),
+            json!({
+                "path": "/home/user/notes.txt",
+                "content": "<10 bytes>",
+                "password": "[redacted]",
+                "audio": "<3 items>",
+                "options": { "append": true, "limit": 10, "api_key": "[redacted]" },
+            })
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Starting llama-server.
+//!
+//! `llama_server::Settings` only knows the port and the GPU layers, so the
+//! server is started here instead, with the whole [`Tuning`] for the machine.
+use crate::brain::hardware::Tuning;
+use crate::brain::model::gguf;
+use crate::brain::Error;
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+/// How long a model may take to load.
+const READY_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Launch {
+    pub model: PathBuf,
+    pub port: u32,
+    pub tuning: Tuning,
+    /// The header of the model file; its chat template and context length
+    /// are applied, if known.
+    pub metadata: Option<gguf::Metadata>,
+}
+
+impl Launch {
+    /// The command-line arguments of llama-server.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--model".to_owned(),
+            self.model.display().to_string(),
+            "--host".to_owned(),
+            "127.0.0.1".to_owned(),
+            "--port".to_owned(),
+            self.port.to_string(),
+            // Tool calls are only understood with the model's own chat template
+            "--jinja".to_owned(),
+            // Documents are indexed and searched with `/embedding`
+            "--embeddings".to_owned(),
+        ];
+
+        // Given explicitly, so builds that miss the template in the header
+        // still use it
+        if let Some(template) = self
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.chat_template.as_ref())
+        {
+            args.push("--chat-template".to_owned());
+            args.push(template.clone());
+        }
+
+        args.extend(self.tuning().args());
+        args
+    }
+
+    /// The environment of llama-server; the same settings, for builds that
+    /// ignore some of the arguments.
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        self.tuning().env()
+    }
+
+    /// The [`Tuning`], with the context capped to the length the model was
+    /// trained with; past it, models produce garbage.
+    fn tuning(&self) -> Tuning {
+        let mut tuning = self.tuning;
+
+        if let Some(trained) = self
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.context_length)
+        {
+            tuning.context = tuning.context.min(trained);
+        }
+
+        tuning
+    }
+
+    /// The command that starts `executable` with these settings.
+    pub fn command(&self, executable: &Path) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(executable);
+
+        let _ = command
+            .args(self.args())
+            .envs(self.env())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        command
+    }
+}
+
+/// A running llama-server, stopped when dropped.
+#[derive(Debug)]
+pub struct Process(pub tokio::process::Child);
+
+impl Process {
+    /// Waits until the server has loaded the model and answers.
+    pub async fn wait_until_ready(&mut self, port: u32) -> Result<(), Error> {
+        let url = format!("http://127.0.0.1:{port}/health");
+        let start = std::time::Instant::now();
+
+        loop {
+            if let Some(status) = self.0.try_wait()? {
+                return Err(Error::ServerExited(status.to_string()));
+            }
+
+            // 503 while the model is loading
+            if crate::http::HttpClient::get(&url)
+                .await
+                .is_ok_and(|response| response.status == 200)
+            {
+                return Ok(());
+            }
+
+            if start.elapsed() > READY_TIMEOUT {
+                return Err(Error::ServerExited(
+                    "the model did not finish loading".to_owned(),
+                ));
+            }
+
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let launch = Launch {
+            model: PathBuf::from("/models/qwen.gguf"),
+            port: 8123,
+            tuning: Tuning {
+                threads: 7,
+                context: 8192,
+                batch: 512,
+                mmap: false,
+                mlock: true,
+                gpu_layers: 0,
+            },
+            metadata: None,
+        };
+
+        let command = launch.command(Path::new("/opt/llama-server"));
+        let command = command.as_std();
+
+        let args: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+
+        assert_eq!(command.get_program(), "/opt/llama-server");
+        assert_eq!(
+            args.join(" "),
+            "--model /models/qwen.gguf --host 127.0.0.1 --port 8123 --jinja --embeddings \
+             --threads 7 --ctx-size 8192 --batch-size 512 --n-gpu-layers 0 \
+             --no-mmap --mlock"
+        );
+
+        let env: Vec<_> = command
+            .get_envs()
+            .map(|(key, value)| {
+                (
+                    key.to_string_lossy().into_owned(),
+                    value.map(|value| value.to_string_lossy().into_owned()),
+                )
+            })
+            .collect();
+
+        assert!(env.contains(&("LLAMA_ARG_CTX_SIZE".to_owned(), Some("8192".to_owned()))));
+        assert!(env.contains(&("LLAMA_ARG_MLOCK".to_owned(), Some("1".to_owned()))));
+
+        // The header of the model applies its template and trained context
+        let launch = Launch {
+            metadata: Some(gguf::Metadata {
+                context_length: Some(4096),
+                chat_template: Some("{{ messages }}".to_owned()),
+                ..gguf::Metadata::default()
+            }),
+            ..launch
+        };
+
+        let args = launch.args();
+
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--chat-template", "{{ messages }}"]));
+        assert!(args.windows(2).any(|pair| pair == ["--ctx-size", "4096"]));
+        assert!(launch
+            .env()
+            .contains(&("LLAMA_ARG_CTX_SIZE", "4096".to_owned())));
+    }
+}
@@ -0,0 +1,354 @@
+//! Backups of chats as Markdown, JSON or HTML.
+//!
+//! JSON exports keep everything and can be imported again with
+//! [`import`](super::import); Markdown and HTML are meant for reading.
+use crate::brain::assistant::{Reasoning, Reply};
+use crate::brain::chat::{schema, Chat, Item};
+use crate::brain::plan::{Outcome, Plan, Status};
+use crate::brain::Error;
+
+use serde_json::json;
+
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Json,
+    Html,
+}
+
+impl Format {
+    pub const ALL: &[Self] = &[Self::Markdown, Self::Json, Self::Html];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::Html => "html",
+        }
+    }
+}
+
+/// Exports one or more chats into a single document.
+///
+/// A single chat is exported as JSON on its own; several are wrapped in
+/// `{ "chats": [...] }`.
+pub fn export(chats: &[Chat], format: Format) -> Result<String, Error> {
+    match format {
+        Format::Json => {
+            let mut values = chats
+                .iter()
+                .map(schema::encode_value)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let value = if values.len() == 1 {
+                values.remove(0)
+            } else {
+                json!({ "version": schema::VERSION, "chats": values })
+            };
+
+            Ok(serde_json::to_string_pretty(&value)?)
+        }
+        Format::Markdown => {
+            let mut markdown = Markdown(String::new());
+
+            for (i, chat) in chats.iter().enumerate() {
+                if i > 0 {
+                    markdown.0.push_str("\n---\n\n");
+                }
+
+                render(&mut markdown, chat);
+            }
+
+            Ok(markdown.0)
+        }
+        Format::Html => {
+            let mut html = Html(String::new());
+
+            for chat in chats {
+                html.0.push_str("<article>\n");
+                render(&mut html, chat);
+                html.0.push_str("</article>\n");
+            }
+
+            let title = match chats {
+                [chat] => chat.title.as_deref().unwrap_or(UNTITLED),
+                _ => "Chats",
+            };
+
+            Ok(format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                <title>{}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+                escape(title),
+                html.0
+            ))
+        }
+    }
+}
+
+const UNTITLED: &str = "Untitled chat";
+
+const STYLE: &str = "body{font-family:sans-serif;max-width:48rem;margin:2rem auto;padding:0 1rem;line-height:1.5}\
+    article{margin-bottom:4rem}h3{margin-bottom:0}pre{background:#f4f4f4;padding:.75rem;overflow-x:auto}\
+    details,blockquote{color:#555}";
+
+/// The pieces a chat is rendered with.
+trait Document {
+    fn title(&mut self, title: &str, model: &str);
+    fn speaker(&mut self, name: &str);
+    fn text(&mut self, text: &str);
+    fn reasoning(&mut self, reasoning: &Reasoning);
+    fn list(&mut self, items: &[String], ordered: bool);
+    fn code(&mut self, code: &str);
+    fn note(&mut self, note: &str);
+}
+
+fn render(document: &mut impl Document, chat: &Chat) {
+    document.title(
+        chat.title.as_deref().unwrap_or(UNTITLED),
+        &format!("{} ({})", chat.file.model.0, chat.file.name),
+    );
+
+    for item in &chat.history {
+        match item {
+            Item::User(message) => {
+                document.speaker("User");
+                document.text(message);
+            }
+            Item::Reply(reply_) => {
+                document.speaker("Assistant");
+                reply(document, reply_);
+            }
+            Item::Plan(plan_) => {
+                document.speaker("Assistant");
+                plan(document, plan_);
+            }
+            Item::Summary(summary) => {
+                document.note("Earlier messages were summarized for the model:");
+                document.text(&summary.content);
+            }
+        }
+    }
+}
+
+fn reply(document: &mut impl Document, reply: &Reply) {
+    if let Some(reasoning) = &reply.reasoning {
+        document.reasoning(reasoning);
+    }
+
+    if !reply.tools.is_empty() {
+        let tools: Vec<_> = reply
+            .tools
+            .iter()
+            .map(|tool| {
+                if tool.is_error {
+                    format!("{} (failed)", tool.activity)
+                } else {
+                    tool.activity.clone()
+                }
+            })
+            .collect();
+
+        document.list(&tools, false);
+    }
+
+    document.text(&reply.content);
+
+    if !reply.sources.is_empty() {
+        let sources: Vec<_> = reply
+            .sources
+            .iter()
+            .enumerate()
+            .map(|(i, source)| {
+                format!(
+                    "[{}] {}, lines {}-{}",
+                    i + 1,
+                    source.path.display(),
+                    source.start_line,
+                    source.end_line
+                )
+            })
+            .collect();
+
+        document.list(&sources, false);
+    }
+}
+
+fn plan(document: &mut impl Document, plan: &Plan) {
+    if let Some(reasoning) = &plan.reasoning {
+        document.reasoning(reasoning);
+    }
+
+    let steps: Vec<_> = plan
+        .steps
+        .iter()
+        .map(|step| {
+            format!(
+                "{} ({}: {})",
+                step.description,
+                step.function,
+                step.inputs.join(", ")
+            )
+        })
+        .collect();
+
+    document.note("Plan:");
+    document.list(&steps, true);
+
+    for outcome in &plan.outcomes {
+        let status = match outcome {
+            Outcome::Search(status) => status.as_ref().map(|links| {
+                document.note("Search results:");
+                document.list(
+                    &links.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                    false,
+                );
+            }),
+            Outcome::ScrapeText(status) => status.as_ref().map(|summaries| {
+                for summary in summaries {
+                    document.note(&format!("Summary of {}:", summary.url));
+                    document.text(&summary.content);
+                }
+            }),
+            Outcome::Tool(status) => status.as_ref().map(|action| {
+                document.note(&action.activity);
+                document.code(&action.output);
+            }),
+            Outcome::Answer(status) => status.as_ref().map(|reply_| reply(document, reply_)),
+        };
+
+        if let Status::Errored(error) = status {
+            document.note(&format!("Failed: {error}"));
+        }
+    }
+}
+
+struct Markdown(String);
+
+impl Document for Markdown {
+    fn title(&mut self, title: &str, model: &str) {
+        let _ = write!(self.0, "# {title}\n\n*{model}*\n\n");
+    }
+
+    fn speaker(&mut self, name: &str) {
+        let _ = write!(self.0, "### {name}\n\n");
+    }
+
+    fn text(&mut self, text: &str) {
+        let _ = write!(self.0, "{}\n\n", text.trim());
+    }
+
+    fn reasoning(&mut self, reasoning: &Reasoning) {
+        let _ = writeln!(
+            self.0,
+            "> *Reasoned for {} seconds*\n>",
+            reasoning.duration.as_secs()
+        );
+
+        for line in reasoning.content.trim().lines() {
+            let _ = writeln!(self.0, "> {line}");
+        }
+
+        self.0.push('\n');
+    }
+
+    fn list(&mut self, items: &[String], ordered: bool) {
+        for (i, item) in items.iter().enumerate() {
+            if ordered {
+                let _ = writeln!(self.0, "{}. {item}", i + 1);
+            } else {
+                let _ = writeln!(self.0, "- {item}");
+            }
+        }
+
+        self.0.push('\n');
+    }
+
+    fn code(&mut self, code: &str) {
+        // Outputs may contain fences of their own
+        let mut fence = "```".to_owned();
+
+        while code.contains(&fence) {
+            fence.push('`');
+        }
+
+        let _ = write!(self.0, "{fence}\n{}\n{fence}\n\n", code.trim_end());
+    }
+
+    fn note(&mut self, note: &str) {
+        let _ = write!(self.0, "*{note}*\n\n");
+    }
+}
+
+struct Html(String);
+
+impl Document for Html {
+    fn title(&mut self, title: &str, model: &str) {
+        let _ = writeln!(
+            self.0,
+            "<h1>{}</h1>\n<p><em>{}</em></p>",
+            escape(title),
+            escape(model)
+        );
+    }
+
+    fn speaker(&mut self, name: &str) {
+        let _ = writeln!(self.0, "<h3>{}</h3>", escape(name));
+    }
+
+    fn text(&mut self, text: &str) {
+        for paragraph in text.trim().split("\n\n") {
+            let _ = writeln!(
+                self.0,
+                "<p>{}</p>",
+                escape(paragraph.trim()).replace('\n', "<br>\n")
+            );
+        }
+    }
+
+    fn reasoning(&mut self, reasoning: &Reasoning) {
+        let _ = writeln!(
+            self.0,
+            "<details><summary>Reasoned for {} seconds</summary>\n<blockquote>{}</blockquote>\n</details>",
+            reasoning.duration.as_secs(),
+            escape(reasoning.content.trim()).replace('\n', "<br>\n")
+        );
+    }
+
+    fn list(&mut self, items: &[String], ordered: bool) {
+        let tag = if ordered { "ol" } else { "ul" };
+
+        let _ = writeln!(self.0, "<{tag}>");
+
+        for item in items {
+            let _ = writeln!(self.0, "<li>{}</li>", escape(item));
+        }
+
+        let _ = writeln!(self.0, "</{tag}>");
+    }
+
+    fn code(&mut self, code: &str) {
+        let _ = writeln!(self.0, "<pre>{}</pre>", escape(code.trim_end()));
+    }
+
+    fn note(&mut self, note: &str) {
+        let _ = writeln!(self.0, "<p><em>{}</em></p>", escape(note));
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
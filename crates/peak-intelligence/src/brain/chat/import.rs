@@ -0,0 +1,296 @@
+//! Chats brought back from backups or from other assistants.
+//!
+//! Understands:
+//!
+//! - JSON exports, of a single chat or of several (see [`export`](super::export)).
+//! - The `conversations.json` file of an OpenAI (ChatGPT) data export.
+//! - Plain message lists, like `[{ "role": "user", "content": "..." }]` or
+//!   `{ "messages": [...] }`.
+use crate::brain::assistant::Reply;
+use crate::brain::chat::{schema, Chat, Id, Item};
+use crate::brain::model;
+use crate::brain::Error;
+
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Parses every chat in `json`.
+///
+/// Imported chats from other assistants get new ids; chats from backups
+/// keep theirs.
+pub fn import(json: &str) -> Result<Vec<Chat>, Error> {
+    let value: Value = serde_json::from_str(json)?;
+
+    let chats = match value {
+        Value::Object(ref fields) if fields.contains_key("chats") => match value["chats"].clone() {
+            Value::Array(chats) => chats
+                .into_iter()
+                .map(schema::decode_value)
+                .collect::<Result<_, _>>()?,
+            _ => return Err(invalid("`chats` is not a list")),
+        },
+        Value::Object(ref fields) if fields.contains_key("history") => {
+            vec![schema::decode_value(value)?]
+        }
+        Value::Object(ref fields) if fields.contains_key("mapping") => {
+            vec![openai(&value).ok_or_else(|| invalid("empty conversation"))?]
+        }
+        Value::Object(ref fields) if fields.contains_key("messages") => {
+            vec![messages(&value["messages"], title(&value))?]
+        }
+        Value::Array(ref items) => match items.first() {
+            None => Vec::new(),
+            Some(item) if item.get("mapping").is_some() => {
+                items.iter().filter_map(openai).collect()
+            }
+            Some(item) if item.get("history").is_some() => items
+                .iter()
+                .cloned()
+                .map(schema::decode_value)
+                .collect::<Result<_, _>>()?,
+            Some(item) if item.get("role").is_some() => vec![messages(&value, None)?],
+            Some(_) => return Err(invalid("unknown list of items")),
+        },
+        _ => return Err(invalid("unknown format")),
+    };
+
+    Ok(chats)
+}
+
+/// Follows an OpenAI conversation from its current message back to the
+/// root, keeping the visible text of the user and the assistant.
+fn openai(conversation: &Value) -> Option<Chat> {
+    let mapping = conversation["mapping"].as_object()?;
+
+    let mut node = conversation["current_node"]
+        .as_str()
+        .filter(|id| mapping.contains_key(*id))
+        .or_else(|| {
+            // Older exports have no current node; take the latest leaf
+            mapping
+                .iter()
+                .filter(|(_, node)| node["children"].as_array().is_none_or(Vec::is_empty))
+                .max_by(|(_, a), (_, b)| {
+                    let time =
+                        |node: &Value| node["message"]["create_time"].as_f64().unwrap_or(0.0);
+
+                    time(a).total_cmp(&time(b))
+                })
+                .map(|(id, _)| id.as_str())
+        });
+
+    let mut messages = Vec::new();
+    let mut model = conversation["default_model_slug"].as_str();
+    let mut visited = 0;
+
+    while let Some(id) = node.take() {
+        let Some(entry) = mapping.get(id) else {
+            break;
+        };
+
+        // Guards against cycles in broken exports
+        visited += 1;
+
+        if visited > mapping.len() {
+            break;
+        }
+
+        let message = &entry["message"];
+        let is_hidden = message["metadata"]["is_visually_hidden_from_conversation"] == true;
+
+        if let (Some(role), Some(text), false) = (
+            message["author"]["role"].as_str(),
+            openai_text(&message["content"]),
+            is_hidden,
+        ) {
+            if role == "assistant" && model.is_none() {
+                model = message["metadata"]["model_slug"].as_str();
+            }
+
+            messages.push((role.to_owned(), text));
+        }
+
+        node = entry["parent"].as_str();
+    }
+
+    messages.reverse();
+
+    let history: Vec<_> = messages
+        .into_iter()
+        .filter_map(|(role, text)| item(&role, text))
+        .collect();
+
+    if history.is_empty() {
+        return None;
+    }
+
+    Some(Chat {
+        id: Id(Uuid::new_v4()),
+        file: file("openai", model.unwrap_or("chatgpt")),
+        title: title(conversation),
+        history,
+    })
+}
+
+fn openai_text(content: &Value) -> Option<String> {
+    if !matches!(
+        content["content_type"].as_str(),
+        Some("text" | "multimodal_text")
+    ) {
+        return None;
+    }
+
+    let text = content["parts"]
+        .as_array()?
+        .iter()
+        .filter_map(Value::as_str)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    (!text.trim().is_empty()).then_some(text)
+}
+
+fn messages(messages: &Value, title: Option<String>) -> Result<Chat, Error> {
+    let history: Vec<_> = messages
+        .as_array()
+        .ok_or_else(|| invalid("`messages` is not a list"))?
+        .iter()
+        .filter_map(|message| {
+            let content = match &message["content"] {
+                Value::String(content) => content.clone(),
+                // Content parts, as in the OpenAI chat API
+                Value::Array(parts) => parts
+                    .iter()
+                    .filter_map(|part| part["text"].as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n\n"),
+                _ => return None,
+            };
+
+            item(message["role"].as_str()?, content)
+        })
+        .collect();
+
+    Ok(Chat {
+        id: Id(Uuid::new_v4()),
+        file: file("imported", "unknown"),
+        title,
+        history,
+    })
+}
+
+fn item(role: &str, content: String) -> Option<Item> {
+    match role {
+        "user" => Some(Item::User(content)),
+        "assistant" => Some(Item::Reply(Reply {
+            content,
+            ..Reply::default()
+        })),
+        _ => None,
+    }
+}
+
+fn title(value: &Value) -> Option<String> {
+    value["title"]
+        .as_str()
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .map(str::to_owned)
+}
+
+/// Chats belong to a model file; imported ones name the model they came
+/// from instead.
+fn file(author: &str, model: &str) -> model::File {
+    model::File {
+        model: model::Id(format!("{author}/{model}")),
+        name: model.to_owned(),
+        size: None,
+        sha256: None,
+        metadata: None,
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::RequestFailed(format!("cannot import chats: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brain::chat::export::{self, Format};
+
+    #[test]
+    fn test_openai_conversations() {
+        let json = r#"[{
+            "title": "Sourdough",
+            "current_node": "c",
+            "mapping": {
+                "root": { "id": "root", "message": null, "parent": null, "children": ["s"] },
+                "s": {
+                    "id": "s", "parent": "root", "children": ["a"],
+                    "message": {
+                        "author": { "role": "system" },
+                        "content": { "content_type": "text", "parts": [""] },
+                        "metadata": { "is_visually_hidden_from_conversation": true }
+                    }
+                },
+                "a": {
+                    "id": "a", "parent": "s", "children": ["b", "b2"],
+                    "message": {
+                        "author": { "role": "user" },
+                        "content": { "content_type": "text", "parts": ["How long should it proof?"] },
+                        "metadata": {}
+                    }
+                },
+                "b2": {
+                    "id": "b2", "parent": "a", "children": [],
+                    "message": {
+                        "author": { "role": "assistant" },
+                        "content": { "content_type": "text", "parts": ["An abandoned branch."] },
+                        "metadata": { "model_slug": "gpt-4o" }
+                    }
+                },
+                "b": {
+                    "id": "b", "parent": "a", "children": ["c"],
+                    "message": {
+                        "author": { "role": "assistant" },
+                        "content": { "content_type": "text", "parts": ["About 4 hours."] },
+                        "metadata": { "model_slug": "gpt-4o" }
+                    }
+                },
+                "c": {
+                    "id": "c", "parent": "b", "children": [],
+                    "message": {
+                        "author": { "role": "user" },
+                        "content": { "content_type": "text", "parts": ["Thanks!"] },
+                        "metadata": {}
+                    }
+                }
+            }
+        }]"#;
+
+        let chats = import(json).unwrap();
+        assert_eq!(chats.len(), 1);
+
+        let chat = &chats[0];
+        assert_eq!(chat.title.as_deref(), Some("Sourdough"));
+        assert_eq!(chat.file.model.0, "openai/gpt-4o");
+        assert_eq!(chat.history.len(), 3);
+        assert!(
+            matches!(&chat.history[1], Item::Reply(reply) if reply.content == "About 4 hours.")
+        );
+
+        // Exports survive a round trip
+        let exported = export::export(&chats, Format::Json).unwrap();
+        let imported = import(&exported).unwrap();
+        assert_eq!(imported[0].id, chat.id);
+        assert_eq!(imported[0].history.len(), 3);
+
+        let markdown = export::export(&chats, Format::Markdown).unwrap();
+        assert!(markdown.starts_with("# Sourdough\n"));
+        assert!(markdown.contains("### Assistant\n\nAbout 4 hours.\n"));
+
+        let html = export::export(&chats, Format::Html).unwrap();
+        assert!(html.contains("<p>How long should it proof?</p>"));
+    }
+}
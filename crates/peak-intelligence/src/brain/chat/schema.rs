@@ -1,20 +1,83 @@
+//! How chats are stored.
+//!
+//! Every chat carries the `version` of its format. Older chats are upgraded
+//! one version at a time by [`MIGRATIONS`] before they are decoded:
+//!
+//! - 0: the first format, derived by `serde` (see `old.rs`).
+//! - 1: tagged items, written by `encode.rs`, without a version.
+//! - 2: the `version` field.
 use crate::brain::{Chat, Error};
 
+use serde_json::Value;
+
 mod decode;
 mod encode;
 mod old;
 
+/// The version written by [`encode`].
+pub const VERSION: u64 = 2;
+
+/// Migration `n` upgrades a chat from version `n` to `n + 1`.
+const MIGRATIONS: [fn(Value) -> Result<Value, Error>; VERSION as usize] = [v0_to_v1, v1_to_v2];
+
 pub fn decode(json: &str) -> Result<Chat, Error> {
-    match decoder::run(serde_json::from_str, decode::chat, json) {
-        Ok(chat) => {
-            return Ok(chat);
-        }
-        Err(error) => {
-            log::warn!("{error:?}");
-        }
+    let value: Value = serde_json::from_str(json)?;
+
+    decode_value(value)
+}
+
+pub fn encode(chat: &Chat) -> Result<String, Error> {
+    Ok(serde_json::to_string_pretty(&encode_value(chat)?)?)
+}
+
+/// Migrates and decodes a chat that was already parsed.
+pub fn decode_value(mut value: Value) -> Result<Chat, Error> {
+    let version = version(&value);
+
+    if version > VERSION {
+        return Err(Error::RequestFailed(format!(
+            "the chat was saved by a newer version (format {version}, expected {VERSION} or older)"
+        )));
     }
 
-    let schema: old::Schema = serde_json::from_str(json)?;
+    for migration in &MIGRATIONS[version as usize..] {
+        value = migration(value)?;
+    }
+
+    Ok(decoder::run(serde_json::from_value, decode::chat, value)?)
+}
+
+pub fn encode_value(chat: &Chat) -> Result<Value, Error> {
+    let mut value = serde_json::to_value(encode::chat(chat.clone()))?;
+
+    if let Some(fields) = value.as_object_mut() {
+        let _ = fields.insert("version".to_owned(), Value::from(VERSION));
+    }
+
+    Ok(value)
+}
+
+/// Chats without a `version` are version 1 if their items are tagged, and
+/// version 0 otherwise.
+fn version(value: &Value) -> u64 {
+    if let Some(version) = value.get("version").and_then(Value::as_u64) {
+        return version;
+    }
+
+    let is_tagged = value["history"]
+        .as_array()
+        .and_then(|history| history.first())
+        .is_none_or(|item| item.get("type").is_some());
+
+    if is_tagged {
+        1
+    } else {
+        0
+    }
+}
+
+fn v0_to_v1(value: Value) -> Result<Value, Error> {
+    let schema: old::Schema = serde_json::from_value(value)?;
 
     let chat = Chat {
         id: schema.id,
@@ -27,11 +90,54 @@ pub fn decode(json: &str) -> Result<Chat, Error> {
             .collect(),
     };
 
-    Ok(chat)
+    Ok(serde_json::to_value(encode::chat(chat))?)
 }
 
-pub fn encode(chat: &Chat) -> Result<String, Error> {
-    let value = encode::chat(chat.clone());
+fn v1_to_v2(mut value: Value) -> Result<Value, Error> {
+    if let Some(fields) = value.as_object_mut() {
+        let _ = fields.insert("version".to_owned(), Value::from(2));
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brain::chat::Item;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_migrations() {
+        let v0 = json!({
+            "id": "0b9f2a52-3c3f-4a8e-9d55-2a1f6f0e8a11",
+            "file": { "model": "bartowski/Qwen", "name": "qwen-q4.gguf" },
+            "title": "Omelettes",
+            "history": [
+                { "User": "How do I cook an omelette?" },
+                { "Assistant": "Whisk the eggs first." }
+            ]
+        });
+
+        let chat = decode_value(v0).unwrap();
 
-    Ok(serde_json::to_string_pretty(&value)?)
+        assert_eq!(chat.title.as_deref(), Some("Omelettes"));
+        assert!(
+            matches!(&chat.history[1], Item::Reply(reply) if reply.content == "Whisk the eggs first.")
+        );
+
+        let v2 = encode_value(&chat).unwrap();
+        assert_eq!(v2["version"], VERSION);
+
+        // Version 1 is version 2 without the field
+        let mut v1 = v2.clone();
+        let _ = v1.as_object_mut().unwrap().remove("version");
+        assert_eq!(version(&v1), 1);
+        assert_eq!(encode_value(&decode_value(v1).unwrap()).unwrap(), v2);
+
+        let mut future = v2;
+        future["version"] = json!(VERSION + 1);
+        assert!(decode_value(future).is_err());
+    }
 }
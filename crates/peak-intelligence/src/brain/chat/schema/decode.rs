@@ -1,11 +1,11 @@
-use crate::brain::assistant::{Reasoning, Reply};
+use crate::brain::assistant::{Reasoning, Reply, Source, ToolUse};
 use crate::brain::chat;
 use crate::brain::model;
 use crate::brain::plan;
 use crate::brain::web;
 use crate::brain::{Chat, Plan, Url};
 
-use decoder::decode::{duration, map, sequence, string};
+use decoder::decode::{bool, duration, map, sequence, string, u64};
 use decoder::{Decoder, Error, Result, Value};
 
 pub fn chat(value: Value) -> Result<Chat> {
@@ -28,6 +28,9 @@ fn item(value: Value) -> Result<chat::Item> {
         "user" => chat::Item::User(item.required("message", string)?),
         "reply" => chat::Item::Reply(reply(item.into_value())?),
         "plan" => chat::Item::Plan(plan(item.into_value())?),
+        "summary" => chat::Item::Summary(chat::Summary {
+            content: item.required("content", string)?,
+        }),
         _ => {
             return Err(Error::custom(format!("invalid chat item: {type_}")));
         }
@@ -42,10 +45,38 @@ fn reply(value: Value) -> Result<Reply> {
     Ok(Reply {
         reasoning: reply.optional("reasoning", reasoning)?,
         content: reply.required("content", string)?,
+        tools: reply
+            .optional("tools", sequence(tool_use))?
+            .unwrap_or_default(),
+        sources: reply
+            .optional("sources", sequence(source))?
+            .unwrap_or_default(),
         last_token: None,
     })
 }
 
+fn source(value: Value) -> Result<Source> {
+    let mut source = map(value)?;
+
+    Ok(Source {
+        path: source.required("path", string)?.into(),
+        start_line: source.required("start_line", u64)? as usize,
+        end_line: source.required("end_line", u64)? as usize,
+    })
+}
+
+fn tool_use(value: Value) -> Result<ToolUse> {
+    let mut tool = map(value)?;
+
+    Ok(ToolUse {
+        id: tool.required("id", string)?,
+        name: tool.required("name", string)?,
+        activity: tool.required("activity", string)?,
+        output: tool.optional("output", string)?,
+        is_error: tool.required("is_error", bool)?,
+    })
+}
+
 fn reasoning(value: Value) -> Result<Reasoning> {
     let mut reasoning = map(value)?;
 
@@ -87,6 +118,7 @@ fn outcome(outcome: Value) -> Result<plan::Outcome> {
             plan::Outcome::ScrapeText(status(sequence(web_summary), outcome.into_value())?)
         }
         "answer" => plan::Outcome::Answer(status(reply, outcome.into_value())?),
+        "tool" => plan::Outcome::Tool(status(action, outcome.into_value())?),
         _ => {
             return Err(Error::custom(format!("invalid plan outcome: {type_}")));
         }
@@ -112,6 +144,16 @@ fn status<T>(decoder: impl Decoder<Output = T>, value: Value) -> Result<plan::St
     Ok(status)
 }
 
+fn action(value: Value) -> Result<plan::Action> {
+    let mut action = map(value)?;
+
+    Ok(plan::Action {
+        function: action.required("function", string)?,
+        activity: action.required("activity", string)?,
+        output: action.required("output", string)?,
+    })
+}
+
 fn web_summary(value: Value) -> Result<web::Summary> {
     let mut summary = map(value)?;
 
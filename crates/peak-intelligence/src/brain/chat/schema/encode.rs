@@ -1,10 +1,10 @@
-use crate::brain::assistant::{Reasoning, Reply};
+use crate::brain::assistant::{Reasoning, Reply, Source, ToolUse};
 use crate::brain::chat;
 use crate::brain::plan;
 use crate::brain::web;
 use crate::brain::{Chat, Plan, Url};
 
-use decoder::encode::{duration, map, optional, sequence, string};
+use decoder::encode::{bool, duration, map, optional, sequence, string, u64};
 use decoder::{Map, Value};
 use function::Binary;
 
@@ -23,6 +23,7 @@ fn item(item: chat::Item) -> Map {
         chat::Item::User(message) => ("user", map([("message", string(message))])),
         chat::Item::Reply(reply_) => ("reply", reply(reply_)),
         chat::Item::Plan(plan_) => ("plan", plan(plan_)),
+        chat::Item::Summary(summary) => ("summary", map([("content", string(summary.content))])),
     };
 
     item.tag("type", type_)
@@ -32,6 +33,26 @@ fn reply(reply: Reply) -> Map {
     map([
         ("reasoning", optional(reasoning, reply.reasoning)),
         ("content", string(reply.content)),
+        ("tools", sequence(tool_use, reply.tools)),
+        ("sources", sequence(source, reply.sources)),
+    ])
+}
+
+fn source(source: Source) -> Map {
+    map([
+        ("path", string(source.path.to_string_lossy().into_owned())),
+        ("start_line", u64(source.start_line as u64)),
+        ("end_line", u64(source.end_line as u64)),
+    ])
+}
+
+fn tool_use(tool: ToolUse) -> Map {
+    map([
+        ("id", string(tool.id)),
+        ("name", string(tool.name)),
+        ("activity", string(tool.activity)),
+        ("output", optional(string, tool.output)),
+        ("is_error", bool(tool.is_error)),
     ])
 }
 
@@ -67,6 +88,7 @@ fn outcome(outcome: plan::Outcome) -> Map {
             status_with(status, sequence.with(web_summary)),
         ),
         plan::Outcome::Answer(status) => ("answer", status_with(status, reply)),
+        plan::Outcome::Tool(status) => ("tool", status_with(status, action)),
     };
 
     status.tag("type", type_)
@@ -85,6 +107,14 @@ where
     map([("status", string(type_)), ("output", output)])
 }
 
+fn action(action: plan::Action) -> Map {
+    map([
+        ("function", string(action.function)),
+        ("activity", string(action.activity)),
+        ("output", string(action.output)),
+    ])
+}
+
 fn web_summary(summary: web::Summary) -> Map {
     map([
         ("url", url(summary.url)),
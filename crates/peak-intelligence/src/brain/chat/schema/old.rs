@@ -60,6 +60,8 @@ impl Reply {
                 })
             },
             content: self.content,
+            tools: Vec::new(),
+            sources: Vec::new(),
             last_token: None,
         }
     }
@@ -0,0 +1,537 @@
+//! Full-text search over every saved chat.
+//!
+//! The text of user messages, replies and plan outcomes is kept in
+//! `search.json` next to the chats, and an inverted index is built from it
+//! when the file is opened. [`Chat::save`] and [`Chat::delete`] keep it
+//! current; if it goes missing, it is rebuilt from the chats on disk.
+use crate::brain::chat::{storage_dir, Chat, Id, Item, List};
+use crate::brain::plan::Outcome;
+use crate::brain::Error;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::sync::Mutex;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// Characters of context shown around the first match.
+const CONTEXT: usize = 80;
+
+/// Serializes updates of the index file.
+static LOCK: Mutex<()> = Mutex::const_new(());
+
+/// What to look for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    pub text: String,
+    /// Only chats with a model whose id contains this, ignoring case.
+    pub model: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Query {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn since(mut self, date: DateTime<Utc>) -> Self {
+        self.since = Some(date);
+        self
+    }
+
+    pub fn until(mut self, date: DateTime<Utc>) -> Self {
+        self.until = Some(date);
+        self
+    }
+}
+
+/// A chat item that matched a [`Query`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub chat: Id,
+    pub title: Option<String>,
+    pub model: String,
+    pub updated: DateTime<Utc>,
+    /// The position of the item in [`Chat::history`].
+    pub item: usize,
+    pub kind: Kind,
+    pub snippet: Snippet,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    User,
+    Reply,
+    Plan,
+}
+
+/// An excerpt of the matching text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    /// Byte ranges of `text` that matched the query.
+    pub highlights: Vec<Range<usize>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Index {
+    chats: BTreeMap<Id, Document>,
+    /// Words mapped to the texts containing them.
+    terms: BTreeMap<String, Postings>,
+}
+
+/// The texts of a word, as chats and positions in [`Document::texts`].
+type Postings = BTreeSet<(Id, usize)>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Document {
+    title: Option<String>,
+    model: String,
+    updated: DateTime<Utc>,
+    texts: Vec<Text>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Text {
+    item: usize,
+    kind: Kind,
+    text: String,
+}
+
+impl Index {
+    /// Opens the index of the saved chats, rebuilding it if needed.
+    pub async fn open() -> Result<Self, Error> {
+        let path = Self::path().await?;
+
+        let chats = match fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).ok(),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error.into()),
+        };
+
+        match chats {
+            Some(chats) => Ok(Self::new(chats)),
+            None => {
+                let index = Self::rebuild().await?;
+                index.save().await?;
+
+                Ok(index)
+            }
+        }
+    }
+
+    /// Finds the chat items matching every word of the query, best first.
+    ///
+    /// The last word also matches longer words, so results show up while
+    /// typing.
+    pub fn search(&self, query: &Query, limit: usize) -> Vec<Hit> {
+        let wanted: Vec<String> = words(&query.text).map(|(_, word)| word).collect();
+
+        let Some((last, rest)) = wanted.split_last() else {
+            return Vec::new();
+        };
+
+        let model = query.model.as_ref().map(|model| model.to_lowercase());
+        let total = self
+            .chats
+            .values()
+            .map(|chat| chat.texts.len())
+            .sum::<usize>() as f32;
+
+        // Each wanted word, with the indexed words it matches and where
+        let mut terms: Vec<(Vec<&str>, Postings)> = Vec::new();
+
+        for word in rest {
+            let postings = self.terms.get(word).cloned().unwrap_or_default();
+            terms.push((vec![word.as_str()], postings));
+        }
+
+        let (prefixed, postings) = self
+            .terms
+            .range(last.clone()..)
+            .take_while(|(term, _)| term.starts_with(last.as_str()))
+            .fold(
+                (Vec::new(), BTreeSet::new()),
+                |(mut variants, mut postings), (term, matches)| {
+                    variants.push(term.as_str());
+                    postings.extend(matches.iter().copied());
+
+                    (variants, postings)
+                },
+            );
+        terms.push((prefixed, postings));
+
+        let mut candidates = terms[0].1.clone();
+
+        for (_, postings) in &terms[1..] {
+            candidates.retain(|posting| postings.contains(posting));
+        }
+
+        let mut hits: Vec<Hit> = candidates
+            .into_iter()
+            .filter_map(|(id, position)| {
+                let chat = self.chats.get(&id)?;
+
+                if model
+                    .as_ref()
+                    .is_some_and(|model| !chat.model.to_lowercase().contains(model))
+                    || query.since.is_some_and(|since| chat.updated < since)
+                    || query.until.is_some_and(|until| chat.updated > until)
+                {
+                    return None;
+                }
+
+                let text = chat.texts.get(position)?;
+                let matches: Vec<Range<usize>> = words(&text.text)
+                    .filter(|(_, word)| {
+                        terms
+                            .iter()
+                            .any(|(variants, _)| variants.contains(&word.as_str()))
+                    })
+                    .map(|(range, _)| range)
+                    .collect();
+
+                let score = terms
+                    .iter()
+                    .map(|(variants, postings)| {
+                        let count = words(&text.text)
+                            .filter(|(_, word)| variants.contains(&word.as_str()))
+                            .count() as f32;
+                        let rarity = (1.0 + total / postings.len().max(1) as f32).ln();
+
+                        (1.0 + count.max(1.0).ln()) * rarity
+                    })
+                    .sum();
+
+                Some(Hit {
+                    chat: id,
+                    title: chat.title.clone(),
+                    model: chat.model.clone(),
+                    updated: chat.updated,
+                    item: text.item,
+                    kind: text.kind,
+                    snippet: snippet(&text.text, &matches),
+                    score,
+                })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| b.updated.cmp(&a.updated))
+        });
+        hits.truncate(limit);
+
+        hits
+    }
+
+    fn new(chats: BTreeMap<Id, Document>) -> Self {
+        let mut index = Self {
+            chats: BTreeMap::new(),
+            terms: BTreeMap::new(),
+        };
+
+        for (id, document) in chats {
+            index.insert(id, document);
+        }
+
+        index
+    }
+
+    fn insert(&mut self, id: Id, document: Document) {
+        let _ = self.remove(&id);
+
+        for (position, text) in document.texts.iter().enumerate() {
+            for (_, word) in words(&text.text) {
+                let _ = self.terms.entry(word).or_default().insert((id, position));
+            }
+        }
+
+        let _ = self.chats.insert(id, document);
+    }
+
+    fn remove(&mut self, id: &Id) -> bool {
+        let Some(document) = self.chats.remove(id) else {
+            return false;
+        };
+
+        for (position, text) in document.texts.iter().enumerate() {
+            for (_, word) in words(&text.text) {
+                if let Some(postings) = self.terms.get_mut(&word) {
+                    let _ = postings.remove(&(*id, position));
+
+                    if postings.is_empty() {
+                        let _ = self.terms.remove(&word);
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    async fn rebuild() -> Result<Self, Error> {
+        let mut chats = BTreeMap::new();
+
+        for entry in List::fetch().await?.entries {
+            let updated = match fs::metadata(Chat::path(&entry.id).await?).await {
+                Ok(metadata) => metadata.modified().map(DateTime::from).unwrap_or_default(),
+                Err(_) => continue,
+            };
+
+            match Chat::read(entry.id).await {
+                Ok(chat) => {
+                    let _ = chats.insert(entry.id, Document::new(&chat, updated));
+                }
+                Err(error) => {
+                    log::warn!("Could not index chat {:?}: {error}", entry.id);
+                }
+            }
+        }
+
+        Ok(Self::new(chats))
+    }
+
+    async fn save(&self) -> Result<(), Error> {
+        let path = Self::path().await?;
+        let temporary = path.with_extension("json.tmp");
+
+        fs::write(&temporary, serde_json::to_vec(&self.chats)?).await?;
+        fs::rename(&temporary, &path).await?;
+
+        Ok(())
+    }
+
+    async fn path() -> Result<PathBuf, Error> {
+        Ok(storage_dir().await?.join("search.json"))
+    }
+}
+
+/// Indexes a chat that was just saved.
+pub(super) async fn track(chat: &Chat) {
+    let _lock = LOCK.lock().await;
+
+    let result = async {
+        let mut index = Index::open().await?;
+        index.insert(chat.id, Document::new(chat, Utc::now()));
+        index.save().await
+    };
+
+    if let Err(error) = result.await {
+        log::warn!("Could not index chat {:?}: {error}", chat.id);
+    }
+}
+
+/// Forgets a chat that was just deleted.
+pub(super) async fn untrack(id: &Id) {
+    let _lock = LOCK.lock().await;
+
+    let result = async {
+        let mut index = Index::open().await?;
+
+        if index.remove(id) {
+            index.save().await?;
+        }
+
+        Ok::<_, Error>(())
+    };
+
+    if let Err(error) = result.await {
+        log::warn!("Could not remove chat {id:?} from the index: {error}");
+    }
+}
+
+impl Document {
+    fn new(chat: &Chat, updated: DateTime<Utc>) -> Self {
+        let mut texts = Vec::new();
+
+        for (item, entry) in chat.history.iter().enumerate() {
+            let mut push = |kind, text: &str| {
+                if !text.trim().is_empty() {
+                    texts.push(Text {
+                        item,
+                        kind,
+                        text: text.to_owned(),
+                    });
+                }
+            };
+
+            match entry {
+                Item::User(message) => push(Kind::User, message),
+                Item::Reply(reply) => push(Kind::Reply, &reply.content),
+                Item::Plan(plan) => {
+                    for outcome in &plan.outcomes {
+                        match outcome {
+                            Outcome::ScrapeText(status) => {
+                                for summary in status.result().into_iter().flatten() {
+                                    push(Kind::Plan, &summary.content);
+                                }
+                            }
+                            Outcome::Tool(status) => {
+                                if let Ok(action) = status.result() {
+                                    push(Kind::Plan, &action.output);
+                                }
+                            }
+                            Outcome::Answer(status) => {
+                                if let Ok(reply) = status.result() {
+                                    push(Kind::Plan, &reply.content);
+                                }
+                            }
+                            Outcome::Search(_) => {}
+                        }
+                    }
+                }
+                // Summaries repeat what is already indexed
+                Item::Summary(_) => {}
+            }
+        }
+
+        Self {
+            title: chat.title.clone(),
+            model: chat.file.model.0.clone(),
+            updated,
+            texts,
+        }
+    }
+}
+
+/// Splits text into lowercase words, with their byte ranges.
+fn words(text: &str) -> impl Iterator<Item = (Range<usize>, String)> + '_ {
+    let mut start = None;
+
+    text.char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+        .filter_map(move |(i, c)| {
+            if c.is_alphanumeric() {
+                let _ = start.get_or_insert(i);
+                None
+            } else {
+                start
+                    .take()
+                    .map(|start| (start..i, text[start..i].to_lowercase()))
+            }
+        })
+}
+
+/// Cuts the text around the first match, keeping the highlights that fit.
+fn snippet(text: &str, matches: &[Range<usize>]) -> Snippet {
+    let first = matches.first().map_or(0, |range| range.start);
+
+    let mut start = first.saturating_sub(CONTEXT);
+    let mut end = (first + CONTEXT * 2).min(text.len());
+
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+
+    let prefix = if start > 0 { "..." } else { "" };
+    let suffix = if end < text.len() { "..." } else { "" };
+
+    let highlights = matches
+        .iter()
+        .filter(|range| range.start >= start && range.end <= end)
+        .map(|range| range.start - start + prefix.len()..range.end - start + prefix.len())
+        .collect();
+
+    Snippet {
+        text: format!("{prefix}{}{suffix}", &text[start..end]),
+        highlights,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brain::assistant::Reply;
+    use crate::brain::model;
+
+    use uuid::Uuid;
+
+    fn chat(model: &str, history: Vec<Item>) -> Chat {
+        Chat {
+            id: Id(Uuid::new_v4()),
+            file: model::File {
+                model: model::Id(model.to_owned()),
+                name: "model.gguf".to_owned(),
+                size: None,
+                sha256: None,
+                metadata: None,
+            },
+            title: None,
+            history,
+        }
+    }
+
+    #[test]
+    fn test_search() {
+        let sourdough = chat(
+            "bartowski/Qwen",
+            vec![
+                Item::User("How long should sourdough proof?".to_owned()),
+                Item::Reply(Reply {
+                    content: "Sourdough proofs for about 4 hours at room temperature.".to_owned(),
+                    ..Reply::default()
+                }),
+            ],
+        );
+
+        let rust = chat(
+            "unsloth/Llama",
+            vec![Item::User("Is Rust good for proofs of concept?".to_owned())],
+        );
+
+        let mut index = Index::default();
+        index.insert(sourdough.id, Document::new(&sourdough, Utc::now()));
+        index.insert(rust.id, Document::new(&rust, Utc::now()));
+
+        let hits = index.search(&Query::new("sourdough proo"), 10);
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|hit| hit.chat == sourdough.id));
+
+        let reply = hits.iter().find(|hit| hit.kind == Kind::Reply).unwrap();
+        assert_eq!(reply.item, 1);
+        assert_eq!(
+            reply
+                .snippet
+                .highlights
+                .iter()
+                .map(|range| &reply.snippet.text[range.clone()])
+                .collect::<Vec<_>>(),
+            ["Sourdough", "proofs"]
+        );
+
+        let hits = index.search(&Query::new("proofs").model("llama"), 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chat, rust.id);
+
+        assert!(index
+            .search(
+                &Query::new("proofs").since(Utc::now() + chrono::Duration::days(1)),
+                10
+            )
+            .is_empty());
+
+        assert!(index.remove(&sourdough.id));
+        assert!(index.search(&Query::new("sourdough"), 10).is_empty());
+        assert!(!index.terms.contains_key("sourdough"));
+    }
+}
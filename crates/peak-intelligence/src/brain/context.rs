@@ -0,0 +1,255 @@
+//! Keeping conversations inside the context window of the model.
+//!
+//! The context is split between the system prompt, the history and the
+//! answer. When the history outgrows its share, the oldest turns are folded
+//! into a rolling summary that replaces them in the prompt.
+use crate::brain::assistant::{Assistant, Message, Token};
+use crate::brain::Error;
+
+use sipper::Sipper;
+
+/// Context length assumed when the server does not report one.
+pub const DEFAULT_CONTEXT: usize = 4096;
+
+/// Tokens a chat template adds around every message.
+const MESSAGE_OVERHEAD: usize = 4;
+
+const SUMMARY_PROMPT: &str = "You write faithful, compact summaries of conversations.";
+
+const SUMMARY_REQUEST: &str = "Summarize our conversation so far in a few short paragraphs. \
+    Keep names, numbers, decisions and open questions. \
+    Only write the summary; don't say anything else.";
+
+/// How the context window is shared, in tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    pub context: usize,
+    pub system: usize,
+    pub history: usize,
+    pub answer: usize,
+}
+
+impl Budget {
+    /// Reserves a quarter of the context for the answer, between 256 and
+    /// 2048 tokens, and gives the history whatever the system prompt leaves.
+    pub fn allocate(context: usize, system: usize) -> Self {
+        let answer = (context / 4).clamp(256, 2048).min(context / 2);
+
+        Self {
+            context,
+            system,
+            history: context.saturating_sub(system + answer),
+            answer,
+        }
+    }
+
+    /// Asks the assistant for its context length and measures the prompt.
+    pub async fn fetch(assistant: &Assistant, system_prompt: &str) -> Self {
+        let context = assistant.context_length().await;
+        let system = assistant.count_tokens(system_prompt).await + MESSAGE_OVERHEAD;
+
+        Self::allocate(context, system)
+    }
+}
+
+/// A rough token count, for when the model cannot be asked: about four
+/// characters per token.
+pub fn estimate(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// The outcome of [`fit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fit {
+    /// How many of the leading turns are covered by the summary now.
+    pub folded: usize,
+    /// The new summary, if any turns were folded.
+    pub summary: Option<String>,
+}
+
+/// Makes a conversation fit the budget of the assistant.
+///
+/// `turns` are the messages that came after `summary`, grouped so that a
+/// turn is never cut in half. If they no longer fit, the oldest turns are
+/// folded into a new summary until the rest fills at most half of the
+/// history budget, so summaries are not rewritten on every turn. The last
+/// turn is always kept.
+pub async fn fit(
+    assistant: &Assistant,
+    system_prompt: &str,
+    summary: Option<&str>,
+    turns: &[Vec<Message>],
+) -> Result<Fit, Error> {
+    let budget = Budget::fetch(assistant, system_prompt).await;
+
+    let summary_size = match summary {
+        Some(summary) => assistant.count_tokens(summary).await + MESSAGE_OVERHEAD,
+        None => 0,
+    };
+
+    let mut sizes = Vec::with_capacity(turns.len());
+
+    for turn in turns {
+        let mut size = 0;
+
+        for message in turn {
+            size += assistant.count_tokens(message.to_tuple().1).await + MESSAGE_OVERHEAD;
+        }
+
+        sizes.push(size);
+    }
+
+    let folded = split(&budget, summary_size, &sizes);
+
+    if folded == 0 {
+        return Ok(Fit {
+            folded,
+            summary: None,
+        });
+    }
+
+    let summary = summarize(assistant, &budget, summary, &turns[..folded].concat()).await?;
+
+    Ok(Fit {
+        folded,
+        summary: Some(summary),
+    })
+}
+
+/// Presents a summary to the model in place of the turns it covers.
+pub fn summary_message(summary: &str) -> Message {
+    Message::System(format!(
+        "Summary of the earlier conversation:\n{}",
+        summary.trim()
+    ))
+}
+
+fn split(budget: &Budget, summary: usize, turns: &[usize]) -> usize {
+    let total = summary + turns.iter().sum::<usize>();
+
+    if total <= budget.history || turns.len() <= 1 {
+        return 0;
+    }
+
+    let target = budget.history / 2;
+    let mut kept_size = 0;
+    let mut kept = 0;
+
+    for size in turns.iter().rev() {
+        if kept > 0 && kept_size + size > target {
+            break;
+        }
+
+        kept_size += size;
+        kept += 1;
+    }
+
+    turns.len() - kept
+}
+
+/// Folds `messages` into `previous`, a chunk at a time so every request
+/// fits the history budget.
+async fn summarize(
+    assistant: &Assistant,
+    budget: &Budget,
+    previous: Option<&str>,
+    messages: &[Message],
+) -> Result<String, Error> {
+    // Chunks are measured with estimates, so leave plenty of headroom
+    let limit = (budget.history / 2).max(1);
+
+    let mut summary = previous.map(str::to_owned);
+    let mut chunk = Vec::new();
+    let mut chunk_size = 0;
+
+    for message in messages {
+        let message = truncate(message, limit);
+        let size = estimate(message.to_tuple().1) + MESSAGE_OVERHEAD;
+
+        if !chunk.is_empty() && chunk_size + size > limit {
+            summary = Some(fold(assistant, summary.as_deref(), std::mem::take(&mut chunk)).await?);
+            chunk_size = 0;
+        }
+
+        chunk.push(message);
+        chunk_size += size;
+    }
+
+    if !chunk.is_empty() {
+        summary = Some(fold(assistant, summary.as_deref(), chunk).await?);
+    }
+
+    Ok(summary.unwrap_or_default())
+}
+
+async fn fold(
+    assistant: &Assistant,
+    previous: Option<&str>,
+    messages: Vec<Message>,
+) -> Result<String, Error> {
+    let history = previous
+        .map(summary_message)
+        .into_iter()
+        .chain(messages)
+        .collect();
+
+    let mut completion = assistant
+        .clone()
+        .complete(
+            SUMMARY_PROMPT,
+            history,
+            vec![Message::User(SUMMARY_REQUEST.to_owned())],
+        )
+        .pin();
+
+    let mut summary = String::new();
+
+    while let Some(token) = completion.sip().await {
+        if let Token::Talking(token) = token {
+            summary.push_str(&token);
+        }
+    }
+
+    completion.await?;
+
+    Ok(summary.trim().to_owned())
+}
+
+/// Cuts a single message that would not fit a request on its own.
+fn truncate(message: &Message, limit: usize) -> Message {
+    let (role, content) = message.to_tuple();
+
+    if estimate(content) <= limit {
+        return message.clone();
+    }
+
+    let content: String = content.chars().take(limit * 4).collect();
+
+    match role {
+        "user" => Message::User(content),
+        "assistant" => Message::Assistant(content),
+        _ => Message::System(content),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_keeps_recent_turns() {
+        let budget = Budget::allocate(4096, 96);
+
+        assert_eq!(budget.answer, 1024);
+        assert_eq!(budget.history, 2976);
+
+        // Everything fits
+        assert_eq!(split(&budget, 0, &[500, 500, 500]), 0);
+
+        // Over budget: keep what fills half of the history
+        assert_eq!(split(&budget, 200, &[1000, 1000, 600, 500, 400]), 3);
+
+        // The last turn is kept even when it is too large on its own
+        assert_eq!(split(&budget, 0, &[1000, 4000]), 1);
+    }
+}
@@ -0,0 +1,144 @@
+//! Constraining what models generate.
+//!
+//! A [`Format`] is either a JSON schema or a GBNF grammar. llama-server
+//! turns both into a grammar and samples only tokens that fit it, so the
+//! output always parses. Ollama and OpenAI-compatible servers only take
+//! schemas; grammars fall back to plain JSON mode there.
+//!
+//! Schemas are also checked after generation with [`Format::validate`],
+//! since not every server enforces every keyword.
+use crate::schema;
+
+use serde_json::{json, Map, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Format {
+    /// A JSON schema, e.g. `{ "type": "object", "properties": {...} }`.
+    Schema(Value),
+    /// A GBNF grammar that produces JSON.
+    Grammar(String),
+}
+
+impl Format {
+    pub fn schema(schema: Value) -> Self {
+        Self::Schema(schema)
+    }
+
+    pub fn grammar(grammar: impl Into<String>) -> Self {
+        Self::Grammar(grammar.into())
+    }
+
+    /// Checks `value` against the schema, like the arguments of tool calls.
+    ///
+    /// See [`schema::validate`] for the keywords supported. Grammars are
+    /// trusted.
+    pub fn validate(&self, value: &Value) -> Result<(), String> {
+        match self {
+            Self::Schema(expected) => schema::validate(expected, value).map_err(|violations| {
+                violations
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            }),
+            Self::Grammar(_) => Ok(()),
+        }
+    }
+
+    /// The fields to add to a llama-server completion request.
+    pub(crate) fn llama(&self) -> Map<String, Value> {
+        match self {
+            Self::Schema(_) => self.openai(),
+            Self::Grammar(grammar) => Map::from_iter([("grammar".to_owned(), json!(grammar))]),
+        }
+    }
+
+    /// The fields to add to an OpenAI chat completion request.
+    pub(crate) fn openai(&self) -> Map<String, Value> {
+        let response_format = match self {
+            Self::Schema(schema) => json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "output",
+                    "strict": true,
+                    "schema": schema,
+                },
+            }),
+            Self::Grammar(_) => json!({ "type": "json_object" }),
+        };
+
+        Map::from_iter([("response_format".to_owned(), response_format)])
+    }
+
+    /// The fields to add to an Ollama chat request.
+    pub(crate) fn ollama(&self) -> Map<String, Value> {
+        let format = match self {
+            Self::Schema(schema) => schema.clone(),
+            Self::Grammar(_) => json!("json"),
+        };
+
+        Map::from_iter([("format".to_owned(), format)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let format = Format::schema(json!({
+            "type": "object",
+            "properties": {
+                "steps": {
+                    "type": "array",
+                    "minItems": 1,
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "function": { "enum": ["search", "answer"] },
+                            "inputs": { "type": "array", "items": { "type": "string" } }
+                        },
+                        "required": ["function", "inputs"],
+                        "additionalProperties": false
+                    }
+                }
+            },
+            "required": ["steps"]
+        }));
+
+        let check = |value: Value| format.validate(&value);
+
+        assert_eq!(
+            check(json!({ "steps": [{ "function": "search", "inputs": ["omelette"] }] })),
+            Ok(())
+        );
+        assert_eq!(check(json!({})), Err("'steps' is required".to_owned()));
+        assert_eq!(
+            check(json!({ "steps": [] })),
+            Err("'steps' must have at least 1 items".to_owned())
+        );
+        assert_eq!(
+            check(json!({ "steps": [{ "function": "delete", "inputs": [] }] })),
+            Err("'steps[0].function' must be one of [\"search\",\"answer\"]".to_owned())
+        );
+        assert_eq!(
+            check(json!({ "steps": [{ "function": "answer", "inputs": [1] }] })),
+            Err("'steps[0].inputs[0]' must be of type \"string\", got number".to_owned())
+        );
+        assert_eq!(
+            check(json!({ "steps": [{ "function": "answer", "inputs": [], "why": "" }] })),
+            Err("'steps[0].why' is not allowed".to_owned())
+        );
+        assert_eq!(
+            check(json!({ "steps": [{ "function": 1 }] })),
+            Err("'steps[0].inputs' is required; \
+                 'steps[0].function' must be one of [\"search\",\"answer\"]"
+                .to_owned())
+        );
+
+        assert!(Format::grammar("root ::= \"{}\"")
+            .validate(&json!(1))
+            .is_ok());
+    }
+}
@@ -0,0 +1,427 @@
+//! Fitting models to the machine.
+//!
+//! [`Hardware::recommend`] ranks the variants of a model by how well they
+//! fit in memory, and [`Tuning`] picks the llama-server settings for the one
+//! that gets booted.
+use crate::brain::assistant::Backend;
+use crate::brain::context;
+use crate::brain::model::{self, gguf};
+
+const GB: u64 = 1024 * 1024 * 1024;
+
+/// Memory taken by llama-server itself, its buffers and some headroom.
+const OVERHEAD: u64 = 512 * 1024 * 1024;
+
+/// Smallest and largest context sizes [`Tuning`] picks.
+const MIN_CONTEXT: u64 = 2048;
+const MAX_CONTEXT: u64 = 32768;
+
+/// GPU layers that offload every layer of any model.
+const ALL_LAYERS: u32 = 999;
+
+/// Models larger than this are too slow without SIMD to be comfortable.
+const SLOW_CPU_LIMIT: u64 = 4_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hardware {
+    /// Bytes of memory, limited by the cgroup if there is one.
+    pub total_memory: u64,
+    pub available_memory: u64,
+    pub physical_cores: usize,
+    pub logical_cores: usize,
+    pub features: Features,
+    pub backend: Backend,
+}
+
+/// The SIMD extensions llama.cpp can use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Features {
+    pub avx2: bool,
+    pub avx512: bool,
+    pub neon: bool,
+}
+
+impl Features {
+    #[cfg(feature = "native")]
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            Self {
+                avx2: std::arch::is_x86_feature_detected!("avx2"),
+                avx512: std::arch::is_x86_feature_detected!("avx512f"),
+                neon: false,
+            }
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        {
+            Self {
+                neon: std::arch::is_aarch64_feature_detected!("neon"),
+                ..Self::default()
+            }
+        }
+
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        Self::default()
+    }
+
+    pub fn has_simd(self) -> bool {
+        self.avx2 || self.avx512 || self.neon
+    }
+}
+
+impl Hardware {
+    #[cfg(feature = "native")]
+    pub fn detect(backend: Backend) -> Self {
+        use sysinfo::System;
+
+        let mut system = System::new();
+        system.refresh_memory();
+        system.refresh_cpu();
+
+        let mut total_memory = system.total_memory();
+        let mut available_memory = system.available_memory();
+
+        // Containers and kiosks may be given less than the machine has
+        if let Some(limits) = system.cgroup_limits() {
+            total_memory = total_memory.min(limits.total_memory);
+            available_memory = available_memory.min(limits.free_memory);
+        }
+
+        let logical_cores = system.cpus().len().max(1);
+
+        Self {
+            total_memory,
+            available_memory,
+            physical_cores: system.physical_core_count().unwrap_or(logical_cores),
+            logical_cores,
+            features: Features::detect(),
+            backend,
+        }
+    }
+
+    /// The memory a model may take: what is free, or half of all of it if
+    /// other apps can make room.
+    pub fn usable_memory(&self) -> u64 {
+        self.available_memory.max(self.total_memory / 2)
+    }
+
+    /// Ranks the files of a model by how well they fit, best first.
+    ///
+    /// Among the files that fit, larger ones come first since they are
+    /// better quantized; the ones that do not fit come last, smallest first.
+    pub fn recommend(&self, files: impl IntoIterator<Item = model::File>) -> Vec<Recommendation> {
+        let mut recommendations: Vec<_> = files
+            .into_iter()
+            .map(|file| {
+                let memory = memory_needed(&file, context::DEFAULT_CONTEXT as u64);
+                let size = file_size(&file);
+
+                let fit = if memory > self.usable_memory() {
+                    Fit::TooLarge
+                } else if memory > self.usable_memory() / 4 * 3
+                    || !self.features.has_simd() && size > SLOW_CPU_LIMIT
+                {
+                    Fit::Tight
+                } else {
+                    Fit::Comfortable
+                };
+
+                Recommendation { file, fit, memory }
+            })
+            .collect();
+
+        recommendations.sort_by(|a, b| {
+            let sizes = (file_size(&a.file), file_size(&b.file));
+
+            a.fit.cmp(&b.fit).then_with(|| {
+                if a.fit == Fit::TooLarge {
+                    sizes.0.cmp(&sizes.1)
+                } else {
+                    sizes.1.cmp(&sizes.0)
+                }
+            })
+        });
+
+        recommendations
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recommendation {
+    pub file: model::File,
+    pub fit: Fit,
+    /// Bytes needed with the default context.
+    pub memory: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Fit {
+    /// Leaves room for other apps.
+    Comfortable,
+    /// Fits, but takes most of the memory or runs slowly.
+    Tight,
+    /// Does not fit.
+    TooLarge,
+}
+
+/// How llama-server should run a model on some [`Hardware`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tuning {
+    pub threads: usize,
+    /// The context size, in tokens.
+    pub context: u64,
+    pub batch: u32,
+    /// Maps the model file instead of reading it into memory.
+    pub mmap: bool,
+    /// Keeps the model from being swapped out.
+    pub mlock: bool,
+    pub gpu_layers: u32,
+}
+
+impl Tuning {
+    pub fn new(hardware: &Hardware, file: &model::File) -> Self {
+        let metadata = file.metadata.as_ref();
+        let uses_gpu = hardware.backend.uses_gpu();
+
+        // llama.cpp is fastest with one thread per physical core; larger
+        // machines keep one for everything else
+        let threads = match hardware.physical_cores {
+            0..=4 => hardware.physical_cores,
+            cores => cores - 1,
+        }
+        .max(1);
+
+        let trained = metadata
+            .and_then(|metadata| metadata.context_length)
+            .unwrap_or(context::DEFAULT_CONTEXT as u64);
+
+        let per_token = kv_cache_per_token(file);
+        let budget = hardware
+            .usable_memory()
+            .saturating_sub(file_size(file) + OVERHEAD);
+
+        let mut context = MIN_CONTEXT;
+
+        while context * 2 <= MAX_CONTEXT.min(trained) && context * 2 * per_token <= budget {
+            context *= 2;
+        }
+
+        let context = context.min(trained);
+
+        let batch = if hardware.total_memory < 8 * GB {
+            256
+        } else if uses_gpu {
+            1024
+        } else {
+            512
+        }
+        .min(context as u32);
+
+        let gpu_layers = if uses_gpu {
+            metadata
+                .and_then(|metadata| metadata.block_count)
+                .and_then(|blocks| u32::try_from(blocks + 1).ok())
+                .unwrap_or(ALL_LAYERS)
+        } else {
+            0
+        };
+
+        // Offloaded weights are copied to the GPU, so mapping them only
+        // keeps a second copy around. Locking is only worth it if the model
+        // leaves plenty of memory for everything else.
+        let mmap = !uses_gpu;
+        let mlock = !uses_gpu && memory_needed(file, context) <= hardware.usable_memory() / 2;
+
+        Self {
+            threads,
+            context,
+            batch,
+            mmap,
+            mlock,
+            gpu_layers,
+        }
+    }
+
+    /// The command-line arguments of llama-server for these settings.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--threads".to_owned(),
+            self.threads.to_string(),
+            "--ctx-size".to_owned(),
+            self.context.to_string(),
+            "--batch-size".to_owned(),
+            self.batch.to_string(),
+            "--n-gpu-layers".to_owned(),
+            self.gpu_layers.to_string(),
+        ];
+
+        if !self.mmap {
+            args.push("--no-mmap".to_owned());
+        }
+
+        if self.mlock {
+            args.push("--mlock".to_owned());
+        }
+
+        args
+    }
+
+    /// The same settings as the environment variables llama-server reads.
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![
+            ("LLAMA_ARG_THREADS", self.threads.to_string()),
+            ("LLAMA_ARG_CTX_SIZE", self.context.to_string()),
+            ("LLAMA_ARG_BATCH", self.batch.to_string()),
+            ("LLAMA_ARG_N_GPU_LAYERS", self.gpu_layers.to_string()),
+        ];
+
+        if !self.mmap {
+            env.push(("LLAMA_ARG_NO_MMAP", "1".to_owned()));
+        }
+
+        if self.mlock {
+            env.push(("LLAMA_ARG_MLOCK", "1".to_owned()));
+        }
+
+        env
+    }
+}
+
+/// The bytes a model takes with `context` tokens of cache.
+pub fn memory_needed(file: &model::File, context: u64) -> u64 {
+    file_size(file) + context * kv_cache_per_token(file) + OVERHEAD
+}
+
+/// The bytes of the key-value cache per token, in 16-bit floats.
+///
+/// Without [`gguf::Metadata`], it is guessed from the size of the file.
+fn kv_cache_per_token(file: &model::File) -> u64 {
+    let from_metadata = |metadata: &gguf::Metadata| {
+        let blocks = metadata.block_count?;
+        let embedding = metadata.embedding_length?;
+
+        // Grouped-query attention shares keys and values between heads
+        let shared = match (metadata.head_count, metadata.head_count_kv) {
+            (Some(heads), Some(kv_heads)) if heads > 0 && kv_heads > 0 => heads / kv_heads,
+            _ => 1,
+        };
+
+        Some(2 * blocks * embedding / shared * 2)
+    };
+
+    file.metadata
+        .as_ref()
+        .and_then(from_metadata)
+        .unwrap_or_else(|| (file_size(file) / 32_768).max(16 * 1024))
+}
+
+fn file_size(file: &model::File) -> u64 {
+    file.size.map_or(0, |size| size.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64) -> model::File {
+        model::File {
+            model: model::Id("bartowski/Llama-3.2-3B-Instruct-GGUF".to_owned()),
+            name: name.to_owned(),
+            size: Some(model::Size(size)),
+            sha256: None,
+            metadata: Some(gguf::Metadata {
+                context_length: Some(131_072),
+                embedding_length: Some(3072),
+                block_count: Some(28),
+                head_count: Some(24),
+                head_count_kv: Some(8),
+                ..gguf::Metadata::default()
+            }),
+        }
+    }
+
+    fn variants() -> Vec<model::File> {
+        vec![
+            file("Q8_0.gguf", 3_420_000_000),
+            file("Q4_K_M.gguf", 2_020_000_000),
+            file("Q2_K.gguf", 1_360_000_000),
+            file("F16.gguf", 6_430_000_000),
+        ]
+    }
+
+    #[test]
+    fn test_kiosk() {
+        let kiosk = Hardware {
+            total_memory: 4 * GB,
+            available_memory: 3 * GB,
+            physical_cores: 4,
+            logical_cores: 4,
+            features: Features {
+                neon: true,
+                ..Features::default()
+            },
+            backend: Backend::Cpu,
+        };
+
+        let recommendations = kiosk.recommend(variants());
+        let names: Vec<_> = recommendations
+            .iter()
+            .map(|recommendation| (recommendation.file.name.as_str(), recommendation.fit))
+            .collect();
+
+        assert_eq!(
+            names,
+            [
+                ("Q2_K.gguf", Fit::Comfortable),
+                ("Q4_K_M.gguf", Fit::Tight),
+                ("Q8_0.gguf", Fit::TooLarge),
+                ("F16.gguf", Fit::TooLarge),
+            ]
+        );
+
+        let tuning = Tuning::new(&kiosk, &recommendations[0].file);
+
+        assert_eq!(tuning.threads, 4);
+        assert_eq!(tuning.batch, 256);
+        assert!(tuning.context >= MIN_CONTEXT);
+        assert!(memory_needed(&recommendations[0].file, tuning.context) <= kiosk.usable_memory());
+        assert!(tuning.mmap);
+        assert!(!tuning.mlock);
+    }
+
+    #[test]
+    fn test_workstation() {
+        let workstation = Hardware {
+            total_memory: 64 * GB,
+            available_memory: 48 * GB,
+            physical_cores: 16,
+            logical_cores: 32,
+            features: Features {
+                avx2: true,
+                avx512: true,
+                neon: false,
+            },
+            backend: Backend::Cuda,
+        };
+
+        let recommendations = workstation.recommend(variants());
+
+        assert_eq!(recommendations[0].file.name, "F16.gguf");
+        assert!(recommendations
+            .iter()
+            .all(|recommendation| recommendation.fit == Fit::Comfortable));
+
+        let tuning = Tuning::new(&workstation, &recommendations[0].file);
+
+        assert_eq!(tuning.threads, 15);
+        assert_eq!(tuning.context, MAX_CONTEXT);
+        assert_eq!(tuning.batch, 1024);
+        assert_eq!(tuning.gpu_layers, 29);
+        assert!(!tuning.mmap);
+        assert!(tuning.args().contains(&"--no-mmap".to_owned()));
+        assert!(tuning
+            .env()
+            .contains(&("LLAMA_ARG_CTX_SIZE", MAX_CONTEXT.to_string())));
+    }
+}
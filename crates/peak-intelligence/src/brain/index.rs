@@ -0,0 +1,612 @@
+//! A vector index over text, Markdown and code files in chosen folders.
+//!
+//! Files are cut into chunks along paragraphs, headings or top-level code
+//! blocks, embedded and stored with their line ranges in `index.json` in
+//! the data directory. [`Index::update`] only embeds files whose
+//! modification time changed since the last run.
+use crate::brain::{directory, Assistant, Error};
+use crate::llm::LlmClient;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use walkdir::{DirEntry, WalkDir};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Larger files are skipped.
+pub const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Target chunk length, in characters.
+const CHUNK_SIZE: usize = 1200;
+
+/// Chunks embedded per request.
+const BATCH_SIZE: usize = 16;
+
+/// Directories never worth indexing.
+const IGNORED: &[&str] = &["node_modules", "target", "__pycache__", "venv"];
+
+/// Turns text into vectors. Vectors of different models do not mix, so the
+/// index is rebuilt when the model changes.
+pub trait Embedder: Send + Sync {
+    fn model(&self) -> &str;
+
+    fn embed<'a>(&'a self, inputs: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, Error>>;
+}
+
+impl Embedder for Assistant {
+    fn model(&self) -> &str {
+        self.name()
+    }
+
+    fn embed<'a>(&'a self, inputs: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, Error>> {
+        Assistant::embed(self, inputs).boxed()
+    }
+}
+
+impl Embedder for LlmClient {
+    fn model(&self) -> &str {
+        LlmClient::model(self)
+    }
+
+    fn embed<'a>(&'a self, inputs: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, Error>> {
+        async move {
+            LlmClient::embed(self, inputs)
+                .await
+                .map_err(Error::RequestFailed)
+        }
+        .boxed()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Index {
+    #[serde(skip)]
+    path: PathBuf,
+    model: Option<String>,
+    folders: Vec<PathBuf>,
+    files: BTreeMap<PathBuf, File>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct File {
+    /// Modification time, in milliseconds since the epoch.
+    modified: u64,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Chunk {
+    start_line: usize,
+    end_line: usize,
+    text: String,
+    /// Normalized, so similarity is a dot product.
+    vector: Vec<f32>,
+}
+
+/// A chunk that matched a query.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Hit {
+    pub path: PathBuf,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    pub score: f32,
+}
+
+/// What [`Index::update`] did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub indexed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    pub failed: usize,
+}
+
+impl Index {
+    /// Opens the index in the data directory.
+    pub fn open_default() -> Result<Self, Error> {
+        Self::open(directory::data().join("index.json"))
+    }
+
+    /// Opens the index stored at `path`, or an empty one if there is none.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+
+        let mut index: Self = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(error) => return Err(error.into()),
+        };
+
+        index.path = path;
+
+        Ok(index)
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+
+        let temporary = self.path.with_extension("json.tmp");
+
+        fs::write(&temporary, serde_json::to_vec(self)?)?;
+        fs::rename(&temporary, &self.path)?;
+
+        Ok(())
+    }
+
+    pub fn folders(&self) -> &[PathBuf] {
+        &self.folders
+    }
+
+    /// Adds a folder to index on the next [`Index::update`].
+    pub fn add_folder(&mut self, folder: impl Into<PathBuf>) {
+        let folder = folder.into();
+
+        if !self.folders.contains(&folder) {
+            self.folders.push(folder);
+        }
+    }
+
+    /// Stops indexing a folder and forgets its files.
+    pub fn remove_folder(&mut self, folder: &Path) -> bool {
+        let before = self.folders.len();
+        self.folders.retain(|existing| existing != folder);
+
+        self.files
+            .retain(|path, _| self.folders.iter().any(|folder| path.starts_with(folder)));
+
+        self.folders.len() != before
+    }
+
+    /// Whether the file at `path` is indexed.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    /// The number of files indexed.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.values().all(|file| file.chunks.is_empty())
+    }
+
+    /// Embeds new and modified files, drops deleted ones and saves the
+    /// index.
+    ///
+    /// Files that cannot be read or embedded are counted as failed and
+    /// retried on the next update.
+    pub async fn update(&mut self, embedder: &dyn Embedder) -> Result<Report, Error> {
+        if self.model.as_deref() != Some(embedder.model()) {
+            self.files.clear();
+            self.model = Some(embedder.model().to_owned());
+        }
+
+        let mut report = Report::default();
+        let mut seen = BTreeSet::new();
+
+        for path in self.walk() {
+            let Some(modified) = modified(&path) else {
+                continue;
+            };
+
+            let _ = seen.insert(path.clone());
+
+            if self
+                .files
+                .get(&path)
+                .is_some_and(|file| file.modified == modified)
+            {
+                report.unchanged += 1;
+                continue;
+            }
+
+            match embed_file(embedder, &path).await {
+                Ok(chunks) => {
+                    let _ = self.files.insert(path, File { modified, chunks });
+                    report.indexed += 1;
+                }
+                Err(error) => {
+                    log::warn!("Could not index {}: {error}", path.display());
+
+                    let _ = self.files.remove(&path);
+                    report.failed += 1;
+                }
+            }
+        }
+
+        let before = self.files.len();
+        self.files.retain(|path, _| seen.contains(path));
+        report.removed = before - self.files.len();
+
+        self.save()?;
+
+        Ok(report)
+    }
+
+    /// Finds the chunks closest to `query`, best first.
+    pub async fn search(
+        &self,
+        embedder: &dyn Embedder,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<Hit>, Error> {
+        if self.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if self.model.as_deref() != Some(embedder.model()) {
+            return Err(Error::RequestFailed(format!(
+                "the index was built with another model ({}); update it first",
+                self.model.as_deref().unwrap_or("none")
+            )));
+        }
+
+        let query = embedder
+            .embed(&[query.to_owned()])
+            .await?
+            .pop()
+            .map(normalize)
+            .unwrap_or_default();
+
+        let mut hits: Vec<Hit> = self
+            .files
+            .iter()
+            .flat_map(|(path, file)| {
+                file.chunks.iter().map(|chunk| Hit {
+                    path: path.clone(),
+                    start_line: chunk.start_line,
+                    end_line: chunk.end_line,
+                    text: chunk.text.clone(),
+                    score: dot(&query, &chunk.vector),
+                })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+
+        Ok(hits)
+    }
+
+    /// Finds the chunks that mention the words of `query` the most, best
+    /// first. Unlike [`Index::search`], no model is needed.
+    pub fn find(&self, query: &str, limit: usize) -> Vec<Hit> {
+        let terms: BTreeSet<String> = words(query).filter(|word| word.len() > 1).collect();
+
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut hits: Vec<Hit> = self
+            .files
+            .iter()
+            .flat_map(|(path, file)| {
+                file.chunks.iter().filter_map(|chunk| {
+                    let mut counts = BTreeMap::new();
+
+                    for word in words(&chunk.text).filter(|word| terms.contains(word)) {
+                        *counts.entry(word).or_insert(0u32) += 1;
+                    }
+
+                    if counts.is_empty() {
+                        return None;
+                    }
+
+                    // Matching more of the terms beats repeating one of them
+                    let score = counts
+                        .values()
+                        .map(|count| 1.0 + (*count as f32).ln())
+                        .sum::<f32>()
+                        / terms.len() as f32;
+
+                    Some(Hit {
+                        path: path.clone(),
+                        start_line: chunk.start_line,
+                        end_line: chunk.end_line,
+                        text: chunk.text.clone(),
+                        score,
+                    })
+                })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+
+        hits
+    }
+
+    fn walk(&self) -> BTreeSet<PathBuf> {
+        let mut paths = BTreeSet::new();
+
+        for folder in &self.folders {
+            let entries = WalkDir::new(folder)
+                .into_iter()
+                .filter_entry(|entry| entry.depth() == 0 || !is_ignored(entry))
+                .filter_map(Result::ok);
+
+            for entry in entries {
+                let is_candidate = entry.file_type().is_file()
+                    && Kind::of(entry.path()).is_some()
+                    && entry
+                        .metadata()
+                        .is_ok_and(|metadata| metadata.len() <= MAX_FILE_SIZE);
+
+                if is_candidate {
+                    let _ = paths.insert(entry.into_path());
+                }
+            }
+        }
+
+        paths
+    }
+}
+
+async fn embed_file(embedder: &dyn Embedder, path: &Path) -> Result<Vec<Chunk>, Error> {
+    let Some(kind) = Kind::of(path) else {
+        return Ok(Vec::new());
+    };
+
+    let text = fs::read_to_string(path)?;
+    let pieces = split(kind, &text);
+
+    let mut chunks = Vec::with_capacity(pieces.len());
+
+    for batch in pieces.chunks(BATCH_SIZE) {
+        let inputs: Vec<String> = batch.iter().map(|piece| piece.text.clone()).collect();
+        let vectors = embedder.embed(&inputs).await?;
+
+        chunks.extend(batch.iter().zip(vectors).map(|(piece, vector)| Chunk {
+            start_line: piece.lines.start + 1,
+            end_line: piece.lines.end,
+            text: piece.text.clone(),
+            vector: normalize(vector),
+        }));
+    }
+
+    Ok(chunks)
+}
+
+fn is_ignored(entry: &DirEntry) -> bool {
+    let name = entry.file_name().to_string_lossy();
+
+    name.starts_with('.') || (entry.file_type().is_dir() && IGNORED.contains(&name.as_ref()))
+}
+
+fn modified(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64)
+}
+
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = dot(&vector, &vector).sqrt();
+
+    if norm > 0.0 {
+        for value in &mut vector {
+            *value /= norm;
+        }
+    }
+
+    vector
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Markdown,
+    Code,
+}
+
+impl Kind {
+    fn of(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        Some(match extension.as_str() {
+            "txt" | "text" | "rst" | "org" | "log" => Self::Text,
+            "md" | "markdown" | "mdx" => Self::Markdown,
+            "rs" | "py" | "js" | "jsx" | "ts" | "tsx" | "go" | "c" | "h" | "cc" | "cpp" | "hpp"
+            | "java" | "kt" | "swift" | "rb" | "php" | "cs" | "lua" | "sh" | "toml" | "yaml"
+            | "yml" | "json" | "html" | "css" | "sql" => Self::Code,
+            _ => return None,
+        })
+    }
+
+    /// Whether a new section starts at `line`.
+    fn is_boundary(self, previous: &str, line: &str) -> bool {
+        match self {
+            Self::Markdown => line.starts_with('#'),
+            Self::Text => previous.trim().is_empty() && !line.trim().is_empty(),
+            Self::Code => {
+                previous.trim().is_empty()
+                    && !line.trim().is_empty()
+                    && !line.starts_with(char::is_whitespace)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Piece {
+    /// Zero-based, end exclusive.
+    lines: Range<usize>,
+    text: String,
+}
+
+/// Cuts text into pieces of about [`CHUNK_SIZE`] characters along section
+/// boundaries. Sections longer than that are cut between lines.
+fn split(kind: Kind, text: &str) -> Vec<Piece> {
+    let lines: Vec<&str> = text.lines().collect();
+
+    let mut sections = Vec::new();
+    let mut start = 0;
+
+    for i in 1..lines.len() {
+        if kind.is_boundary(lines[i - 1], lines[i]) {
+            sections.push(start..i);
+            start = i;
+        }
+    }
+
+    if start < lines.len() {
+        sections.push(start..lines.len());
+    }
+
+    let size = |range: &Range<usize>| -> usize {
+        lines[range.clone()]
+            .iter()
+            .map(|line| line.chars().count() + 1)
+            .sum()
+    };
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+
+    for section in sections {
+        if size(&section) > CHUNK_SIZE {
+            let mut start = section.start;
+
+            for i in section.clone() {
+                if i > start && size(&(start..i + 1)) > CHUNK_SIZE {
+                    ranges.push(start..i);
+                    start = i;
+                }
+            }
+
+            ranges.push(start..section.end);
+            continue;
+        }
+
+        match ranges.last_mut() {
+            Some(last) if size(&(last.start..section.end)) <= CHUNK_SIZE => {
+                last.end = section.end;
+            }
+            _ => ranges.push(section),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|lines_| Piece {
+            text: lines[lines_.clone()].join("\n").trim().to_owned(),
+            lines: lines_,
+        })
+        .filter(|piece| !piece.text.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts letters, so texts sharing words end up close.
+    struct Letters;
+
+    impl Embedder for Letters {
+        fn model(&self) -> &str {
+            "letters"
+        }
+
+        fn embed<'a>(
+            &'a self,
+            inputs: &'a [String],
+        ) -> BoxFuture<'a, Result<Vec<Vec<f32>>, Error>> {
+            async move {
+                Ok(inputs
+                    .iter()
+                    .map(|input| {
+                        let mut vector = vec![0.0; 26];
+
+                        for c in input.to_ascii_lowercase().chars() {
+                            if c.is_ascii_lowercase() {
+                                vector[(c as u8 - b'a') as usize] += 1.0;
+                            }
+                        }
+
+                        vector
+                    })
+                    .collect())
+            }
+            .boxed()
+        }
+    }
+
+    #[test]
+    fn test_split_markdown_by_heading() {
+        // Long enough not to share a chunk with either neighbour
+        let long = "word ".repeat(238);
+        let text = format!("# One\nfirst\n\n# Two\n{long}\n# Three\nthird");
+
+        let pieces = split(Kind::Markdown, &text);
+
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(pieces[0].lines, 0..3);
+        assert_eq!(pieces[0].text, "# One\nfirst");
+        assert_eq!(pieces[1].lines, 3..5);
+        assert_eq!(pieces[2].text, "# Three\nthird");
+    }
+
+    #[tokio::test]
+    async fn test_update_is_incremental() {
+        let root = std::env::temp_dir().join(format!("index-{}", uuid::Uuid::new_v4()));
+        let folder = root.join("notes");
+        fs::create_dir_all(folder.join(".git")).unwrap();
+
+        fs::write(folder.join("zebra.md"), "# Zebras\nzebras zigzag").unwrap();
+        fs::write(folder.join("apple.txt"), "apples are a fruit").unwrap();
+        fs::write(folder.join("photo.png"), "not text").unwrap();
+        fs::write(folder.join(".git").join("HEAD"), "ref: main").unwrap();
+
+        let mut index = Index::open(root.join("index.json")).unwrap();
+        index.add_folder(&folder);
+
+        let report = index.update(&Letters).await.unwrap();
+        assert_eq!(report.indexed, 2);
+
+        // Reopening keeps the vectors
+        let mut index = Index::open(root.join("index.json")).unwrap();
+
+        fs::remove_file(folder.join("apple.txt")).unwrap();
+
+        let report = index.update(&Letters).await.unwrap();
+        assert_eq!(
+            report,
+            Report {
+                unchanged: 1,
+                removed: 1,
+                ..Report::default()
+            }
+        );
+
+        let hits = index.search(&Letters, "zigzag", 5).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, folder.join("zebra.md"));
+        assert_eq!((hits[0].start_line, hits[0].end_line), (1, 2));
+
+        assert_eq!(
+            index.find("Zebras ZIGZAG!", 5)[0].path,
+            folder.join("zebra.md")
+        );
+        assert!(index.find("apples", 5).is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,746 @@
+//! Keeping the right models running.
+//!
+//! The [`Manager`] runs one model per [`Role`], each on its own port. It
+//! boots them when they are first needed, hot-swaps them, unloads the ones
+//! that sit idle and restarts the ones that crash, waiting longer after every
+//! crash in a row. Requests made while a model is booting wait for it.
+use crate::brain::assistant::{Assistant, Backend, BootEvent};
+use crate::brain::model;
+use crate::brain::Error;
+
+use futures::future::{BoxFuture, Shared};
+use futures::FutureExt;
+use sipper::Sipper;
+use tokio::sync::broadcast;
+
+use std::collections::BTreeMap;
+use std::mem;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+/// What a model is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    /// Answers in chats; usually the largest model.
+    Answer,
+    /// Titles, plans and other short tasks. Uses the [`Role::Answer`] model
+    /// unless it has one of its own.
+    Utility,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// Models unused for this long are unloaded to free memory.
+    pub idle_timeout: Duration,
+    /// How often running models are checked.
+    pub check_interval: Duration,
+    /// How long to wait before restarting a crashed model, doubled for
+    /// every crash in a row.
+    pub restart_delay: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(10 * 60),
+            check_interval: Duration::from_secs(5),
+            restart_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+/// What happens to the models, for the UI.
+#[derive(Debug, Clone)]
+pub enum Event {
+    Booting {
+        role: Role,
+        file: Box<model::File>,
+    },
+    Progressed {
+        role: Role,
+        stage: String,
+        percent: u32,
+    },
+    Ready {
+        role: Role,
+        port: u32,
+    },
+    Unloaded {
+        role: Role,
+    },
+    Crashed {
+        role: Role,
+        retry_in: Duration,
+    },
+    Failed {
+        role: Role,
+        error: Error,
+    },
+}
+
+/// The state of the model of a [`Role`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum State {
+    Unloaded,
+    Booting,
+    Ready { port: u32 },
+    Failed(String),
+}
+
+/// Boots and stops the servers of a [`Manager`].
+pub trait Server: Send + Sync + 'static {
+    type Instance: Clone + Send + Sync + 'static;
+
+    fn boot(
+        &self,
+        file: model::File,
+        port: u32,
+        progress: Progress,
+    ) -> BoxFuture<'static, Result<Self::Instance, Error>>;
+
+    fn is_healthy<'a>(&'a self, instance: &'a Self::Instance) -> BoxFuture<'a, bool>;
+
+    /// Why an unhealthy instance stopped, if it was stopped on purpose
+    /// rather than crashing. Such models are not restarted.
+    fn stop_reason(&self, _instance: &Self::Instance) -> Option<Error> {
+        None
+    }
+
+    fn stop(&self, instance: &Self::Instance);
+}
+
+/// Reports the boot progress of a model.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    role: Role,
+    events: broadcast::Sender<Event>,
+}
+
+impl Progress {
+    pub fn report(&self, stage: impl Into<String>, percent: u32) {
+        let _ = self.events.send(Event::Progressed {
+            role: self.role,
+            stage: stage.into(),
+            percent,
+        });
+    }
+}
+
+/// Runs models with llama-server, as [`Assistant`]s.
+#[derive(Debug, Clone)]
+pub struct Llama {
+    pub directory: model::Directory,
+    pub backend: Backend,
+}
+
+impl Server for Llama {
+    type Instance = Assistant;
+
+    fn boot(
+        &self,
+        file: model::File,
+        port: u32,
+        progress: Progress,
+    ) -> BoxFuture<'static, Result<Assistant, Error>> {
+        let directory = self.directory.clone();
+        let backend = self.backend;
+
+        async move {
+            let mut boot = Assistant::boot_on(directory, file, backend, port).pin();
+
+            while let Some(event) = boot.sip().await {
+                if let BootEvent::Progressed { stage, percent } = event {
+                    progress.report(stage, percent);
+                }
+            }
+
+            boot.await
+        }
+        .boxed()
+    }
+
+    fn is_healthy<'a>(&'a self, assistant: &'a Assistant) -> BoxFuture<'a, bool> {
+        assistant.is_healthy().boxed()
+    }
+
+    fn stop_reason(&self, assistant: &Assistant) -> Option<Error> {
+        assistant.stop_reason()
+    }
+
+    fn stop(&self, assistant: &Assistant) {
+        assistant.shutdown();
+    }
+}
+
+/// Runs a model per [`Role`]. Cheap to clone.
+pub struct Manager<S: Server = Llama> {
+    shared: Arc<Inner<S>>,
+}
+
+impl<S: Server> std::fmt::Debug for Manager<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Manager")
+            .field("states", &self.states())
+            .finish()
+    }
+}
+
+impl<S: Server> Clone for Manager<S> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+struct Inner<S: Server> {
+    server: S,
+    settings: Settings,
+    slots: Mutex<BTreeMap<Role, Slot<S::Instance>>>,
+    events: broadcast::Sender<Event>,
+}
+
+#[derive(Debug)]
+struct Slot<I> {
+    file: Option<model::File>,
+    load: Load<I>,
+    /// Bumped on every boot, so stale boots can be told apart.
+    generation: u64,
+    /// Crashes in a row, for the restart delay.
+    crashes: u32,
+    /// When a crashed model is restarted.
+    restart_at: Option<Instant>,
+}
+
+impl<I> Default for Slot<I> {
+    fn default() -> Self {
+        Self {
+            file: None,
+            load: Load::Unloaded,
+            generation: 0,
+            crashes: 0,
+            restart_at: None,
+        }
+    }
+}
+
+type Boot<I> = Shared<BoxFuture<'static, Result<I, Error>>>;
+
+enum Load<I> {
+    Unloaded,
+    Booting(Boot<I>),
+    Ready {
+        instance: I,
+        port: u32,
+        since: Instant,
+        last_used: Instant,
+    },
+    Failed(Error),
+}
+
+impl<I> std::fmt::Debug for Load<I> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unloaded => f.write_str("Unloaded"),
+            Self::Booting(_) => f.write_str("Booting"),
+            Self::Ready { port, .. } => write!(f, "Ready {{ port: {port} }}"),
+            Self::Failed(error) => write!(f, "Failed({error})"),
+        }
+    }
+}
+
+impl<S: Server> Manager<S> {
+    /// Creates a manager and starts watching over its models.
+    ///
+    /// Must be called within a Tokio runtime. The watch stops once every
+    /// clone of the manager is dropped.
+    pub fn new(server: S, settings: Settings) -> Self {
+        let (events, _) = broadcast::channel(64);
+
+        let shared = Arc::new(Inner {
+            server,
+            settings,
+            slots: Mutex::new(BTreeMap::new()),
+            events,
+        });
+
+        tokio::spawn(watch(Arc::downgrade(&shared)));
+
+        Self { shared }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.shared.events.subscribe()
+    }
+
+    pub fn states(&self) -> BTreeMap<Role, State> {
+        self.shared
+            .slots()
+            .iter()
+            .map(|(role, slot)| {
+                let state = match &slot.load {
+                    Load::Unloaded => State::Unloaded,
+                    Load::Booting(_) => State::Booting,
+                    Load::Ready { port, .. } => State::Ready { port: *port },
+                    Load::Failed(error) => State::Failed(error.to_string()),
+                };
+
+                (*role, state)
+            })
+            .collect()
+    }
+
+    /// Sets the model of a role and boots it, stopping the one it replaces.
+    ///
+    /// Requests for the role wait until the new model is ready.
+    pub async fn load(&self, role: Role, file: model::File) -> Result<S::Instance, Error> {
+        {
+            let mut slots = self.shared.slots();
+            let slot = slots.entry(role).or_default();
+
+            let is_running = matches!(slot.load, Load::Booting(_) | Load::Ready { .. });
+
+            if !(is_running
+                && slot
+                    .file
+                    .as_ref()
+                    .is_some_and(|current| same(current, &file)))
+            {
+                slot.file = Some(file.clone());
+                slot.crashes = 0;
+
+                self.shared.stop(role, slot);
+                self.shared.start(role, slot, file);
+            }
+        }
+
+        self.get(role).await
+    }
+
+    /// Returns the model of a role, booting it first if needed.
+    pub async fn get(&self, role: Role) -> Result<S::Instance, Error> {
+        let role = self.resolve(role);
+
+        loop {
+            let (boot, generation) = {
+                let mut slots = self.shared.slots();
+                let slot = slots.entry(role).or_default();
+
+                match &mut slot.load {
+                    Load::Ready {
+                        instance,
+                        last_used,
+                        ..
+                    } => {
+                        *last_used = Instant::now();
+
+                        return Ok(instance.clone());
+                    }
+                    Load::Booting(boot) => (boot.clone(), slot.generation),
+                    // Crashed; the watch restarts it when it is time
+                    Load::Failed(error) if slot.restart_at.is_some() => {
+                        return Err(error.clone());
+                    }
+                    Load::Unloaded | Load::Failed(_) => {
+                        let Some(file) = slot.file.clone() else {
+                            return Err(Error::RequestFailed(format!(
+                                "no model is set for {role:?}"
+                            )));
+                        };
+
+                        self.shared.start(role, slot, file);
+                        continue;
+                    }
+                }
+            };
+
+            let result = boot.await;
+
+            // A swap replaced the model while it was booting; wait for the new one
+            if self
+                .shared
+                .slots()
+                .get(&role)
+                .is_some_and(|slot| slot.generation == generation)
+            {
+                return result;
+            }
+        }
+    }
+
+    /// Stops the model of a role until it is needed again.
+    pub fn unload(&self, role: Role) {
+        let mut slots = self.shared.slots();
+
+        if let Some(slot) = slots.get_mut(&role) {
+            self.shared.stop(role, slot);
+        }
+    }
+
+    /// Roles without a model of their own share the answer model.
+    fn resolve(&self, role: Role) -> Role {
+        let has_model = self
+            .shared
+            .slots()
+            .get(&role)
+            .is_some_and(|slot| slot.file.is_some());
+
+        if has_model {
+            role
+        } else {
+            Role::Answer
+        }
+    }
+}
+
+impl<S: Server> Inner<S> {
+    fn slots(&self) -> MutexGuard<'_, BTreeMap<Role, Slot<S::Instance>>> {
+        self.slots.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Boots the file of a slot on a free port.
+    fn start(self: &Arc<Self>, role: Role, slot: &mut Slot<S::Instance>, file: model::File) {
+        slot.generation += 1;
+        slot.restart_at = None;
+
+        let generation = slot.generation;
+        let port = free_port();
+
+        let _ = self.events.send(Event::Booting {
+            role,
+            file: Box::new(file.clone()),
+        });
+
+        let progress = Progress {
+            role,
+            events: self.events.clone(),
+        };
+
+        let boot = self.server.boot(file, port, progress).shared();
+        slot.load = Load::Booting(boot.clone());
+
+        let shared = Arc::downgrade(self);
+
+        tokio::spawn(async move {
+            let result = boot.await;
+
+            let Some(shared) = shared.upgrade() else {
+                return;
+            };
+
+            let mut slots = shared.slots();
+            let slot = slots.entry(role).or_default();
+
+            if slot.generation != generation {
+                if let Ok(instance) = &result {
+                    shared.server.stop(instance);
+                }
+
+                return;
+            }
+
+            match result {
+                Ok(instance) => {
+                    slot.load = Load::Ready {
+                        instance,
+                        port,
+                        since: Instant::now(),
+                        last_used: Instant::now(),
+                    };
+
+                    let _ = shared.events.send(Event::Ready { role, port });
+                }
+                Err(error) => {
+                    slot.load = Load::Failed(error.clone());
+
+                    let _ = shared.events.send(Event::Failed { role, error });
+                }
+            }
+        });
+    }
+
+    /// Stops the model of a slot, if it is running.
+    fn stop(&self, role: Role, slot: &mut Slot<S::Instance>) {
+        // Boots in progress are stopped once they finish
+        slot.generation += 1;
+        slot.restart_at = None;
+
+        match mem::replace(&mut slot.load, Load::Unloaded) {
+            Load::Ready { instance, .. } => {
+                self.server.stop(&instance);
+
+                let _ = self.events.send(Event::Unloaded { role });
+            }
+            Load::Booting(_) => {
+                let _ = self.events.send(Event::Unloaded { role });
+            }
+            Load::Unloaded | Load::Failed(_) => {}
+        }
+    }
+}
+
+/// Unloads idle models and restarts crashed ones, until the manager is gone.
+async fn watch<S: Server>(shared: Weak<Inner<S>>) {
+    loop {
+        let Some(interval) = shared
+            .upgrade()
+            .map(|shared| shared.settings.check_interval)
+        else {
+            return;
+        };
+
+        tokio::time::sleep(interval).await;
+
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+
+        shared.restart_crashed();
+
+        let running: Vec<_> = shared
+            .slots()
+            .iter()
+            .filter_map(|(role, slot)| match &slot.load {
+                Load::Ready {
+                    instance,
+                    since,
+                    last_used,
+                    ..
+                } => Some((*role, instance.clone(), *since, *last_used, slot.generation)),
+                _ => None,
+            })
+            .collect();
+
+        for (role, instance, since, last_used, generation) in running {
+            let is_idle = last_used.elapsed() >= shared.settings.idle_timeout;
+            let is_healthy = is_idle || shared.server.is_healthy(&instance).await;
+
+            let mut slots = shared.slots();
+
+            let Some(slot) = slots
+                .get_mut(&role)
+                .filter(|slot| slot.generation == generation)
+            else {
+                continue;
+            };
+
+            if is_idle {
+                shared.stop(role, slot);
+            } else if is_healthy {
+                // Up long enough to call the crashes before a fluke
+                if since.elapsed() >= shared.settings.restart_delay * MAX_BACKOFF {
+                    slot.crashes = 0;
+                }
+            } else if let Some(error) = shared.server.stop_reason(&instance) {
+                log::warn!("The {role:?} model was stopped: {error}");
+
+                shared.server.stop(&instance);
+                slot.load = Load::Failed(error.clone());
+
+                let _ = shared.events.send(Event::Failed { role, error });
+            } else {
+                let retry_in =
+                    shared.settings.restart_delay * 2u32.pow(slot.crashes).min(MAX_BACKOFF);
+
+                log::warn!("The {role:?} model crashed; restarting it in {retry_in:?}");
+
+                shared.server.stop(&instance);
+                slot.crashes += 1;
+                slot.load = Load::Failed(Error::ServerExited("the model crashed".to_owned()));
+                slot.restart_at = Some(Instant::now() + retry_in);
+
+                let _ = shared.events.send(Event::Crashed { role, retry_in });
+            }
+        }
+    }
+}
+
+impl<S: Server> Inner<S> {
+    /// Restarts the crashed models whose delay is over.
+    fn restart_crashed(self: &Arc<Self>) {
+        let mut slots = self.slots();
+
+        for (role, slot) in slots.iter_mut() {
+            let is_due = slot
+                .restart_at
+                .is_some_and(|restart_at| restart_at <= Instant::now());
+
+            if let (true, Some(file)) = (is_due, slot.file.clone()) {
+                self.start(*role, slot, file);
+            }
+        }
+    }
+}
+
+fn same(a: &model::File, b: &model::File) -> bool {
+    a.model == b.model && a.name == b.name
+}
+
+/// The longest restart delay, in multiples of [`Settings::restart_delay`].
+const MAX_BACKOFF: u32 = 32;
+
+/// Asks the system for a port nobody is listening on.
+fn free_port() -> u32 {
+    TcpListener::bind(("127.0.0.1", 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|address| u32::from(address.port()))
+        .unwrap_or(Assistant::HOST_PORT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    #[derive(Debug, Clone)]
+    struct Instance {
+        name: String,
+        port: u32,
+        is_alive: Arc<AtomicBool>,
+        is_killed: Arc<AtomicBool>,
+    }
+
+    #[derive(Debug, Default)]
+    struct Fake {
+        boots: AtomicUsize,
+    }
+
+    impl Server for Fake {
+        type Instance = Instance;
+
+        fn boot(
+            &self,
+            file: model::File,
+            port: u32,
+            progress: Progress,
+        ) -> BoxFuture<'static, Result<Instance, Error>> {
+            let _ = self.boots.fetch_add(1, Ordering::SeqCst);
+
+            async move {
+                progress.report("Loading model...", 50);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+
+                Ok(Instance {
+                    name: file.name,
+                    port,
+                    is_alive: Arc::new(AtomicBool::new(true)),
+                    is_killed: Arc::new(AtomicBool::new(false)),
+                })
+            }
+            .boxed()
+        }
+
+        fn is_healthy<'a>(&'a self, instance: &'a Instance) -> BoxFuture<'a, bool> {
+            async move { instance.is_alive.load(Ordering::SeqCst) }.boxed()
+        }
+
+        fn stop_reason(&self, instance: &Instance) -> Option<Error> {
+            instance
+                .is_killed
+                .load(Ordering::SeqCst)
+                .then(|| Error::ResourceLimitExceeded("Killed".to_owned()))
+        }
+
+        fn stop(&self, instance: &Instance) {
+            instance.is_alive.store(false, Ordering::SeqCst);
+        }
+    }
+
+    fn file(name: &str) -> model::File {
+        model::File {
+            model: model::Id("bartowski/Qwen".to_owned()),
+            name: name.to_owned(),
+            size: None,
+            sha256: None,
+            metadata: None,
+        }
+    }
+
+    async fn restarted(events: &mut broadcast::Receiver<Event>) -> (Duration, u32) {
+        let retry_in = loop {
+            if let Event::Crashed { retry_in, .. } = events.recv().await.unwrap() {
+                break retry_in;
+            }
+        };
+
+        loop {
+            if let Event::Ready { port, .. } = events.recv().await.unwrap() {
+                return (retry_in, port);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_manager() {
+        let manager = Manager::new(
+            Fake::default(),
+            Settings {
+                idle_timeout: Duration::from_millis(400),
+                check_interval: Duration::from_millis(50),
+                restart_delay: Duration::from_millis(20),
+            },
+        );
+
+        assert!(manager.get(Role::Utility).await.is_err());
+
+        let small = manager
+            .load(Role::Answer, file("small.gguf"))
+            .await
+            .unwrap();
+        assert_eq!(small.name, "small.gguf");
+
+        // Utility requests share the answer model
+        assert_eq!(manager.get(Role::Utility).await.unwrap().port, small.port);
+
+        // Requests made during a swap wait for the new model
+        let (large, waiting) =
+            tokio::join!(manager.load(Role::Answer, file("large.gguf")), async {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                manager.get(Role::Answer).await
+            });
+
+        assert_eq!(large.unwrap().name, "large.gguf");
+        assert_eq!(waiting.unwrap().name, "large.gguf");
+        assert!(!small.is_alive.load(Ordering::SeqCst));
+
+        // Crashed models are restarted
+        let large = manager.get(Role::Answer).await.unwrap();
+        let mut events = manager.subscribe();
+        large.is_alive.store(false, Ordering::SeqCst);
+
+        let (retry_in, port) = restarted(&mut events).await;
+        let instance = manager.get(Role::Answer).await.unwrap();
+        assert_eq!(retry_in, Duration::from_millis(20));
+        assert_eq!(instance.port, port);
+        assert!(instance.is_alive.load(Ordering::SeqCst));
+        assert_eq!(manager.shared.server.boots.load(Ordering::SeqCst), 3);
+
+        // Crashes in a row back off
+        instance.is_alive.store(false, Ordering::SeqCst);
+
+        let (retry_in, _) = restarted(&mut events).await;
+        let instance = manager.get(Role::Answer).await.unwrap();
+        assert_eq!(retry_in, Duration::from_millis(40));
+        assert_eq!(manager.shared.server.boots.load(Ordering::SeqCst), 4);
+
+        // Idle models are unloaded
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(manager.states()[&Role::Answer], State::Unloaded);
+        assert!(!instance.is_alive.load(Ordering::SeqCst));
+
+        let killed = manager.get(Role::Answer).await.unwrap();
+        assert_eq!(manager.shared.server.boots.load(Ordering::SeqCst), 5);
+
+        // Models killed by the resource guardian stay down
+        killed.is_killed.store(true, Ordering::SeqCst);
+        killed.is_alive.store(false, Ordering::SeqCst);
+
+        while !matches!(events.recv().await.unwrap(), Event::Failed { .. }) {}
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(matches!(manager.states()[&Role::Answer], State::Failed(_)));
+        assert_eq!(manager.shared.server.boots.load(Ordering::SeqCst), 5);
+    }
+}
@@ -1,5 +1,12 @@
 pub mod assistant;
 pub mod chat;
+pub mod context;
+pub mod format;
+pub mod hardware;
+#[cfg(feature = "native")]
+pub mod index;
+#[cfg(feature = "native")]
+pub mod manager;
 pub mod model;
 pub mod plan;
 pub mod settings;
@@ -7,6 +14,8 @@ pub mod web;
 
 pub use assistant::Assistant;
 pub use chat::Chat;
+#[cfg(feature = "native")]
+pub use manager::Manager;
 pub use model::Model;
 pub use plan::Plan;
 pub use settings::Settings;
@@ -31,6 +40,8 @@ pub enum Error {
     #[cfg(feature = "llm")]
     #[error("llama-server failed: {0:?}")]
     ExecutorFailed(llama_server::Error),
+    #[error("llama-server stopped: {0}")]
+    ServerExited(String),
     #[error("JSON deserialization failed: {0}")]
     InvalidJson(Arc<serde_json::Error>),
     #[error("TOML deserialization failed: {0}")]
@@ -2,6 +2,7 @@ use crate::brain::directory;
 use crate::brain::request;
 use crate::brain::Error;
 
+use chrono::{DateTime, Utc};
 use decoder::{decode, encode, Value};
 use serde::{Deserialize, Serialize};
 #[allow(unused_imports)]
@@ -14,6 +15,8 @@ use std::collections::BTreeMap;
 use std::fmt;
 use std::path::{Path, PathBuf};
 
+pub mod gguf;
+
 const HF_URL: &str = "https://huggingface.co";
 const API_URL: &str = "https://huggingface.co/api";
 
@@ -185,6 +188,12 @@ pub struct File {
     pub name: String,
     #[serde(default)]
     pub size: Option<Size>,
+    /// The SHA-256 of the file in the repository, as a hex string.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// What the file says about the model, once it is in the [`Library`].
+    #[serde(skip)]
+    pub metadata: Option<gguf::Metadata>,
 }
 
 impl File {
@@ -192,13 +201,6 @@ impl File {
         let url = format!("{}/models/{}/tree/main", API_URL, id.0);
         let response = crate::http::HttpClient::get(&url).await?;
 
-        #[derive(Debug, Deserialize)]
-        struct Entry {
-            r#type: String,
-            path: String,
-            size: u64,
-        }
-
         let entries: Vec<Entry> = response.json()?;
         let mut files: BTreeMap<Bits, Vec<File>> = BTreeMap::new();
 
@@ -230,12 +232,72 @@ impl File {
                 model: id.clone(),
                 name: entry.path,
                 size: Some(Size(entry.size)),
+                sha256: entry.lfs.map(|lfs| lfs.oid),
+                metadata: None,
             })
         }
 
         Ok(files)
     }
 
+    /// The bytes the library budget of the settings leaves for this file,
+    /// if there is a budget.
+    ///
+    /// Fails if files have to be removed first, naming them.
+    #[cfg(feature = "native")]
+    async fn budget(&self, directory: &Directory) -> Result<Option<u64>, Error> {
+        let Some(budget) = crate::brain::Settings::fetch()
+            .ok()
+            .and_then(|settings| settings.library_budget)
+        else {
+            return Ok(None);
+        };
+
+        let library = Library::scan(&directory.0).await?;
+        let incoming = self.size.unwrap_or(Size(0));
+
+        if incoming.0 > budget.0 {
+            return Err(Error::ResourceLimitExceeded(format!(
+                "{name} takes {incoming}, more than the whole library budget",
+                name = self.name
+            )));
+        }
+
+        let removals = library.suggest_removals(budget, incoming);
+
+        if !removals.is_empty() {
+            let names: Vec<_> = removals.iter().map(|file| file.name.as_str()).collect();
+
+            return Err(Error::ResourceLimitExceeded(format!(
+                "{name} does not fit in the library budget; remove {names} first",
+                name = self.name,
+                names = names.join(", ")
+            )));
+        }
+
+        Ok(Some(budget.0.saturating_sub(library.size().0)))
+    }
+
+    /// Looks up the SHA-256 of the file in the repository.
+    pub async fn checksum(&self) -> Result<Option<String>, Error> {
+        let (folder, _) = self.name.rsplit_once('/').unwrap_or_default();
+        let url = format!("{}/models/{}/tree/main/{folder}", API_URL, self.model.0);
+        let response = crate::http::HttpClient::get(&url).await?;
+
+        let entries: Vec<Entry> = response.json()?;
+
+        Ok(entries
+            .into_iter()
+            .find(|entry| entry.path == self.name)
+            .and_then(|entry| entry.lfs)
+            .map(|lfs| lfs.oid))
+    }
+
+    /// Downloads the file into the library, unless it is already there.
+    ///
+    /// Interrupted downloads resume where they stopped, and finished ones are
+    /// checked against the [`sha256`](Self::sha256) of the repository before
+    /// they are used.
     pub fn download<'a>(
         &'a self,
         #[cfg_attr(not(feature = "native"), allow(unused_variables))] directory: &'a Directory,
@@ -245,15 +307,16 @@ impl File {
                 #[cfg(feature = "native")]
                 {
                     let old_path = Directory::old().0.join(&self.name);
-                    let directory = directory.0.join(&self.model.0);
-                    let model_path = directory.join(&self.name);
+                    let model_directory = directory.0.join(&self.model.0);
+                    let model_path = model_directory.join(&self.name);
 
-                    fs::create_dir_all(&directory).await?;
+                    fs::create_dir_all(&model_directory).await?;
 
                     if fs::try_exists(&model_path).await? {
                         let file_metadata = fs::metadata(&model_path).await?;
 
                         if self.size.is_none_or(|size| size == file_metadata.len()) {
+                            Library::used(directory, self).await;
                             return Ok(model_path);
                         }
 
@@ -262,6 +325,7 @@ impl File {
 
                     if fs::copy(&old_path, &model_path).await.is_ok() {
                         let _ = fs::remove_file(old_path).await;
+                        Library::used(directory, self).await;
                         return Ok(model_path);
                     }
 
@@ -274,8 +338,34 @@ impl File {
 
                     let temp_path = model_path.with_extension("tmp");
 
-                    request::download_file(url, &temp_path).run(&sender).await?;
+                    let checksum = match &self.sha256 {
+                        Some(sha256) => Some(sha256.clone()),
+                        None => self.checksum().await.ok().flatten(),
+                    };
+
+                    let budget = self.budget(directory).await?;
+
+                    request::download_file(url, &temp_path, budget)
+                        .run(&sender)
+                        .await?;
+
+                    if let Some(expected) = checksum {
+                        let actual = sha256(temp_path.clone()).await?;
+
+                        if !actual.eq_ignore_ascii_case(&expected) {
+                            fs::remove_file(&temp_path).await?;
+
+                            return Err(Error::RequestFailed(format!(
+                                "{name} is corrupted: expected SHA-256 {expected}, got {actual}",
+                                name = self.name
+                            )));
+                        }
+                    } else {
+                        log::warn!("No checksum for {}; it was not verified", self.name);
+                    }
+
                     fs::rename(temp_path, &model_path).await?;
+                    Library::used(directory, self).await;
 
                     Ok(model_path)
                 }
@@ -298,6 +388,8 @@ impl File {
             model: Id(file.required("model", string)?),
             name: file.required("name", string)?,
             size: file.optional("size", u64)?.map(Size),
+            sha256: None,
+            metadata: None,
         })
     }
 
@@ -319,6 +411,52 @@ impl File {
     }
 }
 
+/// An entry of the file tree of a repository.
+#[derive(Debug, Deserialize)]
+struct Entry {
+    r#type: String,
+    path: String,
+    size: u64,
+    /// Only present for files stored with Git LFS, like models.
+    #[serde(default)]
+    lfs: Option<Lfs>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Lfs {
+    /// The SHA-256 of the contents.
+    oid: String,
+}
+
+#[cfg(feature = "native")]
+async fn sha256(path: PathBuf) -> Result<String, Error> {
+    use sha2::{Digest, Sha256};
+    use std::io::Read;
+
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; 1 << 20];
+
+        loop {
+            let read = file.read(&mut buffer)?;
+
+            if read == 0 {
+                break;
+            }
+
+            hasher.update(&buffer[..read]);
+        }
+
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect())
+    })
+    .await?
+}
+
 impl fmt::Display for File {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(&self.name)
@@ -379,6 +517,8 @@ impl Readme {
 pub struct Library {
     directory: Directory,
     files: Vec<File>,
+    /// When each file was last booted, by [`File::relative_path`].
+    last_used: BTreeMap<PathBuf, DateTime<Utc>>,
 }
 
 impl Library {
@@ -386,7 +526,9 @@ impl Library {
         #[cfg(feature = "native")]
         {
             let mut files = Vec::new();
+            let mut last_used = BTreeMap::new();
             let directory = directory.as_ref();
+            let usage = Self::usage(directory).await;
             let mut list = fs::read_dir(directory).await?;
 
             while let Some(author) = list.next_entry().await? {
@@ -401,6 +543,13 @@ impl Library {
                         continue;
                     }
 
+                    let id = Id(format!(
+                        "{}/{}",
+                        author.file_name().display(),
+                        model.file_name().display(),
+                    ));
+
+                    let mut shards = Vec::new();
                     let mut directory = fs::read_dir(model.path()).await?;
 
                     while let Some(file) = directory.next_entry().await? {
@@ -411,21 +560,66 @@ impl Library {
                         }
 
                         let name = file.file_name().display().to_string();
+                        let metadata = file.metadata().await?;
+
+                        let header = gguf::Metadata::open(file.path())
+                            .await
+                            .inspect_err(|error| log::warn!("Invalid GGUF file {name}: {error}"))
+                            .ok();
 
                         // If it's a sharded model, only register the first shard as the model entry
-                        if name.contains("-of-") && !name.contains("-00001-of-") {
-                            continue;
+                        if let Some(shard) = shard(&name) {
+                            if !name.contains("-00001-of-") {
+                                shards.push((
+                                    shard.to_owned(),
+                                    metadata.len(),
+                                    header.and_then(|header| header.parameters),
+                                ));
+                                continue;
+                            }
                         }
 
-                        files.push(File {
-                            model: Id(format!(
-                                "{}/{}",
-                                author.file_name().display(),
-                                model.file_name().display(),
-                            )),
+                        let file = File {
+                            model: id.clone(),
                             name,
-                            size: Some(Size(file.metadata().await?.len())),
-                        });
+                            size: Some(Size(metadata.len())),
+                            sha256: None,
+                            metadata: header,
+                        };
+
+                        let path = file.relative_path();
+
+                        if let Some(used) = usage
+                            .get(&path)
+                            .copied()
+                            .or_else(|| metadata.modified().ok().map(DateTime::from))
+                        {
+                            let _ = last_used.insert(path, used);
+                        }
+
+                        files.push(file);
+                    }
+
+                    // ...but with the size and parameters of all of them
+                    for (shard_, size, parameters) in shards {
+                        let Some(file) = files
+                            .iter_mut()
+                            .find(|file| file.model == id && shard(&file.name) == Some(&shard_))
+                        else {
+                            continue;
+                        };
+
+                        if let Some(Size(total)) = &mut file.size {
+                            *total += size;
+                        }
+
+                        if let Some(Parameters(total)) = file
+                            .metadata
+                            .as_mut()
+                            .and_then(|metadata| metadata.parameters.as_mut())
+                        {
+                            *total += parameters.map_or(0, |parameters| parameters.0);
+                        }
                     }
                 }
             }
@@ -433,6 +627,7 @@ impl Library {
             Ok(Self {
                 directory: Directory(directory.to_path_buf()),
                 files,
+                last_used,
             })
         }
 
@@ -440,6 +635,7 @@ impl Library {
         Ok(Self {
             directory: Directory(directory.as_ref().to_path_buf()),
             files: Vec::new(),
+            last_used: BTreeMap::new(),
         })
     }
 
@@ -450,6 +646,150 @@ impl Library {
     pub fn files(&self) -> &[File] {
         &self.files
     }
+
+    /// The space taken by every file, shards included.
+    pub fn size(&self) -> Size {
+        Size(
+            self.files
+                .iter()
+                .filter_map(|file| file.size)
+                .map(|size| size.0)
+                .sum(),
+        )
+    }
+
+    /// When the file was last booted, or modified if it never was.
+    pub fn last_used(&self, file: &File) -> Option<DateTime<Utc>> {
+        self.last_used.get(&file.relative_path()).copied()
+    }
+
+    /// The files to remove, least recently used first, so the library fits
+    /// in the `budget` after adding `incoming` bytes.
+    ///
+    /// Empty if it already fits. If removing everything is not enough, every
+    /// file is suggested.
+    pub fn suggest_removals(&self, budget: Size, incoming: Size) -> Vec<&File> {
+        let mut excess = (self.size().0 + incoming.0).saturating_sub(budget.0);
+
+        let mut files: Vec<&File> = self.files.iter().collect();
+        files.sort_by_key(|file| self.last_used(file));
+
+        files
+            .into_iter()
+            .take_while(|file| {
+                if excess == 0 {
+                    return false;
+                }
+
+                excess = excess.saturating_sub(file.size.map(|size| size.0).unwrap_or(0));
+
+                true
+            })
+            .collect()
+    }
+
+    /// Deletes a file, with its shards and any partial download.
+    ///
+    /// Whoever activated the file stops using it.
+    pub async fn remove(&mut self, file: &File) -> Result<(), Error> {
+        #[cfg(feature = "native")]
+        {
+            let directory = self.directory.0.join(&file.model.0);
+            let shard_ = shard(&file.name);
+
+            let mut entries = fs::read_dir(&directory).await?;
+
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().display().to_string();
+
+                let is_part = name == file.name
+                    || shard_.is_some() && shard(&name) == shard_ && name.ends_with(".gguf");
+
+                if is_part {
+                    fs::remove_file(entry.path()).await?;
+                }
+            }
+
+            let partial = directory.join(&file.name).with_extension("tmp");
+
+            if fs::try_exists(&partial).await? {
+                fs::remove_file(partial).await?;
+            }
+
+            // Only succeeds once the folders are empty
+            let _ = fs::remove_dir(&directory).await;
+
+            if let Some(author) = directory.parent() {
+                let _ = fs::remove_dir(author).await;
+            }
+
+            let is_same = |other: &File| other.model == file.model && other.name == file.name;
+
+            self.files.retain(|other| !is_same(other));
+            let _ = self.last_used.remove(&file.relative_path());
+
+            let mut usage = Self::usage(&self.directory.0).await;
+
+            if usage.remove(&file.relative_path()).is_some() {
+                Self::save_usage(&self.directory.0, &usage).await?;
+            }
+
+            Ok(())
+        }
+
+        #[cfg(not(feature = "native"))]
+        {
+            let _ = file;
+
+            Err(Error::WasmError(
+                "Removing models is not supported on this platform without the native feature"
+                    .into(),
+            ))
+        }
+    }
+
+    /// Remembers that a file was just booted.
+    #[cfg(feature = "native")]
+    async fn used(directory: &Directory, file: &File) {
+        let mut usage = Self::usage(&directory.0).await;
+        let _ = usage.insert(file.relative_path(), Utc::now());
+
+        if let Err(error) = Self::save_usage(&directory.0, &usage).await {
+            log::warn!("Failed to record the use of {file}: {error}");
+        }
+    }
+
+    #[cfg(feature = "native")]
+    async fn usage(directory: &Path) -> BTreeMap<PathBuf, DateTime<Utc>> {
+        let Ok(json) = fs::read_to_string(directory.join(USAGE)).await else {
+            return BTreeMap::new();
+        };
+
+        serde_json::from_str(&json).unwrap_or_default()
+    }
+
+    #[cfg(feature = "native")]
+    async fn save_usage(
+        directory: &Path,
+        usage: &BTreeMap<PathBuf, DateTime<Utc>>,
+    ) -> Result<(), Error> {
+        fs::write(directory.join(USAGE), serde_json::to_vec_pretty(usage)?).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "native")]
+const USAGE: &str = "usage.json";
+
+/// The part of a shard name shared by all the shards of a model, e.g.
+/// `qwen-q4` for `qwen-q4-00002-of-00003.gguf`.
+#[cfg(feature = "native")]
+fn shard(name: &str) -> Option<&str> {
+    let (name, _count) = name.rsplit_once("-of-")?;
+    let (name, _index) = name.rsplit_once('-')?;
+
+    Some(name)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -485,3 +825,73 @@ impl AsRef<Path> for Directory {
         &self.0
     }
 }
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_library_budget() {
+        let root = std::env::temp_dir().join(format!("peak-library-{}", uuid::Uuid::new_v4()));
+        let directory = Directory(root.clone());
+
+        let files = [
+            ("bartowski/Qwen", "qwen-q4-00001-of-00002.gguf", 300),
+            ("bartowski/Qwen", "qwen-q4-00002-of-00002.gguf", 200),
+            ("bartowski/Qwen", "qwen-q4-00001-of-00002.tmp", 50),
+            ("unsloth/Gemma", "gemma-q8.gguf", 400),
+        ];
+
+        for (model, name, size) in files {
+            fs::create_dir_all(root.join(model)).await.unwrap();
+            fs::write(root.join(model).join(name), vec![0; size])
+                .await
+                .unwrap();
+        }
+
+        let gemma = File {
+            model: Id("unsloth/Gemma".to_owned()),
+            name: "gemma-q8.gguf".to_owned(),
+            size: None,
+            sha256: None,
+            metadata: None,
+        };
+
+        // Qwen was used last
+        Library::used(&directory, &gemma).await;
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        Library::used(
+            &directory,
+            &File {
+                model: Id("bartowski/Qwen".to_owned()),
+                name: "qwen-q4-00001-of-00002.gguf".to_owned(),
+                size: None,
+                sha256: None,
+                metadata: None,
+            },
+        )
+        .await;
+
+        let mut library = Library::scan(&root).await.unwrap();
+
+        assert_eq!(library.files().len(), 2);
+        assert_eq!(library.size(), 900);
+
+        assert!(library.suggest_removals(Size(1000), Size(0)).is_empty());
+
+        let removals = library.suggest_removals(Size(1000), Size(200));
+        assert_eq!(removals.len(), 1);
+        assert_eq!(removals[0].name, gemma.name);
+
+        assert_eq!(library.suggest_removals(Size(0), Size(0)).len(), 2);
+
+        let qwen = library.suggest_removals(Size(0), Size(0))[1].clone();
+        library.remove(&qwen).await.unwrap();
+
+        assert_eq!(library.size(), 400);
+        assert!(!fs::try_exists(root.join("bartowski")).await.unwrap());
+        assert_eq!(Library::scan(&root).await.unwrap().files().len(), 1);
+
+        let _ = fs::remove_dir_all(root).await;
+    }
+}
@@ -0,0 +1,573 @@
+//! The header of GGUF files.
+//!
+//! A GGUF file starts with typed key-value metadata, followed by a table of
+//! its tensors and then the tensor data. Only the first two are read here;
+//! they tell what the model is without loading it.
+use crate::brain::model::Parameters;
+use crate::brain::Error;
+
+use std::fmt;
+use std::io::{self, Read};
+#[cfg(feature = "native")]
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"GGUF";
+
+/// Longest string accepted, to avoid allocating for corrupted lengths.
+const MAX_STRING: u64 = 64 * 1024 * 1024;
+
+/// Deepest nesting of arrays accepted, to avoid overflowing the stack on
+/// corrupted files.
+const MAX_DEPTH: usize = 8;
+
+/// What a GGUF file says about its model.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// The version of the GGUF format.
+    pub version: u32,
+    /// The model architecture, e.g. `llama` or `qwen2`.
+    pub architecture: Option<String>,
+    pub name: Option<String>,
+    /// Counted from the tensors in the file. Shards only count their own,
+    /// but the [`Library`](super::Library) adds them up.
+    pub parameters: Option<Parameters>,
+    /// The quantization of most weights, e.g. `Q4_K_M`.
+    pub quantization: Option<String>,
+    /// The context length the model was trained with.
+    pub context_length: Option<u64>,
+    pub embedding_length: Option<u64>,
+    /// The number of layers.
+    pub block_count: Option<u64>,
+    /// The attention heads, and the key-value heads they share.
+    pub head_count: Option<u64>,
+    pub head_count_kv: Option<u64>,
+    /// The Jinja chat template.
+    pub chat_template: Option<String>,
+    pub tokenizer: Tokenizer,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tokenizer {
+    /// The kind of tokenizer, e.g. `gpt2` or `llama`.
+    pub model: Option<String>,
+    pub vocabulary: Option<usize>,
+    pub bos: Option<String>,
+    pub eos: Option<String>,
+}
+
+impl Metadata {
+    /// Reads the header of a GGUF file.
+    #[cfg(feature = "native")]
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+
+        tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::open(path)?;
+
+            Self::read(io::BufReader::new(file))
+        })
+        .await?
+    }
+
+    /// Reads the header of GGUF contents.
+    pub fn read(reader: impl Read) -> Result<Self, Error> {
+        Ok(Reader::new(reader).metadata()?)
+    }
+}
+
+impl fmt::Display for Metadata {
+    /// A one-line summary, e.g. "llama, 8B parameters, Q4_K_M, 128K context".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut specs = Vec::new();
+
+        if let Some(architecture) = &self.architecture {
+            specs.push(architecture.clone());
+        }
+
+        if let Some(parameters) = self.parameters {
+            specs.push(format!("{parameters} parameters"));
+        }
+
+        if let Some(quantization) = &self.quantization {
+            specs.push(quantization.clone());
+        }
+
+        if let Some(context_length) = self.context_length {
+            specs.push(if context_length >= 1024 {
+                format!("{}K context", context_length / 1024)
+            } else {
+                format!("{context_length} context")
+            });
+        }
+
+        f.write_str(&specs.join(", "))
+    }
+}
+
+/// The types of metadata values.
+mod kind {
+    pub const U8: u32 = 0;
+    pub const I8: u32 = 1;
+    pub const U16: u32 = 2;
+    pub const I16: u32 = 3;
+    pub const U32: u32 = 4;
+    pub const I32: u32 = 5;
+    pub const F32: u32 = 6;
+    pub const BOOL: u32 = 7;
+    pub const STRING: u32 = 8;
+    pub const ARRAY: u32 = 9;
+    pub const U64: u32 = 10;
+    pub const I64: u32 = 11;
+    pub const F64: u32 = 12;
+}
+
+/// The metadata values that are kept; the rest is skipped.
+enum Value {
+    Integer(u64),
+    String(String),
+    Strings(Vec<String>),
+    Other,
+}
+
+struct Reader<R> {
+    reader: R,
+    version: u32,
+}
+
+impl<R: Read> Reader<R> {
+    fn new(reader: R) -> Self {
+        Self { reader, version: 0 }
+    }
+
+    fn metadata(mut self) -> io::Result<Metadata> {
+        let mut magic = [0; 4];
+        self.reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(invalid("not a GGUF file"));
+        }
+
+        self.version = self.u32()?;
+
+        if !(1..=3).contains(&self.version) {
+            return Err(invalid(&format!(
+                "unsupported GGUF version {}",
+                self.version
+            )));
+        }
+
+        let tensors = self.count()?;
+        let entries = self.count()?;
+
+        let mut metadata = Metadata {
+            version: self.version,
+            ..Metadata::default()
+        };
+
+        let mut file_type = None;
+        let mut tokens = Vec::new();
+        let mut bos = None;
+        let mut eos = None;
+
+        // Keys are in no particular order, so the ones named after the
+        // architecture are looked up at the end
+        let mut numbers = Vec::new();
+
+        for _ in 0..entries {
+            let key = self.string()?;
+            let kind = self.u32()?;
+
+            // Token lists are large; only the tokens themselves are kept
+            let keep_strings = key == "tokenizer.ggml.tokens";
+            let value = self.value(kind, keep_strings, 0)?;
+
+            match (key.as_str(), value) {
+                ("general.architecture", Value::String(architecture)) => {
+                    metadata.architecture = Some(architecture);
+                }
+                ("general.name", Value::String(name)) => metadata.name = Some(name),
+                ("general.file_type", Value::Integer(kind)) => file_type = Some(kind),
+                ("tokenizer.chat_template", Value::String(template)) => {
+                    metadata.chat_template = Some(template);
+                }
+                ("tokenizer.ggml.model", Value::String(model)) => {
+                    metadata.tokenizer.model = Some(model);
+                }
+                ("tokenizer.ggml.tokens", Value::Strings(list)) => tokens = list,
+                ("tokenizer.ggml.bos_token_id", Value::Integer(id)) => bos = Some(id),
+                ("tokenizer.ggml.eos_token_id", Value::Integer(id)) => eos = Some(id),
+                (key, Value::Integer(number)) => numbers.push((key.to_owned(), number)),
+                _ => {}
+            }
+        }
+
+        if let Some(architecture) = &metadata.architecture {
+            let number = |name: &str| {
+                let key = format!("{architecture}.{name}");
+
+                numbers
+                    .iter()
+                    .find(|(candidate, _)| *candidate == key)
+                    .map(|(_, number)| *number)
+            };
+
+            metadata.context_length = number("context_length");
+            metadata.embedding_length = number("embedding_length");
+            metadata.block_count = number("block_count");
+            metadata.head_count = number("attention.head_count");
+            metadata.head_count_kv = number("attention.head_count_kv");
+        }
+
+        if !tokens.is_empty() {
+            let token = |id: Option<u64>| {
+                id.and_then(|id| tokens.get(usize::try_from(id).ok()?))
+                    .cloned()
+            };
+
+            metadata.tokenizer.vocabulary = Some(tokens.len());
+            metadata.tokenizer.bos = token(bos);
+            metadata.tokenizer.eos = token(eos);
+        }
+
+        let mut parameters = 0u64;
+        let mut weights: Vec<(u32, u64)> = Vec::new();
+
+        for _ in 0..tensors {
+            // The name
+            self.skip_string()?;
+
+            let dimensions = self.u32()?;
+            let mut elements = 1u64;
+
+            for _ in 0..dimensions {
+                elements = elements.saturating_mul(self.u64()?);
+            }
+
+            let kind = self.u32()?;
+            let _offset = self.u64()?;
+
+            parameters = parameters.saturating_add(elements);
+
+            match weights.iter_mut().find(|(candidate, _)| *candidate == kind) {
+                Some((_, total)) => *total += elements,
+                None => weights.push((kind, elements)),
+            }
+        }
+
+        if tensors > 0 {
+            metadata.parameters = Some(Parameters(parameters));
+        }
+
+        metadata.quantization = file_type
+            .and_then(file_type_name)
+            .or_else(|| {
+                // Without a file type, go by the type of most weights
+                weights
+                    .iter()
+                    .max_by_key(|(_, elements)| *elements)
+                    .and_then(|(kind, _)| tensor_type_name(*kind))
+            })
+            .map(str::to_owned);
+
+        Ok(metadata)
+    }
+
+    fn value(&mut self, kind: u32, keep_strings: bool, depth: usize) -> io::Result<Value> {
+        Ok(match kind {
+            kind::U8 => Value::Integer(self.bytes::<1>()?[0].into()),
+            kind::I8 => Value::Integer(self.bytes::<1>()?[0] as i8 as u64),
+            kind::U16 => Value::Integer(u16::from_le_bytes(self.bytes()?).into()),
+            kind::I16 => Value::Integer(i16::from_le_bytes(self.bytes()?) as u64),
+            kind::U32 => Value::Integer(self.u32()?.into()),
+            kind::I32 => Value::Integer(i32::from_le_bytes(self.bytes()?) as u64),
+            kind::U64 => Value::Integer(self.u64()?),
+            kind::I64 => Value::Integer(i64::from_le_bytes(self.bytes()?) as u64),
+            kind::BOOL => Value::Integer(self.bytes::<1>()?[0].into()),
+            kind::F32 => {
+                let _ = self.bytes::<4>()?;
+                Value::Other
+            }
+            kind::F64 => {
+                let _ = self.bytes::<8>()?;
+                Value::Other
+            }
+            kind::STRING => Value::String(self.string()?),
+            kind::ARRAY => {
+                if depth >= MAX_DEPTH {
+                    return Err(invalid("arrays nested too deeply"));
+                }
+
+                let kind = self.u32()?;
+                let length = self.count()?;
+
+                if kind == kind::STRING && keep_strings {
+                    let mut strings = Vec::new();
+
+                    for _ in 0..length {
+                        strings.push(self.string()?);
+                    }
+
+                    Value::Strings(strings)
+                } else {
+                    for _ in 0..length {
+                        if kind == kind::STRING {
+                            self.skip_string()?;
+                        } else {
+                            let _ = self.value(kind, false, depth + 1)?;
+                        }
+                    }
+
+                    Value::Other
+                }
+            }
+            _ => return Err(invalid(&format!("unknown value type {kind}"))),
+        })
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let length = self.count()?;
+
+        if length > MAX_STRING {
+            return Err(invalid("string too long"));
+        }
+
+        let mut bytes = Vec::new();
+        let read = (&mut self.reader).take(length).read_to_end(&mut bytes)?;
+
+        if (read as u64) < length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn skip_string(&mut self) -> io::Result<()> {
+        let length = self.count()?;
+        let skipped = io::copy(&mut (&mut self.reader).take(length), &mut io::sink())?;
+
+        if skipped < length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        Ok(())
+    }
+
+    /// Lengths and counts were 32-bit in version 1.
+    fn count(&mut self) -> io::Result<u64> {
+        if self.version == 1 {
+            self.u32().map(u64::from)
+        } else {
+            self.u64()
+        }
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes)?;
+
+        Ok(bytes)
+    }
+}
+
+/// The names of `general.file_type`, as in `llama_ftype`.
+fn file_type_name(file_type: u64) -> Option<&'static str> {
+    Some(match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        36 => "TQ1_0",
+        37 => "TQ2_0",
+        _ => return None,
+    })
+}
+
+/// The names of tensor types, as in `ggml_type`.
+fn tensor_type_name(tensor_type: u32) -> Option<&'static str> {
+    Some(match tensor_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        6 => "Q5_0",
+        7 => "Q5_1",
+        8 => "Q8_0",
+        10 => "Q2_K",
+        11 => "Q3_K",
+        12 => "Q4_K",
+        13 => "Q5_K",
+        14 => "Q6_K",
+        16 => "IQ2_XXS",
+        17 => "IQ2_XS",
+        18 => "IQ3_XXS",
+        19 => "IQ1_S",
+        20 => "IQ4_NL",
+        21 => "IQ3_S",
+        22 => "IQ2_S",
+        23 => "IQ4_XS",
+        29 => "IQ1_M",
+        30 => "BF16",
+        34 => "TQ1_0",
+        35 => "TQ2_0",
+        _ => return None,
+    })
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes GGUF headers for the tests.
+    #[derive(Default)]
+    struct Writer(Vec<u8>);
+
+    impl Writer {
+        fn header(mut self, tensors: u64, entries: u64) -> Self {
+            self.0.extend_from_slice(MAGIC);
+            self.0.extend_from_slice(&3u32.to_le_bytes());
+            self.0.extend_from_slice(&tensors.to_le_bytes());
+            self.0.extend_from_slice(&entries.to_le_bytes());
+            self
+        }
+
+        fn string(mut self, string: &str) -> Self {
+            self.0
+                .extend_from_slice(&(string.len() as u64).to_le_bytes());
+            self.0.extend_from_slice(string.as_bytes());
+            self
+        }
+
+        fn u32(mut self, value: u32) -> Self {
+            self.0.extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        fn u64(mut self, value: u64) -> Self {
+            self.0.extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        fn entry(self, key: &str, kind: u32) -> Self {
+            self.string(key).u32(kind)
+        }
+
+        fn tensor(self, name: &str, dimensions: &[u64], kind: u32) -> Self {
+            let mut writer = self.string(name).u32(dimensions.len() as u32);
+
+            for dimension in dimensions {
+                writer = writer.u64(*dimension);
+            }
+
+            writer.u32(kind).u64(0)
+        }
+    }
+
+    #[test]
+    fn test_metadata() {
+        let gguf = Writer::default()
+            .header(2, 10)
+            .entry("general.architecture", kind::STRING)
+            .string("llama")
+            .entry("general.name", kind::STRING)
+            .string("Tiny Llama")
+            .entry("llama.context_length", kind::U32)
+            .u32(131_072)
+            .entry("llama.block_count", kind::U32)
+            .u32(16)
+            .entry("general.file_type", kind::U32)
+            .u32(15)
+            .entry("tokenizer.ggml.model", kind::STRING)
+            .string("gpt2")
+            .entry("tokenizer.ggml.tokens", kind::ARRAY)
+            .u32(kind::STRING)
+            .u64(3)
+            .string("<s>")
+            .string("</s>")
+            .string("hello")
+            .entry("tokenizer.ggml.bos_token_id", kind::U32)
+            .u32(0)
+            .entry("tokenizer.ggml.scores", kind::ARRAY)
+            .u32(kind::F32)
+            .u64(3)
+            .u32(0)
+            .u32(0)
+            .u32(0)
+            .entry("tokenizer.chat_template", kind::STRING)
+            .string("{% for message in messages %}{{ message.content }}{% endfor %}")
+            .tensor("token_embd.weight", &[2048, 3], 12)
+            .tensor("output_norm.weight", &[2048], 0)
+            .0;
+
+        let metadata = Metadata::read(gguf.as_slice()).unwrap();
+
+        assert_eq!(metadata.version, 3);
+        assert_eq!(metadata.architecture.as_deref(), Some("llama"));
+        assert_eq!(metadata.name.as_deref(), Some("Tiny Llama"));
+        assert_eq!(metadata.context_length, Some(131_072));
+        assert_eq!(metadata.block_count, Some(16));
+        assert_eq!(metadata.parameters, Some(Parameters(8192)));
+        assert_eq!(metadata.quantization.as_deref(), Some("Q4_K_M"));
+        assert_eq!(metadata.tokenizer.model.as_deref(), Some("gpt2"));
+        assert_eq!(metadata.tokenizer.vocabulary, Some(3));
+        assert_eq!(metadata.tokenizer.bos.as_deref(), Some("<s>"));
+        assert_eq!(metadata.tokenizer.eos, None);
+        assert!(metadata.chat_template.is_some());
+        assert_eq!(
+            metadata.to_string(),
+            "llama, 8K parameters, Q4_K_M, 128K context"
+        );
+
+        // Truncated and foreign files are rejected
+        assert!(Metadata::read(&gguf[..gguf.len() - 4]).is_err());
+        assert!(Metadata::read(b"GGML\x03\0\0\0".as_slice()).is_err());
+
+        // So are arrays of arrays nested past the limit
+        let mut nested = Writer::default().header(0, 1).entry("nested", kind::ARRAY);
+
+        for _ in 0..MAX_DEPTH {
+            nested = nested.u32(kind::ARRAY).u64(1);
+        }
+
+        let nested = nested.u32(kind::U32).u64(1).u32(0).0;
+
+        assert!(Metadata::read(nested.as_slice()).is_err());
+    }
+}
@@ -1,13 +1,33 @@
-use crate::brain::assistant::{Assistant, Message, Reasoning, Reply};
+use crate::brain::assistant::{Assistant, Message, Reasoning, Reply, Source};
+use crate::brain::chat::CITATIONS;
+use crate::brain::format::Format;
 use crate::brain::web;
 use crate::brain::Error;
 
 use serde::Deserialize;
+use serde_json::json;
 use sipper::{sipper, Sender, Sipper, Straw};
 use url::Url;
 
 use std::collections::{BTreeMap, HashMap};
 
+#[cfg(feature = "native")]
+mod tools;
+
+#[cfg(feature = "native")]
+pub use tools::{Confirmation, Tools};
+
+/// System tools cannot be called on the web.
+#[cfg(not(feature = "native"))]
+#[derive(Debug, Clone)]
+pub enum Tools {}
+
+/// Plan functions that browse the web.
+const WEB_FUNCTIONS: &[&str] = &["search", "scrape_text", "answer"];
+
+/// Plan functions backed by system tools.
+const TOOL_FUNCTIONS: &[&str] = &["read_file", "list_dir", "run_command", "telemetry"];
+
 #[derive(Debug, Clone, Default)]
 pub struct Plan {
     pub reasoning: Option<Reasoning>,
@@ -28,6 +48,16 @@ pub enum Outcome {
     Search(Status<Vec<Url>>),
     ScrapeText(Status<Vec<web::Summary>>),
     Answer(Status<Reply>),
+    Tool(Status<Action>),
+}
+
+/// A plan step that called a system tool.
+#[derive(Debug, Clone, Default)]
+pub struct Action {
+    /// The plan function, e.g. `read_file`.
+    pub function: String,
+    pub activity: String,
+    pub output: String,
 }
 
 #[derive(Debug, Clone)]
@@ -68,12 +98,24 @@ pub enum Event {
     Designed(Plan),
     OutcomeAdded(Outcome),
     OutcomeChanged(Outcome),
+    /// A risky step is waiting for the user to allow it.
+    #[cfg(feature = "native")]
+    ConfirmationRequested(Confirmation),
 }
 
 impl Plan {
+    /// Designs a plan to answer the last user message and runs it.
+    ///
+    /// Steps may search and scrape the web and, given `tools`, read files,
+    /// list directories, run sandboxed commands and query telemetry.
+    ///
+    /// Answers cite the document excerpts quoted in `history`, if any, by
+    /// their position in `sources`.
     pub fn search<'a>(
         assistant: &'a Assistant,
         history: &'a [Message],
+        sources: &'a [Source],
+        tools: Option<&'a Tools>,
     ) -> impl Straw<(), Event, Error> + 'a {
         sipper(move |mut progress| async move {
             let Some(query) = history.iter().rev().find_map(|item| {
@@ -86,26 +128,15 @@ impl Plan {
                 return Ok(());
             };
 
-            let plan = {
-                let mut attempt = 0;
-
-                loop {
-                    log::info!("Designing plan ({attempt})...");
-
-                    match design(assistant, history).run(&progress).await {
-                        Err(error) if attempt < 3 => {
-                            log::warn!("Plan design failed: {error}");
-                        }
-                        result => break result?,
-                    }
+            log::info!("Designing plan...");
 
-                    attempt += 1;
-                }
-            };
+            let plan = design(assistant, history, tools.is_some())
+                .run(&progress)
+                .await?;
 
             progress.send(Event::Designed(plan.clone())).await;
 
-            let _ = execute(assistant, history, query, &plan)
+            let _ = execute(assistant, history, sources, query, &plan, tools)
                 .run(progress)
                 .await?;
 
@@ -124,38 +155,82 @@ impl Plan {
 fn design<'a>(
     assistant: &'a Assistant,
     history: &'a [Message],
+    has_tools: bool,
 ) -> impl Straw<Plan, Event, Error> + 'a {
     sipper(move |progress| async move {
-        let reply = assistant
+        let actions = if has_tools {
+            format!("{WEB_ACTIONS}\n{TOOL_ACTIONS}")
+        } else {
+            WEB_ACTIONS.to_owned()
+        };
+
+        #[derive(Deserialize)]
+        struct Design {
+            steps: Vec<Step>,
+        }
+
+        let (design, reply) = assistant
             .clone()
-            .reply(
-                "You are a helpful assistant.".to_string(),
+            .complete_json::<Design>(
+                "You are a helpful assistant.",
                 history.to_vec(),
-                vec![Message::System(BROWSE_PROMPT.to_owned())],
+                vec![Message::System(
+                    BROWSE_PROMPT.replace("{actions}", &actions),
+                )],
+                schema(has_tools),
             )
             .filter_with(|(reply, _token)| reply.reasoning.map(Event::Designing))
             .run(progress)
             .await?;
 
-        let steps = reply.content.split("```").nth(1).unwrap_or(&reply.content);
-
-        let plan = steps.trim_start_matches("json").trim();
-
-        log::info!("Plan designed:\n{plan}");
+        log::info!("Plan designed:\n{}", reply.content);
 
         Ok(Plan {
             reasoning: reply.reasoning,
-            steps: serde_json::from_str(plan)?,
+            steps: design.steps,
             outcomes: Vec::new(),
         })
     })
 }
 
+/// The shape of a plan, so models can only call the functions they have.
+fn schema(has_tools: bool) -> Format {
+    let functions: Vec<_> = WEB_FUNCTIONS
+        .iter()
+        .chain(if has_tools { TOOL_FUNCTIONS } else { &[] })
+        .collect();
+
+    Format::schema(json!({
+        "type": "object",
+        "properties": {
+            "steps": {
+                "type": "array",
+                "minItems": 1,
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "evidence": { "type": "string" },
+                        "description": { "type": "string" },
+                        "function": { "enum": functions },
+                        "inputs": { "type": "array", "items": { "type": "string" } }
+                    },
+                    "required": ["evidence", "description", "function", "inputs"],
+                    "additionalProperties": false
+                }
+            }
+        },
+        "required": ["steps"],
+        "additionalProperties": false
+    }))
+}
+
 fn execute<'a>(
     assistant: &'a Assistant,
     history: &'a [Message],
+    sources: &'a [Source],
     query: &'a str,
     plan: &'a Plan,
+    tools: Option<&'a Tools>,
 ) -> impl Straw<Vec<Outcome>, Event, Error> + 'a {
     struct Process {
         outcomes: Vec<Outcome>,
@@ -214,6 +289,23 @@ fn execute<'a>(
                 .collect()
         }
 
+        /// Joins the inputs of a step, replacing references to earlier
+        /// steps with their text output.
+        #[cfg(feature = "native")]
+        fn input(&self, inputs: &[String]) -> String {
+            inputs
+                .iter()
+                .map(|input| match input.strip_prefix('$') {
+                    Some(evidence) => match self.outputs.get(evidence.trim()) {
+                        Some(Output::Text(text)) => text.join("\n"),
+                        _ => String::new(),
+                    },
+                    None => input.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+
         async fn push(&mut self, outcome: Outcome) {
             self.outcomes.push(outcome.clone());
             self.sender.send(Event::OutcomeAdded(outcome)).await;
@@ -255,6 +347,10 @@ fn execute<'a>(
                 Outcome::Answer(Status::Active(reply)) => {
                     (Output::Answer, Outcome::Answer(Status::Done(reply)))
                 }
+                Outcome::Tool(Status::Active(action)) => (
+                    Output::Text(vec![action.output.clone()]),
+                    Outcome::Tool(Status::Done(action)),
+                ),
                 _ => {
                     return;
                 }
@@ -267,6 +363,58 @@ fn execute<'a>(
                 .send(Event::OutcomeChanged(outcome.clone()))
                 .await;
         }
+
+        async fn tool(&mut self, tools: Option<&Tools>, step: &Step) {
+            let Some(tools) = tools else {
+                self.push(Outcome::Tool(Status::Errored(format!(
+                    "{} is not available here",
+                    step.function
+                ))))
+                .await;
+
+                return;
+            };
+
+            #[cfg(feature = "native")]
+            {
+                let input = self.input(&step.inputs);
+
+                let mut action = Action {
+                    function: step.function.clone(),
+                    activity: tools.activity(&step.function, &input),
+                    output: String::new(),
+                };
+
+                self.push(Outcome::Tool(Status::Active(action.clone())))
+                    .await;
+
+                let mut call = tools.call(&step.function, &input).pin();
+
+                while let Some(confirmation) = call.sip().await {
+                    self.sender
+                        .send(Event::ConfirmationRequested(confirmation))
+                        .await;
+                }
+
+                match call.await {
+                    Ok(output) => {
+                        action.output = output;
+
+                        self.update(Outcome::Tool(Status::Active(action))).await;
+                        self.done(&step.evidence).await;
+                    }
+                    Err(error) => {
+                        log::warn!("Plan step failed: {error}");
+
+                        self.update(Outcome::Tool(Status::Errored(error.to_string())))
+                            .await;
+                    }
+                }
+            }
+
+            #[cfg(not(feature = "native"))]
+            match *tools {}
+        }
     }
 
     sipper(move |sender| async move {
@@ -388,21 +536,28 @@ fn execute<'a>(
                         Message::User(query.to_owned()),
                     ];
 
+                    let system_prompt = if sources.is_empty() {
+                        "You are a helpful assistant.".to_string()
+                    } else {
+                        format!("You are a helpful assistant. {CITATIONS}")
+                    };
+
                     let mut reply = assistant
                         .clone()
-                        .reply(
-                            "You are a helpful assistant.".to_string(),
-                            history.to_vec(),
-                            query.to_vec(),
-                        )
+                        .reply(system_prompt, history.to_vec(), query.to_vec())
                         .pin();
 
-                    while let Some((reply, _token)) = reply.sip().await {
+                    while let Some((mut reply, _token)) = reply.sip().await {
+                        reply.sources = sources.to_vec();
+
                         process.update(Outcome::Answer(Status::Active(reply))).await;
                     }
 
                     process.done(&step.evidence).await;
                 }
+                function if TOOL_FUNCTIONS.contains(&function) => {
+                    process.tool(tools, step).await;
+                }
                 _ => {}
             }
         }
@@ -415,33 +570,46 @@ const BROWSE_PROMPT: &str = r#"Please construct a systematic plan to generate an
 
 Here are the tools available to be called:
 
-- search: Search for information using the Google search engine. This action is helpful in locating a suitable list of sites that may contain the answer to the user's query. It does not directly answer the question but finds a list of sites that might have the answer.
-- scrape_text: Load one or more websites from the input string, where input is one or more links, and produces plain text output containing the content of the links.
-- answer: Answer a question by reasoning from evidence obtained with previous actions.
+{actions}
 
 The output should be in JSON:
 
 ```json
-[
-    {
-        "evidence": "search_0",
-        "description": "Search how to cook an omelette",
-        "function": "search",
-        "inputs": ["how to cook an omelette best recipe"]
-    },
-    {
-        "evidence": "scrape_0",
-        "description": "Scrape websites obtained from the previous search",
-        "function": "scrape_text",
-        "inputs": ["$search_0"]
-    },
-    ...
-    {
-        "evidence": "final_answer",
-        "description": "Understand the scraped text and provide a final answer.",
-        "function": "answer",
-        "inputs": ["$scrape_0"] // You can provide multiple inputs for the answer action
-    }
-]
+{
+    "steps": [
+        {
+            "evidence": "search_0",
+            "description": "Search how to cook an omelette",
+            "function": "search",
+            "inputs": ["how to cook an omelette best recipe"]
+        },
+        {
+            "evidence": "scrape_0",
+            "description": "Scrape websites obtained from the previous search",
+            "function": "scrape_text",
+            "inputs": ["$search_0"]
+        },
+        ...
+        {
+            "evidence": "final_answer",
+            "description": "Understand the scraped text and provide a final answer.",
+            "function": "answer",
+            "inputs": ["$scrape_0"] // You can provide multiple inputs for the answer action
+        }
+    ]
+}
 ```
 Reply only with the plan in JSON."#;
+
+const WEB_ACTIONS: &str = "\
+- search: Search for information using the Google search engine. This action is helpful in locating a suitable list of sites that may contain the answer to the user's query. It does not directly answer the question but finds a list of sites that might have the answer.
+- scrape_text: Load one or more websites from the input string, where input is one or more links, and produces plain text output containing the content of the links.
+- answer: Answer a question by reasoning from evidence obtained with previous actions.";
+
+const TOOL_ACTIONS: &str = "\
+- read_file: Read a text file of this computer. The input is an absolute path.
+- list_dir: List the files and directories in a folder of this computer. The input is an absolute path.
+- run_command: Run a command line on this computer in a sandbox, without a shell, and get its output. The user may be asked to allow it.
+- telemetry: Get the current memory usage, load, CPU temperature, battery level, power draw, disk and network throughput and uptime of this computer. It takes no inputs.
+
+Inputs may refer to the output of a previous step with its evidence, e.g. \"$read_0\".";
@@ -0,0 +1,181 @@
+use crate::brain::Error;
+use crate::mcp::{Context, Registry};
+use crate::policy::Caller;
+
+pub use crate::policy::Confirmation;
+
+use serde_json::{json, Value};
+use sipper::{sipper, Straw};
+use tokio::sync::mpsc;
+
+use std::sync::Arc;
+
+/// The registry tools the steps of a plan may call.
+///
+/// Calls go through the [`Registry`], so its [`Policy`] applies: risky
+/// steps ask for confirmation with [`Event::ConfirmationRequested`]. Without
+/// a policy, only tools that never need confirmation are allowed.
+///
+/// [`Event::ConfirmationRequested`]: super::Event::ConfirmationRequested
+/// [`Policy`]: crate::policy::Policy
+#[derive(Debug, Clone)]
+pub struct Tools {
+    registry: Arc<Registry>,
+    caller: Caller,
+}
+
+impl Tools {
+    pub fn new(registry: Arc<Registry>, caller: Caller) -> Self {
+        Self { registry, caller }
+    }
+
+    /// Describes what a step is about to do, e.g. "Reading /etc/hosts...".
+    pub(super) fn activity(&self, function: &str, input: &str) -> String {
+        let Some((name, arguments)) = call(function, input) else {
+            return format!("Running {function}...");
+        };
+
+        self.registry
+            .get(name)
+            .map(|tool| tool.activity(&arguments))
+            .unwrap_or_else(|| format!("Running {name}..."))
+    }
+
+    /// Runs the tool behind a plan function and returns its output as text.
+    pub(super) fn call<'a>(
+        &'a self,
+        function: &'a str,
+        input: &'a str,
+    ) -> impl Straw<String, Confirmation, Error> + 'a {
+        sipper(move |mut sender| async move {
+            let (name, arguments) = call(function, input).ok_or_else(|| {
+                Error::RequestFailed(format!("unknown plan function: {function}"))
+            })?;
+
+            let tool = self
+                .registry
+                .get(name)
+                .ok_or_else(|| Error::RequestFailed(format!("tool not available: {name}")))?;
+
+            let policy = self.registry.policy().cloned();
+
+            if policy.is_none() && tool.risk() == crate::policy::Risk::Destructive {
+                return Err(Error::RequestFailed(format!(
+                    "{name} needs confirmation, but there is no policy to ask for it"
+                )));
+            }
+
+            let (notifications, mut requests) = mpsc::channel(1);
+            let context = Context::new(notifications).with_caller(self.caller.clone());
+
+            let call = self.registry.call(name, Some(arguments), &context);
+            tokio::pin!(call);
+
+            let result = loop {
+                tokio::select! {
+                    result = &mut call => break result,
+                    Some(request) = requests.recv() => {
+                        let Some(policy) = &policy else {
+                            continue;
+                        };
+
+                        if let Some(confirmation) = Confirmation::parse(&request, policy) {
+                            sender.send(confirmation).await;
+                        }
+                    }
+                }
+            };
+
+            let result = result.map_err(|error| Error::RequestFailed(error.to_string()))?;
+
+            let output = result
+                .content
+                .into_iter()
+                .map(|content| content.text)
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            if result.is_error == Some(true) {
+                return Err(Error::RequestFailed(output));
+            }
+
+            Ok(output)
+        })
+    }
+}
+
+/// Maps a plan function and its input to a registry tool and arguments.
+fn call(function: &str, input: &str) -> Option<(&'static str, Value)> {
+    Some(match function {
+        "read_file" => ("read_file", json!({ "path": input })),
+        "list_dir" => ("read_dir", json!({ "path": input })),
+        "run_command" => ("run_command", json!({ "command": input })),
+        "telemetry" => ("system_telemetry", json!({})),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::ToolHandler;
+    use crate::policy::{Decision, Permissions, Policy};
+
+    use futures::future::BoxFuture;
+    use futures::FutureExt;
+    use sipper::Sipper;
+
+    /// Stands in for `run_command`, echoing its arguments.
+    struct Echo;
+
+    impl ToolHandler for Echo {
+        fn name(&self) -> &str {
+            "run_command"
+        }
+
+        fn description(&self) -> &str {
+            "Echoes its arguments."
+        }
+
+        fn input_schema(&self) -> Value {
+            json!({ "type": "object" })
+        }
+
+        fn call<'a>(
+            &'a self,
+            arguments: Value,
+            _context: &'a Context,
+        ) -> BoxFuture<'a, anyhow::Result<Value>> {
+            async move { Ok(arguments) }.boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_risky_steps_are_confirmed() {
+        let mut registry = Registry::new();
+        registry.register(Echo);
+        let grants = std::env::temp_dir().join(format!("grants-{}.json", uuid::Uuid::new_v4()));
+        registry.set_policy(Arc::new(Policy::with_grants(
+            Permissions::default(),
+            &grants,
+        )));
+
+        let tools = Tools::new(Arc::new(registry), Caller::default());
+
+        let mut call = tools.call("run_command", "uptime").pin();
+
+        let confirmation = call.sip().await.unwrap();
+        assert_eq!(confirmation.tool, "run_command");
+        assert_eq!(confirmation.arguments, json!({ "command": "uptime" }));
+
+        confirmation.answer(Decision::AllowOnce);
+        assert_eq!(call.await.unwrap(), r#"{"command":"uptime"}"#);
+
+        // Denied steps fail
+        let mut call = tools.call("run_command", "uptime").pin();
+        call.sip().await.unwrap().answer(Decision::Deny);
+
+        assert!(call.await.is_err());
+        assert!(!grants.exists());
+    }
+}
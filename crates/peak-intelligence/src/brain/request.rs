@@ -9,7 +9,7 @@ use tokio::io::{self, AsyncWriteExt};
 
 use std::path::Path;
 #[cfg(feature = "native")]
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Progress {
@@ -29,42 +29,131 @@ impl Progress {
     }
 }
 
+/// Downloads `url` into `destination`, resuming from whatever is already
+/// there.
+///
+/// Servers that ignore the range start over. Fails before writing anything
+/// if the disk cannot fit the rest of the file, or if the whole file takes
+/// more than `budget` bytes; an interrupted download keeps its partial file,
+/// so the next attempt picks up where it stopped.
 pub fn download_file<'a>(
     url: impl AsRef<str> + Send + 'a,
     destination: impl AsRef<Path> + Send + 'a,
+    budget: Option<u64>,
 ) -> impl Straw<(), Progress, Error> + 'a {
     sipper(
         move |#[cfg_attr(not(feature = "native"), allow(unused_mut))] mut progress| async move {
             #[cfg(feature = "native")]
             {
+                use futures::StreamExt;
+                use reqwest::{header, StatusCode};
+
                 let url = url.as_ref();
                 let destination = destination.as_ref();
-                let mut file = io::BufWriter::new(fs::File::create(destination).await?);
 
-                // For now, we use HttpClient which reads the whole body.
-                // In a future update, we can add streaming to HttpClient.
-                let response = crate::http::HttpClient::get(url).await?;
-                let body = response.bytes();
-                let total = Some(body.len() as u64);
+                let existing = fs::metadata(destination)
+                    .await
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(0);
+
+                let mut request = reqwest::Client::new().get(url);
+
+                if existing > 0 {
+                    request = request.header(header::RANGE, format!("bytes={existing}-"));
+                }
+
+                let response = request.send().await?;
+
+                // The partial file is already complete
+                if existing > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+                    return Ok(());
+                }
+
+                let response = response.error_for_status()?;
+                let is_resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+                let offset = if is_resumed { existing } else { 0 };
+                let total = response.content_length().map(|length| offset + length);
+
+                if let Some(total) = total {
+                    if let Some(budget) = budget.filter(|budget| total > *budget) {
+                        return Err(Error::ResourceLimitExceeded(format!(
+                            "{url} takes {total} bytes, but the budget only leaves {budget}"
+                        )));
+                    }
+
+                    // Starting over frees the partial file first
+                    let needed = total - offset;
+                    let freed = existing - offset;
+
+                    if let Some(available) = available_space(destination) {
+                        if available + freed < needed {
+                            return Err(Error::ResourceLimitExceeded(format!(
+                                "{needed} bytes are needed to download {url}, \
+                                but only {available} are free"
+                            )));
+                        }
+                    }
+                }
+
+                let file = if is_resumed {
+                    fs::OpenOptions::new()
+                        .append(true)
+                        .open(destination)
+                        .await?
+                } else {
+                    fs::File::create(destination).await?
+                };
+
+                let mut file = io::BufWriter::new(file);
+                let mut body = response.bytes_stream();
+                let mut downloaded = offset;
                 let start = Instant::now();
+                let mut last_report = start;
+
+                while let Some(chunk) = body.next().await {
+                    let chunk = chunk?;
+
+                    file.write_all(&chunk).await?;
+                    downloaded += chunk.len() as u64;
+
+                    if last_report.elapsed() >= REPORT_INTERVAL {
+                        last_report = Instant::now();
+
+                        progress
+                            .send(Progress {
+                                total,
+                                downloaded,
+                                speed: ((downloaded - offset) as f32
+                                    / start.elapsed().as_secs_f32())
+                                    as u64,
+                            })
+                            .await;
+                    }
+                }
+
+                file.flush().await?;
+
+                if total.is_some_and(|total| downloaded < total) {
+                    return Err(Error::RequestFailed(format!(
+                        "download of {url} was interrupted at {downloaded} bytes"
+                    )));
+                }
 
                 progress
                     .send(Progress {
-                        total,
-                        downloaded: body.len() as u64,
-                        speed: (body.len() as f32 / start.elapsed().as_secs_f32()) as u64,
+                        total: Some(downloaded),
+                        downloaded,
+                        speed: ((downloaded - offset) as f32 / start.elapsed().as_secs_f32())
+                            as u64,
                     })
                     .await;
 
-                file.write_all(body).await?;
-                file.flush().await?;
-
                 Ok(())
             }
 
             #[cfg(not(feature = "native"))]
             {
-                let _ = (url, destination, progress);
+                let _ = (url, destination, budget, progress);
                 Err(Error::WasmError(
                     "File download not supported on this platform without the native feature"
                         .to_string(),
@@ -73,3 +162,117 @@ pub fn download_file<'a>(
         },
     )
 }
+
+#[cfg(feature = "native")]
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The free bytes on the disk that holds `path`, if it can be found.
+#[cfg(feature = "native")]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use sysinfo::Disks;
+
+    // The file may not exist yet
+    let path = path
+        .ancestors()
+        .find_map(|ancestor| std::fs::canonicalize(ancestor).ok())?;
+
+    let disks = Disks::new_with_refreshed_list();
+
+    disks
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+
+    use sipper::Sipper;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    /// Serves `body`, honoring ranges, and counts the bytes it was asked for.
+    async fn serve(body: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    break;
+                };
+
+                let mut request = vec![0; 4096];
+                let read = stream.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..read]).to_lowercase();
+
+                let start = request
+                    .lines()
+                    .find_map(|line| line.strip_prefix("range: bytes="))
+                    .and_then(|range| range.trim().trim_end_matches('-').parse().ok());
+
+                let response = match start {
+                    Some(start) => format!(
+                        "HTTP/1.1 206 Partial Content\r\ncontent-length: {}\r\n\
+                        content-range: bytes {start}-{}/{}\r\nconnection: close\r\n\r\n",
+                        body.len() - start,
+                        body.len() - 1,
+                        body.len()
+                    ),
+                    None => format!(
+                        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                        body.len()
+                    ),
+                };
+
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.write_all(&body[start.unwrap_or(0)..]).await.unwrap();
+            }
+        });
+
+        format!("http://{address}/model.gguf")
+    }
+
+    #[tokio::test]
+    async fn test_resume() {
+        let url = serve(b"0123456789abcdef").await;
+        let directory =
+            std::env::temp_dir().join(format!("peak-download-{}", uuid::Uuid::new_v4()));
+        let destination = directory.join("model.tmp");
+
+        fs::create_dir_all(&directory).await.unwrap();
+        fs::write(&destination, b"0123456").await.unwrap();
+
+        // Over budget, nothing is written
+        let over = directory.join("over.tmp");
+
+        assert!(matches!(
+            download_file(&url, &over, Some(10)).await,
+            Err(Error::ResourceLimitExceeded(_))
+        ));
+        assert!(!fs::try_exists(&over).await.unwrap());
+
+        let mut download = download_file(&url, &destination, Some(16)).pin();
+        let mut last = None;
+
+        while let Some(progress) = download.sip().await {
+            last = Some(progress);
+        }
+
+        download.await.unwrap();
+
+        assert_eq!(fs::read(&destination).await.unwrap(), b"0123456789abcdef");
+        assert_eq!(last.unwrap().percent(), Some((16, 100)));
+
+        // Complete files are left alone
+        download_file(&url, &destination, None).await.unwrap();
+        assert_eq!(fs::read(&destination).await.unwrap().len(), 16);
+
+        assert!(available_space(&destination).is_some_and(|space| space > 0));
+
+        let _ = fs::remove_dir_all(directory).await;
+    }
+}
@@ -1,18 +1,31 @@
 #![allow(dead_code)]
 use crate::brain::directory;
 use crate::brain::model;
+use crate::brain::web::{self, search};
 use crate::brain::Error;
+use crate::llm::{Endpoint, ModelProvider};
 
 use decoder::{decode, encode, Value};
 #[cfg(feature = "native")]
 use tokio::fs;
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Default)]
 pub struct Settings {
     pub library: model::Directory,
+    /// The most the [`library`](Self::library) should take, in bytes.
+    ///
+    /// See [`model::Library::suggest_removals`].
+    pub library_budget: Option<model::Size>,
+    /// The local model the assistant boots.
+    pub model: Option<model::File>,
     pub theme: Theme,
+    pub mcp_servers: Vec<McpServer>,
+    pub llm: Option<Llm>,
+    pub search: search::Settings,
 }
 
 impl Settings {
@@ -45,6 +58,8 @@ impl Settings {
             fs::write(path, toml).await?;
         }
 
+        web::configure(self.search);
+
         Ok(())
     }
 
@@ -55,19 +70,61 @@ impl Settings {
             .optional("library", model::Directory::decode)?
             .unwrap_or_default();
 
+        let library_budget = settings
+            .optional("library_budget", decode::u64)?
+            .map(model::Size);
+
+        let model = settings.optional("model", model::File::decode)?;
+
         let theme = settings
             .optional("theme", Theme::decode)?
             .unwrap_or_default();
 
-        Ok(Self { library, theme })
+        let mcp_servers = settings
+            .optional("mcp_servers", decode::sequence(McpServer::decode))?
+            .unwrap_or_default();
+
+        let llm = settings.optional("llm", Llm::decode)?;
+
+        let search = settings
+            .optional("search", search::Settings::decode)?
+            .unwrap_or_default();
+
+        Ok(Self {
+            library,
+            library_budget,
+            model,
+            theme,
+            mcp_servers,
+            llm,
+            search,
+        })
     }
 
     fn encode(&self) -> Value {
-        encode::map([
+        let mut fields = vec![
             ("library", self.library.encode()),
             ("theme", self.theme.encode()),
-        ])
-        .into_value()
+            (
+                "mcp_servers",
+                encode::sequence(McpServer::encode, &self.mcp_servers),
+            ),
+            ("search", self.search.encode()),
+        ];
+
+        if let Some(budget) = self.library_budget {
+            fields.push(("library_budget", encode::u64(budget.0)));
+        }
+
+        if let Some(model) = &self.model {
+            fields.push(("model", model.clone().encode()));
+        }
+
+        if let Some(llm) = &self.llm {
+            fields.push(("llm", llm.encode()));
+        }
+
+        encode::map(fields).into_value()
     }
 
     fn path() -> PathBuf {
@@ -75,6 +132,175 @@ impl Settings {
     }
 }
 
+/// The model server used by [`crate::llm::LlmClient`].
+///
+/// If set, it answers instead of a local model: see
+/// [`Assistant::configured`](crate::brain::assistant::Assistant::configured).
+///
+/// ```toml
+/// [llm]
+/// provider = "ollama" # or "llama-cpp", "openrouter", "openai-compatible"
+/// model = "llama3.2"
+/// base_url = "http://gpu-box.lan:11434"
+/// api_key = "..."
+/// timeout = 120 # seconds
+/// accept_invalid_certs = false
+/// ca_certificate = "/etc/ssl/certs/lan-ca.pem"
+/// headers = { X-Tenant = "home" }
+/// ```
+///
+/// Every field but `provider` and `model` is optional; `base_url` defaults to
+/// where the provider usually listens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Llm {
+    pub provider: ModelProvider,
+    pub model: String,
+    pub endpoint: Endpoint,
+}
+
+impl Llm {
+    fn decode(value: Value) -> decoder::Result<Self> {
+        use serde::de::{Deserialize, IntoDeserializer};
+
+        let mut llm = decode::map(value)?;
+
+        let provider = llm.required("provider", |value: Value| {
+            let slug = decode::string(value)?;
+
+            ModelProvider::from_slug(&slug)
+                .ok_or_else(|| decoder::Error::custom(format!("unknown LLM provider: {slug}")))
+        })?;
+
+        let endpoint = Endpoint {
+            base_url: llm
+                .optional("base_url", decode::string)?
+                .unwrap_or_else(|| provider.default_base_url().to_owned()),
+            headers: llm
+                .optional("headers", |value: Value| {
+                    BTreeMap::deserialize(value.into_deserializer()).map_err(decoder::Error::custom)
+                })?
+                .unwrap_or_default(),
+            api_key: llm.optional("api_key", decode::string)?,
+            timeout: llm
+                .optional("timeout", decode::u64)?
+                .map(Duration::from_secs),
+            accept_invalid_certs: llm
+                .optional("accept_invalid_certs", decode::bool)?
+                .unwrap_or(false),
+            ca_certificate: llm
+                .optional("ca_certificate", decode::string)?
+                .map(PathBuf::from),
+        };
+
+        Ok(Self {
+            provider,
+            model: llm.required("model", decode::string)?,
+            endpoint,
+        })
+    }
+
+    fn encode(&self) -> Value {
+        let endpoint = &self.endpoint;
+
+        let mut fields = vec![
+            ("provider", encode::string(self.provider.slug())),
+            ("model", encode::string(&self.model)),
+            ("base_url", encode::string(&endpoint.base_url)),
+            (
+                "headers",
+                encode::map(
+                    endpoint
+                        .headers
+                        .iter()
+                        .map(|(key, value)| (key.as_str(), encode::string(value))),
+                )
+                .into_value(),
+            ),
+            (
+                "accept_invalid_certs",
+                encode::bool(endpoint.accept_invalid_certs),
+            ),
+        ];
+
+        if let Some(api_key) = &endpoint.api_key {
+            fields.push(("api_key", encode::string(api_key)));
+        }
+
+        if let Some(timeout) = endpoint.timeout {
+            fields.push(("timeout", encode::u64(timeout.as_secs())));
+        }
+
+        if let Some(ca_certificate) = &endpoint.ca_certificate {
+            fields.push((
+                "ca_certificate",
+                encode::string(ca_certificate.to_string_lossy()),
+            ));
+        }
+
+        encode::map(fields).into_value()
+    }
+}
+
+/// An external MCP server spoken to over stdio.
+///
+/// ```toml
+/// [[mcp_servers]]
+/// name = "tracker"
+/// command = "tracker-mcp"
+/// args = ["--stdio"]
+/// env = { TRACKER_TOKEN = "..." }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct McpServer {
+    /// Namespace for the server's tools, e.g. `tracker/create_issue`.
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub enabled: bool,
+}
+
+impl McpServer {
+    fn decode(value: Value) -> decoder::Result<Self> {
+        use serde::de::{Deserialize, IntoDeserializer};
+
+        let mut server = decode::map(value)?;
+
+        Ok(Self {
+            name: server.required("name", decode::string)?,
+            command: server.required("command", decode::string)?,
+            args: server
+                .optional("args", decode::sequence(decode::string))?
+                .unwrap_or_default(),
+            env: server
+                .optional("env", |value: Value| {
+                    BTreeMap::deserialize(value.into_deserializer()).map_err(decoder::Error::custom)
+                })?
+                .unwrap_or_default(),
+            enabled: server.optional("enabled", decode::bool)?.unwrap_or(true),
+        })
+    }
+
+    fn encode(&self) -> Value {
+        encode::map([
+            ("name", encode::string(&self.name)),
+            ("command", encode::string(&self.command)),
+            ("args", encode::sequence(encode::string, &self.args)),
+            (
+                "env",
+                encode::map(
+                    self.env
+                        .iter()
+                        .map(|(key, value)| (key.as_str(), encode::string(value))),
+                )
+                .into_value(),
+            ),
+            ("enabled", encode::bool(self.enabled)),
+        ])
+        .into_value()
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub enum Theme {
     Light,
@@ -172,3 +398,36 @@ impl Theme {
         Self::Ferra,
     ];
 }
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_llm_endpoint() {
+        let config: Value = toml::from_str(
+            r#"
+            [llm]
+            provider = "ollama"
+            model = "llama3.2"
+            base_url = "http://gpu-box.lan:11434"
+            timeout = 90
+            headers = { X-Tenant = "home" }
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::decode(config).unwrap();
+        let llm = settings.llm.clone().unwrap();
+
+        assert_eq!(llm.provider, ModelProvider::Ollama);
+        assert_eq!(llm.endpoint.base_url, "http://gpu-box.lan:11434");
+        assert_eq!(llm.endpoint.timeout, Some(Duration::from_secs(90)));
+        assert_eq!(llm.endpoint.headers["X-Tenant"], "home");
+
+        let encoded = toml::to_string_pretty(&settings.encode()).unwrap();
+        let decoded = Settings::decode(toml::from_str(&encoded).unwrap()).unwrap();
+
+        assert_eq!(decoded.llm, Some(llm));
+    }
+}
@@ -1,8 +1,15 @@
+pub mod search;
+
+pub use search::SearchProvider;
+
 use crate::brain::assistant::Message;
-use crate::brain::{Assistant, Error, Url};
+use crate::brain::{Assistant, Error, Settings, Url};
 
+use search::Cache;
 use sipper::{sipper, Sipper, Straw};
 
+use std::sync::{LazyLock, RwLock};
+
 pub struct Search {
     pub results: Vec<Url>,
 }
@@ -19,54 +26,61 @@ impl Summary {
     }
 }
 
+/// The search settings, read once and kept current by [`Settings::save`].
+static SETTINGS: RwLock<Option<search::Settings>> = RwLock::new(None);
+
+/// Searches the web with the provider picked in the [`Settings`].
+///
+/// Results are cached for the configured time.
 pub async fn search(query: &str) -> Result<Search, Error> {
-    log::info!("Searching on DuckDuckGo: {query}");
+    static CACHE: LazyLock<Cache> = LazyLock::new(Cache::new);
 
-    let mut headers = std::collections::HashMap::new();
-    headers.insert(
-        "User-Agent".to_string(),
-        "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/132.0.0.0 Safari/537.36".to_string(),
-    );
-    headers.insert("Accept".to_string(), "*/*".to_string());
+    let settings = settings().await;
 
-    let url = format!(
-        "https://html.duckduckgo.com/html/?q={}",
-        urlencoding::encode(query)
-    );
+    let provider = settings.provider();
 
-    let response = crate::http::HttpClient::get_with_headers(&url, headers).await?;
-    let search_results = response
-        .text()
-        .map_err(|e| Error::WasmError(format!("Invalid text response: {}", e)))?;
+    log::info!("Searching on {}: {query}", provider.name());
 
-    let html = scraper::Html::parse_document(&search_results);
-    let selector = scraper::Selector::parse(".result__a").unwrap();
-
-    let results = html
-        .select(&selector)
-        .filter_map(|link| {
-            let encoded = link.attr("href")?;
-
-            if encoded.contains("ad_domain") {
-                return None;
-            }
-
-            let query_pairs = url::form_urlencoded::parse(encoded.as_bytes());
-            for (_key, value) in query_pairs {
-                if let Ok(url) = url::Url::parse(&value) {
-                    return Some(url);
-                }
-            }
-            None
-        })
-        .take(5)
-        .collect();
+    let results = CACHE
+        .search(
+            provider.as_ref(),
+            query,
+            settings.results,
+            settings.cache_ttl,
+        )
+        .await?;
 
     log::info!("-- Found: {results:?}");
 
     Ok(Search { results })
 }
 
+/// Replaces the search settings used from now on.
+pub(crate) fn configure(settings: search::Settings) {
+    *SETTINGS.write().unwrap() = Some(settings);
+}
+
+async fn settings() -> search::Settings {
+    if let Some(settings) = SETTINGS.read().unwrap().clone() {
+        return settings;
+    }
+
+    #[cfg(feature = "native")]
+    let settings = tokio::task::spawn_blocking(Settings::fetch)
+        .await
+        .map_err(|error| Error::JoinFailed(std::sync::Arc::new(error)))
+        .and_then(|settings| settings);
+
+    #[cfg(not(feature = "native"))]
+    let settings = Settings::fetch();
+
+    let settings = settings.map(|settings| settings.search).unwrap_or_default();
+
+    configure(settings.clone());
+
+    settings
+}
+
 pub fn summarize<'a>(
     assistant: &'a Assistant,
     query: &'a str,
@@ -105,6 +119,16 @@ pub fn summarize<'a>(
 async fn scrape(url: Url) -> Result<String, Error> {
     log::info!("Scraping text: {url}");
 
+    match url.scheme() {
+        "file" => return read(&url).await,
+        "http" | "https" => {}
+        scheme => {
+            return Err(Error::RequestFailed(format!(
+                "cannot scrape {scheme} links: {url}"
+            )))
+        }
+    }
+
     let candidates = scraper::Selector::parse("p, a").unwrap();
 
     let mut headers = std::collections::HashMap::new();
@@ -134,3 +158,39 @@ async fn scrape(url: Url) -> Result<String, Error> {
 
     Ok(lines.join("\n"))
 }
+
+/// Reads a local document found by [`search::LocalDocs`].
+///
+/// Only indexed documents can be read, so a plan cannot be steered into
+/// reading any other file.
+async fn read(url: &Url) -> Result<String, Error> {
+    #[cfg(feature = "native")]
+    {
+        use crate::brain::index::Index;
+
+        let path = url
+            .to_file_path()
+            .map_err(|_| Error::RequestFailed(format!("invalid file URL: {url}")))?;
+
+        let is_indexed = {
+            let path = path.clone();
+
+            tokio::task::spawn_blocking(move || {
+                Index::open_default().is_ok_and(|index| index.contains(&path))
+            })
+            .await
+            .unwrap_or(false)
+        };
+
+        if !is_indexed {
+            return Err(Error::RequestFailed(format!(
+                "{url} is not one of the indexed documents"
+            )));
+        }
+
+        Ok(tokio::fs::read_to_string(path).await?)
+    }
+
+    #[cfg(not(feature = "native"))]
+    Err(Error::WasmError(format!("cannot read local files: {url}")))
+}
@@ -0,0 +1,662 @@
+//! Where [`web::search`](super::search) finds its results.
+//!
+//! A [`SearchProvider`] turns a query into links. DuckDuckGo is scraped by
+//! default; a self-hosted SearXNG instance or the local document
+//! [`Index`](crate::brain::index::Index) can be picked in the settings:
+//!
+//! ```toml
+//! [search]
+//! provider = "searxng" # or "duckduckgo", "local"
+//! url = "http://searx.lan:8080"
+//! results = 5
+//! cache_ttl = 3600 # seconds, 0 disables the cache
+//! ```
+use crate::brain::{Error, Url};
+use crate::http::HttpClient;
+
+use decoder::{decode, encode, Value};
+use futures::FutureExt;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Results returned when the settings do not say otherwise.
+pub const DEFAULT_RESULTS: usize = 5;
+
+/// How long results are reused when the settings do not say otherwise.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/132.0.0.0 Safari/537.36";
+
+/// A pending search. Requests are not `Send` on the web.
+#[cfg(not(target_arch = "wasm32"))]
+pub type SearchFuture<'a> = futures::future::BoxFuture<'a, Result<Vec<Url>, Error>>;
+
+/// A pending search. Requests are not `Send` on the web.
+#[cfg(target_arch = "wasm32")]
+pub type SearchFuture<'a> = futures::future::LocalBoxFuture<'a, Result<Vec<Url>, Error>>;
+
+/// Something that can turn a query into links.
+pub trait SearchProvider: Send + Sync {
+    /// A short name for logs, e.g. `duckduckgo`.
+    fn name(&self) -> &str;
+
+    /// Where the results come from, e.g. the URL of the instance, so
+    /// instances of the same provider do not share cached results.
+    fn url(&self) -> String;
+
+    /// The schemes its links may have; [`Cache::search`] drops the others.
+    fn schemes(&self) -> &'static [&'static str] {
+        &["http", "https"]
+    }
+
+    /// Finds at most `limit` links for `query`, best first.
+    fn search<'a>(&'a self, query: &'a str, limit: usize) -> SearchFuture<'a>;
+}
+
+/// The search settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub provider: Provider,
+    /// Where the provider listens; its public instance when unset.
+    pub url: Option<String>,
+    pub results: usize,
+    pub cache_ttl: Duration,
+}
+
+impl Settings {
+    /// Builds the configured provider.
+    pub fn provider(&self) -> Box<dyn SearchProvider> {
+        match self.provider {
+            Provider::DuckDuckGo => Box::new(match &self.url {
+                Some(url) => DuckDuckGo::new(url),
+                None => DuckDuckGo::default(),
+            }),
+            Provider::SearXng => Box::new(SearXng::new(
+                self.url.as_deref().unwrap_or(SearXng::DEFAULT_URL),
+            )),
+            #[cfg(feature = "native")]
+            Provider::Local => Box::new(match &self.url {
+                Some(path) => LocalDocs::new(path),
+                None => LocalDocs::default(),
+            }),
+            #[cfg(not(feature = "native"))]
+            Provider::Local => Box::new(Unsupported),
+        }
+    }
+
+    pub(crate) fn decode(value: Value) -> decoder::Result<Self> {
+        let mut search = decode::map(value)?;
+
+        let provider = search
+            .optional("provider", |value: Value| {
+                let slug = decode::string(value)?;
+
+                Provider::from_slug(&slug).ok_or_else(|| {
+                    decoder::Error::custom(format!("unknown search provider: {slug}"))
+                })
+            })?
+            .unwrap_or_default();
+
+        Ok(Self {
+            provider,
+            url: search.optional("url", decode::string)?,
+            results: search
+                .optional("results", decode::u64)?
+                .map_or(DEFAULT_RESULTS, |results| results as usize),
+            cache_ttl: search
+                .optional("cache_ttl", decode::u64)?
+                .map_or(DEFAULT_TTL, Duration::from_secs),
+        })
+    }
+
+    pub(crate) fn encode(&self) -> Value {
+        let mut fields = vec![
+            ("provider", encode::string(self.provider.slug())),
+            ("results", encode::u64(self.results as u64)),
+            ("cache_ttl", encode::u64(self.cache_ttl.as_secs())),
+        ];
+
+        if let Some(url) = &self.url {
+            fields.push(("url", encode::string(url)));
+        }
+
+        encode::map(fields).into_value()
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            provider: Provider::default(),
+            url: None,
+            results: DEFAULT_RESULTS,
+            cache_ttl: DEFAULT_TTL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Provider {
+    #[default]
+    DuckDuckGo,
+    SearXng,
+    Local,
+}
+
+impl Provider {
+    pub fn slug(self) -> &'static str {
+        match self {
+            Self::DuckDuckGo => "duckduckgo",
+            Self::SearXng => "searxng",
+            Self::Local => "local",
+        }
+    }
+
+    pub fn from_slug(slug: &str) -> Option<Self> {
+        Some(match slug {
+            "duckduckgo" => Self::DuckDuckGo,
+            "searxng" => Self::SearXng,
+            "local" => Self::Local,
+            _ => return None,
+        })
+    }
+}
+
+/// Scrapes the HTML version of DuckDuckGo.
+#[derive(Debug, Clone)]
+pub struct DuckDuckGo {
+    base_url: String,
+}
+
+impl DuckDuckGo {
+    pub const DEFAULT_URL: &str = "https://html.duckduckgo.com";
+
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+        }
+    }
+}
+
+impl Default for DuckDuckGo {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_URL)
+    }
+}
+
+impl SearchProvider for DuckDuckGo {
+    fn name(&self) -> &str {
+        "duckduckgo"
+    }
+
+    fn url(&self) -> String {
+        self.base_url.clone()
+    }
+
+    fn search<'a>(&'a self, query: &'a str, limit: usize) -> SearchFuture<'a> {
+        boxed(async move {
+            let url = format!(
+                "{}/html/?q={}",
+                self.base_url.trim_end_matches('/'),
+                urlencoding::encode(query)
+            );
+
+            let html = get(&url, "*/*").await?;
+
+            let html = scraper::Html::parse_document(&html);
+            let selector = scraper::Selector::parse(".result__a").unwrap();
+
+            let results = html
+                .select(&selector)
+                .filter_map(|link| {
+                    let encoded = link.attr("href")?;
+
+                    if encoded.contains("ad_domain") {
+                        return None;
+                    }
+
+                    // Links go through a redirect, e.g. `//duckduckgo.com/l/?uddg=...`
+                    url::form_urlencoded::parse(encoded.as_bytes())
+                        .find_map(|(_key, value)| Url::parse(&value).ok())
+                })
+                .take(limit)
+                .collect();
+
+            Ok(results)
+        })
+    }
+}
+
+/// Queries the JSON API of a SearXNG instance.
+///
+/// The instance must list `json` among its `search.formats`.
+#[derive(Debug, Clone)]
+pub struct SearXng {
+    base_url: String,
+}
+
+impl SearXng {
+    pub const DEFAULT_URL: &str = "http://localhost:8888";
+
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+        }
+    }
+}
+
+impl SearchProvider for SearXng {
+    fn name(&self) -> &str {
+        "searxng"
+    }
+
+    fn url(&self) -> String {
+        self.base_url.clone()
+    }
+
+    fn search<'a>(&'a self, query: &'a str, limit: usize) -> SearchFuture<'a> {
+        #[derive(serde::Deserialize)]
+        struct Response {
+            results: Vec<Entry>,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Entry {
+            url: String,
+        }
+
+        boxed(async move {
+            let url = format!(
+                "{}/search?q={}&format=json",
+                self.base_url.trim_end_matches('/'),
+                urlencoding::encode(query)
+            );
+
+            let response: Response = serde_json::from_str(&get(&url, "application/json").await?)?;
+
+            Ok(response
+                .results
+                .into_iter()
+                .filter_map(|result| Url::parse(&result.url).ok())
+                .take(limit)
+                .collect())
+        })
+    }
+}
+
+/// Searches the local document index by keywords, so it works offline and
+/// without a model server. Results are `file://` links.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone)]
+pub struct LocalDocs {
+    index: std::path::PathBuf,
+}
+
+#[cfg(feature = "native")]
+impl LocalDocs {
+    pub fn new(index: impl Into<std::path::PathBuf>) -> Self {
+        Self {
+            index: index.into(),
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+impl Default for LocalDocs {
+    fn default() -> Self {
+        Self::new(crate::brain::directory::data().join("index.json"))
+    }
+}
+
+#[cfg(feature = "native")]
+impl SearchProvider for LocalDocs {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn url(&self) -> String {
+        self.index.display().to_string()
+    }
+
+    /// Only local documents may be read as `file://` links.
+    fn schemes(&self) -> &'static [&'static str] {
+        &["file"]
+    }
+
+    fn search<'a>(&'a self, query: &'a str, limit: usize) -> SearchFuture<'a> {
+        use crate::brain::index::Index;
+
+        boxed(async move {
+            let index = Index::open(&self.index)?;
+            let mut results: Vec<Url> = Vec::with_capacity(limit);
+
+            for hit in index.find(query, usize::MAX) {
+                let Ok(url) = Url::from_file_path(&hit.path) else {
+                    continue;
+                };
+
+                if !results.contains(&url) {
+                    results.push(url);
+                }
+
+                if results.len() == limit {
+                    break;
+                }
+            }
+
+            Ok(results)
+        })
+    }
+}
+
+#[cfg(not(feature = "native"))]
+struct Unsupported;
+
+#[cfg(not(feature = "native"))]
+impl SearchProvider for Unsupported {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn url(&self) -> String {
+        String::new()
+    }
+
+    fn search<'a>(&'a self, _query: &'a str, _limit: usize) -> SearchFuture<'a> {
+        boxed(async {
+            Err(Error::WasmError(
+                "local documents cannot be searched here".to_owned(),
+            ))
+        })
+    }
+}
+
+/// Remembers results for a while, so retrying a plan does not hit the
+/// provider again.
+#[derive(Debug, Default)]
+pub struct Cache {
+    entries: Mutex<HashMap<Key, (Instant, Vec<Url>)>>,
+}
+
+type Key = (String, String, usize);
+
+impl Cache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the results cached for `query` if they are younger than
+    /// `ttl`, or asks `provider` for new ones. Failures are not cached.
+    ///
+    /// Links with a scheme the provider does not allow are dropped.
+    pub async fn search(
+        &self,
+        provider: &dyn SearchProvider,
+        query: &str,
+        limit: usize,
+        ttl: Duration,
+    ) -> Result<Vec<Url>, Error> {
+        if ttl.is_zero() {
+            return fetch(provider, query, limit).await;
+        }
+
+        let key = (provider.url(), query.trim().to_lowercase(), limit);
+
+        if let Some((fetched_at, results)) = self.entries.lock().unwrap().get(&key) {
+            if fetched_at.elapsed() < ttl {
+                log::info!("-- Cached results for: {query}");
+                return Ok(results.clone());
+            }
+        }
+
+        let results = fetch(provider, query, limit).await?;
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (fetched_at, _)| fetched_at.elapsed() < ttl);
+        let _ = entries.insert(key, (Instant::now(), results.clone()));
+
+        Ok(results)
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+async fn fetch(
+    provider: &dyn SearchProvider,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<Url>, Error> {
+    let mut results = provider.search(query, limit).await?;
+
+    results.retain(|url| {
+        let is_allowed = provider.schemes().contains(&url.scheme());
+
+        if !is_allowed {
+            log::warn!("Dropping {url} from the {} results", provider.name());
+        }
+
+        is_allowed
+    });
+
+    Ok(results)
+}
+
+async fn get(url: &str, accept: &str) -> Result<String, Error> {
+    let headers = HashMap::from([
+        ("User-Agent".to_owned(), USER_AGENT.to_owned()),
+        ("Accept".to_owned(), accept.to_owned()),
+    ]);
+
+    let response = HttpClient::get_with_headers(url, headers).await?;
+
+    if response.status >= 400 {
+        return Err(Error::RequestFailed(format!(
+            "{url} answered with status {}",
+            response.status
+        )));
+    }
+
+    response
+        .text()
+        .map_err(|e| Error::WasmError(format!("Invalid text response: {}", e)))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn boxed<'a>(
+    future: impl std::future::Future<Output = Result<Vec<Url>, Error>> + Send + 'a,
+) -> SearchFuture<'a> {
+    future.boxed()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn boxed<'a>(
+    future: impl std::future::Future<Output = Result<Vec<Url>, Error>> + 'a,
+) -> SearchFuture<'a> {
+    future.boxed_local()
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    /// Answers a single request with `body` and hands back its request line.
+    fn serve(
+        content_type: &'static str,
+        body: &'static str,
+    ) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+
+            while !request.ends_with(b"\r\n\r\n") {
+                let read = stream.read(&mut buffer).unwrap();
+
+                if read == 0 {
+                    break;
+                }
+
+                request.extend_from_slice(&buffer[..read]);
+            }
+
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+
+            String::from_utf8_lossy(&request)
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_owned()
+        });
+
+        (base_url, server)
+    }
+
+    #[tokio::test]
+    async fn test_duckduckgo() {
+        let (base_url, server) = serve(
+            "text/html",
+            r#"<html><body>
+            <a class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Ficed.rs%2F&amp;rut=1">iced</a>
+            <a class="result__a" href="//duckduckgo.com/y.js?ad_domain=ads.example&amp;u3=https%3A%2F%2Fads.example">Ad</a>
+            <a class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fdocs.rs%2Ficed">docs</a>
+            <a class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fgithub.com%2Ficed-rs">github</a>
+            </body></html>"#,
+        );
+
+        let results = DuckDuckGo::new(base_url)
+            .search("iced gui", 2)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            results,
+            [
+                Url::parse("https://iced.rs/").unwrap(),
+                Url::parse("https://docs.rs/iced").unwrap()
+            ]
+        );
+        assert_eq!(server.join().unwrap(), "GET /html/?q=iced%20gui HTTP/1.1");
+    }
+
+    #[tokio::test]
+    async fn test_searxng() {
+        let (base_url, server) = serve(
+            "application/json",
+            r#"{"query":"iced","results":[
+                {"url":"https://iced.rs/","title":"iced"},
+                {"url":"not a url","title":"broken"},
+                {"url":"https://docs.rs/iced","title":"docs"}
+            ]}"#,
+        );
+
+        let results = SearXng::new(format!("{base_url}/"))
+            .search("iced", 5)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            results,
+            [
+                Url::parse("https://iced.rs/").unwrap(),
+                Url::parse("https://docs.rs/iced").unwrap()
+            ]
+        );
+        assert_eq!(
+            server.join().unwrap(),
+            "GET /search?q=iced&format=json HTTP/1.1"
+        );
+    }
+
+    struct Counter(&'static str, AtomicUsize);
+
+    impl SearchProvider for Counter {
+        fn name(&self) -> &str {
+            "counter"
+        }
+
+        fn url(&self) -> String {
+            self.0.to_owned()
+        }
+
+        fn search<'a>(&'a self, query: &'a str, _limit: usize) -> SearchFuture<'a> {
+            let calls = self.1.fetch_add(1, Ordering::SeqCst) + 1;
+
+            boxed(async move {
+                Ok(vec![
+                    Url::parse(&format!("https://{query}.example/{calls}")).unwrap(),
+                    Url::parse("file:///etc/passwd").unwrap(),
+                ])
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache() {
+        let provider = Counter("http://one.lan", AtomicUsize::new(0));
+        let cache = Cache::new();
+        let hour = Duration::from_secs(3600);
+
+        let first = cache.search(&provider, "rust", 5, hour).await.unwrap();
+        let second = cache.search(&provider, " Rust ", 5, hour).await.unwrap();
+
+        // Web providers cannot point at local files
+        assert_eq!(first, [Url::parse("https://rust.example/1").unwrap()]);
+        assert_eq!(first, second);
+        assert_eq!(provider.1.load(Ordering::SeqCst), 1);
+
+        // Other instances have their own results
+        let other = Counter("http://two.lan", AtomicUsize::new(0));
+        let _ = cache.search(&other, "rust", 5, hour).await.unwrap();
+        assert_eq!(other.1.load(Ordering::SeqCst), 1);
+
+        // Expired entries and a disabled cache go back to the provider
+        let _ = cache.search(&provider, "rust", 5, Duration::ZERO).await;
+        let _ = cache
+            .search(&provider, "rust", 5, Duration::from_nanos(1))
+            .await;
+
+        assert_eq!(provider.1.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_settings() {
+        let config: Value = toml::from_str(
+            r#"
+            provider = "searxng"
+            url = "http://searx.lan:8080"
+            cache_ttl = 0
+            "#,
+        )
+        .unwrap();
+
+        let settings = Settings::decode(config).unwrap();
+
+        assert_eq!(settings.provider, Provider::SearXng);
+        assert_eq!(settings.results, DEFAULT_RESULTS);
+        assert_eq!(settings.cache_ttl, Duration::ZERO);
+        assert_eq!(settings.provider().name(), "searxng");
+
+        let encoded = toml::to_string_pretty(&settings.encode()).unwrap();
+        let decoded = Settings::decode(toml::from_str(&encoded).unwrap()).unwrap();
+
+        assert_eq!(decoded, settings);
+    }
+}
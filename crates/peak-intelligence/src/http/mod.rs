@@ -14,9 +14,24 @@ pub use web as client;
 
 use serde::{Deserialize, Serialize};
 
+use std::path::PathBuf;
+use std::time::Duration;
+
 #[derive(Debug, Clone)]
 pub struct HttpClient;
 
+/// Transport options for a request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestOptions {
+    /// Maximum time to wait for a response. For streams, the maximum time
+    /// between two chunks.
+    pub timeout: Option<Duration>,
+    /// Accept self-signed or otherwise invalid certificates (native only).
+    pub accept_invalid_certs: bool,
+    /// Additional PEM root certificate to trust (native only).
+    pub ca_certificate: Option<PathBuf>,
+}
+
 #[derive(Debug)]
 pub struct HttpResponse {
     pub status: u16,
@@ -58,7 +73,16 @@ impl HttpClient {
         url: &str,
         headers: std::collections::HashMap<String, String>,
     ) -> Result<HttpResponse, HttpError> {
-        client::get_with_headers(url, headers).await
+        Self::get_with_options(url, headers, &RequestOptions::default()).await
+    }
+
+    /// Perform a GET request with custom headers and transport options
+    pub async fn get_with_options(
+        url: &str,
+        headers: std::collections::HashMap<String, String>,
+        options: &RequestOptions,
+    ) -> Result<HttpResponse, HttpError> {
+        client::get_with_headers(url, headers, options).await
     }
 
     /// Perform a POST request with JSON body
@@ -72,7 +96,17 @@ impl HttpClient {
         body: &T,
         headers: std::collections::HashMap<String, String>,
     ) -> Result<HttpResponse, HttpError> {
-        client::post_json_with_headers(url, body, headers).await
+        Self::post_json_with_options(url, body, headers, &RequestOptions::default()).await
+    }
+
+    /// Perform a POST request with JSON body, custom headers and transport options
+    pub async fn post_json_with_options<T: Serialize>(
+        url: &str,
+        body: &T,
+        headers: std::collections::HashMap<String, String>,
+        options: &RequestOptions,
+    ) -> Result<HttpResponse, HttpError> {
+        client::post_json_with_headers(url, body, headers, options).await
     }
 
     /// Perform a POST request with JSON body and custom headers, returning a stream
@@ -82,7 +116,18 @@ impl HttpClient {
         body: &T,
         headers: std::collections::HashMap<String, String>,
     ) -> Result<impl futures::Stream<Item = Result<bytes::Bytes, reqwest::Error>>, HttpError> {
-        native::post_json_stream(url, body, headers).await
+        Self::post_json_stream_with_options(url, body, headers, &RequestOptions::default()).await
+    }
+
+    /// Perform a streaming POST request with transport options
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn post_json_stream_with_options<T: Serialize>(
+        url: &str,
+        body: &T,
+        headers: std::collections::HashMap<String, String>,
+        options: &RequestOptions,
+    ) -> Result<impl futures::Stream<Item = Result<bytes::Bytes, reqwest::Error>>, HttpError> {
+        native::post_json_stream(url, body, headers, options).await
     }
 
     #[cfg(target_arch = "wasm32")]
@@ -91,7 +136,17 @@ impl HttpClient {
         body: &T,
         headers: std::collections::HashMap<String, String>,
     ) -> Result<impl futures::Stream<Item = Result<bytes::Bytes, String>>, HttpError> {
-        web::post_json_stream(url, body, headers).await
+        Self::post_json_stream_with_options(url, body, headers, &RequestOptions::default()).await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub async fn post_json_stream_with_options<T: Serialize>(
+        url: &str,
+        body: &T,
+        headers: std::collections::HashMap<String, String>,
+        options: &RequestOptions,
+    ) -> Result<impl futures::Stream<Item = Result<bytes::Bytes, String>>, HttpError> {
+        web::post_json_stream(url, body, headers, options).await
     }
 }
 
@@ -1,20 +1,30 @@
 // Native HTTP implementation using reqwest
 
-use super::{HttpError, HttpResponse};
+use super::{HttpError, HttpResponse, RequestOptions};
 use serde::Serialize;
 
 #[allow(dead_code)]
 pub async fn get(url: &str) -> Result<HttpResponse, HttpError> {
-    get_with_headers(url, std::collections::HashMap::new()).await
+    get_with_headers(
+        url,
+        std::collections::HashMap::new(),
+        &RequestOptions::default(),
+    )
+    .await
 }
 
 pub async fn get_with_headers(
     url: &str,
     headers: std::collections::HashMap<String, String>,
+    options: &RequestOptions,
 ) -> Result<HttpResponse, HttpError> {
-    let client = reqwest::Client::new();
+    let client = client(options)?;
     let mut request = client.get(url);
 
+    if let Some(timeout) = options.timeout {
+        request = request.timeout(timeout);
+    }
+
     for (key, value) in headers {
         request = request.header(key, value);
     }
@@ -28,17 +38,28 @@ pub async fn get_with_headers(
 
 #[allow(dead_code)]
 pub async fn post_json<T: Serialize>(url: &str, body: &T) -> Result<HttpResponse, HttpError> {
-    post_json_with_headers(url, body, std::collections::HashMap::new()).await
+    post_json_with_headers(
+        url,
+        body,
+        std::collections::HashMap::new(),
+        &RequestOptions::default(),
+    )
+    .await
 }
 
 pub async fn post_json_with_headers<T: Serialize>(
     url: &str,
     body: &T,
     headers: std::collections::HashMap<String, String>,
+    options: &RequestOptions,
 ) -> Result<HttpResponse, HttpError> {
-    let client = reqwest::Client::new();
+    let client = client(options)?;
     let mut request = client.post(url).json(body);
 
+    if let Some(timeout) = options.timeout {
+        request = request.timeout(timeout);
+    }
+
     for (key, value) in headers {
         request = request.header(key, value);
     }
@@ -57,8 +78,9 @@ pub async fn post_json_stream<T: Serialize>(
     url: &str,
     body: &T,
     headers: std::collections::HashMap<String, String>,
+    options: &RequestOptions,
 ) -> Result<impl futures::Stream<Item = Result<bytes::Bytes, reqwest::Error>>, HttpError> {
-    let client = reqwest::Client::new();
+    let client = client(options)?;
     let mut request = client.post(url).json(body);
 
     for (key, value) in headers {
@@ -75,3 +97,31 @@ pub async fn post_json_stream<T: Serialize>(
 
     Ok(response.bytes_stream())
 }
+
+fn client(options: &RequestOptions) -> Result<reqwest::Client, HttpError> {
+    if *options == RequestOptions::default() {
+        return Ok(reqwest::Client::new());
+    }
+
+    let mut builder =
+        reqwest::Client::builder().danger_accept_invalid_certs(options.accept_invalid_certs);
+
+    if let Some(timeout) = options.timeout {
+        // Streams may legitimately run for minutes, so only bound the
+        // connection and the gaps between chunks here.
+        builder = builder.connect_timeout(timeout).read_timeout(timeout);
+    }
+
+    if let Some(path) = &options.ca_certificate {
+        let pem = std::fs::read(path).map_err(|error| {
+            HttpError::RequestFailed(format!(
+                "could not read CA certificate {}: {error}",
+                path.display()
+            ))
+        })?;
+
+        builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+    }
+
+    Ok(builder.build()?)
+}
@@ -1,5 +1,5 @@
 // WASM HTTP implementation using reqwest and wasm-streams
-use super::{HttpError, HttpResponse};
+use super::{HttpError, HttpResponse, RequestOptions};
 use bytes::Bytes;
 use futures::StreamExt;
 use serde::Serialize;
@@ -7,8 +7,14 @@ use serde::Serialize;
 pub async fn get_with_headers(
     url: &str,
     headers: std::collections::HashMap<String, String>,
+    options: &RequestOptions,
 ) -> Result<HttpResponse, HttpError> {
+    // TLS is handled by the browser
     let mut client = reqwest::Client::new().get(url);
+
+    if let Some(timeout) = options.timeout {
+        client = client.timeout(timeout);
+    }
     for (key, value) in headers {
         client = client.header(key, value);
     }
@@ -32,8 +38,14 @@ pub async fn post_json_with_headers<T: Serialize>(
     url: &str,
     body: &T,
     headers: std::collections::HashMap<String, String>,
+    options: &RequestOptions,
 ) -> Result<HttpResponse, HttpError> {
+    // TLS is handled by the browser
     let mut client = reqwest::Client::new().post(url).json(body);
+
+    if let Some(timeout) = options.timeout {
+        client = client.timeout(timeout);
+    }
     for (key, value) in headers {
         client = client.header(key, value);
     }
@@ -60,6 +72,7 @@ pub async fn post_json_stream<T: Serialize>(
     url: &str,
     body: &T,
     headers: std::collections::HashMap<String, String>,
+    _options: &RequestOptions,
 ) -> Result<impl futures::Stream<Item = Result<Bytes, String>>, HttpError> {
     let mut client = reqwest::Client::new().post(url).json(body);
     for (key, value) in headers {
@@ -1,41 +1,112 @@
-use once_cell::sync::Lazy;
 use serde::Serialize;
-use std::sync::Mutex;
+use std::time::Instant;
 use sysinfo::System;
 
-static SYSTEM: Lazy<Mutex<System>> = Lazy::new(|| Mutex::new(System::new_all()));
+pub mod history;
+pub mod sysfs;
+
+pub use history::History;
+pub use sysfs::Sysfs;
 
 #[derive(Serialize, Clone, Debug)]
 pub struct SystemTelemetry {
-    pub cpu_temp: f32,     // Degrees Celsius
-    pub battery_level: u8, // 0-100
-    pub memory_used: u64,  // in MB
-    pub memory_total: u64, // in MB
-    pub uptime: u64,       // Seconds
-    pub load_avg: f32,     // 1-minute load
-    pub is_charging: bool,
+    pub cpu_temp: Option<f32>,     // Degrees Celsius
+    pub battery_level: Option<u8>, // 0-100
+    pub memory_used: u64,          // in MB
+    pub memory_total: u64,         // in MB
+    pub uptime: u64,               // Seconds
+    pub load_avg: f32,             // 1-minute load
+    pub is_charging: Option<bool>,
+    pub on_ac: Option<bool>,
+    pub power_draw: Option<f32>, // Watts
+    pub cpu_loads: Vec<f32>,     // Percent, per core
+    pub disk_read: u64,          // Bytes per second
+    pub disk_write: u64,         // Bytes per second
+    pub network_rx: u64,         // Bytes per second
+    pub network_tx: u64,         // Bytes per second
+}
+
+/// Takes [`SystemTelemetry`] snapshots.
+///
+/// Loads and throughputs are measured since the previous snapshot, so the
+/// first one reports them as zero. Each consumer keeps its own monitor, so
+/// one polling often does not shorten the interval of another.
+#[derive(Debug)]
+pub struct Monitor {
+    system: System,
+    sysfs: Sysfs,
+    last: Option<(Instant, sysfs::Counters)>,
 }
 
-impl SystemTelemetry {
-    pub fn snapshot() -> Self {
-        let mut sys = SYSTEM.lock().unwrap();
-        sys.refresh_all();
+impl Default for Monitor {
+    fn default() -> Self {
+        Self::new(Sysfs::default())
+    }
+}
+
+impl Monitor {
+    pub fn new(sysfs: Sysfs) -> Self {
+        Self {
+            system: System::new(),
+            sysfs,
+            last: None,
+        }
+    }
+
+    pub fn snapshot(&mut self) -> SystemTelemetry {
+        // Only what is shown; processes and disks are expensive to list
+        self.system.refresh_memory();
+        self.system.refresh_cpu_usage();
+
+        let counters = self.sysfs.counters();
+        let now = Instant::now();
+
+        let rate = |current: u64, previous: u64, seconds: f64| {
+            if seconds > 0.0 {
+                (current.saturating_sub(previous) as f64 / seconds) as u64
+            } else {
+                0
+            }
+        };
+
+        let (disk_read, disk_write, network_rx, network_tx) = match self.last {
+            Some((then, last)) => {
+                let seconds = now.duration_since(then).as_secs_f64();
+
+                (
+                    rate(counters.disk_read, last.disk_read, seconds),
+                    rate(counters.disk_written, last.disk_written, seconds),
+                    rate(counters.network_received, last.network_received, seconds),
+                    rate(counters.network_sent, last.network_sent, seconds),
+                )
+            }
+            None => (0, 0, 0, 0),
+        };
 
-        // Traits seem to be missing or version is weird,
-        // using what we know about System struct for now.
-        let memory_total = sys.total_memory() / 1024 / 1024; // Bytes -> MB
-        let memory_used = sys.used_memory() / 1024 / 1024; // Bytes -> MB
+        self.last = Some((now, counters));
 
-        let load = System::load_average().one as f32;
+        let power = self.sysfs.power();
 
         SystemTelemetry {
-            cpu_temp: 0.0,
-            battery_level: 100,
-            memory_used,
-            memory_total,
+            cpu_temp: self.sysfs.cpu_temperature(),
+            battery_level: power.battery_level,
+            memory_used: self.system.used_memory() / 1024 / 1024, // Bytes -> MB
+            memory_total: self.system.total_memory() / 1024 / 1024, // Bytes -> MB
             uptime: System::uptime(),
-            load_avg: load,
-            is_charging: true,
+            load_avg: System::load_average().one as f32,
+            is_charging: power.is_charging,
+            on_ac: power.on_ac,
+            power_draw: power.watts,
+            cpu_loads: self
+                .system
+                .cpus()
+                .iter()
+                .map(|cpu| cpu.cpu_usage())
+                .collect(),
+            disk_read,
+            disk_write,
+            network_rx,
+            network_tx,
         }
     }
 }
@@ -0,0 +1,466 @@
+//! What the machine was doing lately.
+//!
+//! [`History`] keeps every second of the last ten minutes and every minute
+//! of the last day, so graphs can be drawn and questions like "what was
+//! slowing my machine down this morning" answered. It also watches the
+//! samples for [`Rule`]s like "memory above 90% for a minute".
+use crate::kernel::SystemTelemetry;
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, VecDeque};
+
+/// Seconds kept at full resolution.
+const SECONDS: usize = 10 * 60;
+
+/// Minutes kept once averaged.
+const MINUTES: usize = 24 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Mean load of all cores, in percent.
+    Cpu,
+    /// Used memory, in percent.
+    Memory,
+    Load,
+    Temperature,
+    Battery,
+    Power,
+    DiskRead,
+    DiskWrite,
+    NetworkRx,
+    NetworkTx,
+}
+
+impl Metric {
+    pub const ALL: &'static [Self] = &[
+        Self::Cpu,
+        Self::Memory,
+        Self::Load,
+        Self::Temperature,
+        Self::Battery,
+        Self::Power,
+        Self::DiskRead,
+        Self::DiskWrite,
+        Self::NetworkRx,
+        Self::NetworkTx,
+    ];
+
+    fn of(self, telemetry: &SystemTelemetry) -> Option<f32> {
+        match self {
+            Self::Cpu => (!telemetry.cpu_loads.is_empty()).then(|| {
+                telemetry.cpu_loads.iter().sum::<f32>() / telemetry.cpu_loads.len() as f32
+            }),
+            Self::Memory => (telemetry.memory_total > 0)
+                .then(|| telemetry.memory_used as f32 / telemetry.memory_total as f32 * 100.0),
+            Self::Load => Some(telemetry.load_avg),
+            Self::Temperature => telemetry.cpu_temp,
+            Self::Battery => telemetry.battery_level.map(f32::from),
+            Self::Power => telemetry.power_draw,
+            Self::DiskRead => Some(telemetry.disk_read as f32),
+            Self::DiskWrite => Some(telemetry.disk_write as f32),
+            Self::NetworkRx => Some(telemetry.network_rx as f32),
+            Self::NetworkTx => Some(telemetry.network_tx as f32),
+        }
+    }
+}
+
+/// The metrics of a moment; missing sensors are left out.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Point {
+    pub time: DateTime<Utc>,
+    pub values: BTreeMap<Metric, f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    Second,
+    Minute,
+}
+
+/// Filters for [`History::query`]. Points are returned oldest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Query {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Every metric if empty.
+    pub metrics: Vec<Metric>,
+    /// Seconds if they still cover `since`, minutes otherwise.
+    pub resolution: Option<Resolution>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Series {
+    pub resolution: Resolution,
+    pub points: Vec<Point>,
+}
+
+/// Fires when a metric stays past a threshold for a while.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub metric: Metric,
+    pub condition: Condition,
+    /// How long the condition must hold, in seconds.
+    pub duration: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    Above(f32),
+    Below(f32),
+}
+
+impl Condition {
+    fn holds(self, value: f32) -> bool {
+        match self {
+            Self::Above(threshold) => value > threshold,
+            Self::Below(threshold) => value < threshold,
+        }
+    }
+}
+
+impl Rule {
+    pub fn new(
+        name: impl Into<String>,
+        metric: Metric,
+        condition: Condition,
+        duration: u64,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            metric,
+            condition,
+            duration,
+        }
+    }
+
+    /// Memory, heat and battery trouble.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::new(
+                "Memory is almost full",
+                Metric::Memory,
+                Condition::Above(90.0),
+                60,
+            ),
+            Self::new(
+                "The CPU is busy",
+                Metric::Cpu,
+                Condition::Above(95.0),
+                5 * 60,
+            ),
+            Self::new(
+                "The CPU is overheating",
+                Metric::Temperature,
+                Condition::Above(90.0),
+                30,
+            ),
+            Self::new(
+                "The battery is low",
+                Metric::Battery,
+                Condition::Below(10.0),
+                0,
+            ),
+        ]
+    }
+}
+
+/// A [`Rule`] that started or stopped firing.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub rule: Rule,
+    pub is_firing: bool,
+    pub value: f32,
+    /// When the condition started holding.
+    pub since: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct History {
+    seconds: VecDeque<Point>,
+    minutes: VecDeque<Point>,
+    /// The samples of the minute in progress.
+    minute: Vec<Point>,
+    rules: Vec<(Rule, Watch)>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Watch {
+    since: Option<DateTime<Utc>>,
+    is_firing: bool,
+}
+
+impl History {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self {
+            seconds: VecDeque::with_capacity(SECONDS),
+            minutes: VecDeque::with_capacity(MINUTES),
+            minute: Vec::new(),
+            rules: rules
+                .into_iter()
+                .map(|rule| (rule, Watch::default()))
+                .collect(),
+        }
+    }
+
+    /// Adds a sample and returns the alerts it starts or stops.
+    pub fn record(&mut self, time: DateTime<Utc>, telemetry: &SystemTelemetry) -> Vec<Alert> {
+        let point = Point {
+            time,
+            values: Metric::ALL
+                .iter()
+                .filter_map(|metric| Some((*metric, metric.of(telemetry)?)))
+                .collect(),
+        };
+
+        let minute = truncate(time);
+
+        if self
+            .minute
+            .first()
+            .is_some_and(|first| truncate(first.time) != minute)
+        {
+            let samples = std::mem::take(&mut self.minute);
+
+            push(&mut self.minutes, average(&samples), MINUTES);
+        }
+
+        self.minute.push(point.clone());
+        push(&mut self.seconds, point.clone(), SECONDS);
+
+        self.rules
+            .iter_mut()
+            .filter_map(|(rule, watch)| {
+                let value = point.values.get(&rule.metric).copied();
+
+                match value.filter(|value| rule.condition.holds(*value)) {
+                    Some(value) => {
+                        let since = *watch.since.get_or_insert(time);
+                        let has_lasted = time - since >= Duration::seconds(rule.duration as i64);
+
+                        (has_lasted && !watch.is_firing).then(|| {
+                            watch.is_firing = true;
+
+                            Alert {
+                                rule: rule.clone(),
+                                is_firing: true,
+                                value,
+                                since,
+                            }
+                        })
+                    }
+                    None => {
+                        let since = watch.since.take()?;
+
+                        watch.is_firing.then(|| {
+                            watch.is_firing = false;
+
+                            Alert {
+                                rule: rule.clone(),
+                                is_firing: false,
+                                value: value.unwrap_or(f32::NAN),
+                                since,
+                            }
+                        })
+                    }
+                }
+            })
+            .collect()
+    }
+
+    pub fn query(&self, query: &Query) -> Series {
+        let resolution = query.resolution.unwrap_or_else(|| {
+            let is_covered = match (query.since, self.seconds.front()) {
+                (Some(since), Some(oldest)) => since >= oldest.time,
+                (None, _) => false,
+                (Some(_), None) => true,
+            };
+
+            if is_covered {
+                Resolution::Second
+            } else {
+                Resolution::Minute
+            }
+        });
+
+        let points: Vec<_> = match resolution {
+            Resolution::Second => self.seconds.iter().cloned().collect(),
+            Resolution::Minute => self
+                .minutes
+                .iter()
+                .cloned()
+                .chain((!self.minute.is_empty()).then(|| average(&self.minute)))
+                .collect(),
+        };
+
+        let points = points
+            .into_iter()
+            .filter(|point| {
+                query.since.is_none_or(|since| point.time >= since)
+                    && query.until.is_none_or(|until| point.time <= until)
+            })
+            .map(|mut point| {
+                if !query.metrics.is_empty() {
+                    point
+                        .values
+                        .retain(|metric, _| query.metrics.contains(metric));
+                }
+
+                point
+            })
+            .collect();
+
+        Series { resolution, points }
+    }
+
+    /// The rules that are firing right now.
+    pub fn alerts(&self) -> Vec<&Rule> {
+        self.rules
+            .iter()
+            .filter(|(_, watch)| watch.is_firing)
+            .map(|(rule, _)| rule)
+            .collect()
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(Rule::defaults())
+    }
+}
+
+fn push(ring: &mut VecDeque<Point>, point: Point, capacity: usize) {
+    if ring.len() == capacity {
+        let _ = ring.pop_front();
+    }
+
+    ring.push_back(point);
+}
+
+fn truncate(time: DateTime<Utc>) -> DateTime<Utc> {
+    time.duration_trunc(Duration::minutes(1)).unwrap_or(time)
+}
+
+/// The mean of every metric over some samples, at the start of their minute.
+fn average(samples: &[Point]) -> Point {
+    let mut sums: BTreeMap<Metric, (f32, usize)> = BTreeMap::new();
+
+    for sample in samples {
+        for (metric, value) in &sample.values {
+            let (sum, count) = sums.entry(*metric).or_default();
+
+            *sum += value;
+            *count += 1;
+        }
+    }
+
+    Point {
+        time: samples
+            .first()
+            .map_or_else(Utc::now, |sample| truncate(sample.time)),
+        values: sums
+            .into_iter()
+            .map(|(metric, (sum, count))| (metric, sum / count as f32))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn telemetry(memory_used: u64) -> SystemTelemetry {
+        SystemTelemetry {
+            cpu_temp: None,
+            battery_level: None,
+            memory_used,
+            memory_total: 1024,
+            uptime: 0,
+            load_avg: 1.0,
+            is_charging: None,
+            on_ac: None,
+            power_draw: None,
+            cpu_loads: vec![10.0, 30.0],
+            disk_read: 0,
+            disk_write: 0,
+            network_rx: 0,
+            network_tx: 0,
+        }
+    }
+
+    #[test]
+    fn test_history() {
+        let start: DateTime<Utc> = "2026-03-01T08:00:00Z".parse().unwrap();
+        let mut history = History::new(vec![Rule::new(
+            "Memory is almost full",
+            Metric::Memory,
+            Condition::Above(90.0),
+            60,
+        )]);
+
+        let mut alerts = Vec::new();
+
+        // Half an hour at 50% memory, then 2 minutes at 97% and one back at 50%
+        for second in 0..33 * 60 {
+            let memory = if (30 * 60..32 * 60).contains(&second) {
+                992
+            } else {
+                512
+            };
+
+            let time = start + Duration::seconds(second);
+
+            alerts.extend(
+                history
+                    .record(time, &telemetry(memory))
+                    .into_iter()
+                    .map(|alert| (time, alert.is_firing)),
+            );
+        }
+
+        assert_eq!(
+            alerts,
+            [
+                (start + Duration::seconds(31 * 60), true),
+                (start + Duration::seconds(32 * 60), false),
+            ]
+        );
+        assert!(history.alerts().is_empty());
+
+        // The last ten minutes are kept by the second
+        let recent = history.query(&Query {
+            since: Some(start + Duration::minutes(25)),
+            metrics: vec![Metric::Memory],
+            ..Query::default()
+        });
+
+        assert_eq!(recent.resolution, Resolution::Second);
+        assert_eq!(recent.points.len(), 8 * 60);
+        assert_eq!(
+            recent.points[0].values,
+            BTreeMap::from([(Metric::Memory, 50.0)])
+        );
+
+        // Older ones by the minute
+        let morning = history.query(&Query {
+            since: Some(start),
+            until: Some(start + Duration::minutes(31)),
+            metrics: vec![Metric::Cpu, Metric::Memory],
+            ..Query::default()
+        });
+
+        assert_eq!(morning.resolution, Resolution::Minute);
+        assert_eq!(morning.points.len(), 32);
+        assert_eq!(morning.points[30].time, start + Duration::minutes(30));
+        assert_eq!(
+            morning.points[30].values,
+            BTreeMap::from([(Metric::Cpu, 20.0), (Metric::Memory, 96.875)])
+        );
+    }
+}
@@ -0,0 +1,362 @@
+//! Sensors, batteries and I/O counters exposed by Linux under `/sys`.
+//!
+//! Everything is optional: machines without a battery, virtual machines
+//! without thermal zones and containers without `/sys` at all just read as
+//! `None`.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Reads `/sys`, or a copy of it somewhere else.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sysfs {
+    root: PathBuf,
+}
+
+/// The batteries and chargers of the machine.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Power {
+    /// Combined charge of the system batteries, 0-100.
+    pub battery_level: Option<u8>,
+    pub is_charging: Option<bool>,
+    /// Whether a charger is plugged in.
+    pub on_ac: Option<bool>,
+    /// What the batteries are drawing, or being charged with, in watts.
+    pub watts: Option<f32>,
+}
+
+/// Cumulative I/O counters, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Counters {
+    pub disk_read: u64,
+    pub disk_written: u64,
+    pub network_received: u64,
+    pub network_sent: u64,
+}
+
+/// Hardware monitoring drivers that report the CPU package.
+const CPU_SENSORS: &[&str] = &["coretemp", "k10temp", "zenpower", "cpu_thermal"];
+
+/// Thermal zones that belong to the CPU, best first.
+const CPU_ZONES: &[&str] = &[
+    "x86_pkg_temp",
+    "cpu-thermal",
+    "cpu_thermal",
+    "cpu0-thermal",
+    "soc_thermal",
+    "acpitz",
+];
+
+/// Block devices that sit on top of other disks or live in memory.
+const VIRTUAL_DISKS: &[&str] = &["loop", "ram", "zram", "dm-", "md"];
+
+/// `/sys/block/*/stat` counts 512-byte sectors, whatever the disk.
+const SECTOR_SIZE: u64 = 512;
+
+impl Sysfs {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The CPU temperature, in degrees Celsius.
+    pub fn cpu_temperature(&self) -> Option<f32> {
+        self.hwmon_temperature()
+            .or_else(|| self.thermal_zone_temperature())
+    }
+
+    pub fn power(&self) -> Power {
+        let mut power = Power::default();
+
+        let mut energy = 0.0;
+        let mut energy_full = 0.0;
+        let mut capacities = Vec::new();
+
+        for supply in self.entries("class/power_supply") {
+            match read(&supply.join("type")).as_deref() {
+                Some("Mains") | Some("USB") => {
+                    if let Some(online) = number(&supply.join("online")) {
+                        power.on_ac = Some(power.on_ac.unwrap_or(false) || online > 0.0);
+                    }
+                }
+                Some("Battery") => {
+                    // Mice and headsets report their batteries too
+                    if read(&supply.join("scope")).as_deref() == Some("Device") {
+                        continue;
+                    }
+
+                    if let Some(status) = read(&supply.join("status")) {
+                        let is_charging = status == "Charging";
+
+                        power.is_charging = Some(power.is_charging.unwrap_or(false) || is_charging);
+                    }
+
+                    if let Some(capacity) = number(&supply.join("capacity")) {
+                        capacities.push(capacity);
+                    }
+
+                    if let (Some(now), Some(full)) = (
+                        number(&supply.join("energy_now"))
+                            .or_else(|| number(&supply.join("charge_now"))),
+                        number(&supply.join("energy_full"))
+                            .or_else(|| number(&supply.join("charge_full"))),
+                    ) {
+                        energy += now;
+                        energy_full += full;
+                    }
+
+                    // Microwatts, or microamperes times microvolts
+                    let watts = number(&supply.join("power_now"))
+                        .map(|power| power / 1e6)
+                        .or_else(|| {
+                            let current = number(&supply.join("current_now"))?;
+                            let voltage = number(&supply.join("voltage_now"))?;
+
+                            Some(current * voltage / 1e12)
+                        });
+
+                    if let Some(watts) = watts {
+                        power.watts = Some(power.watts.unwrap_or_default() + watts.abs() as f32);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let level = if energy_full > 0.0 {
+            Some(energy / energy_full * 100.0)
+        } else if !capacities.is_empty() {
+            Some(capacities.iter().sum::<f64>() / capacities.len() as f64)
+        } else {
+            None
+        };
+
+        power.battery_level = level.map(|level| level.round().clamp(0.0, 100.0) as u8);
+        power
+    }
+
+    /// How much every physical disk and network interface has moved so far.
+    pub fn counters(&self) -> Counters {
+        let mut counters = Counters::default();
+
+        for disk in self.entries("block") {
+            let is_virtual = disk
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_none_or(|name| VIRTUAL_DISKS.iter().any(|prefix| name.starts_with(prefix)));
+
+            if is_virtual {
+                continue;
+            }
+
+            let Some(stat) = read(&disk.join("stat")) else {
+                continue;
+            };
+
+            let fields: Vec<u64> = stat
+                .split_whitespace()
+                .map(|field| field.parse().unwrap_or_default())
+                .collect();
+
+            if let (Some(read), Some(written)) = (fields.get(2), fields.get(6)) {
+                counters.disk_read += read * SECTOR_SIZE;
+                counters.disk_written += written * SECTOR_SIZE;
+            }
+        }
+
+        for interface in self.entries("class/net") {
+            if interface.file_name().is_some_and(|name| name == "lo") {
+                continue;
+            }
+
+            let statistics = interface.join("statistics");
+
+            counters.network_received +=
+                number(&statistics.join("rx_bytes")).unwrap_or_default() as u64;
+            counters.network_sent +=
+                number(&statistics.join("tx_bytes")).unwrap_or_default() as u64;
+        }
+
+        counters
+    }
+
+    fn hwmon_temperature(&self) -> Option<f32> {
+        self.entries("class/hwmon")
+            .into_iter()
+            .filter(|hwmon| {
+                read(&hwmon.join("name")).is_some_and(|name| CPU_SENSORS.contains(&name.as_str()))
+            })
+            .find_map(|hwmon| {
+                // The first input is the package, or the hottest die
+                number(&hwmon.join("temp1_input")).map(|millidegrees| millidegrees as f32 / 1000.0)
+            })
+    }
+
+    fn thermal_zone_temperature(&self) -> Option<f32> {
+        let zones: Vec<_> = self
+            .entries("class/thermal")
+            .into_iter()
+            .filter(|zone| {
+                zone.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("thermal_zone"))
+            })
+            .filter_map(|zone| Some((read(&zone.join("type"))?, zone)))
+            .collect();
+
+        CPU_ZONES.iter().find_map(|kind| {
+            zones
+                .iter()
+                .filter(|(zone_kind, _)| zone_kind == kind)
+                .find_map(|(_, zone)| number(&zone.join("temp")))
+                .map(|millidegrees| millidegrees as f32 / 1000.0)
+        })
+    }
+
+    /// The entries of a directory, sorted so the order is stable.
+    fn entries(&self, directory: &str) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(self.root.join(directory)) else {
+            return Vec::new();
+        };
+
+        let mut entries: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+        entries.sort();
+        entries
+    }
+}
+
+impl Default for Sysfs {
+    fn default() -> Self {
+        Self::new("/sys")
+    }
+}
+
+fn read(path: &Path) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|content| content.trim().to_owned())
+}
+
+fn number(path: &Path) -> Option<f64> {
+    read(path)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fake `/sys`, removed when dropped.
+    struct Fixture {
+        root: PathBuf,
+        sysfs: Sysfs,
+    }
+
+    impl std::ops::Deref for Fixture {
+        type Target = Sysfs;
+
+        fn deref(&self) -> &Sysfs {
+            &self.sysfs
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn fixture(files: &[(&str, &str)]) -> Fixture {
+        let root = std::env::temp_dir().join(format!("peak-sysfs-{}", uuid::Uuid::new_v4()));
+
+        for (path, content) in files {
+            let path = root.join(path);
+
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, format!("{content}\n")).unwrap();
+        }
+
+        Fixture {
+            sysfs: Sysfs::new(&root),
+            root,
+        }
+    }
+
+    #[test]
+    fn test_laptop() {
+        let sysfs = fixture(&[
+            ("class/hwmon/hwmon0/name", "acpitz"),
+            ("class/hwmon/hwmon0/temp1_input", "27800"),
+            ("class/hwmon/hwmon3/name", "coretemp"),
+            ("class/hwmon/hwmon3/temp1_input", "54000"),
+            ("class/thermal/thermal_zone0/type", "x86_pkg_temp"),
+            ("class/thermal/thermal_zone0/temp", "53000"),
+            ("class/power_supply/AC/type", "Mains"),
+            ("class/power_supply/AC/online", "0"),
+            ("class/power_supply/BAT0/type", "Battery"),
+            ("class/power_supply/BAT0/status", "Discharging"),
+            ("class/power_supply/BAT0/capacity", "80"),
+            ("class/power_supply/BAT0/energy_now", "40000000"),
+            ("class/power_supply/BAT0/energy_full", "50000000"),
+            ("class/power_supply/BAT0/power_now", "7500000"),
+            ("class/power_supply/BAT1/type", "Battery"),
+            ("class/power_supply/BAT1/status", "Discharging"),
+            ("class/power_supply/BAT1/energy_now", "10000000"),
+            ("class/power_supply/BAT1/energy_full", "50000000"),
+            ("class/power_supply/BAT1/current_now", "500000"),
+            ("class/power_supply/BAT1/voltage_now", "11000000"),
+            ("class/power_supply/hidpp_battery_0/type", "Battery"),
+            ("class/power_supply/hidpp_battery_0/scope", "Device"),
+            ("class/power_supply/hidpp_battery_0/status", "Charging"),
+            (
+                "block/nvme0n1/stat",
+                "  1000 0 2000 0   3000 0 4000 0 0 0 0 0 0 0 0 0 0",
+            ),
+            ("block/loop0/stat", "9 0 9 0 9 0 9 0 0 0 0"),
+            ("class/net/lo/statistics/rx_bytes", "999"),
+            ("class/net/lo/statistics/tx_bytes", "999"),
+            ("class/net/wlan0/statistics/rx_bytes", "1500"),
+            ("class/net/wlan0/statistics/tx_bytes", "500"),
+        ]);
+
+        assert_eq!(sysfs.cpu_temperature(), Some(54.0));
+
+        assert_eq!(
+            sysfs.power(),
+            Power {
+                battery_level: Some(50),
+                is_charging: Some(false),
+                on_ac: Some(false),
+                watts: Some(13.0),
+            }
+        );
+
+        assert_eq!(
+            sysfs.counters(),
+            Counters {
+                disk_read: 2000 * 512,
+                disk_written: 4000 * 512,
+                network_received: 1500,
+                network_sent: 500,
+            }
+        );
+    }
+
+    #[test]
+    fn test_missing() {
+        let sysfs = fixture(&[
+            ("class/thermal/thermal_zone0/type", "iwlwifi_1"),
+            ("class/thermal/thermal_zone0/temp", "40000"),
+            ("class/thermal/thermal_zone1/type", "acpitz"),
+            ("class/thermal/thermal_zone1/temp", "not a number"),
+            ("class/thermal/thermal_zone2/type", "cpu-thermal"),
+            ("class/thermal/thermal_zone2/temp", "61500"),
+        ]);
+
+        assert_eq!(sysfs.cpu_temperature(), Some(61.5));
+        assert_eq!(sysfs.power(), Power::default());
+        assert_eq!(sysfs.counters(), Counters::default());
+
+        let nothing = Sysfs::new("/nonexistent/sys");
+
+        assert_eq!(nothing.cpu_temperature(), None);
+        assert_eq!(nothing.power(), Power::default());
+    }
+}
@@ -1,3 +1,7 @@
+#[cfg(feature = "native")]
+pub mod agent;
+#[cfg(feature = "native")]
+pub mod audit;
 pub mod brain;
 pub mod http;
 
@@ -7,6 +11,9 @@ pub mod llm;
 #[cfg(feature = "native")]
 pub mod mcp;
 #[cfg(feature = "native")]
+pub mod policy;
+pub mod schema;
+#[cfg(feature = "native")]
 pub mod steam;
 #[cfg(feature = "native")]
 pub mod terminal;
@@ -1,24 +1,248 @@
+use crate::brain::format::Format;
+use crate::http::{HttpClient, RequestOptions};
+
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ModelProvider {
     Ollama,
     LlamaCpp,
     OpenRouter,
+    /// Any server speaking the OpenAI chat completions API, like vLLM,
+    /// LM Studio or LocalAI.
+    OpenAiCompatible,
+}
+
+impl ModelProvider {
+    pub const ALL: &'static [Self] = &[
+        Self::Ollama,
+        Self::LlamaCpp,
+        Self::OpenRouter,
+        Self::OpenAiCompatible,
+    ];
+
+    pub fn slug(self) -> &'static str {
+        match self {
+            Self::Ollama => "ollama",
+            Self::LlamaCpp => "llama-cpp",
+            Self::OpenRouter => "openrouter",
+            Self::OpenAiCompatible => "openai-compatible",
+        }
+    }
+
+    pub fn from_slug(slug: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|provider| provider.slug() == slug)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Ollama => "Ollama",
+            Self::LlamaCpp => "Llama.cpp",
+            Self::OpenRouter => "OpenRouter",
+            Self::OpenAiCompatible => "OpenAI-compatible server",
+        }
+    }
+
+    /// Where the provider listens when nothing else is configured.
+    pub fn default_base_url(self) -> &'static str {
+        match self {
+            Self::Ollama => "http://localhost:11434",
+            Self::LlamaCpp => "http://localhost:8080/v1",
+            Self::OpenRouter => "https://openrouter.ai/api/v1",
+            Self::OpenAiCompatible => "http://localhost:8000/v1",
+        }
+    }
+}
+
+/// Where and how to reach a provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    /// Base URL of the API, e.g. `http://gpu-box.lan:11434` for Ollama or
+    /// `http://localhost:1234/v1` for an OpenAI-compatible server.
+    pub base_url: String,
+    /// Extra headers sent with every request.
+    pub headers: BTreeMap<String, String>,
+    pub api_key: Option<String>,
+    pub timeout: Option<Duration>,
+    pub accept_invalid_certs: bool,
+    pub ca_certificate: Option<PathBuf>,
+}
+
+impl Endpoint {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            headers: BTreeMap::new(),
+            api_key: None,
+            timeout: None,
+            accept_invalid_certs: false,
+            ca_certificate: None,
+        }
+    }
+
+    pub fn default_for(provider: ModelProvider) -> Self {
+        Self::new(provider.default_base_url())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    /// A URL outside of the versioned API, like llama-server's `/props`.
+    fn root_url(&self, path: &str) -> String {
+        let base_url = self.base_url.trim_end_matches('/');
+
+        format!(
+            "{}/{}",
+            base_url.strip_suffix("/v1").unwrap_or(base_url),
+            path
+        )
+    }
+
+    fn options(&self) -> RequestOptions {
+        RequestOptions {
+            timeout: self.timeout,
+            accept_invalid_certs: self.accept_invalid_certs,
+            ca_certificate: self.ca_certificate.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: String,
     pub content: String,
+    /// Calls requested by the model in an `assistant` message.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// The call a `tool` message answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// The tool that produced a `tool` message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Message {
+    pub fn new(role: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            role: role.into(),
+            content: content.into(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+            name: None,
+        }
+    }
+
+    pub fn tool_result(call: &ToolCall, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: Some(call.id.clone()),
+            name: Some(call.name.clone()),
+            ..Self::new("tool", content)
+        }
+    }
+
+    fn to_openai(&self) -> Value {
+        let mut message = json!({ "role": self.role, "content": self.content });
+
+        if !self.tool_calls.is_empty() {
+            message["tool_calls"] = self
+                .tool_calls
+                .iter()
+                .map(|call| {
+                    json!({
+                        "id": call.id,
+                        "type": "function",
+                        "function": {
+                            "name": call.name,
+                            // OpenAI expects the arguments as a JSON string
+                            "arguments": call.arguments.to_string(),
+                        }
+                    })
+                })
+                .collect();
+        }
+
+        if let Some(id) = &self.tool_call_id {
+            message["tool_call_id"] = json!(id);
+        }
+
+        message
+    }
+
+    fn to_ollama(&self) -> Value {
+        let mut message = json!({ "role": self.role, "content": self.content });
+
+        if !self.tool_calls.is_empty() {
+            message["tool_calls"] = self
+                .tool_calls
+                .iter()
+                .map(|call| {
+                    json!({
+                        "function": { "name": call.name, "arguments": call.arguments }
+                    })
+                })
+                .collect();
+        }
+
+        if let Some(name) = &self.name {
+            message["tool_name"] = json!(name);
+        }
+
+        message
+    }
+}
+
+/// A function the model may call, described by a JSON schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+}
+
+impl ToolDefinition {
+    fn encode(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.parameters,
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// A piece of a streamed completion.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Chunk {
+    Content(String),
+    /// A complete tool call; emitted once all of its fragments arrived.
+    ToolCall(ToolCall),
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct LlmClient {
     provider: ModelProvider,
     model: String,
-    api_key: Option<String>,
+    endpoint: Endpoint,
 }
 
 impl LlmClient {
@@ -26,10 +250,42 @@ impl LlmClient {
         Self {
             provider,
             model,
-            api_key,
+            endpoint: Endpoint {
+                api_key,
+                ..Endpoint::default_for(provider)
+            },
+        }
+    }
+
+    pub fn with_endpoint(provider: ModelProvider, model: String, endpoint: Endpoint) -> Self {
+        Self {
+            provider,
+            model,
+            endpoint,
         }
     }
 
+    /// Builds a client from the `[llm]` section of the settings.
+    pub fn from_settings(settings: &crate::brain::settings::Llm) -> Self {
+        Self::with_endpoint(
+            settings.provider,
+            settings.model.clone(),
+            settings.endpoint.clone(),
+        )
+    }
+
+    /// Builds the client set up in the settings, if they have an `[llm]`
+    /// section.
+    #[cfg(feature = "native")]
+    pub async fn configured() -> Option<Self> {
+        let settings = tokio::task::spawn_blocking(crate::brain::Settings::fetch)
+            .await
+            .ok()?
+            .ok()?;
+
+        settings.llm.as_ref().map(Self::from_settings)
+    }
+
     pub fn model(&self) -> &str {
         &self.model
     }
@@ -38,14 +294,45 @@ impl LlmClient {
         self.provider
     }
 
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
     pub async fn chat(&self, messages: Vec<Message>) -> Result<String, String> {
         match self.provider {
-            ModelProvider::Ollama => self.chat_ollama(messages).await,
-            ModelProvider::LlamaCpp => self.chat_llamacpp(messages).await,
-            ModelProvider::OpenRouter => self.chat_openrouter(messages).await,
+            ModelProvider::Ollama => self.chat_ollama(messages, None).await,
+            ModelProvider::LlamaCpp
+            | ModelProvider::OpenRouter
+            | ModelProvider::OpenAiCompatible => self.chat_openai(messages, None).await,
         }
     }
 
+    /// Asks for a reply in the given [`Format`], then checks and deserializes
+    /// it.
+    pub async fn chat_json<T: DeserializeOwned>(
+        &self,
+        messages: Vec<Message>,
+        format: &Format,
+    ) -> Result<T, String> {
+        let content = match self.provider {
+            ModelProvider::Ollama => self.chat_ollama(messages, Some(format)).await?,
+            ModelProvider::LlamaCpp
+            | ModelProvider::OpenRouter
+            | ModelProvider::OpenAiCompatible => self.chat_openai(messages, Some(format)).await?,
+        };
+
+        let value: Value = serde_json::from_str(&content).map_err(|error| {
+            format!(
+                "{} replied with invalid JSON: {error}",
+                self.provider.name()
+            )
+        })?;
+
+        format.validate(&value)?;
+
+        serde_json::from_value(value).map_err(|error| error.to_string())
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     pub fn chat_stream(
         &self,
@@ -53,50 +340,28 @@ impl LlmClient {
     ) -> impl futures::Stream<Item = Result<String, String>> + Send {
         let client = self.clone();
         async_stream::stream! {
+            use futures::StreamExt;
+
             match client.provider {
                 ModelProvider::Ollama => {
-                    let stream = client.chat_ollama_stream(messages).await;
-                    match stream {
-                        Ok(s) => {
-                            use futures::StreamExt;
-                            let mut s = Box::pin(s);
-                            while let Some(chunk) = s.next().await {
-                                match chunk {
-                                    Ok(text) => yield Ok(text),
-                                    Err(e) => yield Err(e),
-                                }
-                            }
-                        }
-                        Err(e) => yield Err(e),
-                    }
-                }
-                ModelProvider::LlamaCpp => {
-                    let stream = client.chat_llamacpp_stream(messages).await;
-                    match stream {
+                    match client.chat_ollama_stream(messages).await {
                         Ok(s) => {
-                            use futures::StreamExt;
                             let mut s = Box::pin(s);
                             while let Some(chunk) = s.next().await {
-                                match chunk {
-                                    Ok(text) => yield Ok(text),
-                                    Err(e) => yield Err(e),
-                                }
+                                yield chunk;
                             }
                         }
                         Err(e) => yield Err(e),
                     }
                 }
-                ModelProvider::OpenRouter => {
-                    let stream = client.chat_openrouter_stream(messages).await;
-                    match stream {
+                ModelProvider::LlamaCpp
+                | ModelProvider::OpenRouter
+                | ModelProvider::OpenAiCompatible => {
+                    match client.chat_openai_stream(messages).await {
                         Ok(s) => {
-                            use futures::StreamExt;
                             let mut s = Box::pin(s);
                             while let Some(chunk) = s.next().await {
-                                match chunk {
-                                    Ok(text) => yield Ok(text),
-                                    Err(e) => yield Err(e),
-                                }
+                                yield chunk;
                             }
                         }
                         Err(e) => yield Err(e),
@@ -113,50 +378,28 @@ impl LlmClient {
     ) -> impl futures::Stream<Item = Result<String, String>> {
         let client = self.clone();
         async_stream::stream! {
+            use futures::StreamExt;
+
             match client.provider {
                 ModelProvider::Ollama => {
-                    let stream = client.chat_ollama_stream(messages).await;
-                    match stream {
-                        Ok(s) => {
-                            use futures::StreamExt;
-                            let mut s = Box::pin(s);
-                            while let Some(chunk) = s.next().await {
-                                match chunk {
-                                    Ok(text) => yield Ok(text),
-                                    Err(e) => yield Err(e),
-                                }
-                            }
-                        }
-                        Err(e) => yield Err(e),
-                    }
-                }
-                ModelProvider::LlamaCpp => {
-                    let stream = client.chat_llamacpp_stream(messages).await;
-                    match stream {
+                    match client.chat_ollama_stream(messages).await {
                         Ok(s) => {
-                            use futures::StreamExt;
                             let mut s = Box::pin(s);
                             while let Some(chunk) = s.next().await {
-                                match chunk {
-                                    Ok(text) => yield Ok(text),
-                                    Err(e) => yield Err(e),
-                                }
+                                yield chunk;
                             }
                         }
                         Err(e) => yield Err(e),
                     }
                 }
-                ModelProvider::OpenRouter => {
-                    let stream = client.chat_openrouter_stream(messages).await;
-                    match stream {
+                ModelProvider::LlamaCpp
+                | ModelProvider::OpenRouter
+                | ModelProvider::OpenAiCompatible => {
+                    match client.chat_openai_stream(messages).await {
                         Ok(s) => {
-                            use futures::StreamExt;
                             let mut s = Box::pin(s);
                             while let Some(chunk) = s.next().await {
-                                match chunk {
-                                    Ok(text) => yield Ok(text),
-                                    Err(e) => yield Err(e),
-                                }
+                                yield chunk;
                             }
                         }
                         Err(e) => yield Err(e),
@@ -166,63 +409,279 @@ impl LlmClient {
         }
     }
 
-    async fn chat_ollama_stream(
+    /// Streams a completion that may call any of the given tools.
+    ///
+    /// Content is yielded as it arrives. Tool calls are yielded whole, after
+    /// the model finished describing them, in both the OpenAI and the Ollama
+    /// wire formats.
+    pub async fn chat_stream_with_tools(
         &self,
-        messages: Vec<Message>,
-    ) -> Result<impl futures::Stream<Item = Result<String, String>>, String> {
-        let url = "http://localhost:11434/api/chat";
-        let body = serde_json::json!({
+        messages: &[Message],
+        tools: &[ToolDefinition],
+    ) -> Result<impl futures::Stream<Item = Result<Chunk, String>>, String> {
+        self.stream_chunks(messages, tools, None).await
+    }
+
+    /// Like [`LlmClient::chat_stream_with_tools`], but constrains the reply
+    /// to `format`, if any. Checking the reply is up to the caller.
+    pub(crate) async fn stream_chunks(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        format: Option<&Format>,
+    ) -> Result<impl futures::Stream<Item = Result<Chunk, String>>, String> {
+        let (url, messages): (_, Vec<_>) = match self.provider {
+            ModelProvider::Ollama => (
+                self.endpoint.url("api/chat"),
+                messages.iter().map(Message::to_ollama).collect(),
+            ),
+            ModelProvider::LlamaCpp
+            | ModelProvider::OpenRouter
+            | ModelProvider::OpenAiCompatible => (
+                self.endpoint.url("chat/completions"),
+                messages.iter().map(Message::to_openai).collect(),
+            ),
+        };
+
+        let mut body = json!({
             "model": self.model,
             "messages": messages,
             "stream": true
         });
 
-        let stream =
-            crate::http::HttpClient::post_json_stream(url, &body, std::collections::HashMap::new())
-                .await
-                .map_err(|e| e.to_string())?;
+        if !tools.is_empty() {
+            body["tools"] = tools.iter().map(ToolDefinition::encode).collect();
+        }
 
-        use futures::StreamExt;
-        let mapped = stream.map(|res| {
-            res.map_err(|e| e.to_string()).map(|bytes| {
-                let s = String::from_utf8_lossy(bytes.as_ref());
-                let mut full_content = String::new();
+        if let (Some(format), Some(fields)) = (format, body.as_object_mut()) {
+            fields.extend(match self.provider {
+                ModelProvider::Ollama => format.ollama(),
+                ModelProvider::LlamaCpp => format.llama(),
+                ModelProvider::OpenRouter | ModelProvider::OpenAiCompatible => format.openai(),
+            });
+        }
 
-                for line in s.lines() {
-                    let line = line.trim();
-                    if line.is_empty() {
-                        continue;
-                    }
+        let stream = HttpClient::post_json_stream_with_options(
+            &url,
+            &body,
+            self.headers()?,
+            &self.endpoint.options(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
 
-                    if let Ok(json) = serde_json::from_str::<Value>(line) {
-                        if let Some(content) = json["message"]["content"].as_str() {
-                            full_content.push_str(content);
+        Ok(async_stream::stream! {
+            use futures::StreamExt;
+
+            let mut stream = Box::pin(stream);
+            let mut parser = ToolStream::default();
+
+            while let Some(bytes) = stream.next().await {
+                match bytes {
+                    Ok(bytes) => {
+                        for chunk in parser.feed(bytes.as_ref()) {
+                            yield Ok(chunk);
                         }
                     }
+                    Err(error) => {
+                        yield Err(error.to_string());
+                        return;
+                    }
                 }
-                full_content
-            })
+            }
+
+            for chunk in parser.finish() {
+                yield Ok(chunk);
+            }
+        })
+    }
+
+    /// Embeds every input with the model, returning one vector per input.
+    pub async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let url = match self.provider {
+            ModelProvider::Ollama => self.endpoint.url("api/embed"),
+            ModelProvider::LlamaCpp
+            | ModelProvider::OpenRouter
+            | ModelProvider::OpenAiCompatible => self.endpoint.url("embeddings"),
+        };
+
+        let body = json!({
+            "model": self.model,
+            "input": inputs,
         });
 
-        Ok(mapped)
+        let res = HttpClient::post_json_with_options(
+            &url,
+            &body,
+            self.headers()?,
+            &self.endpoint.options(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        if res.status != 200 {
+            return Err(format!("{} error: {}", self.provider.name(), res.status));
+        }
+
+        let json: Value = res.json().map_err(|e| e.to_string())?;
+
+        let embeddings = match self.provider {
+            ModelProvider::Ollama => json["embeddings"].as_array().cloned(),
+            ModelProvider::LlamaCpp
+            | ModelProvider::OpenRouter
+            | ModelProvider::OpenAiCompatible => json["data"].as_array().map(|data| {
+                let mut data = data.clone();
+                data.sort_by_key(|item| item["index"].as_u64());
+                data.into_iter()
+                    .map(|item| item["embedding"].clone())
+                    .collect()
+            }),
+        };
+
+        embeddings
+            .filter(|embeddings| embeddings.len() == inputs.len())
+            .and_then(|embeddings| embeddings.iter().map(vector).collect())
+            .ok_or(format!(
+                "Invalid embeddings response from {}",
+                self.provider.name()
+            ))
+    }
+
+    /// The context length the server runs the model with, if it says.
+    pub async fn context_length(&self) -> Option<usize> {
+        let as_length = |value: &Value| value.as_u64().and_then(|n| usize::try_from(n).ok());
+
+        match self.provider {
+            // Only an explicit `num_ctx` is used; Ollama runs models with a
+            // short default context, whatever they were trained with
+            ModelProvider::Ollama => {
+                let json = self
+                    .post(
+                        &self.endpoint.url("api/show"),
+                        &json!({ "model": self.model }),
+                    )
+                    .await?;
+
+                json["parameters"].as_str()?.lines().find_map(|line| {
+                    line.strip_prefix("num_ctx")
+                        .and_then(|length| length.trim().parse().ok())
+                })
+            }
+            ModelProvider::LlamaCpp => {
+                let json = self.get(&self.endpoint.root_url("props")).await?;
+
+                as_length(&json["default_generation_settings"]["n_ctx"])
+            }
+            // vLLM says `max_model_len`, OpenRouter `context_length`
+            ModelProvider::OpenRouter | ModelProvider::OpenAiCompatible => {
+                let json = self.get(&self.endpoint.url("models")).await?;
+
+                let model = json["data"]
+                    .as_array()?
+                    .iter()
+                    .find(|model| model["id"].as_str() == Some(&self.model))?;
+
+                as_length(&model["max_model_len"]).or_else(|| as_length(&model["context_length"]))
+            }
+        }
+    }
+
+    /// Counts the tokens of `text` with the tokenizer of the model, if the
+    /// server has a tokenize endpoint.
+    pub async fn count_tokens(&self, text: &str) -> Option<usize> {
+        let body = match self.provider {
+            ModelProvider::LlamaCpp => json!({ "content": text }),
+            // vLLM
+            ModelProvider::OpenAiCompatible => json!({ "model": self.model, "prompt": text }),
+            ModelProvider::Ollama | ModelProvider::OpenRouter => return None,
+        };
+
+        let json = self
+            .post(&self.endpoint.root_url("tokenize"), &body)
+            .await?;
+
+        json["count"]
+            .as_u64()
+            .and_then(|count| usize::try_from(count).ok())
+            .or_else(|| json["tokens"].as_array().map(Vec::len))
+    }
+
+    /// Fetches JSON, or nothing if the server does not answer with it.
+    async fn get(&self, url: &str) -> Option<Value> {
+        let response =
+            HttpClient::get_with_options(url, self.headers().ok()?, &self.endpoint.options())
+                .await
+                .ok()?;
+
+        (response.status == 200)
+            .then(|| response.json().ok())
+            .flatten()
+    }
+
+    /// Posts JSON, returning the JSON answer if there is one.
+    async fn post(&self, url: &str, body: &Value) -> Option<Value> {
+        let response = HttpClient::post_json_with_options(
+            url,
+            body,
+            self.headers().ok()?,
+            &self.endpoint.options(),
+        )
+        .await
+        .ok()?;
+
+        (response.status == 200)
+            .then(|| response.json().ok())
+            .flatten()
+    }
+
+    fn headers(&self) -> Result<HashMap<String, String>, String> {
+        let mut headers: HashMap<String, String> = self
+            .endpoint
+            .headers
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        match &self.endpoint.api_key {
+            Some(api_key) => {
+                headers.insert("Authorization".to_string(), format!("Bearer {}", api_key));
+            }
+            None if self.provider == ModelProvider::OpenRouter => {
+                return Err("OpenRouter API key required".to_string());
+            }
+            None => {}
+        }
+
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.provider == ModelProvider::OpenRouter {
+            headers.insert("HTTP-Referer".to_string(), "https://peakos.dev".to_string());
+            headers.insert("X-Title".to_string(), "PeakOS Intelligence".to_string());
+        }
+
+        Ok(headers)
     }
 
-    async fn chat_llamacpp_stream(
+    async fn chat_ollama_stream(
         &self,
         messages: Vec<Message>,
     ) -> Result<impl futures::Stream<Item = Result<String, String>>, String> {
-        let url = "http://localhost:8080/v1/chat/completions";
-
+        let url = self.endpoint.url("api/chat");
         let body = serde_json::json!({
             "model": self.model,
             "messages": messages,
             "stream": true
         });
 
-        let stream =
-            crate::http::HttpClient::post_json_stream(url, &body, std::collections::HashMap::new())
-                .await
-                .map_err(|e| e.to_string())?;
+        let stream = HttpClient::post_json_stream_with_options(
+            &url,
+            &body,
+            self.headers()?,
+            &self.endpoint.options(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
 
         use futures::StreamExt;
         let mapped = stream.map(|res| {
@@ -236,16 +695,9 @@ impl LlmClient {
                         continue;
                     }
 
-                    if line.starts_with("data: ") {
-                        let json_str = &line[6..].trim();
-                        if *json_str == "[DONE]" {
-                            continue;
-                        }
-
-                        if let Ok(json) = serde_json::from_str::<Value>(json_str) {
-                            if let Some(content) = json["choices"][0]["delta"]["content"].as_str() {
-                                full_content.push_str(content);
-                            }
+                    if let Ok(json) = serde_json::from_str::<Value>(line) {
+                        if let Some(content) = json["message"]["content"].as_str() {
+                            full_content.push_str(content);
                         }
                     }
                 }
@@ -256,22 +708,18 @@ impl LlmClient {
         Ok(mapped)
     }
 
-    async fn chat_openrouter_stream(
+    async fn chat_openai_stream(
         &self,
         messages: Vec<Message>,
     ) -> Result<impl futures::Stream<Item = Result<String, String>>, String> {
-        let url = "https://openrouter.ai/api/v1/chat/completions";
-        let api_key = self.api_key.as_ref().ok_or("OpenRouter API key required")?;
+        let url = self.endpoint.url("chat/completions");
 
         log::info!(
-            "OpenRouter Request: model={}, messages={}, key_len={}",
+            "{} Request: model={}, messages={}",
+            self.provider.name(),
             self.model,
             messages.len(),
-            api_key.len()
         );
-        if api_key.len() > 5 {
-            log::info!("Key fragment: {}...", &api_key[0..5]);
-        }
 
         let body = serde_json::json!({
             "model": self.model,
@@ -279,19 +727,14 @@ impl LlmClient {
             "stream": true
         });
 
-        let mut headers = std::collections::HashMap::new();
-        headers.insert("Authorization".to_string(), format!("Bearer {}", api_key));
-        headers.insert("Content-Type".to_string(), "application/json".to_string());
-
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            headers.insert("HTTP-Referer".to_string(), "https://peakos.dev".to_string());
-            headers.insert("X-Title".to_string(), "PeakOS Intelligence".to_string());
-        }
-
-        let stream = crate::http::HttpClient::post_json_stream(url, &body, headers)
-            .await
-            .map_err(|e| e.to_string())?;
+        let stream = HttpClient::post_json_stream_with_options(
+            &url,
+            &body,
+            self.headers()?,
+            &self.endpoint.options(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
 
         use futures::StreamExt;
         let mapped = stream.map(|res| {
@@ -305,9 +748,9 @@ impl LlmClient {
                         continue;
                     }
 
-                    if line.starts_with("data: ") {
-                        let json_str = &line[6..].trim();
-                        if *json_str == "[DONE]" {
+                    if let Some(json_str) = line.strip_prefix("data: ") {
+                        let json_str = json_str.trim();
+                        if json_str == "[DONE]" {
                             continue;
                         }
 
@@ -325,17 +768,30 @@ impl LlmClient {
         Ok(mapped)
     }
 
-    async fn chat_ollama(&self, messages: Vec<Message>) -> Result<String, String> {
-        let url = "http://localhost:11434/api/chat";
-        let body = serde_json::json!({
+    async fn chat_ollama(
+        &self,
+        messages: Vec<Message>,
+        format: Option<&Format>,
+    ) -> Result<String, String> {
+        let url = self.endpoint.url("api/chat");
+        let mut body = serde_json::json!({
             "model": self.model,
             "messages": messages,
             "stream": false
         });
 
-        let res = crate::http::HttpClient::post_json(url, &body)
-            .await
-            .map_err(|e| e.to_string())?;
+        if let (Some(format), Some(fields)) = (format, body.as_object_mut()) {
+            fields.extend(format.ollama());
+        }
+
+        let res = HttpClient::post_json_with_options(
+            &url,
+            &body,
+            self.headers()?,
+            &self.endpoint.options(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
 
         if res.status != 200 {
             return Err(format!("Ollama error: {}", res.status));
@@ -349,20 +805,36 @@ impl LlmClient {
             .ok_or("Invalid response format from Ollama".to_string())
     }
 
-    async fn chat_llamacpp(&self, messages: Vec<Message>) -> Result<String, String> {
-        let url = "http://localhost:8080/v1/chat/completions";
-        let body = serde_json::json!({
+    async fn chat_openai(
+        &self,
+        messages: Vec<Message>,
+        format: Option<&Format>,
+    ) -> Result<String, String> {
+        let url = self.endpoint.url("chat/completions");
+        let mut body = serde_json::json!({
             "model": self.model,
             "messages": messages,
             "stream": false
         });
 
-        let res = crate::http::HttpClient::post_json(url, &body)
-            .await
-            .map_err(|e| e.to_string())?;
+        if let (Some(format), Some(fields)) = (format, body.as_object_mut()) {
+            fields.extend(match self.provider {
+                ModelProvider::LlamaCpp => format.llama(),
+                _ => format.openai(),
+            });
+        }
+
+        let res = HttpClient::post_json_with_options(
+            &url,
+            &body,
+            self.headers()?,
+            &self.endpoint.options(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
 
         if res.status != 200 {
-            return Err(format!("Llama.cpp error: {}", res.status));
+            return Err(format!("{} error: {}", self.provider.name(), res.status));
         }
 
         let json: Value = res.json().map_err(|e| e.to_string())?;
@@ -370,41 +842,215 @@ impl LlmClient {
         json["choices"][0]["message"]["content"]
             .as_str()
             .map(|s| s.to_string())
-            .ok_or("Invalid response format from Llama.cpp".to_string())
+            .ok_or(format!(
+                "Invalid response format from {}",
+                self.provider.name()
+            ))
     }
+}
 
-    async fn chat_openrouter(&self, messages: Vec<Message>) -> Result<String, String> {
-        let url = "https://openrouter.ai/api/v1/chat/completions";
-        let api_key = self.api_key.as_ref().ok_or("OpenRouter API key required")?;
+/// Reads an embedding from a JSON array of numbers.
+pub(crate) fn vector(value: &Value) -> Option<Vec<f32>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|n| n.as_f64().map(|n| n as f32))
+        .collect()
+}
 
-        let body = serde_json::json!({
-            "model": self.model,
-            "messages": messages
-        });
+/// Turns the lines of a streamed completion into [`Chunk`]s.
+///
+/// Understands both the OpenAI server-sent events, where the arguments of a
+/// tool call arrive as string fragments spread over many deltas, and the
+/// Ollama JSON lines, where every call arrives whole.
+#[derive(Default)]
+struct ToolStream {
+    buffer: Vec<u8>,
+    calls: BTreeMap<u64, PartialCall>,
+    received: usize,
+}
 
-        let mut headers = std::collections::HashMap::new();
-        headers.insert("Authorization".to_string(), format!("Bearer {}", api_key));
-        headers.insert("Content-Type".to_string(), "application/json".to_string());
+#[derive(Default)]
+struct PartialCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
 
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            headers.insert("HTTP-Referer".to_string(), "https://peakos.dev".to_string());
-            headers.insert("X-Title".to_string(), "PeakOS Intelligence".to_string());
+impl ToolStream {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<Chunk> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut chunks = Vec::new();
+
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            self.line(&String::from_utf8_lossy(&line), &mut chunks);
         }
 
-        let res = crate::http::HttpClient::post_json_with_headers(url, &body, headers)
-            .await
-            .map_err(|e| e.to_string())?;
+        chunks
+    }
 
-        if res.status != 200 {
-            return Err(format!("OpenRouter error: {}", res.status));
+    fn finish(mut self) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+
+        let rest = std::mem::take(&mut self.buffer);
+        self.line(&String::from_utf8_lossy(&rest), &mut chunks);
+
+        for (_, call) in std::mem::take(&mut self.calls) {
+            chunks.push(self.complete(call));
         }
 
-        let json: Value = res.json().map_err(|e| e.to_string())?;
+        chunks
+    }
 
-        json["choices"][0]["message"]["content"]
-            .as_str()
-            .map(|s| s.to_string())
-            .ok_or("Invalid response format from OpenRouter".to_string())
+    fn line(&mut self, line: &str, chunks: &mut Vec<Chunk>) {
+        let line = line.trim();
+        let line = line.strip_prefix("data:").unwrap_or(line).trim();
+
+        if line.is_empty() || line == "[DONE]" {
+            return;
+        }
+
+        let Ok(json) = serde_json::from_str::<Value>(line) else {
+            return;
+        };
+
+        // OpenAI
+        if let Some(delta) = json["choices"].get(0).map(|choice| &choice["delta"]) {
+            if let Some(content) = delta["content"].as_str().filter(|c| !c.is_empty()) {
+                chunks.push(Chunk::Content(content.to_owned()));
+            }
+
+            for fragment in delta["tool_calls"].as_array().into_iter().flatten() {
+                let index = fragment["index"].as_u64().unwrap_or_default();
+                let call = self.calls.entry(index).or_default();
+
+                if let Some(id) = fragment["id"].as_str() {
+                    call.id.push_str(id);
+                }
+
+                if let Some(name) = fragment["function"]["name"].as_str() {
+                    call.name.push_str(name);
+                }
+
+                if let Some(arguments) = fragment["function"]["arguments"].as_str() {
+                    call.arguments.push_str(arguments);
+                }
+            }
+
+            return;
+        }
+
+        // Ollama
+        let message = &json["message"];
+
+        if let Some(content) = message["content"].as_str().filter(|c| !c.is_empty()) {
+            chunks.push(Chunk::Content(content.to_owned()));
+        }
+
+        for call in message["tool_calls"].as_array().into_iter().flatten() {
+            let function = &call["function"];
+
+            let call = PartialCall {
+                id: call["id"].as_str().unwrap_or_default().to_owned(),
+                name: function["name"].as_str().unwrap_or_default().to_owned(),
+                arguments: match &function["arguments"] {
+                    Value::String(arguments) => arguments.clone(),
+                    Value::Null => String::new(),
+                    arguments => arguments.to_string(),
+                },
+            };
+
+            chunks.push(self.complete(call));
+        }
+    }
+
+    fn complete(&mut self, call: PartialCall) -> Chunk {
+        self.received += 1;
+
+        let arguments = if call.arguments.trim().is_empty() {
+            json!({})
+        } else {
+            // Leave malformed arguments as text; schema validation will
+            // reject them and the model gets to see why
+            serde_json::from_str(&call.arguments).unwrap_or(Value::String(call.arguments))
+        };
+
+        Chunk::ToolCall(ToolCall {
+            id: if call.id.is_empty() {
+                format!("call_{}", self.received)
+            } else {
+                call.id
+            },
+            name: call.name,
+            arguments,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_tool_call_fragments() {
+        let mut stream = ToolStream::default();
+
+        let mut chunks = stream.feed(
+            br#"data: {"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_a","function":{"name":"read_dir","arguments":"{\"pa"}}]}}]}
+data: {"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"th\": \"/tmp\"}"}}]}}]}
+data: {"choices":[{"delta":{"con"#,
+        );
+        chunks.extend(stream.feed(b"tent\":\"Done\"}}]}\ndata: [DONE]\n"));
+        chunks.extend(stream.finish());
+
+        assert_eq!(
+            chunks,
+            vec![
+                Chunk::Content("Done".to_owned()),
+                Chunk::ToolCall(ToolCall {
+                    id: "call_a".to_owned(),
+                    name: "read_dir".to_owned(),
+                    arguments: json!({ "path": "/tmp" }),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ollama_tool_calls() {
+        let mut stream = ToolStream::default();
+
+        let mut chunks = stream.feed(
+            br#"{"message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"list_processes","arguments":{}}}]},"done":false}
+{"message":{"role":"assistant","content":"Hi"},"done":true}
+"#,
+        );
+        chunks.extend(stream.finish());
+
+        assert_eq!(
+            chunks,
+            vec![
+                Chunk::ToolCall(ToolCall {
+                    id: "call_1".to_owned(),
+                    name: "list_processes".to_owned(),
+                    arguments: json!({}),
+                }),
+                Chunk::Content("Hi".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_root_url() {
+        assert_eq!(
+            Endpoint::new("http://gpu-box.lan:8000/v1/").root_url("tokenize"),
+            "http://gpu-box.lan:8000/tokenize"
+        );
+        assert_eq!(
+            Endpoint::new("http://gpu-box.lan:11434").root_url("tokenize"),
+            "http://gpu-box.lan:11434/tokenize"
+        );
     }
 }
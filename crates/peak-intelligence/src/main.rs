@@ -1,27 +1,19 @@
-mod mcp;
-mod terminal;
-mod tools;
-mod voice;
-
-#[cfg(feature = "voice")]
-use crate::voice::VoiceManager;
-#[cfg(feature = "voice")]
-use voice::VOICE;
-
-use mcp::{
-    CallToolParams, CallToolResult, JsonRpcRequest, JsonRpcResponse, ListToolsResult, Tool,
-    ToolContent,
+use chrono::Utc;
+use peak_os_intelligence::audit;
+use peak_os_intelligence::brain::{Error, Settings};
+use peak_os_intelligence::kernel::{self, history, History};
+use peak_os_intelligence::mcp::{
+    self, CallToolParams, Context, JsonRpcRequest, JsonRpcResponse, Registry,
 };
-use once_cell::sync::Lazy;
-use peak_os_intelligence::kernel;
-use serde_json::json;
-use terminal::TerminalManager;
+use peak_os_intelligence::policy::{Caller, Policy};
+use peak_os_intelligence::terminal::TerminalManager;
+use peak_os_intelligence::tools;
+use serde_json::{json, Value};
 use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::sync::mpsc;
-use tokio::time::{sleep, Duration};
+use tokio::time::{interval, Duration};
 
-static TERMINAL: Lazy<TerminalManager> = Lazy::new(TerminalManager::new);
-// No longer here
+use std::sync::{Arc, Mutex};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -37,6 +29,30 @@ async fn main() -> anyhow::Result<()> {
 
     let (tx, mut rx) = mpsc::channel::<String>(100);
 
+    // Built-in tools plus any external MCP servers from the settings
+    let settings = match Settings::fetch() {
+        Ok(settings) => settings,
+        Err(Error::IOFailed(error)) if error.kind() == std::io::ErrorKind::NotFound => {
+            Settings::default()
+        }
+        Err(error) => {
+            eprintln!("Settings unreadable, external MCP servers disabled: {error}");
+            Settings::default()
+        }
+    };
+    let mut registry = tools::builtin::registry(Arc::new(TerminalManager::new()));
+    let _servers = mcp::client::mount(&settings.mcp_servers, &mut registry).await;
+
+    let policy = Arc::new(Policy::load());
+    registry.set_policy(policy.clone());
+
+    match audit::Log::open_default() {
+        Ok(log) => registry.set_audit(Arc::new(log)),
+        Err(error) => eprintln!("Audit log unavailable: {error}"),
+    }
+
+    let registry = Arc::new(registry);
+
     // Stdout writer task
     let _stdout_tx = tx.clone();
     tokio::spawn(async move {
@@ -49,21 +65,39 @@ async fn main() -> anyhow::Result<()> {
     });
 
     // --- DEEP CORE TELEMETRY LOOP ---
+    let history = Arc::new(Mutex::new(History::default()));
+
     let telemetry_tx = tx.clone();
+    let telemetry_history = history.clone();
     tokio::spawn(async move {
-        loop {
-            // "The Deep Core Pulse" - 2 seconds
-            sleep(Duration::from_secs(2)).await;
-
-            let snapshot = kernel::SystemTelemetry::snapshot();
-            let notification = json!({
-                "jsonrpc": "2.0",
-                "method": "system/telemetry",
-                "params": snapshot
-            });
+        let mut ticks = interval(Duration::from_secs(1));
+        let mut monitor = kernel::Monitor::default();
+
+        for tick in 0u64.. {
+            // Sampled every second, "The Deep Core Pulse" every 2 seconds
+            let _ = ticks.tick().await;
+
+            let snapshot = monitor.snapshot();
+            let alerts = telemetry_history
+                .lock()
+                .unwrap_or_else(|error| error.into_inner())
+                .record(Utc::now(), &snapshot);
+
+            let notifications = alerts
+                .into_iter()
+                .map(|alert| ("system/alert", json!(alert)))
+                .chain((tick % 2 == 1).then(|| ("system/telemetry", json!(snapshot))));
+
+            for (method, params) in notifications {
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": method,
+                    "params": params
+                });
 
-            if let Ok(msg) = serde_json::to_string(&notification) {
-                let _ = telemetry_tx.send(msg).await;
+                if let Ok(msg) = serde_json::to_string(&notification) {
+                    let _ = telemetry_tx.send(msg).await;
+                }
             }
         }
     });
@@ -72,26 +106,50 @@ async fn main() -> anyhow::Result<()> {
     // Actually, TerminalManager's thread can use a global channel or similar.
     // For now, let's just make it simple.
 
+    let peer = peer();
+
     // Main Loop
     while let Some(line) = reader.next_line().await? {
         if line.trim().is_empty() {
             continue;
         }
 
-        let request: Result<JsonRpcRequest, _> = serde_json::from_str(&line);
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            let err_res = JsonRpcResponse::error(None, -32700, "Parse error".into());
+            if let Ok(err_str) = serde_json::to_string(&err_res) {
+                let _ = tx.send(err_str).await;
+            }
+            continue;
+        };
+
+        // Responses to our own requests, e.g. permission confirmations
+        if message.get("method").is_none() {
+            if let Some(id) = message.get("id") {
+                if !policy.resolve(id, &message) {
+                    eprintln!("Unexpected response: {id}");
+                }
+            }
+            continue;
+        }
+
+        let request: Result<JsonRpcRequest, _> = serde_json::from_value(message);
 
         match request {
             Ok(req) => {
                 let tx_clone = tx.clone();
+                let registry = registry.clone();
+                let history = history.clone();
+                let peer = peer.clone();
                 tokio::spawn(async move {
-                    let response = handle_request(req, tx_clone.clone()).await;
+                    let response =
+                        handle_request(req, &registry, &history, &peer, tx_clone.clone()).await;
                     if let Ok(response_str) = serde_json::to_string(&response) {
                         let _ = tx_clone.send(response_str).await;
                     }
                 });
             }
             Err(_e) => {
-                let err_res = JsonRpcResponse::error(None, -32700, "Parse error".into());
+                let err_res = JsonRpcResponse::error(None, -32600, "Invalid Request".into());
                 if let Ok(err_str) = serde_json::to_string(&err_res) {
                     let _ = tx.send(err_str).await;
                 }
@@ -102,407 +160,132 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn handle_request(req: JsonRpcRequest, tx: mpsc::Sender<String>) -> JsonRpcResponse {
+/// Identifies the other end of stdio, the parent process, by its executable
+/// instead of trusting what it reports about itself.
+fn peer() -> Caller {
+    let parent = std::os::unix::process::parent_id();
+
+    let app = std::fs::read_link(format!("/proc/{parent}/exe"))
+        .ok()
+        .and_then(|exe| {
+            exe.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        });
+
+    Caller {
+        app,
+        model: None,
+        session: Some(format!("{parent}-{}", std::process::id())),
+    }
+}
+
+async fn handle_request(
+    req: JsonRpcRequest,
+    registry: &Registry,
+    history: &Mutex<History>,
+    peer: &Caller,
+    tx: mpsc::Sender<String>,
+) -> JsonRpcResponse {
     match req.method.as_str() {
         "tools/list" => {
-            let tools = vec![
-                Tool {
-                    name: "list_processes".into(),
-                    description: "List all running processes with PID, CPU, and Memory usage."
-                        .into(),
-                    input_schema: json!({ "type": "object", "properties": {} }),
-                },
-                Tool {
-                    name: "terminal_open".into(),
-                    description: "Open a new terminal PTY session.".into(),
-                    input_schema: json!({
-                        "type": "object",
-                        "properties": {
-                            "rows": { "type": "number", "default": 24 },
-                            "cols": { "type": "number", "default": 80 }
-                        }
-                    }),
-                },
-                Tool {
-                    name: "terminal_write".into(),
-                    description: "Write data to the active terminal.".into(),
-                    input_schema: json!({
-                        "type": "object",
-                        "properties": {
-                            "data": { "type": "string" }
-                        },
-                        "required": ["data"]
-                    }),
-                },
-                Tool {
-                    name: "terminal_resize".into(),
-                    description: "Resize the active terminal.".into(),
-                    input_schema: json!({
-                        "type": "object",
-                        "properties": {
-                            "rows": { "type": "number" },
-                            "cols": { "type": "number" }
-                        },
-                        "required": ["rows", "cols"]
-                    }),
-                },
-                // ... other tools ...
-                Tool {
-                    name: "read_file".into(),
-                    description: "Read content of a file from the system.".into(),
-                    input_schema: json!({
-                        "type": "object",
-                        "properties": {
-                            "path": { "type": "string", "description": "Absolute path to file" }
-                        },
-                        "required": ["path"]
-                    }),
-                },
-                Tool {
-                    name: "write_file".into(),
-                    description: "Write content to a file.".into(),
-                    input_schema: json!({
-                        "type": "object",
-                        "properties": {
-                            "path": { "type": "string", "description": "Absolute path to file" },
-                            "content": { "type": "string", "description": "Content to write" }
-                        },
-                        "required": ["path", "content"]
-                    }),
-                },
-                Tool {
-                    name: "kill_process".into(),
-                    description: "Terminate a system process by PID.".into(),
-                    input_schema: json!({
-                        "type": "object",
-                        "properties": {
-                            "pid": { "type": "string", "description": "Process ID to kill" }
-                        },
-                        "required": ["pid"]
-                    }),
-                },
-                Tool {
-                    name: "read_dir".into(),
-                    description: "List files and directories in a path.".into(),
-                    input_schema: json!({
-                        "type": "object",
-                        "properties": {
-                            "path": { "type": "string", "description": "Directory path" }
-                        },
-                        "required": ["path"]
-                    }),
-                },
-                Tool {
-                    name: "scan_wifi".into(),
-                    description: "Scan for available WiFi networks.".into(),
-                    input_schema: json!({ "type": "object", "properties": {} }),
-                },
-                Tool {
-                    name: "connect_wifi".into(),
-                    description: "Connect to a WiFi network.".into(),
-                    input_schema: json!({
-                        "type": "object",
-                        "properties": {
-                            "ssid": { "type": "string", "description": "SSID of the network" },
-                            "password": { "type": "string", "description": "WiFi password" }
-                        },
-                        "required": ["ssid", "password"]
-                    }),
-                },
-                Tool {
-                    name: "search_files".into(),
-                    description: "Search for files and directories by name.".into(),
-                    input_schema: json!({
-                        "type": "object",
-                        "properties": {
-                            "query": { "type": "string", "description": "Search term" },
-                            "base_path": { "type": "string", "description": "Path to search from" }
-                        },
-                        "required": ["query", "base_path"]
-                    }),
-                },
-                Tool {
-                    name: "intelligence/stt".into(),
-                    description: "Convert PCM audio data (f32, 16kHz) to text using Whisper."
-                        .into(),
-                    input_schema: json!({
-                        "type": "object",
-                        "properties": {
-                            "audio": { "type": "array", "items": { "type": "number" } }
-                        },
-                        "required": ["audio"]
-                    }),
-                },
-                Tool {
-                    name: "intelligence/tts".into(),
-                    description: "Convert text to speech samples (f32, 22kHz) using Piper.".into(),
-                    input_schema: json!({
-                        "type": "object",
-                        "properties": {
-                            "text": { "type": "string" },
-                            "voice": { "type": "string", "default": "en_US-lessac-medium" }
-                        },
-                        "required": ["text"]
-                    }),
-                },
-            ];
+            JsonRpcResponse::success(req.id, serde_json::to_value(registry.list()).unwrap())
+        }
+        "tools/call" => {
+            let Some(params) = req.params else {
+                return JsonRpcResponse::error(req.id, -32602, "Missing params".into());
+            };
+
+            let Ok(call) = serde_json::from_value::<CallToolParams>(params) else {
+                return JsonRpcResponse::error(req.id, -32602, "Invalid params".into());
+            };
+
+            // Only the model is taken from `_meta`; the peer cannot be spoofed
+            let context = Context::new(tx).with_caller(Caller {
+                model: call.meta.model,
+                ..peer.clone()
+            });
+
+            match registry.call(&call.name, call.arguments, &context).await {
+                Ok(result) => {
+                    JsonRpcResponse::success(req.id, serde_json::to_value(result).unwrap())
+                }
+                Err(error) => error.into_response(req.id),
+            }
+        }
+        "permissions/list" => {
+            let Some(policy) = registry.policy() else {
+                return JsonRpcResponse::success(req.id, json!({}));
+            };
 
             JsonRpcResponse::success(
                 req.id,
-                serde_json::to_value(ListToolsResult { tools }).unwrap(),
+                json!({
+                    "remembered": policy.remembered(),
+                    "allow": policy.permissions().allow,
+                    "deny": policy.permissions().deny,
+                }),
             )
         }
-        "tools/call" => {
-            if let Some(params) = req.params {
-                let call_params: Result<CallToolParams, _> = serde_json::from_value(params);
-                match call_params {
-                    Ok(p) => {
-                        let result = match p.name.as_str() {
-                            "terminal_open" => {
-                                let rows =
-                                    p.arguments
-                                        .as_ref()
-                                        .and_then(|a| a.get("rows"))
-                                        .and_then(|v| v.as_u64())
-                                        .unwrap_or(24) as u16;
-                                let cols =
-                                    p.arguments
-                                        .as_ref()
-                                        .and_then(|a| a.get("cols"))
-                                        .and_then(|v| v.as_u64())
-                                        .unwrap_or(80) as u16;
-                                TERMINAL.open(rows, cols, tx)
-                            }
-                            "terminal_write" => {
-                                let data = p
-                                    .arguments
-                                    .as_ref()
-                                    .and_then(|a| a.get("data"))
-                                    .and_then(|v| v.as_str())
-                                    .unwrap_or("");
-                                TERMINAL.write(data)
-                            }
-                            "terminal_resize" => {
-                                let rows =
-                                    p.arguments
-                                        .as_ref()
-                                        .and_then(|a| a.get("rows"))
-                                        .and_then(|v| v.as_u64())
-                                        .unwrap_or(24) as u16;
-                                let cols =
-                                    p.arguments
-                                        .as_ref()
-                                        .and_then(|a| a.get("cols"))
-                                        .and_then(|v| v.as_u64())
-                                        .unwrap_or(80) as u16;
-                                TERMINAL.resize(rows, cols)
-                            }
-                            "list_processes" => tools::list_processes(),
-                            "read_file" => {
-                                if let Some(args) = p.arguments {
-                                    if let Some(path) = args.get("path").and_then(|v| v.as_str()) {
-                                        tools::read_file(path)
-                                    } else {
-                                        return JsonRpcResponse::error(
-                                            req.id,
-                                            -32602,
-                                            "Missing 'path' argument".into(),
-                                        );
-                                    }
-                                } else {
-                                    return JsonRpcResponse::error(
-                                        req.id,
-                                        -32602,
-                                        "Missing arguments".into(),
-                                    );
-                                }
-                            }
-                            "write_file" => {
-                                if let Some(args) = p.arguments {
-                                    let path = args.get("path").and_then(|v| v.as_str());
-                                    let content = args.get("content").and_then(|v| v.as_str());
-                                    if let (Some(path), Some(content)) = (path, content) {
-                                        tools::write_file(path, content)
-                                    } else {
-                                        return JsonRpcResponse::error(
-                                            req.id,
-                                            -32602,
-                                            "Missing 'path' or 'content' arguments".into(),
-                                        );
-                                    }
-                                } else {
-                                    return JsonRpcResponse::error(
-                                        req.id,
-                                        -32602,
-                                        "Missing arguments".into(),
-                                    );
-                                }
-                            }
-                            "kill_process" => {
-                                if let Some(args) = p.arguments {
-                                    if let Some(pid) = args.get("pid").and_then(|v| v.as_str()) {
-                                        tools::kill_process(pid)
-                                    } else {
-                                        return JsonRpcResponse::error(
-                                            req.id,
-                                            -32602,
-                                            "Missing 'pid' argument".into(),
-                                        );
-                                    }
-                                } else {
-                                    return JsonRpcResponse::error(
-                                        req.id,
-                                        -32602,
-                                        "Missing arguments".into(),
-                                    );
-                                }
-                            }
-                            "read_dir" => {
-                                if let Some(args) = p.arguments {
-                                    if let Some(path) = args.get("path").and_then(|v| v.as_str()) {
-                                        tools::read_dir(path)
-                                    } else {
-                                        return JsonRpcResponse::error(
-                                            req.id,
-                                            -32602,
-                                            "Missing 'path' argument".into(),
-                                        );
-                                    }
-                                } else {
-                                    return JsonRpcResponse::error(
-                                        req.id,
-                                        -32602,
-                                        "Missing arguments".into(),
-                                    );
-                                }
-                            }
-                            "scan_wifi" => tools::scan_wifi(),
-                            "connect_wifi" => {
-                                if let Some(args) = p.arguments {
-                                    let ssid = args.get("ssid").and_then(|v| v.as_str());
-                                    let password = args.get("password").and_then(|v| v.as_str());
-                                    if let (Some(ssid), Some(password)) = (ssid, password) {
-                                        tools::connect_wifi(ssid, password)
-                                    } else {
-                                        return JsonRpcResponse::error(
-                                            req.id,
-                                            -32602,
-                                            "Missing 'ssid' or 'password' arguments".into(),
-                                        );
-                                    }
-                                } else {
-                                    return JsonRpcResponse::error(
-                                        req.id,
-                                        -32602,
-                                        "Missing arguments".into(),
-                                    );
-                                }
-                            }
-                            "search_files" => {
-                                if let Some(args) = p.arguments {
-                                    let query = args.get("query").and_then(|v| v.as_str());
-                                    let base_path = args.get("base_path").and_then(|v| v.as_str());
-                                    if let (Some(query), Some(base_path)) = (query, base_path) {
-                                        tools::search_files(query, base_path)
-                                    } else {
-                                        return JsonRpcResponse::error(
-                                            req.id,
-                                            -32602,
-                                            "Missing 'query' or 'base_path' arguments".into(),
-                                        );
-                                    }
-                                } else {
-                                    return JsonRpcResponse::error(
-                                        req.id,
-                                        -32602,
-                                        "Missing arguments".into(),
-                                    );
-                                }
-                            }
-                            #[cfg(feature = "voice")]
-                            "intelligence/stt" => {
-                                let args = p
-                                    .arguments
-                                    .ok_or_else(|| anyhow::anyhow!("Missing arguments"))?;
-                                let audio_val = args
-                                    .get("audio")
-                                    .ok_or_else(|| anyhow::anyhow!("Missing 'audio' argument"))?;
-                                let audio_arr = audio_val
-                                    .as_array()
-                                    .ok_or_else(|| anyhow::anyhow!("'audio' must be an array"))?;
-
-                                let samples: Vec<f32> = audio_arr
-                                    .iter()
-                                    .filter_map(|v| v.as_f64().map(|f| f as f32))
-                                    .collect();
-
-                                let mut manager = VOICE.lock().await;
-                                manager.init_whisper("tiny.en").await?;
-                                manager.transcribe(&samples).await.map(|s| json!(s))
-                            }
-                            #[cfg(feature = "voice")]
-                            "intelligence/tts" => {
-                                let args = p
-                                    .arguments
-                                    .ok_or_else(|| anyhow::anyhow!("Missing arguments"))?;
-                                let text = args.get("text").and_then(|v| v.as_str()).unwrap_or("");
-                                let voice = args
-                                    .get("voice")
-                                    .and_then(|v| v.as_str())
-                                    .unwrap_or("en_US-lessac-medium");
-
-                                let mut manager = VOICE.lock().await;
-                                manager
-                                    .synthesize(text, voice)
-                                    .await
-                                    .map(|samples| json!({ "samples": samples }))
-                            }
-                            _ => {
-                                return JsonRpcResponse::error(
-                                    req.id,
-                                    -32601,
-                                    format!("Tool not found: {}", p.name),
-                                );
-                            }
-                        };
-
-                        match result {
-                            Ok(tool_res) => {
-                                let content = vec![ToolContent {
-                                    r#type: "text".into(),
-                                    text: tool_res.to_string(),
-                                }];
-                                JsonRpcResponse::success(
-                                    req.id,
-                                    serde_json::to_value(CallToolResult {
-                                        content,
-                                        is_error: Some(false),
-                                    })
-                                    .unwrap(),
-                                )
-                            }
-                            Err(e) => {
-                                let content = vec![ToolContent {
-                                    r#type: "text".into(),
-                                    text: format!("Error: {}", e),
-                                }];
-                                JsonRpcResponse::success(
-                                    req.id,
-                                    serde_json::to_value(CallToolResult {
-                                        content,
-                                        is_error: Some(true),
-                                    })
-                                    .unwrap(),
-                                )
-                            }
-                        }
-                    }
-                    Err(_) => JsonRpcResponse::error(req.id, -32602, "Invalid params".into()),
+        "permissions/revoke" => {
+            let params = req.params.unwrap_or_default();
+
+            let (Some(principal), Some(tool)) =
+                (params["principal"].as_str(), params["tool"].as_str())
+            else {
+                return JsonRpcResponse::error(req.id, -32602, "Invalid params".into());
+            };
+
+            let revoked = registry
+                .policy()
+                .is_some_and(|policy| policy.forget(principal, tool));
+
+            JsonRpcResponse::success(req.id, json!({ "revoked": revoked }))
+        }
+        "audit/query" => {
+            let Some(log) = registry.audit() else {
+                return JsonRpcResponse::error(req.id, -32603, "Audit log unavailable".into());
+            };
+
+            let Ok(query) =
+                serde_json::from_value::<audit::Query>(req.params.unwrap_or_else(|| json!({})))
+            else {
+                return JsonRpcResponse::error(req.id, -32602, "Invalid params".into());
+            };
+
+            match log.query(&query) {
+                Ok(entries) => JsonRpcResponse::success(req.id, json!({ "entries": entries })),
+                Err(error) => JsonRpcResponse::error(req.id, -32603, error.to_string()),
+            }
+        }
+        "audit/verify" => {
+            let Some(log) = registry.audit() else {
+                return JsonRpcResponse::error(req.id, -32603, "Audit log unavailable".into());
+            };
+
+            match log.verify() {
+                Ok(integrity) => {
+                    JsonRpcResponse::success(req.id, serde_json::to_value(integrity).unwrap())
                 }
-            } else {
-                JsonRpcResponse::error(req.id, -32602, "Missing params".into())
+                Err(error) => JsonRpcResponse::error(req.id, -32603, error.to_string()),
             }
         }
+        "telemetry/query" => {
+            let Ok(query) =
+                serde_json::from_value::<history::Query>(req.params.unwrap_or_else(|| json!({})))
+            else {
+                return JsonRpcResponse::error(req.id, -32602, "Invalid params".into());
+            };
+
+            let history = history.lock().unwrap_or_else(|error| error.into_inner());
+
+            JsonRpcResponse::success(
+                req.id,
+                json!({
+                    "series": history.query(&query),
+                    "alerts": history.alerts(),
+                }),
+            )
+        }
         _ => JsonRpcResponse::error(req.id, -32601, "Method not found".into()),
     }
 }
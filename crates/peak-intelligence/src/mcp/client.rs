@@ -0,0 +1,633 @@
+// MCP client: mounts external stdio servers and exposes their tools.
+//
+// Each configured server is spawned as a child process and spoken to with
+// line-delimited JSON-RPC over its stdin/stdout. Servers that crash are
+// restarted with exponential backoff, either by the supervisor task or lazily
+// on the next call.
+
+use super::{
+    CallToolResult, Context, JsonRpcError, ListToolsResult, Registry, Tool, ToolHandler, ToolSource,
+};
+use crate::brain::settings::McpServer;
+
+use anyhow::{anyhow, Context as _};
+use futures::future::{self, BoxFuture};
+use futures::FutureExt;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::{oneshot, Mutex};
+use tokio::time::{self, Duration, Instant};
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Spawns every enabled server at once and registers its tools as
+/// `server/tool`.
+///
+/// Servers that fail to start are logged and still registered; they are
+/// supervised and their tools appear as soon as they come up.
+pub async fn mount(servers: &[McpServer], registry: &mut Registry) -> Vec<Arc<Server>> {
+    mount_with(servers, registry, backoff).await
+}
+
+/// Like [`mount`], but waits `backoff(failures)` after a failed start.
+async fn mount_with(
+    servers: &[McpServer],
+    registry: &mut Registry,
+    backoff: fn(u32) -> Duration,
+) -> Vec<Arc<Server>> {
+    let mounted: Vec<_> = servers
+        .iter()
+        .filter(|server| server.enabled)
+        .map(|config| Server::with_backoff(config.clone(), backoff))
+        .collect();
+
+    let started = future::join_all(mounted.iter().map(|server| server.connection())).await;
+
+    for (server, result) in mounted.iter().zip(started) {
+        match result {
+            Ok(_) => {
+                log::info!(
+                    "Mounted MCP server '{}' with {} tools",
+                    server.name(),
+                    server.tools.lock().unwrap().len()
+                );
+            }
+            Err(error) => {
+                log::warn!("Failed to mount MCP server '{}': {error:#}", server.name());
+            }
+        }
+
+        registry.add_source(server.clone());
+
+        tokio::spawn(server.clone().supervise());
+    }
+
+    mounted
+}
+
+/// How long to wait before starting a server again after `failures` failed
+/// starts in a row.
+fn backoff(failures: u32) -> Duration {
+    Duration::from_secs(1 << failures.min(6)).min(MAX_BACKOFF)
+}
+
+/// A supervised external MCP server.
+pub struct Server {
+    config: McpServer,
+    /// Only locked to read or swap the state, never across a request.
+    state: std::sync::Mutex<State>,
+    /// Held by the one caller that (re)starts the server, so concurrent
+    /// callers wait for its handshake instead of starting their own.
+    starting: Mutex<()>,
+    backoff: fn(u32) -> Duration,
+    /// The tools listed by the running server, refreshed on every start.
+    tools: std::sync::Mutex<Vec<Tool>>,
+    this: Weak<Server>,
+}
+
+#[derive(Default)]
+struct State {
+    connection: Option<Arc<Connection>>,
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl Server {
+    pub fn new(config: McpServer) -> Arc<Self> {
+        Self::with_backoff(config, backoff)
+    }
+
+    fn with_backoff(config: McpServer, backoff: fn(u32) -> Duration) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            config,
+            state: std::sync::Mutex::new(State::default()),
+            starting: Mutex::new(()),
+            backoff,
+            tools: std::sync::Mutex::new(Vec::new()),
+            this: this.clone(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running().is_some()
+    }
+
+    pub async fn list_tools(&self) -> anyhow::Result<Vec<Tool>> {
+        self.connection().await?.list_tools().await
+    }
+
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> anyhow::Result<CallToolResult> {
+        let connection = self.connection().await?;
+
+        let result = connection
+            .request(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Restarts the server whenever it exits, until the server is dropped.
+    async fn supervise(self: Arc<Self>) {
+        loop {
+            time::sleep(SUPERVISE_INTERVAL).await;
+
+            // Only the supervisor holds the server once the registry is gone.
+            if Arc::strong_count(&self) == 1 {
+                break;
+            }
+
+            if !self.is_running() {
+                if let Err(error) = self.connection().await {
+                    log::debug!("MCP server '{}' still down: {error:#}", self.name());
+                }
+            }
+        }
+    }
+
+    /// The connection to the server, (re)starting it if it is not running.
+    async fn connection(&self) -> anyhow::Result<Arc<Connection>> {
+        if let Some(connection) = self.running() {
+            return Ok(connection);
+        }
+
+        let _starting = self.starting.lock().await;
+
+        // Another caller may have restarted the server while we waited
+        if let Some(connection) = self.running() {
+            return Ok(connection);
+        }
+
+        {
+            let mut state = self.state.lock().unwrap();
+
+            if state.connection.take().is_some() {
+                log::warn!("MCP server '{}' exited; restarting", self.name());
+            }
+
+            if let Some(retry_at) = state.retry_at {
+                if Instant::now() < retry_at {
+                    return Err(anyhow!("MCP server '{}' is restarting", self.name()));
+                }
+            }
+        }
+
+        let started = match Connection::start(&self.config).await {
+            Ok(connection) => connection
+                .list_tools()
+                .await
+                .map(|tools| (connection, tools)),
+            Err(error) => Err(error),
+        };
+
+        let mut state = self.state.lock().unwrap();
+
+        match started {
+            Ok((connection, tools)) => {
+                let connection = Arc::new(connection);
+
+                *self.tools.lock().unwrap() = tools;
+
+                state.connection = Some(connection.clone());
+                state.failures = 0;
+                state.retry_at = None;
+
+                Ok(connection)
+            }
+            Err(error) => {
+                state.retry_at = Some(Instant::now() + (self.backoff)(state.failures));
+                state.failures += 1;
+
+                Err(error.context(format!("failed to start MCP server '{}'", self.name())))
+            }
+        }
+    }
+
+    fn running(&self) -> Option<Arc<Connection>> {
+        self.state
+            .lock()
+            .unwrap()
+            .connection
+            .clone()
+            .filter(|connection| connection.is_alive())
+    }
+}
+
+impl ToolSource for Server {
+    fn tools(&self) -> Vec<Arc<dyn ToolHandler>> {
+        let Some(server) = self.this.upgrade() else {
+            return Vec::new();
+        };
+
+        self.tools
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|tool| {
+                Arc::new(RemoteTool {
+                    name: format!("{}/{}", self.name(), tool.name),
+                    tool: tool.clone(),
+                    server: server.clone(),
+                }) as Arc<dyn ToolHandler>
+            })
+            .collect()
+    }
+}
+
+type Pending = HashMap<u64, oneshot::Sender<Result<Value, JsonRpcError>>>;
+
+struct Connection {
+    stdin: Arc<Mutex<ChildStdin>>,
+    pending: Arc<std::sync::Mutex<Pending>>,
+    alive: Arc<AtomicBool>,
+    next_id: AtomicU64,
+    _child: Child,
+}
+
+impl Connection {
+    async fn start(config: &McpServer) -> anyhow::Result<Self> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("could not spawn `{}`", config.command))?;
+
+        let stdin = Arc::new(Mutex::new(child.stdin.take().expect("piped stdin")));
+        let stdout = child.stdout.take().expect("piped stdout");
+
+        let pending = Arc::new(std::sync::Mutex::new(Pending::new()));
+        let alive = Arc::new(AtomicBool::new(true));
+
+        tokio::spawn(Self::read(
+            BufReader::new(stdout),
+            stdin.clone(),
+            pending.clone(),
+            alive.clone(),
+        ));
+
+        let connection = Self {
+            stdin,
+            pending,
+            alive,
+            next_id: AtomicU64::new(1),
+            _child: child,
+        };
+
+        connection
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": "PeakOS Intelligence",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    }
+                }),
+            )
+            .await?;
+
+        connection
+            .send(json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/initialized",
+            }))
+            .await?;
+
+        Ok(connection)
+    }
+
+    async fn list_tools(&self) -> anyhow::Result<Vec<Tool>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+
+            let mut result = self.request("tools/list", params).await?;
+            let next_cursor = result["nextCursor"].as_str().map(str::to_owned);
+
+            let page: ListToolsResult = serde_json::from_value(result.take())?;
+            tools.extend(page.tools);
+
+            match next_cursor {
+                Some(next) if cursor.as_ref() != Some(&next) => cursor = Some(next),
+                _ => break,
+            }
+        }
+
+        Ok(tools)
+    }
+
+    fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    async fn request(&self, method: &str, params: Value) -> anyhow::Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+
+        let _ = self.pending.lock().unwrap().insert(id, sender);
+
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }))
+        .await?;
+
+        match time::timeout(REQUEST_TIMEOUT, receiver).await {
+            Ok(Ok(Ok(result))) => Ok(result),
+            Ok(Ok(Err(error))) => Err(anyhow!("{} ({})", error.message, error.code)),
+            Ok(Err(_)) => Err(anyhow!("MCP server exited during `{method}`")),
+            Err(_) => {
+                let _ = self.pending.lock().unwrap().remove(&id);
+
+                Err(anyhow!("MCP server timed out during `{method}`"))
+            }
+        }
+    }
+
+    async fn send(&self, message: Value) -> anyhow::Result<()> {
+        write(&self.stdin, &message).await
+    }
+
+    async fn read(
+        stdout: BufReader<tokio::process::ChildStdout>,
+        stdin: Arc<Mutex<ChildStdin>>,
+        pending: Arc<std::sync::Mutex<Pending>>,
+        alive: Arc<AtomicBool>,
+    ) {
+        let mut lines = stdout.lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                log::debug!("Ignoring non JSON-RPC output: {line}");
+                continue;
+            };
+
+            match (message.get("method"), message.get("id")) {
+                // Server to client request; we do not offer any capabilities
+                (Some(method), Some(id)) => {
+                    let response = if method == "ping" {
+                        json!({ "jsonrpc": "2.0", "id": id, "result": {} })
+                    } else {
+                        json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": { "code": -32601, "message": "Method not found" }
+                        })
+                    };
+
+                    let _ = write(&stdin, &response).await;
+                }
+                // Notification
+                (Some(method), None) => {
+                    log::debug!("MCP notification: {method}");
+                }
+                // Response
+                (None, Some(id)) => {
+                    let Some(sender) = id
+                        .as_u64()
+                        .and_then(|id| pending.lock().unwrap().remove(&id))
+                    else {
+                        continue;
+                    };
+
+                    let result = match message.get("error") {
+                        Some(error) => Err(serde_json::from_value(error.clone()).unwrap_or(
+                            JsonRpcError {
+                                code: -32603,
+                                message: error.to_string(),
+                                data: None,
+                            },
+                        )),
+                        None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                    };
+
+                    let _ = sender.send(result);
+                }
+                (None, None) => {}
+            }
+        }
+
+        alive.store(false, Ordering::SeqCst);
+
+        // Dropping the senders wakes up every pending request
+        pending.lock().unwrap().clear();
+    }
+}
+
+async fn write(stdin: &Mutex<ChildStdin>, message: &Value) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(&line).await?;
+    stdin.flush().await?;
+
+    Ok(())
+}
+
+/// A tool provided by a mounted server.
+pub struct RemoteTool {
+    name: String,
+    tool: Tool,
+    server: Arc<Server>,
+}
+
+impl ToolHandler for RemoteTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.tool.description
+    }
+
+    fn input_schema(&self) -> Value {
+        self.tool.input_schema.clone()
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        _context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move {
+            let result = self.server.call_tool(&self.tool.name, arguments).await?;
+
+            let text = result
+                .content
+                .iter()
+                .map(|content| content.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            if result.is_error.unwrap_or(false) {
+                return Err(anyhow!(text));
+            }
+
+            Ok(serde_json::from_str(&text).unwrap_or(Value::String(text)))
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+    use std::io::{BufRead, Write};
+    use std::path::Path;
+
+    const FAKE_SERVER: &str = "PEAK_FAKE_MCP_SERVER";
+
+    /// Acts as a stdio MCP server with a single `echo` tool when the test
+    /// binary is spawned with [`FAKE_SERVER`] set, and does nothing otherwise.
+    ///
+    /// The server refuses to start until the file named by the variable
+    /// exists, and exits when asked to echo "die".
+    #[test]
+    fn fake_server() {
+        let Ok(ready) = std::env::var(FAKE_SERVER) else {
+            return;
+        };
+
+        if !Path::new(&ready).exists() {
+            std::process::exit(1);
+        }
+
+        for line in std::io::stdin().lock().lines() {
+            let request: Value = serde_json::from_str(&line.unwrap()).unwrap();
+
+            let Some(id) = request.get("id") else {
+                continue;
+            };
+
+            let result = match request["method"].as_str() {
+                Some("initialize") => json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "fake", "version": "0" },
+                }),
+                Some("tools/list") => json!({
+                    "tools": [{
+                        "name": "echo",
+                        "description": "Echoes x",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": { "x": { "type": "string" } },
+                            "required": ["x"],
+                        },
+                    }],
+                }),
+                Some("tools/call") => {
+                    let x = request["params"]["arguments"]["x"].as_str().unwrap();
+
+                    if x == "die" {
+                        std::process::exit(1);
+                    }
+
+                    json!({ "content": [{ "type": "text", "text": x }], "isError": false })
+                }
+                _ => json!({}),
+            };
+
+            let mut stdout = std::io::stdout().lock();
+            writeln!(
+                stdout,
+                "{}",
+                json!({ "jsonrpc": "2.0", "id": id, "result": result })
+            )
+            .unwrap();
+            stdout.flush().unwrap();
+        }
+
+        std::process::exit(0);
+    }
+
+    fn names(registry: &Registry) -> Vec<String> {
+        registry
+            .list()
+            .tools
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_mount_and_restart() {
+        let ready = std::env::temp_dir().join(format!("peak-fake-mcp-{}", std::process::id()));
+        let _ = std::fs::remove_file(&ready);
+
+        let config = McpServer {
+            name: "fake".to_owned(),
+            command: std::env::current_exe().unwrap().display().to_string(),
+            args: vec![
+                "mcp::client::tests::fake_server".to_owned(),
+                "--exact".to_owned(),
+                "--quiet".to_owned(),
+            ],
+            env: BTreeMap::from([(FAKE_SERVER.to_owned(), ready.display().to_string())]),
+            enabled: true,
+        };
+
+        // A server that fails to start is mounted without tools...
+        let mut registry = Registry::new();
+        let servers = mount_with(&[config], &mut registry, |_| Duration::ZERO).await;
+
+        assert_eq!(servers.len(), 1);
+        assert!(names(&registry).is_empty());
+
+        // ...and its tools appear once it comes up
+        std::fs::write(&ready, "").unwrap();
+
+        assert_eq!(servers[0].list_tools().await.unwrap().len(), 1);
+        assert_eq!(names(&registry), ["fake/echo"]);
+
+        let context = Context::new(tokio::sync::mpsc::channel(1).0);
+        let echo = |x: &'static str| registry.call("fake/echo", Some(json!({ "x": x })), &context);
+
+        let result = echo("hello").await.unwrap();
+        assert_eq!(result.is_error, Some(false));
+        assert_eq!(result.content[0].text, "\"hello\"");
+
+        // A crash fails the call in flight; the next call restarts the server
+        let result = echo("die").await.unwrap();
+        assert_eq!(result.is_error, Some(true));
+        assert!(!servers[0].is_running());
+
+        let result = echo("again").await.unwrap();
+        assert_eq!(result.content[0].text, "\"again\"");
+        assert!(servers[0].is_running());
+        assert_eq!(names(&registry), ["fake/echo"]);
+
+        std::fs::remove_file(&ready).unwrap();
+    }
+}
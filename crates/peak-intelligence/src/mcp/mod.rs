@@ -1,3 +1,10 @@
+pub mod client;
+
+mod registry;
+
+pub use crate::schema::{validate, Violation};
+pub use registry::{CallError, Context, Registry, ToolHandler, ToolSource};
+
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -31,10 +38,12 @@ pub struct JsonRpcError {
 
 // MCP Specific Types
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tool {
     pub name: String,
+    #[serde(default)]
     pub description: String,
+    #[serde(alias = "inputSchema")]
     pub input_schema: Value, // JSON Schema
 }
 
@@ -42,6 +51,8 @@ pub struct Tool {
 pub struct CallToolParams {
     pub name: String,
     pub arguments: Option<Value>,
+    #[serde(default, rename = "_meta")]
+    pub meta: crate::policy::Caller,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -52,12 +63,14 @@ pub struct ListToolsResult {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CallToolResult {
     pub content: Vec<ToolContent>,
+    #[serde(alias = "isError")]
     pub is_error: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ToolContent {
     pub r#type: String, // "text"
+    #[serde(default)]
     pub text: String,
 }
 
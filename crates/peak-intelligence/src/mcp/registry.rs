@@ -0,0 +1,337 @@
+use super::{CallToolResult, JsonRpcResponse, ListToolsResult, Tool, ToolContent};
+use crate::audit::{self, Record, Status};
+use crate::policy::{Caller, Policy, Risk};
+use crate::schema::{self, Violation};
+
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A tool that can be listed and called through `tools/list` and `tools/call`.
+///
+/// Arguments are validated against [`ToolHandler::input_schema`] before
+/// [`ToolHandler::call`] runs, so handlers can rely on required fields being
+/// present and well-typed.
+pub trait ToolHandler: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    fn input_schema(&self) -> Value;
+
+    /// How much harm the tool can do. Tools are destructive unless they say
+    /// otherwise, so new tools ask for confirmation by default.
+    fn risk(&self) -> Risk {
+        Risk::Destructive
+    }
+
+    /// The file system paths the call would touch, checked against the
+    /// allow and deny lists of the [`Policy`].
+    fn paths(&self, _arguments: &Value) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// A short description of what a call is doing, shown while it runs,
+    /// e.g. "Listing processes...".
+    fn activity(&self, _arguments: &Value) -> String {
+        format!("Running {}...", self.name())
+    }
+
+    /// What the audit log keeps of the arguments of a call. Tools whose
+    /// arguments carry contents, e.g. a file body or keystrokes, should keep
+    /// only their size with [`audit::summarize`].
+    fn audit_summary(&self, arguments: &Value) -> Value {
+        audit::summarize(arguments, &[])
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>>;
+}
+
+/// Per-request state made available to tool handlers.
+#[derive(Debug, Clone)]
+pub struct Context {
+    /// Channel used to push JSON-RPC notifications back to the shell.
+    pub notifications: mpsc::Sender<String>,
+    /// Who issued the call; see [`Caller`] for what may be self-reported.
+    pub caller: Caller,
+    /// The policy the call was authorized against, for tools that find the
+    /// paths they touch as they go, e.g. while walking a directory.
+    pub policy: Option<Arc<Policy>>,
+}
+
+impl Context {
+    pub fn new(notifications: mpsc::Sender<String>) -> Self {
+        Self {
+            notifications,
+            caller: Caller::default(),
+            policy: None,
+        }
+    }
+
+    pub fn with_caller(mut self, caller: Caller) -> Self {
+        self.caller = caller;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallError {
+    NotFound(String),
+    InvalidArguments(Vec<Violation>),
+    Denied(String),
+}
+
+impl CallError {
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::NotFound(_) => -32601,
+            Self::InvalidArguments(_) => -32602,
+            Self::Denied(_) => -32001,
+        }
+    }
+
+    pub fn into_response(self, id: Option<Value>) -> JsonRpcResponse {
+        let code = self.code();
+        let message = self.to_string();
+
+        let mut response = JsonRpcResponse::error(id, code, message);
+
+        if let (Self::InvalidArguments(violations), Some(error)) = (&self, &mut response.error) {
+            error.data = Some(json!(violations
+                .iter()
+                .map(|violation| json!({
+                    "path": violation.path,
+                    "message": violation.message,
+                }))
+                .collect::<Vec<_>>()));
+        }
+
+        response
+    }
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(name) => write!(f, "Tool not found: {name}"),
+            Self::InvalidArguments(violations) => {
+                f.write_str("Invalid arguments: ")?;
+
+                for (i, violation) in violations.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("; ")?;
+                    }
+
+                    write!(f, "{violation}")?;
+                }
+
+                Ok(())
+            }
+            Self::Denied(reason) => write!(f, "Permission denied: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
+
+/// A set of tools that can change while the registry is in use, e.g. the
+/// tools of an external server that is restarted.
+pub trait ToolSource: Send + Sync {
+    fn tools(&self) -> Vec<Arc<dyn ToolHandler>>;
+}
+
+#[derive(Default, Clone)]
+pub struct Registry {
+    tools: Vec<Arc<dyn ToolHandler>>,
+    sources: Vec<Arc<dyn ToolSource>>,
+    policy: Option<Arc<Policy>>,
+    audit: Option<Arc<audit::Log>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a tool, replacing any previously registered tool with the same name.
+    pub fn register(&mut self, tool: impl ToolHandler + 'static) {
+        let tool: Arc<dyn ToolHandler> = Arc::new(tool);
+
+        match self.tools.iter_mut().find(|t| t.name() == tool.name()) {
+            Some(existing) => *existing = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
+    /// Adds a source whose tools are looked up on every use. Tools registered
+    /// directly take precedence over tools of a source with the same name.
+    pub fn add_source(&mut self, source: Arc<dyn ToolSource>) {
+        self.sources.push(source);
+    }
+
+    /// Checks every call against the given policy before running it.
+    pub fn set_policy(&mut self, policy: Arc<Policy>) {
+        self.policy = Some(policy);
+    }
+
+    pub fn policy(&self) -> Option<&Arc<Policy>> {
+        self.policy.as_ref()
+    }
+
+    /// Records every call, successful or not, in the given audit log.
+    pub fn set_audit(&mut self, audit: Arc<audit::Log>) {
+        self.audit = Some(audit);
+    }
+
+    pub fn audit(&self) -> Option<&Arc<audit::Log>> {
+        self.audit.as_ref()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn ToolHandler>> {
+        self.tools
+            .iter()
+            .find(|tool| tool.name() == name)
+            .cloned()
+            .or_else(|| {
+                self.sources
+                    .iter()
+                    .flat_map(|source| source.tools())
+                    .find(|tool| tool.name() == name)
+            })
+    }
+
+    pub fn list(&self) -> ListToolsResult {
+        let mut tools = self.tools.clone();
+
+        for tool in self.sources.iter().flat_map(|source| source.tools()) {
+            if !tools.iter().any(|existing| existing.name() == tool.name()) {
+                tools.push(tool);
+            }
+        }
+
+        ListToolsResult {
+            tools: tools
+                .iter()
+                .map(|tool| Tool {
+                    name: tool.name().to_owned(),
+                    description: tool.description().to_owned(),
+                    input_schema: tool.input_schema(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Validates the arguments, asks the policy for permission and runs the
+    /// tool.
+    ///
+    /// Errors raised by the tool itself are reported inside the
+    /// [`CallToolResult`] with `is_error` set, as MCP expects; only protocol
+    /// level failures are returned as [`CallError`].
+    pub async fn call(
+        &self,
+        name: &str,
+        arguments: Option<Value>,
+        context: &Context,
+    ) -> Result<CallToolResult, CallError> {
+        let Some(audit) = &self.audit else {
+            return self.run(name, arguments, context).await;
+        };
+
+        let summary = match (&arguments, self.get(name)) {
+            (Some(arguments), Some(tool)) => tool.audit_summary(arguments),
+            (Some(arguments), None) => audit::summarize(arguments, &[]),
+            (None, _) => json!({}),
+        };
+        let start = Instant::now();
+
+        let result = self.run(name, arguments, context).await;
+
+        let (status, message) = match &result {
+            Ok(result) if result.is_error == Some(true) => (
+                Status::Error,
+                result.content.first().map(|content| content.text.clone()),
+            ),
+            Ok(_) => (Status::Ok, None),
+            Err(error @ CallError::NotFound(_)) => (Status::NotFound, Some(error.to_string())),
+            Err(error @ CallError::InvalidArguments(_)) => {
+                (Status::InvalidArguments, Some(error.to_string()))
+            }
+            Err(error @ CallError::Denied(_)) => (Status::Denied, Some(error.to_string())),
+        };
+
+        let record = Record {
+            caller: context.caller.clone(),
+            tool: name.to_owned(),
+            arguments: summary,
+            status,
+            message,
+            duration: start.elapsed(),
+        };
+
+        if let Err(error) = audit.append(record) {
+            log::error!("Failed to write audit entry for {name}: {error}");
+        }
+
+        result
+    }
+
+    async fn run(
+        &self,
+        name: &str,
+        arguments: Option<Value>,
+        context: &Context,
+    ) -> Result<CallToolResult, CallError> {
+        let tool = self
+            .get(name)
+            .ok_or_else(|| CallError::NotFound(name.to_owned()))?;
+
+        let arguments = arguments.unwrap_or_else(|| json!({}));
+
+        schema::validate(&tool.input_schema(), &arguments).map_err(CallError::InvalidArguments)?;
+
+        let mut context = context.clone();
+
+        if let Some(policy) = &self.policy {
+            policy
+                .authorize(tool.as_ref(), &arguments, &context)
+                .await
+                .map_err(|denial| CallError::Denied(denial.to_string()))?;
+
+            context.policy = Some(policy.clone());
+        }
+
+        Ok(match tool.call(arguments, &context).await {
+            Ok(value) => CallToolResult {
+                content: vec![ToolContent {
+                    r#type: "text".into(),
+                    text: value.to_string(),
+                }],
+                is_error: Some(false),
+            },
+            Err(error) => CallToolResult {
+                content: vec![ToolContent {
+                    r#type: "text".into(),
+                    text: format!("Error: {}", error),
+                }],
+                is_error: Some(true),
+            },
+        })
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.tools.iter().map(|tool| tool.name()))
+            .finish()
+    }
+}
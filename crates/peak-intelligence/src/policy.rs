@@ -0,0 +1,1001 @@
+//! Permission layer for tool calls.
+//!
+//! Every `tools/call` goes through [`Policy::authorize`] before the tool runs:
+//!
+//! 1. Paths touched by the tool are checked against the allow and deny lists.
+//! 2. Decisions remembered for the caller ("always allow", "always deny") apply.
+//! 3. [`Risk::Destructive`] tools without a grant ask the shell for
+//!    confirmation with a `permission/request` JSON-RPC request and wait for
+//!    its response.
+//!
+//! The shell answers a confirmation with
+//! `{ "jsonrpc": "2.0", "id": <request id>, "result": { "decision": "allow_once" } }`,
+//! where the decision is one of `allow_once`, `allow_session`, `allow_always`,
+//! `deny` or `deny_always`. Errors and timeouts count as `deny`.
+use crate::brain::{directory, Error};
+use crate::mcp::{Context, ToolHandler};
+
+use decoder::{decode, encode, Value as Config};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+use tokio::time::{self, Duration};
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Risk {
+    /// Read-only and harmless; never needs confirmation.
+    Safe,
+    /// Reads user data; allowed, but subject to the path rules.
+    Sensitive,
+    /// Changes the system; needs a grant or an interactive confirmation.
+    Destructive,
+}
+
+impl Risk {
+    fn encode(self) -> Config {
+        encode::string(match self {
+            Self::Safe => "safe",
+            Self::Sensitive => "sensitive",
+            Self::Destructive => "destructive",
+        })
+    }
+}
+
+/// Who is calling a tool.
+///
+/// The app and session are set by whoever owns the connection the call
+/// came in on; only the model may be reported by the caller, in the `_meta`
+/// field of `tools/call`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Caller {
+    #[serde(default)]
+    pub app: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub session: Option<String>,
+}
+
+impl Caller {
+    /// The key remembered decisions are stored under.
+    ///
+    /// Models are scoped to their app, so a caller cannot claim the grants
+    /// of a model driven by another app.
+    pub fn principal(&self) -> String {
+        match (&self.app, &self.model) {
+            (Some(app), Some(model)) => format!("app:{app}/model:{model}"),
+            (Some(app), None) => format!("app:{app}"),
+            (None, Some(model)) => format!("model:{model}"),
+            (None, None) => "unknown".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    AllowOnce,
+    AllowSession,
+    AllowAlways,
+    Deny,
+    DenyAlways,
+}
+
+impl Decision {
+    fn is_allowed(self) -> bool {
+        matches!(
+            self,
+            Self::AllowOnce | Self::AllowSession | Self::AllowAlways
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Denial {
+    Path(PathBuf),
+    Remembered,
+    Rejected,
+}
+
+impl fmt::Display for Denial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "access to {} is not allowed", path.display()),
+            Self::Remembered => f.write_str("the user has blocked this tool"),
+            Self::Rejected => f.write_str("the user declined the request"),
+        }
+    }
+}
+
+/// User configurable rules, stored in `permissions.toml`.
+///
+/// ```toml
+/// allow = ["~", "/tmp"]
+/// deny = ["~/.ssh", "*.pem"]
+///
+/// [tools]
+/// kill_process = "sensitive"
+/// ```
+///
+/// Deny rules containing a `/` are path prefixes; other rules are file name
+/// patterns where `*` matches any run of characters. An empty allow list
+/// permits every path that is not denied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permissions {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+    pub tools: BTreeMap<String, Risk>,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            deny: [
+                "~/.ssh",
+                "~/.gnupg",
+                "~/.aws",
+                "~/.kube",
+                "~/.docker/config.json",
+                "~/.netrc",
+                "~/.password-store",
+                "~/.local/share/keyrings",
+                "~/.peak/intelligence/config",
+                "/etc/shadow",
+                "/etc/gshadow",
+                "/etc/sudoers",
+                "id_rsa*",
+                "id_ed25519*",
+                "*.pem",
+                "*.key",
+            ]
+            .into_iter()
+            .map(str::to_owned)
+            .collect(),
+            tools: BTreeMap::new(),
+        }
+    }
+}
+
+impl Permissions {
+    pub fn fetch() -> Result<Self, Error> {
+        let config = std::fs::read_to_string(Self::path())?;
+        let config: Config = toml::from_str(&config)?;
+
+        Ok(Self::decode(config)?)
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let toml = toml::to_string_pretty(&self.encode())?;
+        let path = Self::path();
+
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+
+        std::fs::write(path, toml)?;
+
+        Ok(())
+    }
+
+    fn decode(value: Config) -> decoder::Result<Self> {
+        use serde::de::IntoDeserializer;
+
+        let mut permissions = decode::map(value)?;
+        let default = Self::default();
+
+        Ok(Self {
+            allow: permissions
+                .optional("allow", decode::sequence(decode::string))?
+                .unwrap_or(default.allow),
+            deny: permissions
+                .optional("deny", decode::sequence(decode::string))?
+                .unwrap_or(default.deny),
+            tools: permissions
+                .optional("tools", |value: Config| {
+                    BTreeMap::deserialize(value.into_deserializer()).map_err(decoder::Error::custom)
+                })?
+                .unwrap_or_default(),
+        })
+    }
+
+    fn encode(&self) -> Config {
+        encode::map([
+            ("allow", encode::sequence(encode::string, &self.allow)),
+            ("deny", encode::sequence(encode::string, &self.deny)),
+            (
+                "tools",
+                encode::map(
+                    self.tools
+                        .iter()
+                        .map(|(tool, risk)| (tool.as_str(), risk.encode())),
+                )
+                .into_value(),
+            ),
+        ])
+        .into_value()
+    }
+
+    fn path() -> PathBuf {
+        directory::config().join("permissions.toml")
+    }
+
+    /// Returns `false` if the path is denied or outside of the allow list.
+    pub fn is_path_allowed(&self, path: &Path) -> bool {
+        let path = resolve(path);
+
+        let denied = self.deny.iter().any(|rule| {
+            if rule.contains('/') {
+                path.starts_with(resolve(Path::new(rule)))
+            } else {
+                path.components().any(|component| {
+                    matches!(component, Component::Normal(name)
+                        if wildcard(rule, &name.to_string_lossy()))
+                })
+            }
+        });
+
+        if denied {
+            return false;
+        }
+
+        self.allow.is_empty()
+            || self
+                .allow
+                .iter()
+                .any(|rule| path.starts_with(resolve(Path::new(rule))))
+    }
+}
+
+/// Decides whether tool calls may run.
+pub struct Policy {
+    permissions: Permissions,
+    remembered: Mutex<Remembered>,
+    grants: PathBuf,
+    sessions: Mutex<HashSet<(String, String)>>,
+    confirmations: Mutex<HashMap<String, oneshot::Sender<Decision>>>,
+    next_confirmation: AtomicU64,
+    /// How long a confirmation waits for an answer before it is denied.
+    timeout: Duration,
+}
+
+impl Policy {
+    pub fn new(permissions: Permissions) -> Self {
+        Self::with_grants(permissions, Remembered::path())
+    }
+
+    /// Like [`Policy::new`], but remembers decisions in `grants` instead of
+    /// `grants.json` in the config directory.
+    pub fn with_grants(permissions: Permissions, grants: impl Into<PathBuf>) -> Self {
+        let grants = grants.into();
+
+        Self {
+            permissions,
+            remembered: Mutex::new(Remembered::fetch(&grants)),
+            grants,
+            sessions: Mutex::new(HashSet::new()),
+            confirmations: Mutex::new(HashMap::new()),
+            next_confirmation: AtomicU64::new(1),
+            timeout: CONFIRMATION_TIMEOUT,
+        }
+    }
+
+    /// Loads `permissions.toml`, falling back to the defaults when it is
+    /// missing or invalid.
+    pub fn load() -> Self {
+        let permissions = Permissions::fetch().unwrap_or_else(|error| {
+            if !matches!(&error, Error::IOFailed(error) if error.kind() == std::io::ErrorKind::NotFound) {
+                log::error!("Invalid permissions.toml, using defaults: {error}");
+            }
+
+            Permissions::default()
+        });
+
+        Self::new(permissions)
+    }
+
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    pub fn risk(&self, tool: &dyn ToolHandler) -> Risk {
+        self.permissions
+            .tools
+            .get(tool.name())
+            .copied()
+            .unwrap_or_else(|| tool.risk())
+    }
+
+    pub async fn authorize(
+        &self,
+        tool: &dyn ToolHandler,
+        arguments: &Value,
+        context: &Context,
+    ) -> Result<(), Denial> {
+        for path in tool.paths(arguments) {
+            if !self.permissions.is_path_allowed(&path) {
+                return Err(Denial::Path(path));
+            }
+        }
+
+        let principal = context.caller.principal();
+        let remembered = self.remembered.lock().unwrap().get(&principal, tool.name());
+
+        match remembered {
+            Some(Decision::DenyAlways) => return Err(Denial::Remembered),
+            Some(Decision::AllowAlways) => return Ok(()),
+            _ => {}
+        }
+
+        if self.risk(tool) < Risk::Destructive {
+            return Ok(());
+        }
+
+        let session = context
+            .caller
+            .session
+            .as_ref()
+            .map(|session| (session.clone(), tool.name().to_owned()));
+
+        if session
+            .as_ref()
+            .is_some_and(|key| self.sessions.lock().unwrap().contains(key))
+        {
+            return Ok(());
+        }
+
+        let decision = self.confirm(tool, arguments, context).await;
+
+        match decision {
+            Decision::AllowSession => {
+                if let Some(key) = session {
+                    let _ = self.sessions.lock().unwrap().insert(key);
+                }
+            }
+            Decision::AllowAlways | Decision::DenyAlways => {
+                let mut remembered = self.remembered.lock().unwrap();
+                remembered.set(&principal, tool.name(), decision);
+
+                if let Err(error) = remembered.save(&self.grants) {
+                    log::error!("Failed to save permission grants: {error}");
+                }
+            }
+            Decision::AllowOnce | Decision::Deny => {}
+        }
+
+        if decision.is_allowed() {
+            Ok(())
+        } else {
+            Err(Denial::Rejected)
+        }
+    }
+
+    /// Handles a JSON-RPC response sent by the shell.
+    ///
+    /// Returns `false` if the response does not belong to a pending
+    /// confirmation.
+    pub fn resolve(&self, id: &Value, response: &Value) -> bool {
+        let Some(sender) = id
+            .as_str()
+            .and_then(|id| self.confirmations.lock().unwrap().remove(id))
+        else {
+            return false;
+        };
+
+        let decision = response
+            .get("result")
+            .and_then(|result| result.get("decision"))
+            .and_then(|decision| serde_json::from_value(decision.clone()).ok())
+            .unwrap_or(Decision::Deny);
+
+        let _ = sender.send(decision);
+
+        true
+    }
+
+    /// Lists remembered decisions, grouped by principal.
+    pub fn remembered(&self) -> BTreeMap<String, BTreeMap<String, Decision>> {
+        self.remembered.lock().unwrap().0.clone()
+    }
+
+    /// Forgets a remembered decision, returning whether one existed.
+    pub fn forget(&self, principal: &str, tool: &str) -> bool {
+        let mut remembered = self.remembered.lock().unwrap();
+
+        let existed = remembered
+            .0
+            .get_mut(principal)
+            .and_then(|tools| tools.remove(tool))
+            .is_some();
+
+        if existed {
+            if let Err(error) = remembered.save(&self.grants) {
+                log::error!("Failed to save permission grants: {error}");
+            }
+        }
+
+        existed
+    }
+
+    async fn confirm(
+        &self,
+        tool: &dyn ToolHandler,
+        arguments: &Value,
+        context: &Context,
+    ) -> Decision {
+        let id = format!(
+            "permission-{}",
+            self.next_confirmation.fetch_add(1, Ordering::SeqCst)
+        );
+
+        let (sender, receiver) = oneshot::channel();
+        let _ = self
+            .confirmations
+            .lock()
+            .unwrap()
+            .insert(id.clone(), sender);
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "permission/request",
+            "params": {
+                "tool": tool.name(),
+                "description": tool.description(),
+                "risk": self.risk(tool),
+                "arguments": redact(arguments),
+                "caller": context.caller,
+            }
+        });
+
+        if context
+            .notifications
+            .send(request.to_string())
+            .await
+            .is_err()
+        {
+            let _ = self.confirmations.lock().unwrap().remove(&id);
+            return Decision::Deny;
+        }
+
+        match time::timeout(self.timeout, receiver).await {
+            Ok(Ok(decision)) => decision,
+            _ => {
+                let _ = self.confirmations.lock().unwrap().remove(&id);
+                Decision::Deny
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Policy")
+            .field("permissions", &self.permissions)
+            .finish()
+    }
+}
+
+/// A call waiting for the user to allow it, parsed from the
+/// `permission/request` sent by [`Policy::authorize`].
+///
+/// The call is denied if nobody answers within the timeout of the
+/// [`Policy`].
+#[derive(Debug, Clone)]
+pub struct Confirmation {
+    pub tool: String,
+    pub description: String,
+    pub arguments: Value,
+    pub caller: Caller,
+    id: String,
+    policy: Arc<Policy>,
+}
+
+impl Confirmation {
+    /// Parses a notification of a tool [`Context`], returning `None` if it
+    /// is not a `permission/request`.
+    pub fn parse(request: &str, policy: &Arc<Policy>) -> Option<Self> {
+        let request: Value = serde_json::from_str(request).ok()?;
+
+        if request["method"] != "permission/request" {
+            return None;
+        }
+
+        let params = &request["params"];
+
+        Some(Self {
+            tool: params["tool"].as_str()?.to_owned(),
+            description: params["description"].as_str()?.to_owned(),
+            arguments: params["arguments"].clone(),
+            caller: serde_json::from_value(params["caller"].clone()).unwrap_or_default(),
+            id: request["id"].as_str()?.to_owned(),
+            policy: policy.clone(),
+        })
+    }
+
+    pub fn answer(&self, decision: Decision) {
+        let _ = self.policy.resolve(
+            &json!(self.id),
+            &json!({ "result": { "decision": decision } }),
+        );
+    }
+}
+
+/// Decisions remembered per principal and tool, stored in `grants.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Remembered(BTreeMap<String, BTreeMap<String, Decision>>);
+
+impl Remembered {
+    fn path() -> PathBuf {
+        directory::config().join("grants.json")
+    }
+
+    fn fetch(path: &Path) -> Self {
+        std::fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    fn get(&self, principal: &str, tool: &str) -> Option<Decision> {
+        self.0.get(principal)?.get(tool).copied()
+    }
+
+    fn set(&mut self, principal: &str, tool: &str, decision: Decision) {
+        let _ = self
+            .0
+            .entry(principal.to_owned())
+            .or_default()
+            .insert(tool.to_owned(), decision);
+    }
+}
+
+/// Replaces the values of secret looking keys with `"[redacted]"`.
+pub fn redact(value: &Value) -> Value {
+    const SECRETS: &[&str] = &[
+        "password",
+        "passphrase",
+        "secret",
+        "token",
+        "api_key",
+        "apikey",
+    ];
+
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| {
+                    let key_lower = key.to_lowercase();
+
+                    if SECRETS.iter().any(|secret| key_lower.contains(secret)) {
+                        (key.clone(), json!("[redacted]"))
+                    } else {
+                        (key.clone(), redact(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        value => value.clone(),
+    }
+}
+
+/// Expands `~`, removes `.` and `..` lexically and resolves symlinks of the
+/// longest existing ancestor.
+fn resolve(path: &Path) -> PathBuf {
+    let path = match path.strip_prefix("~") {
+        Ok(rest) => home().join(rest),
+        Err(_) => path.to_path_buf(),
+    };
+
+    let mut normal = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                let _ = normal.pop();
+            }
+            component => normal.push(component),
+        }
+    }
+
+    let mut existing = normal.as_path();
+    let mut rest = Vec::new();
+
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_owned());
+                existing = parent;
+            }
+            _ => return normal,
+        }
+    }
+
+    let mut resolved = existing
+        .canonicalize()
+        .unwrap_or_else(|_| existing.to_path_buf());
+
+    resolved.extend(rest.into_iter().rev());
+    resolved
+}
+
+fn home() -> PathBuf {
+    std::env::var("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("/"))
+}
+
+fn wildcard(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+
+            (0..=name.len())
+                .filter(|i| name.is_char_boundary(*i))
+                .any(|i| wildcard(rest, &name[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_rules() {
+        let permissions = Permissions {
+            allow: vec!["/tmp".to_owned(), "/srv/data".to_owned()],
+            deny: vec!["/srv/data/private".to_owned(), "*.pem".to_owned()],
+            tools: BTreeMap::new(),
+        };
+
+        assert!(permissions.is_path_allowed(Path::new("/srv/data/notes.txt")));
+        assert!(!permissions.is_path_allowed(Path::new("/srv/data/private/a.txt")));
+        assert!(!permissions.is_path_allowed(Path::new("/srv/data/../data/private/a")));
+        assert!(!permissions.is_path_allowed(Path::new("/srv/data/cert.pem")));
+        assert!(!permissions.is_path_allowed(Path::new("/etc/hosts")));
+    }
+
+    #[test]
+    fn test_default_denies_ssh_keys() {
+        let permissions = Permissions::default();
+
+        assert!(!permissions.is_path_allowed(&home().join(".ssh/id_ed25519")));
+        assert!(!permissions.is_path_allowed(Path::new("/backup/keys/id_rsa.pub")));
+        assert!(permissions.is_path_allowed(&home().join("Documents/report.md")));
+    }
+
+    #[test]
+    fn test_models_are_scoped_to_their_app() {
+        let caller = |app: Option<&str>, model: Option<&str>| Caller {
+            app: app.map(str::to_owned),
+            model: model.map(str::to_owned),
+            session: None,
+        };
+
+        assert_eq!(
+            caller(Some("desktop"), Some("qwen")).principal(),
+            "app:desktop/model:qwen"
+        );
+        assert_ne!(
+            caller(Some("other"), Some("qwen")).principal(),
+            caller(Some("desktop"), Some("qwen")).principal()
+        );
+        assert_eq!(caller(Some("desktop"), None).principal(), "app:desktop");
+        assert_eq!(caller(None, None).principal(), "unknown");
+    }
+
+    #[test]
+    fn test_redact() {
+        let arguments = json!({ "ssid": "Home", "password": "hunter2" });
+
+        assert_eq!(
+            redact(&arguments),
+            json!({ "ssid": "Home", "password": "[redacted]" })
+        );
+    }
+
+    struct Fake {
+        name: &'static str,
+        risk: Risk,
+    }
+
+    impl ToolHandler for Fake {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn description(&self) -> &str {
+            "Does nothing."
+        }
+
+        fn input_schema(&self) -> Value {
+            json!({ "type": "object" })
+        }
+
+        fn risk(&self) -> Risk {
+            self.risk
+        }
+
+        fn call<'a>(
+            &'a self,
+            _arguments: Value,
+            _context: &'a Context,
+        ) -> futures::future::BoxFuture<'a, anyhow::Result<Value>> {
+            Box::pin(async { Ok(Value::Null) })
+        }
+    }
+
+    const DELETE: Fake = Fake {
+        name: "delete_file",
+        risk: Risk::Destructive,
+    };
+
+    fn caller(session: &str) -> Caller {
+        Caller {
+            app: Some("desktop".to_owned()),
+            model: Some("qwen".to_owned()),
+            session: Some(session.to_owned()),
+        }
+    }
+
+    fn grants() -> PathBuf {
+        std::env::temp_dir().join(format!("grants-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    /// Authorizes a call of `tool`, answering its confirmation, if one is
+    /// requested, with a JSON-RPC `response` built from the request id.
+    ///
+    /// Returns whether the user was asked.
+    async fn authorize(
+        policy: &Policy,
+        tool: &Fake,
+        caller: Caller,
+        response: impl FnOnce(&Value) -> Value,
+    ) -> (Result<(), Denial>, bool) {
+        let (notifications, mut requests) = tokio::sync::mpsc::channel(8);
+        let context = Context::new(notifications).with_caller(caller);
+
+        let arguments = json!({});
+
+        let authorization = policy.authorize(tool, &arguments, &context);
+        tokio::pin!(authorization);
+
+        let request = tokio::select! {
+            result = &mut authorization => return (result, false),
+            request = requests.recv() => request.unwrap(),
+        };
+
+        let request: Value = serde_json::from_str(&request).unwrap();
+        assert_eq!(request["method"], "permission/request");
+        assert_eq!(request["params"]["tool"], tool.name);
+
+        let _ = policy.resolve(&request["id"], &response(&request["id"]));
+
+        (authorization.await, true)
+    }
+
+    fn answer(decision: &'static str) -> impl FnOnce(&Value) -> Value {
+        move |id| json!({ "jsonrpc": "2.0", "id": id, "result": { "decision": decision } })
+    }
+
+    #[tokio::test]
+    async fn test_only_destructive_tools_are_confirmed() {
+        let grants = grants();
+        let policy = Policy::with_grants(Permissions::default(), &grants);
+
+        for risk in [Risk::Safe, Risk::Sensitive] {
+            let tool = Fake { name: "read", risk };
+
+            assert_eq!(
+                authorize(&policy, &tool, caller("a"), answer("deny")).await,
+                (Ok(()), false)
+            );
+        }
+
+        assert_eq!(
+            authorize(&policy, &DELETE, caller("a"), answer("allow_once")).await,
+            (Ok(()), true)
+        );
+        assert_eq!(
+            authorize(&policy, &DELETE, caller("a"), answer("deny")).await,
+            (Err(Denial::Rejected), true)
+        );
+
+        // Users may lower the risk of a tool
+        let permissions = Permissions {
+            tools: BTreeMap::from([("delete_file".to_owned(), Risk::Sensitive)]),
+            ..Permissions::default()
+        };
+        let policy = Policy::with_grants(permissions, &grants);
+
+        assert_eq!(
+            authorize(&policy, &DELETE, caller("a"), answer("deny")).await,
+            (Ok(()), false)
+        );
+        assert!(!grants.exists());
+    }
+
+    #[tokio::test]
+    async fn test_decisions_are_remembered() {
+        let grants = grants();
+        let policy = Policy::with_grants(Permissions::default(), &grants);
+        let principal = caller("a").principal();
+
+        // Once is once
+        let _ = authorize(&policy, &DELETE, caller("a"), answer("allow_once")).await;
+        assert_eq!(
+            authorize(&policy, &DELETE, caller("a"), answer("allow_once")).await,
+            (Ok(()), true)
+        );
+
+        // A session grant lasts for that session only
+        let _ = authorize(&policy, &DELETE, caller("a"), answer("allow_session")).await;
+        assert_eq!(
+            authorize(&policy, &DELETE, caller("a"), answer("deny")).await,
+            (Ok(()), false)
+        );
+        assert_eq!(
+            authorize(&policy, &DELETE, caller("b"), answer("deny")).await,
+            (Err(Denial::Rejected), true)
+        );
+        assert!(!grants.exists());
+
+        // Lasting decisions outlive the policy
+        let _ = authorize(&policy, &DELETE, caller("b"), answer("allow_always")).await;
+
+        let saved: Value = serde_json::from_slice(&std::fs::read(&grants).unwrap()).unwrap();
+        assert_eq!(
+            saved,
+            json!({ principal.clone(): { "delete_file": "allow_always" } })
+        );
+
+        let policy = Policy::with_grants(Permissions::default(), &grants);
+        assert_eq!(
+            authorize(&policy, &DELETE, caller("c"), answer("deny")).await,
+            (Ok(()), false)
+        );
+
+        assert!(policy.forget(&principal, "delete_file"));
+        assert!(!policy.forget(&principal, "delete_file"));
+        assert!(policy.remembered().values().all(BTreeMap::is_empty));
+
+        let saved: Value = serde_json::from_slice(&std::fs::read(&grants).unwrap()).unwrap();
+        assert_eq!(saved, json!({ principal.clone(): {} }));
+
+        // Denials too, without asking again
+        assert_eq!(
+            authorize(&policy, &DELETE, caller("c"), answer("deny_always")).await,
+            (Err(Denial::Rejected), true)
+        );
+        assert_eq!(
+            authorize(&policy, &DELETE, caller("d"), answer("allow_once")).await,
+            (Err(Denial::Remembered), false)
+        );
+
+        let policy = Policy::with_grants(Permissions::default(), &grants);
+        assert_eq!(
+            policy.remembered(),
+            BTreeMap::from([(
+                principal,
+                BTreeMap::from([("delete_file".to_owned(), Decision::DenyAlways)])
+            )])
+        );
+
+        std::fs::remove_file(&grants).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_confirmations_are_denied() {
+        let mut policy = Policy::with_grants(Permissions::default(), grants());
+        policy.timeout = Duration::from_millis(10);
+
+        let (notifications, mut requests) = tokio::sync::mpsc::channel(8);
+        let context = Context::new(notifications).with_caller(caller("a"));
+
+        assert_eq!(
+            policy.authorize(&DELETE, &json!({}), &context).await,
+            Err(Denial::Rejected)
+        );
+
+        // Late answers are ignored
+        let request: Value = serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+        assert!(!policy.resolve(
+            &request["id"],
+            &json!({ "result": { "decision": "allow_once" } })
+        ));
+
+        // Nobody to ask
+        drop(requests);
+        assert_eq!(
+            policy.authorize(&DELETE, &json!({}), &context).await,
+            Err(Denial::Rejected)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_responses_deny() {
+        let policy = Policy::with_grants(Permissions::default(), grants());
+
+        let responses: [fn(&Value) -> Value; 3] = [
+            |id| json!({ "jsonrpc": "2.0", "id": id, "error": { "code": -32000, "message": "closed" } }),
+            |id| json!({ "jsonrpc": "2.0", "id": id, "result": { "decision": "maybe" } }),
+            |id| json!({ "jsonrpc": "2.0", "id": id, "result": "allow_once" }),
+        ];
+
+        for response in responses {
+            assert_eq!(
+                authorize(&policy, &DELETE, caller("a"), response).await,
+                (Err(Denial::Rejected), true)
+            );
+        }
+
+        assert!(!policy.resolve(&json!("permission-404"), &json!({})));
+        assert!(!policy.resolve(&json!(1), &json!({})));
+    }
+
+    #[test]
+    fn test_confirmation_parse() {
+        let policy = Arc::new(Policy::with_grants(Permissions::default(), grants()));
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": "permission-1",
+            "method": "permission/request",
+            "params": {
+                "tool": "delete_file",
+                "description": "Deletes a file.",
+                "risk": "destructive",
+                "arguments": { "path": "/tmp/a" },
+                "caller": caller("a"),
+            }
+        });
+
+        let confirmation = Confirmation::parse(&request.to_string(), &policy).unwrap();
+        assert_eq!(confirmation.tool, "delete_file");
+        assert_eq!(confirmation.arguments, json!({ "path": "/tmp/a" }));
+        assert_eq!(confirmation.caller, caller("a"));
+
+        let malformed = |change: fn(&mut Value)| {
+            let mut request = request.clone();
+            change(&mut request);
+
+            Confirmation::parse(&request.to_string(), &policy)
+        };
+
+        assert!(malformed(|request| request["method"] = json!("voice/transcript")).is_none());
+        assert!(malformed(|request| request["id"] = json!(1)).is_none());
+        assert!(malformed(|request| request["params"]["tool"] = Value::Null).is_none());
+        assert!(malformed(|request| request["params"] = json!([])).is_none());
+        assert!(Confirmation::parse("{ not json", &policy).is_none());
+
+        // A missing caller is unknown, not a reason to drop the request
+        let confirmation = malformed(|request| request["params"]["caller"] = Value::Null).unwrap();
+        assert_eq!(confirmation.caller, Caller::default());
+    }
+}
@@ -0,0 +1,304 @@
+// Minimal JSON Schema validation for tool arguments and output formats.
+//
+// Only the subset used by tool input schemas and output formats is
+// supported: `type`, `enum`, `const`, `anyOf`, `properties`, `required`,
+// `additionalProperties`, `items`, `minItems`, `maxItems`, `minimum`,
+// `maximum`, `minLength` and `maxLength`, plus the `true` and `false`
+// schemas. Unknown keywords are ignored, so richer schemas still validate the
+// parts we understand.
+
+use serde_json::{Map, Value};
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "'{}' {}", self.path, self.message)
+        }
+    }
+}
+
+/// Validates `value` against `schema`, returning every violation found.
+pub fn validate(schema: &Value, value: &Value) -> Result<(), Vec<Violation>> {
+    let mut violations = Vec::new();
+    check(schema, value, "", &mut violations);
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+fn check(schema: &Value, value: &Value, path: &str, violations: &mut Vec<Violation>) {
+    let Some(schema) = schema.as_object() else {
+        // `true` accepts anything and `false` nothing
+        if schema == &Value::Bool(false) {
+            violations.push(Violation {
+                path: path.to_owned(),
+                message: "is not allowed".to_owned(),
+            });
+        }
+
+        return;
+    };
+
+    let mut fail = |message: String| {
+        violations.push(Violation {
+            path: path.to_owned(),
+            message,
+        })
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let matches = match expected {
+            Value::String(kind) => is_type(kind, value),
+            Value::Array(kinds) => kinds
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|kind| is_type(kind, value)),
+            _ => true,
+        };
+
+        if !matches {
+            fail(format!(
+                "must be of type {expected}, got {}",
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(constant) = schema.get("const") {
+        if value != constant {
+            fail(format!("must be {constant}"));
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            fail(format!("must be one of {}", Value::Array(options.clone())));
+        }
+    }
+
+    if let Some(schemas) = schema.get("anyOf").and_then(Value::as_array) {
+        if !schemas.iter().any(|schema| validate(schema, value).is_ok()) {
+            fail("must match one of the allowed schemas".to_owned());
+        }
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+            if number < minimum {
+                fail(format!("must be >= {minimum}"));
+            }
+        }
+
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+            if number > maximum {
+                fail(format!("must be <= {maximum}"));
+            }
+        }
+    }
+
+    if let Some(string) = value.as_str() {
+        let length = string.chars().count() as u64;
+
+        if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+            if length < min {
+                fail(format!("must be at least {min} characters long"));
+            }
+        }
+
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+            if length > max {
+                fail(format!("must be at most {max} characters long"));
+            }
+        }
+    }
+
+    if let Value::Array(array) = value {
+        let length = array.len() as u64;
+
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if length < min {
+                fail(format!("must have at least {min} items"));
+            }
+        }
+
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if length > max {
+                fail(format!("must have at most {max} items"));
+            }
+        }
+
+        if let Some(items) = schema.get("items") {
+            for (i, item) in array.iter().enumerate() {
+                check(items, item, &format!("{path}[{i}]"), violations);
+            }
+        }
+    }
+
+    if let Value::Object(object) = value {
+        check_object(schema, object, path, violations);
+    }
+}
+
+fn check_object(
+    schema: &Map<String, Value>,
+    object: &Map<String, Value>,
+    path: &str,
+    violations: &mut Vec<Violation>,
+) {
+    let properties = schema.get("properties").and_then(Value::as_object);
+
+    if let Some(required) = schema.get("required").and_then(Value::as_array) {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !object.contains_key(name) {
+                violations.push(Violation {
+                    path: join(path, name),
+                    message: "is required".to_owned(),
+                });
+            }
+        }
+    }
+
+    for (name, value) in object {
+        let property = properties
+            .and_then(|properties| properties.get(name))
+            .or_else(|| schema.get("additionalProperties"));
+
+        if let Some(property) = property {
+            check(property, value, &join(path, name), violations);
+        }
+    }
+}
+
+fn is_type(kind: &str, value: &Value) -> bool {
+    match kind {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64()
+                || value.is_u64()
+                || value.as_f64().is_some_and(|number| number.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_required_and_types() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "rows": { "type": "integer", "minimum": 1 }
+            },
+            "required": ["path"]
+        });
+
+        assert!(validate(&schema, &json!({ "path": "/tmp", "rows": 24 })).is_ok());
+
+        let violations = validate(&schema, &json!({ "rows": 0 })).unwrap_err();
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].path, "path");
+        assert_eq!(violations[1].path, "rows");
+
+        let violations = validate(&schema, &json!({ "path": 42 })).unwrap_err();
+        assert_eq!(
+            violations[0].to_string(),
+            "'path' must be of type \"string\", got number"
+        );
+    }
+
+    #[test]
+    fn test_nested_items() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "audio": { "type": "array", "items": { "type": "number" } }
+            },
+            "additionalProperties": false
+        });
+
+        assert!(validate(&schema, &json!({ "audio": [0.1, -0.5] })).is_ok());
+
+        let violations =
+            validate(&schema, &json!({ "audio": [0.1, "x"], "extra": 1 })).unwrap_err();
+        let paths: Vec<_> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, ["audio[1]", "extra"]);
+    }
+
+    #[test]
+    fn test_combinators() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "kind": { "const": "search" },
+                "limit": { "anyOf": [{ "type": "integer" }, { "type": "null" }] },
+                "terms": { "type": "array", "minItems": 1, "maxItems": 2 }
+            },
+            "additionalProperties": { "type": "string" }
+        });
+
+        assert!(validate(
+            &schema,
+            &json!({ "kind": "search", "limit": null, "terms": ["a"], "note": "x" })
+        )
+        .is_ok());
+
+        let violations = validate(
+            &schema,
+            &json!({ "kind": "answer", "limit": "5", "terms": [], "note": 1 }),
+        )
+        .unwrap_err();
+        let messages: Vec<_> = violations.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "'kind' must be \"search\"",
+                "'limit' must match one of the allowed schemas",
+                "'note' must be of type \"string\", got number",
+                "'terms' must have at least 1 items",
+            ]
+        );
+    }
+}
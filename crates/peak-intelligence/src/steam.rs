@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+pub mod vdf;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SteamGame {
     pub app_id: String,
@@ -9,83 +12,365 @@ pub struct SteamGame {
     pub install_dir: PathBuf,
     pub size_gb: f32,
     pub last_played: u64, // Unix Timestamp
+    pub state: InstallState,
+    /// A game added to Steam from outside of the store
+    pub is_shortcut: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InstallState {
+    Installed,
+    /// Downloading or applying an update, from 0 to 1
+    Updating(f32),
+    /// Waiting for an update that has not started
+    UpdateRequired,
+    /// Files are missing, being removed, or the state is unknown
+    Incomplete,
+}
+
+// `StateFlags` bits of an app manifest
+const UPDATE_REQUIRED: u64 = 1 << 1;
+const FULLY_INSTALLED: u64 = 1 << 2;
+const UPDATE_RUNNING: u64 = 1 << 8;
+const UPDATE_STARTED: u64 = 1 << 10;
+
+/// Steamworks Common Redistributables, which is not a game
+const REDISTRIBUTABLES: &str = "228980";
+
+impl SteamGame {
+    /// The URI that starts the game through Steam.
+    pub fn launch_uri(&self) -> String {
+        if self.is_shortcut {
+            format!("steam://rungameid/{}", self.app_id)
+        } else {
+            format!("steam://run/{}", self.app_id)
+        }
+    }
 }
 
 /// The Master Scan Function
 pub fn scan_library() -> Vec<SteamGame> {
-    let mut games = Vec::new();
+    locate().map(|steam| scan(&steam)).unwrap_or_default()
+}
 
-    // 1. Locate Steam Base Directory (Linux Standard)
+/// Finds the Steam installation, e.g. `~/.steam/steam`.
+pub fn locate() -> Option<PathBuf> {
     #[cfg(not(target_arch = "wasm32"))]
-    let home = std::env::var("HOME").unwrap_or_else(|_| "/".into());
+    let home = PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| "/".into()));
     #[cfg(target_arch = "wasm32")]
-    let home = "/".to_string();
-
-    let steam_base = PathBuf::from(format!("{}/.steam/steam", home));
-
-    // 2. Find Library Folders (Valve Logic)
-    // Steam can have games across multiple drives. We check the default first.
-    let library_folders = vec![
-        steam_base.join("steamapps"),
-        // Add logic here to parse libraryfolders.vdf for external drives if needed
-    ];
-
-    // 3. Iterate all libraries
-    for lib_path in library_folders {
-        if let Ok(entries) = fs::read_dir(lib_path) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                // We are looking for "appmanifest_12345.acf"
-                if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                    if file_name.starts_with("appmanifest_") && file_name.ends_with(".acf") {
-                        if let Some(game) = parse_manifest(&path) {
-                            games.push(game);
-                        }
-                    }
-                }
-            }
-        }
-    }
+    let home = PathBuf::from("/");
+
+    [
+        // Linux
+        home.join(".steam/steam"),
+        home.join(".local/share/Steam"),
+        // Flatpak
+        home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"),
+        // MacOS
+        home.join("Library/Application Support/Steam"),
+        // Windows
+        PathBuf::from(r"C:\Program Files (x86)\Steam"),
+    ]
+    .into_iter()
+    .find(|path| path.join("steamapps").is_dir())
+}
+
+/// Scans every library of a Steam installation, and the games added to it
+/// from outside of the store, most recently played first.
+pub fn scan(steam: &Path) -> Vec<SteamGame> {
+    let mut games: Vec<_> = libraries(steam)
+        .iter()
+        .flat_map(|library| scan_folder(library))
+        .collect();
+
+    games.extend(shortcuts(steam));
+
+    // A game may be listed by several libraries, e.g. after being moved
+    let mut seen = HashSet::new();
+    games.retain(|game| seen.insert((game.app_id.clone(), game.is_shortcut)));
 
     // Sort by Last Played (Most recent first)
-    games.sort_by(|a, b| b.last_played.cmp(&a.last_played));
+    games.sort_by_key(|game| std::cmp::Reverse(game.last_played));
     games
 }
 
-/// The VDF/ACF Parser (Simple Text Parsing)
-fn parse_manifest(path: &Path) -> Option<SteamGame> {
-    let content = fs::read_to_string(path).ok()?;
-
-    let mut app_id = String::new();
-    let mut name = String::new();
-    let mut install_dir = String::new();
-    let mut last_played = 0;
-
-    // Rough line-by-line parsing (Robust enough for Valve's format)
-    for line in content.lines() {
-        let clean = line.trim().replace("\"", "");
-        let parts: Vec<&str> = clean.split_whitespace().collect();
-
-        if parts.len() >= 2 {
-            match parts[0] {
-                "appid" => app_id = parts[1].to_string(),
-                "name" => name = clean.split_once("name").unwrap().1.trim().to_string(), // Handle spaces in names
-                "installdir" => install_dir = parts[1].to_string(),
-                "LastPlayed" => last_played = parts[1].parse::<u64>().unwrap_or(0),
-                _ => {}
+/// The library folders of a Steam installation, starting with its own.
+pub fn libraries(steam: &Path) -> Vec<PathBuf> {
+    let mut libraries = vec![steam.to_path_buf()];
+
+    let folders = fs::read_to_string(steam.join("steamapps/libraryfolders.vdf"))
+        .ok()
+        .and_then(|text| vdf::parse(&text).ok());
+
+    if let Some(folders) = folders.as_ref().and_then(|vdf| vdf.map("libraryfolders")) {
+        for (key, value) in folders.iter() {
+            // Numbered entries; older versions keep just the path
+            if key.parse::<u32>().is_err() {
+                continue;
+            }
+
+            let path = match value {
+                vdf::Value::Map(folder) => folder.str("path"),
+                value => value.as_str(),
+            };
+
+            if let Some(path) = path.map(PathBuf::from) {
+                if !libraries.contains(&path) {
+                    libraries.push(path);
+                }
             }
         }
     }
 
-    if !app_id.is_empty() && !name.is_empty() {
-        Some(SteamGame {
-            app_id,
-            name,
-            install_dir: PathBuf::from(install_dir),
-            size_gb: 0.0, // Calculate directory size later if needed
-            last_played,
+    libraries
+}
+
+/// The installed games of one library folder.
+pub fn scan_folder(library: &Path) -> Vec<SteamGame> {
+    let steamapps = library.join("steamapps");
+
+    let Ok(entries) = fs::read_dir(&steamapps) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            // We are looking for "appmanifest_12345.acf"
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("appmanifest_") && name.ends_with(".acf"))
+        })
+        .filter_map(|path| {
+            let text = fs::read_to_string(&path).ok()?;
+
+            parse_manifest(&text, &steamapps)
+        })
+        .filter(|game| game.app_id != REDISTRIBUTABLES)
+        .collect()
+}
+
+/// Reads an `appmanifest_*.acf` of a library's `steamapps` folder.
+pub fn parse_manifest(text: &str, steamapps: &Path) -> Option<SteamGame> {
+    let manifest = vdf::parse(text).ok()?;
+    let app = manifest.map("AppState")?;
+
+    let app_id = app.str("appid")?.to_owned();
+    let name = app.str("name")?.to_owned();
+
+    let flags = app.u64("StateFlags").unwrap_or(0);
+
+    let state = if flags & (UPDATE_RUNNING | UPDATE_STARTED) != 0 {
+        let downloaded = app.u64("BytesDownloaded").unwrap_or(0);
+        let total = app.u64("BytesToDownload").unwrap_or(0);
+
+        InstallState::Updating(if total > 0 {
+            (downloaded as f64 / total as f64).min(1.0) as f32
+        } else {
+            0.0
         })
+    } else if flags & UPDATE_REQUIRED != 0 {
+        InstallState::UpdateRequired
+    } else if flags & FULLY_INSTALLED != 0 {
+        InstallState::Installed
     } else {
-        None
+        InstallState::Incomplete
+    };
+
+    Some(SteamGame {
+        app_id,
+        name,
+        install_dir: steamapps
+            .join("common")
+            .join(app.str("installdir").unwrap_or_default()),
+        size_gb: app.u64("SizeOnDisk").unwrap_or(0) as f32 / 1_073_741_824.0,
+        last_played: app.u64("LastPlayed").unwrap_or(0),
+        state,
+        is_shortcut: false,
+    })
+}
+
+/// The games added from outside of the store, for every user.
+pub fn shortcuts(steam: &Path) -> Vec<SteamGame> {
+    let Ok(users) = fs::read_dir(steam.join("userdata")) else {
+        return Vec::new();
+    };
+
+    users
+        .flatten()
+        .filter_map(|user| fs::read(user.path().join("config/shortcuts.vdf")).ok())
+        .flat_map(|bytes| parse_shortcuts(&bytes))
+        .collect()
+}
+
+/// Reads a binary `shortcuts.vdf`.
+pub fn parse_shortcuts(bytes: &[u8]) -> Vec<SteamGame> {
+    let Ok(vdf) = vdf::parse_binary(bytes) else {
+        return Vec::new();
+    };
+
+    let Some(shortcuts) = vdf.map("shortcuts") else {
+        return Vec::new();
+    };
+
+    shortcuts
+        .iter()
+        .filter_map(|(_, shortcut)| {
+            let shortcut = shortcut.as_map()?;
+
+            // Steam keeps the id as a signed 32-bit number; the launch id
+            // puts it in the upper half
+            let app_id = shortcut.get("appid")?.as_i64()? as u32;
+            let game_id = (u64::from(app_id) << 32) | 0x0200_0000;
+
+            let start_dir = shortcut
+                .str("StartDir")
+                .unwrap_or_default()
+                .trim_matches('"');
+
+            Some(SteamGame {
+                app_id: game_id.to_string(),
+                name: shortcut.str("AppName")?.to_owned(),
+                install_dir: PathBuf::from(start_dir),
+                size_gb: 0.0,
+                last_played: shortcut.u64("LastPlayTime").unwrap_or(0),
+                state: InstallState::Installed,
+                is_shortcut: true,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("peak-steam-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&path).unwrap();
+
+        path.join(name)
+    }
+
+    fn manifest(app_id: &str, name: &str, flags: u32, last_played: u64) -> String {
+        format!(
+            r#""AppState"
+{{
+	"appid"		"{app_id}"
+	"Universe"		"1"
+	"name"		"{name}"
+	"StateFlags"		"{flags}"
+	"installdir"		"{name}"
+	"LastPlayed"		"{last_played}"
+	"SizeOnDisk"		"2147483648"
+	"BytesToDownload"		"400"
+	"BytesDownloaded"		"100"
+	"UserConfig"
+	{{
+		"language"		"english"
+	}}
+}}"#
+        )
+    }
+
+    fn shortcut(app_id: i32, name: &str, last_played: u32) -> Vec<u8> {
+        let mut bytes = vec![0x02];
+        bytes.extend_from_slice(b"appid\0");
+        bytes.extend_from_slice(&app_id.to_le_bytes());
+        bytes.push(0x01);
+        bytes.extend_from_slice(format!("AppName\0{name}\0").as_bytes());
+        bytes.push(0x01);
+        bytes.extend_from_slice(b"StartDir\0\"/opt/games\"\0");
+        bytes.push(0x02);
+        bytes.extend_from_slice(b"LastPlayTime\0");
+        bytes.extend_from_slice(&last_played.to_le_bytes());
+        bytes.push(0x00);
+        bytes.extend_from_slice(b"tags\0\x08");
+        bytes.push(0x08);
+
+        bytes
+    }
+
+    #[test]
+    fn test_scan() {
+        let steam = fixture("steam");
+        let external = steam.parent().unwrap().join("external");
+
+        fs::create_dir_all(steam.join("steamapps")).unwrap();
+        fs::create_dir_all(external.join("steamapps")).unwrap();
+        fs::create_dir_all(steam.join("userdata/1234/config")).unwrap();
+
+        fs::write(
+            steam.join("steamapps/libraryfolders.vdf"),
+            format!(
+                r#""libraryfolders"
+{{
+	"0"
+	{{
+		"path"		"{}"
+		"apps" {{ "620" "2147483648" }}
+	}}
+	"1"
+	{{
+		"path"		"{}"
+	}}
+}}"#,
+                steam.display(),
+                external.display()
+            ),
+        )
+        .unwrap();
+
+        let files = [
+            (&steam, "620", "Portal 2", 4, 1_700_000_000),
+            (&steam, "228980", "Steamworks Common Redistributables", 4, 0),
+            (&external, "570", "Dota 2", 6, 1_600_000_000),
+            (&external, "730", "Counter-Strike 2", 1030, 1_650_000_000),
+        ];
+
+        for (library, app_id, name, flags, last_played) in files {
+            fs::write(
+                library.join(format!("steamapps/appmanifest_{app_id}.acf")),
+                manifest(app_id, name, flags, last_played),
+            )
+            .unwrap();
+        }
+
+        let mut shortcuts = vec![0x00];
+        shortcuts.extend_from_slice(b"shortcuts\0");
+        shortcuts.push(0x00);
+        shortcuts.extend_from_slice(b"0\0");
+        shortcuts.extend(shortcut(-1_000_000, "Celeste", 1_800_000_000));
+        shortcuts.extend_from_slice(&[0x08, 0x08]);
+
+        fs::write(steam.join("userdata/1234/config/shortcuts.vdf"), shortcuts).unwrap();
+
+        assert_eq!(libraries(&steam), [steam.clone(), external.clone()]);
+
+        let games = scan(&steam);
+
+        let names: Vec<_> = games.iter().map(|game| game.name.as_str()).collect();
+        assert_eq!(names, ["Celeste", "Portal 2", "Counter-Strike 2", "Dota 2"]);
+
+        let celeste = &games[0];
+        assert!(celeste.is_shortcut);
+        assert_eq!(celeste.app_id, "18442449106447106048");
+        assert_eq!(
+            celeste.launch_uri(),
+            "steam://rungameid/18442449106447106048"
+        );
+        assert_eq!(celeste.install_dir, PathBuf::from("/opt/games"));
+
+        let portal = &games[1];
+        assert_eq!(portal.state, InstallState::Installed);
+        assert_eq!(portal.size_gb, 2.0);
+        assert_eq!(portal.install_dir, steam.join("steamapps/common/Portal 2"));
+        assert_eq!(portal.launch_uri(), "steam://run/620");
+
+        assert_eq!(games[2].state, InstallState::Updating(0.25));
+        assert_eq!(games[3].state, InstallState::UpdateRequired);
+
+        fs::remove_dir_all(steam.parent().unwrap()).unwrap();
     }
 }
@@ -0,0 +1,428 @@
+//! Valve's KeyValues format, which Steam keeps its library in.
+//!
+//! `libraryfolders.vdf` and the `appmanifest_*.acf` files are text:
+//!
+//! ```text
+//! "AppState"
+//! {
+//!     "appid"     "620"
+//!     "name"      "Portal 2"
+//! }
+//! ```
+//!
+//! `shortcuts.vdf`, the games added from outside of Steam, is the binary
+//! flavor, where every value starts with a byte saying what it is.
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Int(i64),
+    Float(f32),
+    Map(Map),
+}
+
+/// Keys and values in file order. Keys may repeat and are compared without
+/// case, as Steam does.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Map(pub Vec<(String, Value)>);
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    #[error("expected {expected} on line {line}, found {found}")]
+    Unexpected {
+        line: usize,
+        expected: &'static str,
+        found: String,
+    },
+    #[error("unexpected end of file")]
+    UnexpectedEnd,
+    #[error("unknown value type {0:#04x}")]
+    UnknownType(u8),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// The number, also when it was written as text.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(int) => Some(*int),
+            Value::String(string) => string.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::String(string) => string.trim().parse().ok(),
+            _ => self.as_i64().and_then(|int| u64::try_from(int).ok()),
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&Map> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+}
+
+impl Map {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    pub fn str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(Value::as_str)
+    }
+
+    pub fn u64(&self, key: &str) -> Option<u64> {
+        self.get(key).and_then(Value::as_u64)
+    }
+
+    pub fn map(&self, key: &str) -> Option<&Map> {
+        self.get(key).and_then(Value::as_map)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.0.iter().map(|(key, value)| (key.as_str(), value))
+    }
+}
+
+/// Reads the text format.
+pub fn parse(text: &str) -> Result<Map, Error> {
+    let mut tokens = Tokens {
+        text: text.strip_prefix('\u{feff}').unwrap_or(text),
+        line: 1,
+    };
+
+    let map = tokens.map()?;
+
+    match tokens.next()? {
+        None => Ok(map),
+        Some(token) => Err(tokens.unexpected("a key", token)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    String(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "{{"),
+            Token::Close => write!(f, "}}"),
+            Token::String(string) => write!(f, "{string:?}"),
+        }
+    }
+}
+
+struct Tokens<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+impl Tokens<'_> {
+    /// Pairs until a closing brace or the end.
+    fn map(&mut self) -> Result<Map, Error> {
+        let mut map = Map::default();
+
+        loop {
+            let key = match self.peek()? {
+                None | Some(Token::Close) => return Ok(map),
+                Some(Token::Open) => return Err(self.unexpected("a key", Token::Open)),
+                Some(Token::String(_)) => match self.next()? {
+                    Some(Token::String(key)) => key,
+                    _ => unreachable!(),
+                },
+            };
+
+            let value = match self.next()? {
+                Some(Token::String(value)) => Value::String(value),
+                Some(Token::Open) => {
+                    let value = self.map()?;
+
+                    match self.next()? {
+                        Some(Token::Close) => Value::Map(value),
+                        Some(token) => return Err(self.unexpected("}", token)),
+                        None => return Err(Error::UnexpectedEnd),
+                    }
+                }
+                Some(Token::Close) => return Err(self.unexpected("a value", Token::Close)),
+                None => return Err(Error::UnexpectedEnd),
+            };
+
+            self.condition();
+
+            map.0.push((key, value));
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<Token>, Error> {
+        let (text, line) = (self.text, self.line);
+        let token = self.next();
+
+        self.text = text;
+        self.line = line;
+
+        token
+    }
+
+    fn next(&mut self) -> Result<Option<Token>, Error> {
+        self.skip();
+
+        let mut characters = self.text.chars();
+
+        let token = match characters.next() {
+            None => return Ok(None),
+            Some('{') => {
+                self.text = characters.as_str();
+                Token::Open
+            }
+            Some('}') => {
+                self.text = characters.as_str();
+                Token::Close
+            }
+            Some('"') => Token::String(self.quoted()?),
+            Some(_) => {
+                let end = self
+                    .text
+                    .find(|character: char| {
+                        character.is_whitespace() || matches!(character, '{' | '}' | '"')
+                    })
+                    .unwrap_or(self.text.len());
+
+                let (word, rest) = self.text.split_at(end);
+                self.text = rest;
+
+                Token::String(word.to_owned())
+            }
+        };
+
+        Ok(Some(token))
+    }
+
+    fn quoted(&mut self) -> Result<String, Error> {
+        let mut string = String::new();
+        let mut characters = self.text[1..].char_indices();
+
+        while let Some((offset, character)) = characters.next() {
+            match character {
+                '"' => {
+                    self.text = &self.text[1 + offset + 1..];
+                    return Ok(string);
+                }
+                '\\' => match characters.next().map(|(_, character)| character) {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some(character) => string.push(character),
+                    None => break,
+                },
+                '\n' => {
+                    self.line += 1;
+                    string.push('\n');
+                }
+                character => string.push(character),
+            }
+        }
+
+        Err(Error::UnexpectedEnd)
+    }
+
+    /// Skips whitespace and comments.
+    fn skip(&mut self) {
+        loop {
+            let trimmed = self.text.trim_start();
+            self.line += self.text[..self.text.len() - trimmed.len()]
+                .matches('\n')
+                .count();
+            self.text = trimmed;
+
+            if !self.text.starts_with("//") {
+                return;
+            }
+
+            self.text = self.text.find('\n').map_or("", |end| &self.text[end..]);
+        }
+    }
+
+    /// Skips a platform condition after a value, e.g. `[$WIN32]`.
+    fn condition(&mut self) {
+        let trimmed = self.text.trim_start_matches([' ', '\t']);
+
+        if trimmed.starts_with('[') {
+            if let Some(end) = trimmed.find(']') {
+                self.text = &trimmed[end + 1..];
+            }
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str, found: Token) -> Error {
+        Error::Unexpected {
+            line: self.line,
+            expected,
+            found: found.to_string(),
+        }
+    }
+}
+
+const MAP: u8 = 0x00;
+const STRING: u8 = 0x01;
+const INT: u8 = 0x02;
+const FLOAT: u8 = 0x03;
+const POINTER: u8 = 0x04;
+const COLOR: u8 = 0x06;
+const UINT64: u8 = 0x07;
+const END: u8 = 0x08;
+const INT64: u8 = 0x0A;
+const ALTERNATE_END: u8 = 0x0B;
+
+/// Reads the binary format.
+pub fn parse_binary(bytes: &[u8]) -> Result<Map, Error> {
+    let mut bytes = bytes;
+
+    binary_map(&mut bytes)
+}
+
+fn binary_map(bytes: &mut &[u8]) -> Result<Map, Error> {
+    let mut map = Map::default();
+
+    loop {
+        // The outermost map may end with the file instead
+        let Some((&kind, rest)) = bytes.split_first() else {
+            return Ok(map);
+        };
+
+        *bytes = rest;
+
+        if matches!(kind, END | ALTERNATE_END) {
+            return Ok(map);
+        }
+
+        let key = c_string(bytes)?;
+
+        let value = match kind {
+            MAP => Value::Map(binary_map(bytes)?),
+            STRING => Value::String(c_string(bytes)?),
+            INT | POINTER | COLOR => Value::Int(i64::from(i32::from_le_bytes(take(bytes)?))),
+            FLOAT => Value::Float(f32::from_le_bytes(take(bytes)?)),
+            // Kept as text, which `i64` would not fit
+            UINT64 => Value::String(u64::from_le_bytes(take(bytes)?).to_string()),
+            INT64 => Value::Int(i64::from_le_bytes(take(bytes)?)),
+            kind => return Err(Error::UnknownType(kind)),
+        };
+
+        map.0.push((key, value));
+    }
+}
+
+fn c_string(bytes: &mut &[u8]) -> Result<String, Error> {
+    let end = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .ok_or(Error::UnexpectedEnd)?;
+
+    let string = String::from_utf8_lossy(&bytes[..end]).into_owned();
+    *bytes = &bytes[end + 1..];
+
+    Ok(string)
+}
+
+fn take<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], Error> {
+    if bytes.len() < N {
+        return Err(Error::UnexpectedEnd);
+    }
+
+    let (taken, rest) = bytes.split_at(N);
+    *bytes = rest;
+
+    Ok(taken.try_into().expect("N bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let vdf = parse(
+            "\u{feff}// Written by Steam
+            \"AppState\"
+            {
+                \"appid\"\t\t\"620\"
+                \"name\"\t\t\"Portal 2: \\\"Deluxe\\\"\"
+                \"UserConfig\" { \"language\" \"english\" }
+                unquoted    value [$WIN32]
+            }",
+        )
+        .unwrap();
+
+        let app = vdf.map("appstate").unwrap();
+
+        assert_eq!(app.str("appid"), Some("620"));
+        assert_eq!(app.u64("AppID"), Some(620));
+        assert_eq!(app.str("name"), Some("Portal 2: \"Deluxe\""));
+        assert_eq!(
+            app.map("UserConfig").unwrap().str("language"),
+            Some("english")
+        );
+        assert_eq!(app.str("unquoted"), Some("value"));
+
+        assert_eq!(
+            parse("\"a\"\n{\n\"b\" }"),
+            Err(Error::Unexpected {
+                line: 3,
+                expected: "a value",
+                found: "}".to_owned(),
+            })
+        );
+        assert_eq!(parse("\"a\" { \"b\" \"c\""), Err(Error::UnexpectedEnd));
+        assert_eq!(
+            parse("\"a\" \"b\" }").unwrap_err().to_string(),
+            "expected a key on line 1, found }"
+        );
+    }
+
+    #[test]
+    fn test_parse_binary() {
+        let mut bytes = vec![MAP];
+        bytes.extend_from_slice(b"shortcuts\0");
+        bytes.push(MAP);
+        bytes.extend_from_slice(b"0\0");
+        bytes.push(INT);
+        bytes.extend_from_slice(b"appid\0");
+        bytes.extend_from_slice(&(-5i32).to_le_bytes());
+        bytes.push(STRING);
+        bytes.extend_from_slice(b"AppName\0Celeste\0");
+        bytes.push(FLOAT);
+        bytes.extend_from_slice(b"scale\0");
+        bytes.extend_from_slice(&1.5f32.to_le_bytes());
+        bytes.extend_from_slice(&[END, END, END]);
+
+        let vdf = parse_binary(&bytes).unwrap();
+        let shortcut = vdf.map("shortcuts").unwrap().map("0").unwrap();
+
+        assert_eq!(shortcut.get("appid"), Some(&Value::Int(-5)));
+        assert_eq!(shortcut.str("appname"), Some("Celeste"));
+        assert_eq!(shortcut.get("scale"), Some(&Value::Float(1.5)));
+
+        assert_eq!(parse_binary(&bytes[..20]), Err(Error::UnexpectedEnd));
+        assert_eq!(
+            parse_binary(&[0x05, b'a', 0]),
+            Err(Error::UnknownType(0x05))
+        );
+    }
+}
@@ -1,23 +1,99 @@
 #[cfg(feature = "native")]
-use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use portable_pty::{native_pty_system, ChildKiller, CommandBuilder, MasterPty, PtySize};
 #[cfg(feature = "native")]
 use serde_json::json;
+use serde_json::Value;
+use std::collections::BTreeMap;
+#[cfg(feature = "native")]
+use std::collections::{HashMap, VecDeque};
 #[cfg(feature = "native")]
 use std::io::{Read, Write};
+use std::path::PathBuf;
 #[cfg(feature = "native")]
 use std::sync::{Arc, Mutex};
 #[cfg(feature = "native")]
 use std::thread;
 
+/// Bytes of output kept per session for [`TerminalManager::scrollback`].
+pub const SCROLLBACK_LIMIT: usize = 256 * 1024;
+
+/// How to start a terminal session.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Program to run; defaults to the user's login shell.
+    pub shell: Option<String>,
+    pub args: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    pub env: BTreeMap<String, String>,
+    pub rows: u16,
+    pub cols: u16,
+    /// Principal of the caller opening the session; only it may write to,
+    /// resize, read or close the session.
+    pub owner: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            shell: None,
+            args: Vec::new(),
+            cwd: None,
+            env: BTreeMap::new(),
+            rows: 24,
+            cols: 80,
+            owner: String::new(),
+        }
+    }
+}
+
+/// Runs any number of PTY sessions, addressed by ID.
+///
+/// Output is streamed as `terminal/output` notifications carrying the
+/// session ID, and a `terminal/exit` notification is sent once the process
+/// ends. Exited sessions stay around, so their scrollback can still be
+/// fetched, until they are closed.
 #[cfg(feature = "native")]
 pub struct TerminalManager {
-    pty_pair: Arc<Mutex<Option<portable_pty::PtyPair>>>,
-    writer: Arc<Mutex<Option<Box<dyn Write + Send>>>>,
+    sessions: Arc<Mutex<HashMap<String, Arc<Session>>>>,
 }
 
 #[cfg(not(feature = "native"))]
 pub struct TerminalManager;
 
+#[cfg(feature = "native")]
+struct Session {
+    owner: String,
+    shell: String,
+    cwd: Option<PathBuf>,
+    pid: Option<u32>,
+    master: Mutex<Box<dyn MasterPty + Send>>,
+    writer: Mutex<Box<dyn Write + Send>>,
+    killer: Mutex<Box<dyn ChildKiller + Send + Sync>>,
+    scrollback: Mutex<Scrollback>,
+    exit_code: Mutex<Option<u32>>,
+}
+
+#[cfg(feature = "native")]
+#[derive(Default)]
+struct Scrollback {
+    buffer: VecDeque<u8>,
+    dropped: u64,
+}
+
+#[cfg(feature = "native")]
+impl Scrollback {
+    fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend(bytes);
+
+        let excess = self.buffer.len().saturating_sub(SCROLLBACK_LIMIT);
+
+        if excess > 0 {
+            let _ = self.buffer.drain(..excess);
+            self.dropped += excess as u64;
+        }
+    }
+}
+
 impl Default for TerminalManager {
     fn default() -> Self {
         Self::new()
@@ -28,8 +104,7 @@ impl TerminalManager {
     #[cfg(feature = "native")]
     pub fn new() -> Self {
         Self {
-            pty_pair: Arc::new(Mutex::new(None)),
-            writer: Arc::new(Mutex::new(None)),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -41,49 +116,86 @@ impl TerminalManager {
     #[cfg(feature = "native")]
     pub fn open(
         &self,
-        rows: u16,
-        cols: u16,
+        options: Options,
         tx: tokio::sync::mpsc::Sender<String>,
-    ) -> anyhow::Result<serde_json::Value> {
+    ) -> anyhow::Result<Value> {
         let pty_system = native_pty_system();
         let pair = pty_system.openpty(PtySize {
-            rows,
-            cols,
+            rows: options.rows,
+            cols: options.cols,
             pixel_width: 0,
             pixel_height: 0,
         })?;
 
-        let shell = if cfg!(target_os = "windows") {
-            "powershell.exe"
-        } else {
-            "bash"
+        let mut cmd = match &options.shell {
+            Some(shell) => CommandBuilder::new(shell),
+            None => CommandBuilder::new_default_prog(),
         };
 
-        let cmd = CommandBuilder::new(shell);
-        let _child = pair.slave.spawn_command(cmd)?;
+        cmd.args(&options.args);
+
+        if let Some(cwd) = &options.cwd {
+            cmd.cwd(cwd);
+        }
+
+        for (key, value) in &options.env {
+            cmd.env(key, value);
+        }
+
+        let shell = options.shell.clone().unwrap_or_else(|| cmd.get_shell());
+
+        let mut child = pair.slave.spawn_command(cmd)?;
+
+        // The reader only sees EOF once every handle to the slave is closed
+        drop(pair.slave);
 
         let reader = pair.master.try_clone_reader()?;
         let writer = pair.master.take_writer()?;
 
-        let mut pty_pair_lock = self.pty_pair.lock().unwrap();
-        *pty_pair_lock = Some(pair);
+        let id = uuid::Uuid::new_v4().to_string();
+        let pid = child.process_id();
+
+        let session = Arc::new(Session {
+            owner: options.owner,
+            shell,
+            cwd: options.cwd,
+            pid,
+            master: Mutex::new(pair.master),
+            writer: Mutex::new(writer),
+            killer: Mutex::new(child.clone_killer()),
+            scrollback: Mutex::new(Scrollback::default()),
+            exit_code: Mutex::new(None),
+        });
 
-        let mut writer_lock = self.writer.lock().unwrap();
-        *writer_lock = Some(writer);
+        let _ = self
+            .sessions
+            .lock()
+            .unwrap()
+            .insert(id.clone(), session.clone());
 
-        let tx = tx.clone();
         // Spawn reader thread
+        let session_id = id.clone();
         thread::spawn(move || {
             let mut reader = reader;
-            let mut buffer = [0u8; 1024];
+            let mut buffer = [0u8; 4096];
+            let mut pending = Vec::new();
+
             loop {
                 match reader.read(&mut buffer) {
                     Ok(n) if n > 0 => {
-                        let data = String::from_utf8_lossy(&buffer[..n]).to_string();
+                        session.scrollback.lock().unwrap().push(&buffer[..n]);
+
+                        pending.extend_from_slice(&buffer[..n]);
+                        let data = take_utf8(&mut pending);
+
+                        if data.is_empty() {
+                            continue;
+                        }
+
                         let notification = json!({
                             "jsonrpc": "2.0",
                             "method": "terminal/output",
-                            "params": { "data": data }
+                            "params": { "session": session_id, "data": data }
                         });
                         let _ = tx.blocking_send(notification.to_string());
                     }
@@ -91,56 +203,392 @@ impl TerminalManager {
                     Err(_) => break,
                 }
             }
+
+            let status = child.wait().ok();
+            let code = status.as_ref().map(|status| status.exit_code());
+
+            *session.exit_code.lock().unwrap() = Some(code.unwrap_or(1));
+
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "terminal/exit",
+                "params": {
+                    "session": session_id,
+                    "code": code,
+                    "success": status.is_some_and(|status| status.success()),
+                }
+            });
+            let _ = tx.blocking_send(notification.to_string());
         });
 
-        Ok(json!("Terminal opened"))
+        Ok(json!({ "session": id, "pid": pid }))
     }
 
     #[cfg(not(feature = "native"))]
     pub fn open(
         &self,
+        _options: Options,
+        _tx: tokio::sync::mpsc::Sender<String>,
+    ) -> anyhow::Result<Value> {
+        Err(anyhow::anyhow!("Terminal not supported on web"))
+    }
+
+    #[cfg(feature = "native")]
+    pub fn write(&self, session: &str, data: &str, caller: &str) -> anyhow::Result<Value> {
+        let session = self.running(session, caller)?;
+
+        let mut writer = session.writer.lock().unwrap();
+        writer.write_all(data.as_bytes())?;
+        writer.flush()?;
+
+        Ok(json!("Data written"))
+    }
+
+    #[cfg(not(feature = "native"))]
+    pub fn write(&self, _session: &str, _data: &str, _caller: &str) -> anyhow::Result<Value> {
+        Err(anyhow::anyhow!("Terminal not supported on web"))
+    }
+
+    #[cfg(feature = "native")]
+    pub fn resize(
+        &self,
+        session: &str,
+        rows: u16,
+        cols: u16,
+        caller: &str,
+    ) -> anyhow::Result<Value> {
+        let session = self.running(session, caller)?;
+
+        session.master.lock().unwrap().resize(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
+
+        Ok(json!("Terminal resized"))
+    }
+
+    #[cfg(not(feature = "native"))]
+    pub fn resize(
+        &self,
+        _session: &str,
         _rows: u16,
         _cols: u16,
-        _tx: tokio::sync::mpsc::Sender<String>,
-    ) -> anyhow::Result<serde_json::Value> {
+        _caller: &str,
+    ) -> anyhow::Result<Value> {
         Err(anyhow::anyhow!("Terminal not supported on web"))
     }
 
+    /// Kills the process, if still running, and forgets the session.
     #[cfg(feature = "native")]
-    pub fn write(&self, data: &str) -> anyhow::Result<serde_json::Value> {
-        let mut writer_lock = self.writer.lock().unwrap();
-        if let Some(writer) = writer_lock.as_mut() {
-            writer.write_all(data.as_bytes())?;
-            writer.flush()?;
-            Ok(json!("Data written"))
-        } else {
-            Err(anyhow::anyhow!("Terminal not open"))
+    pub fn close(&self, session: &str, caller: &str) -> anyhow::Result<Value> {
+        let session = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let _ = owned(sessions.get(session), session, caller)?;
+
+            sessions.remove(session).expect("session was found")
+        };
+
+        if session.exit_code.lock().unwrap().is_none() {
+            let _ = session.killer.lock().unwrap().kill();
         }
+
+        Ok(json!("Terminal closed"))
     }
 
     #[cfg(not(feature = "native"))]
-    pub fn write(&self, _data: &str) -> anyhow::Result<serde_json::Value> {
+    pub fn close(&self, _session: &str, _caller: &str) -> anyhow::Result<Value> {
         Err(anyhow::anyhow!("Terminal not supported on web"))
     }
 
+    /// Returns up to `limit` of the most recent output bytes of a session.
     #[cfg(feature = "native")]
-    pub fn resize(&self, rows: u16, cols: u16) -> anyhow::Result<serde_json::Value> {
-        let pty_pair_lock = self.pty_pair.lock().unwrap();
-        if let Some(pair) = pty_pair_lock.as_ref() {
-            pair.master.resize(PtySize {
-                rows,
-                cols,
-                pixel_width: 0,
-                pixel_height: 0,
-            })?;
-            Ok(json!("Terminal resized"))
-        } else {
-            Err(anyhow::anyhow!("Terminal not open"))
-        }
+    pub fn scrollback(
+        &self,
+        session: &str,
+        limit: Option<usize>,
+        caller: &str,
+    ) -> anyhow::Result<Value> {
+        let session = self.session(session, caller)?;
+        let scrollback = session.scrollback.lock().unwrap();
+
+        let limit = limit.unwrap_or(SCROLLBACK_LIMIT);
+        let skip = scrollback.buffer.len().saturating_sub(limit);
+
+        let mut bytes: Vec<u8> = scrollback.buffer.iter().skip(skip).copied().collect();
+
+        // Do not start in the middle of a UTF-8 sequence
+        let start = bytes
+            .iter()
+            .position(|byte| (*byte & 0xC0) != 0x80)
+            .unwrap_or(bytes.len());
+        let _ = bytes.drain(..start);
+
+        Ok(json!({
+            "data": String::from_utf8_lossy(&bytes),
+            "truncated": scrollback.dropped > 0 || skip > 0,
+        }))
+    }
+
+    #[cfg(not(feature = "native"))]
+    pub fn scrollback(
+        &self,
+        _session: &str,
+        _limit: Option<usize>,
+        _caller: &str,
+    ) -> anyhow::Result<Value> {
+        Err(anyhow::anyhow!("Terminal not supported on web"))
+    }
+
+    #[cfg(feature = "native")]
+    pub fn list(&self) -> anyhow::Result<Value> {
+        let sessions = self.sessions.lock().unwrap();
+
+        Ok(json!(sessions
+            .iter()
+            .map(|(id, session)| {
+                let exit_code = *session.exit_code.lock().unwrap();
+
+                json!({
+                    "session": id,
+                    "shell": session.shell,
+                    "cwd": session.cwd,
+                    "pid": session.pid,
+                    "running": exit_code.is_none(),
+                    "exit_code": exit_code,
+                })
+            })
+            .collect::<Vec<_>>()))
     }
 
     #[cfg(not(feature = "native"))]
-    pub fn resize(&self, _rows: u16, _cols: u16) -> anyhow::Result<serde_json::Value> {
+    pub fn list(&self) -> anyhow::Result<Value> {
         Err(anyhow::anyhow!("Terminal not supported on web"))
     }
+
+    /// Returns a session of `caller`.
+    #[cfg(feature = "native")]
+    fn session(&self, id: &str, caller: &str) -> anyhow::Result<Arc<Session>> {
+        owned(self.sessions.lock().unwrap().get(id), id, caller).cloned()
+    }
+
+    #[cfg(feature = "native")]
+    fn running(&self, id: &str, caller: &str) -> anyhow::Result<Arc<Session>> {
+        let session = self.session(id, caller)?;
+
+        if session.exit_code.lock().unwrap().is_some() {
+            return Err(anyhow::anyhow!("Terminal session has exited: {id}"));
+        }
+
+        Ok(session)
+    }
+}
+
+/// Checks that `session` was opened by `caller`.
+#[cfg(feature = "native")]
+fn owned<'a>(
+    session: Option<&'a Arc<Session>>,
+    id: &str,
+    caller: &str,
+) -> anyhow::Result<&'a Arc<Session>> {
+    let session = session.ok_or_else(|| anyhow::anyhow!("Unknown terminal session: {id}"))?;
+
+    if session.owner != caller {
+        return Err(anyhow::anyhow!(
+            "Terminal session {id} was opened by another caller"
+        ));
+    }
+
+    Ok(session)
+}
+
+/// Takes the longest valid UTF-8 prefix, keeping an incomplete trailing
+/// sequence for the next read. Invalid bytes are replaced.
+#[cfg(feature = "native")]
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let valid = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        Err(error) if error.error_len().is_none() => error.valid_up_to(),
+        Err(_) => pending.len(),
+    };
+
+    let rest = pending.split_off(valid);
+    let data = String::from_utf8_lossy(pending).into_owned();
+    *pending = rest;
+
+    data
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_utf8_keeps_partial_sequence() {
+        let mut pending = "héllo".as_bytes()[..2].to_vec();
+
+        assert_eq!(take_utf8(&mut pending), "h");
+        assert_eq!(pending.len(), 1);
+
+        pending.extend_from_slice(&"héllo".as_bytes()[2..]);
+        assert_eq!(take_utf8(&mut pending), "éllo");
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_scrollback_is_bounded() {
+        let mut scrollback = Scrollback::default();
+
+        scrollback.push(&vec![b'a'; SCROLLBACK_LIMIT]);
+        scrollback.push(b"tail");
+
+        assert_eq!(scrollback.buffer.len(), SCROLLBACK_LIMIT);
+        assert_eq!(scrollback.dropped, 4);
+        assert!(scrollback.buffer.iter().rev().take(4).eq(b"liat".iter()));
+    }
+
+    const OWNER: &str = "owner";
+
+    fn script(script: &str) -> Options {
+        Options {
+            shell: Some("/bin/sh".to_owned()),
+            args: vec!["-c".to_owned(), script.to_owned()],
+            owner: OWNER.to_owned(),
+            ..Options::default()
+        }
+    }
+
+    fn open(
+        terminals: &TerminalManager,
+        options: Options,
+        tx: &tokio::sync::mpsc::Sender<String>,
+    ) -> String {
+        let opened = terminals.open(options, tx.clone()).unwrap();
+
+        opened["session"].as_str().unwrap().to_owned()
+    }
+
+    /// Collects the output and the `terminal/exit` params of each session
+    /// until all of `sessions` have exited.
+    async fn until_exit(
+        rx: &mut tokio::sync::mpsc::Receiver<String>,
+        sessions: &[&str],
+    ) -> HashMap<String, (String, Value)> {
+        let mut sessions: HashMap<String, (String, Value)> = sessions
+            .iter()
+            .map(|session| (session.to_string(), (String::new(), Value::Null)))
+            .collect();
+
+        while sessions.values().any(|(_, exit)| exit.is_null()) {
+            let notification = tokio::time::timeout(std::time::Duration::from_secs(10), rx.recv())
+                .await
+                .expect("terminal did not exit")
+                .unwrap();
+            let notification: Value = serde_json::from_str(&notification).unwrap();
+            let params = &notification["params"];
+            let (output, exit) = sessions
+                .get_mut(params["session"].as_str().unwrap())
+                .unwrap();
+
+            match notification["method"].as_str().unwrap() {
+                "terminal/output" => output.push_str(params["data"].as_str().unwrap()),
+                "terminal/exit" => *exit = params.clone(),
+                method => panic!("unexpected notification {method}"),
+            }
+        }
+
+        sessions
+    }
+
+    #[tokio::test]
+    async fn test_sessions_are_independent() {
+        let terminals = TerminalManager::new();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        let first = open(&terminals, script("read line; echo got:$line"), &tx);
+        let second = open(&terminals, script("read line; echo got:$line"), &tx);
+
+        terminals.write(&second, "two\n", OWNER).unwrap();
+        terminals.write(&first, "one\n", OWNER).unwrap();
+
+        let exits = until_exit(&mut rx, &[&first, &second]).await;
+
+        assert!(exits[&first].0.contains("got:one"));
+        assert!(!exits[&first].0.contains("two"));
+        assert!(exits[&second].0.contains("got:two"));
+        assert!(!exits[&second].0.contains("one"));
+
+        let scrollback = terminals.scrollback(&first, None, OWNER).unwrap();
+        assert!(scrollback["data"].as_str().unwrap().contains("got:one"));
+        assert!(!scrollback["data"].as_str().unwrap().contains("two"));
+    }
+
+    #[tokio::test]
+    async fn test_exit_is_notified() {
+        let terminals = TerminalManager::new();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        let session = open(&terminals, script("echo bye; exit 3"), &tx);
+
+        let exits = until_exit(&mut rx, &[&session]).await;
+        let (output, exit) = &exits[&session];
+
+        assert!(output.contains("bye"));
+        assert_eq!(exit["code"], 3);
+        assert_eq!(exit["success"], false);
+
+        // The scrollback outlives the process, but it takes no more input
+        let listed = terminals.list().unwrap();
+        assert_eq!(listed[0]["running"], false);
+        assert_eq!(listed[0]["exit_code"], 3);
+        assert!(terminals.write(&session, "ls\n", OWNER).is_err());
+        assert!(terminals.scrollback(&session, None, OWNER).unwrap()["data"]
+            .as_str()
+            .unwrap()
+            .contains("bye"));
+    }
+
+    #[tokio::test]
+    async fn test_close_kills_and_forgets_the_session() {
+        let terminals = TerminalManager::new();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        let session = open(&terminals, script("sleep 30"), &tx);
+
+        terminals.close(&session, OWNER).unwrap();
+
+        let exits = until_exit(&mut rx, &[&session]).await;
+
+        assert_eq!(exits[&session].1["success"], false);
+        assert_eq!(terminals.list().unwrap(), json!([]));
+        assert!(terminals.close(&session, OWNER).is_err());
+        assert!(terminals.scrollback(&session, None, OWNER).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_only_the_owner_may_use_a_session() {
+        let terminals = TerminalManager::new();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        let session = open(&terminals, script("read line; echo got:$line"), &tx);
+
+        for error in [
+            terminals.write(&session, "intruder\n", "intruder"),
+            terminals.resize(&session, 10, 10, "intruder"),
+            terminals.scrollback(&session, None, "intruder"),
+            terminals.close(&session, "intruder"),
+        ] {
+            assert!(error
+                .unwrap_err()
+                .to_string()
+                .contains("opened by another caller"));
+        }
+
+        terminals.resize(&session, 10, 10, OWNER).unwrap();
+        terminals.write(&session, "owner\n", OWNER).unwrap();
+
+        let exits = until_exit(&mut rx, &[&session]).await;
+
+        assert!(exits[&session].0.contains("got:owner"));
+        assert!(!exits[&session].0.contains("intruder"));
+
+        terminals.close(&session, OWNER).unwrap();
+    }
 }
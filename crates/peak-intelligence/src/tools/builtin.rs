@@ -0,0 +1,1107 @@
+// Built-in tool handlers exposed by the intelligence daemon.
+
+use crate::audit;
+use crate::kernel::Monitor;
+use crate::mcp::{Context, Registry, ToolHandler};
+use crate::policy::Risk;
+use crate::terminal::{self, TerminalManager};
+use crate::tools::{self, search};
+use crate::voice::{self, audio, listener, Listener, Transcriber, Wav, VOICE};
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde_json::{json, Value};
+
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Builds a registry containing every built-in tool.
+pub fn registry(terminal: Arc<TerminalManager>) -> Registry {
+    let mut registry = Registry::new();
+
+    registry.register(ListProcesses);
+    registry.register(TerminalOpen(terminal.clone()));
+    registry.register(TerminalWrite(terminal.clone()));
+    registry.register(TerminalResize(terminal.clone()));
+    registry.register(TerminalClose(terminal.clone()));
+    registry.register(TerminalScrollback(terminal.clone()));
+    registry.register(TerminalList(terminal));
+    registry.register(ReadFile);
+    registry.register(WriteFile);
+    registry.register(KillProcess);
+    registry.register(ReadDir);
+    registry.register(ScanWifi);
+    registry.register(ConnectWifi);
+    registry.register(SearchFiles);
+    registry.register(RunCommand);
+    registry.register(SystemTelemetry::default());
+    registry.register(SpeechToText);
+    registry.register(TextToSpeech);
+
+    registry
+}
+
+fn string<'a>(arguments: &'a Value, name: &str) -> &'a str {
+    arguments[name].as_str().unwrap_or_default()
+}
+
+fn path(arguments: &Value, name: &str) -> Vec<PathBuf> {
+    vec![PathBuf::from(string(arguments, name))]
+}
+
+fn dimension(arguments: &Value, name: &str, default: u16) -> u16 {
+    arguments[name]
+        .as_u64()
+        .map(|value| value.min(u16::MAX as u64) as u16)
+        .unwrap_or(default)
+}
+
+pub struct ListProcesses;
+
+impl ToolHandler for ListProcesses {
+    fn name(&self) -> &str {
+        "list_processes"
+    }
+
+    fn description(&self) -> &str {
+        "List all running processes with PID, CPU, and Memory usage."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Safe
+    }
+
+    fn activity(&self, _arguments: &Value) -> String {
+        "Listing processes...".to_owned()
+    }
+
+    fn call<'a>(
+        &'a self,
+        _arguments: Value,
+        _context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move { tools::list_processes() }.boxed()
+    }
+}
+
+pub struct TerminalOpen(pub Arc<TerminalManager>);
+
+impl ToolHandler for TerminalOpen {
+    fn name(&self) -> &str {
+        "terminal_open"
+    }
+
+    fn description(&self) -> &str {
+        "Open a new terminal PTY session and return its session ID."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "shell": { "type": "string", "description": "Program to run, defaults to the login shell" },
+                "args": { "type": "array", "items": { "type": "string" } },
+                "cwd": { "type": "string", "description": "Working directory" },
+                "env": { "type": "object", "additionalProperties": { "type": "string" } },
+                "rows": { "type": "integer", "minimum": 1, "default": 24 },
+                "cols": { "type": "integer", "minimum": 1, "default": 80 }
+            }
+        })
+    }
+
+    /// Runs any program the caller names.
+    fn risk(&self) -> Risk {
+        Risk::Destructive
+    }
+
+    fn paths(&self, arguments: &Value) -> Vec<PathBuf> {
+        arguments["cwd"]
+            .as_str()
+            .map(PathBuf::from)
+            .into_iter()
+            .collect()
+    }
+
+    fn activity(&self, _arguments: &Value) -> String {
+        "Opening a terminal...".to_owned()
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move {
+            let options = terminal::Options {
+                shell: arguments["shell"].as_str().map(str::to_owned),
+                args: serde_json::from_value(arguments["args"].clone()).unwrap_or_default(),
+                cwd: arguments["cwd"].as_str().map(PathBuf::from),
+                env: serde_json::from_value(arguments["env"].clone()).unwrap_or_default(),
+                rows: dimension(&arguments, "rows", 24),
+                cols: dimension(&arguments, "cols", 80),
+                owner: context.caller.principal(),
+            };
+
+            self.0.open(options, context.notifications.clone())
+        }
+        .boxed()
+    }
+}
+
+pub struct TerminalWrite(pub Arc<TerminalManager>);
+
+impl ToolHandler for TerminalWrite {
+    fn name(&self) -> &str {
+        "terminal_write"
+    }
+
+    fn description(&self) -> &str {
+        "Write data to a terminal session."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "session": { "type": "string" },
+                "data": { "type": "string" }
+            },
+            "required": ["session", "data"]
+        })
+    }
+
+    /// Only reaches sessions the caller opened, which needed a confirmation
+    /// already.
+    fn risk(&self) -> Risk {
+        Risk::Sensitive
+    }
+
+    fn activity(&self, _arguments: &Value) -> String {
+        "Typing in the terminal...".to_owned()
+    }
+
+    /// Keystrokes may be passwords typed at a prompt.
+    fn audit_summary(&self, arguments: &Value) -> Value {
+        audit::summarize(arguments, &["data"])
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move {
+            self.0.write(
+                string(&arguments, "session"),
+                string(&arguments, "data"),
+                &context.caller.principal(),
+            )
+        }
+        .boxed()
+    }
+}
+
+pub struct TerminalResize(pub Arc<TerminalManager>);
+
+impl ToolHandler for TerminalResize {
+    fn name(&self) -> &str {
+        "terminal_resize"
+    }
+
+    fn description(&self) -> &str {
+        "Resize a terminal session."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "session": { "type": "string" },
+                "rows": { "type": "integer", "minimum": 1 },
+                "cols": { "type": "integer", "minimum": 1 }
+            },
+            "required": ["session", "rows", "cols"]
+        })
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Safe
+    }
+
+    fn activity(&self, _arguments: &Value) -> String {
+        "Resizing the terminal...".to_owned()
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move {
+            let rows = dimension(&arguments, "rows", 24);
+            let cols = dimension(&arguments, "cols", 80);
+
+            self.0.resize(
+                string(&arguments, "session"),
+                rows,
+                cols,
+                &context.caller.principal(),
+            )
+        }
+        .boxed()
+    }
+}
+
+pub struct TerminalClose(pub Arc<TerminalManager>);
+
+impl ToolHandler for TerminalClose {
+    fn name(&self) -> &str {
+        "terminal_close"
+    }
+
+    fn description(&self) -> &str {
+        "Close a terminal session, killing its process if still running."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "session": { "type": "string" }
+            },
+            "required": ["session"]
+        })
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Sensitive
+    }
+
+    fn activity(&self, _arguments: &Value) -> String {
+        "Closing the terminal...".to_owned()
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move {
+            self.0
+                .close(string(&arguments, "session"), &context.caller.principal())
+        }
+        .boxed()
+    }
+}
+
+pub struct TerminalScrollback(pub Arc<TerminalManager>);
+
+impl ToolHandler for TerminalScrollback {
+    fn name(&self) -> &str {
+        "terminal_scrollback"
+    }
+
+    fn description(&self) -> &str {
+        "Fetch the most recent output of a terminal session."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "session": { "type": "string" },
+                "limit": { "type": "integer", "minimum": 1, "description": "Maximum number of bytes" }
+            },
+            "required": ["session"]
+        })
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Sensitive
+    }
+
+    fn activity(&self, _arguments: &Value) -> String {
+        "Reading terminal output...".to_owned()
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move {
+            let limit = arguments["limit"].as_u64().map(|limit| limit as usize);
+
+            self.0.scrollback(
+                string(&arguments, "session"),
+                limit,
+                &context.caller.principal(),
+            )
+        }
+        .boxed()
+    }
+}
+
+pub struct TerminalList(pub Arc<TerminalManager>);
+
+impl ToolHandler for TerminalList {
+    fn name(&self) -> &str {
+        "terminal_list"
+    }
+
+    fn description(&self) -> &str {
+        "List open terminal sessions."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Safe
+    }
+
+    fn activity(&self, _arguments: &Value) -> String {
+        "Listing terminals...".to_owned()
+    }
+
+    fn call<'a>(
+        &'a self,
+        _arguments: Value,
+        _context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move { self.0.list() }.boxed()
+    }
+}
+
+pub struct ReadFile;
+
+impl ToolHandler for ReadFile {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+
+    fn description(&self) -> &str {
+        "Read content of a file from the system."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Absolute path to file" }
+            },
+            "required": ["path"]
+        })
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Sensitive
+    }
+
+    fn paths(&self, arguments: &Value) -> Vec<PathBuf> {
+        path(arguments, "path")
+    }
+
+    fn activity(&self, arguments: &Value) -> String {
+        format!("Reading {}...", string(arguments, "path"))
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        _context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move { tools::read_file(string(&arguments, "path")) }.boxed()
+    }
+}
+
+pub struct WriteFile;
+
+impl ToolHandler for WriteFile {
+    fn name(&self) -> &str {
+        "write_file"
+    }
+
+    fn description(&self) -> &str {
+        "Write content to a file."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Absolute path to file" },
+                "content": { "type": "string", "description": "Content to write" }
+            },
+            "required": ["path", "content"]
+        })
+    }
+
+    fn paths(&self, arguments: &Value) -> Vec<PathBuf> {
+        path(arguments, "path")
+    }
+
+    fn activity(&self, arguments: &Value) -> String {
+        format!("Writing {}...", string(arguments, "path"))
+    }
+
+    /// The path is kept, but not what was written.
+    fn audit_summary(&self, arguments: &Value) -> Value {
+        audit::summarize(arguments, &["content"])
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        _context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move { tools::write_file(string(&arguments, "path"), string(&arguments, "content")) }
+            .boxed()
+    }
+}
+
+pub struct KillProcess;
+
+impl ToolHandler for KillProcess {
+    fn name(&self) -> &str {
+        "kill_process"
+    }
+
+    fn description(&self) -> &str {
+        "Terminate a system process by PID."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "pid": { "type": "string", "description": "Process ID to kill" }
+            },
+            "required": ["pid"]
+        })
+    }
+
+    fn activity(&self, arguments: &Value) -> String {
+        format!("Stopping process {}...", string(arguments, "pid"))
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        _context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move { tools::kill_process(string(&arguments, "pid")) }.boxed()
+    }
+}
+
+pub struct ReadDir;
+
+impl ToolHandler for ReadDir {
+    fn name(&self) -> &str {
+        "read_dir"
+    }
+
+    fn description(&self) -> &str {
+        "List files and directories in a path."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Directory path" }
+            },
+            "required": ["path"]
+        })
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Safe
+    }
+
+    fn paths(&self, arguments: &Value) -> Vec<PathBuf> {
+        path(arguments, "path")
+    }
+
+    fn activity(&self, arguments: &Value) -> String {
+        format!("Listing {}...", string(arguments, "path"))
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        _context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move { tools::read_dir(string(&arguments, "path")) }.boxed()
+    }
+}
+
+pub struct ScanWifi;
+
+impl ToolHandler for ScanWifi {
+    fn name(&self) -> &str {
+        "scan_wifi"
+    }
+
+    fn description(&self) -> &str {
+        "Scan for available WiFi networks."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Safe
+    }
+
+    fn activity(&self, _arguments: &Value) -> String {
+        "Scanning Wi-Fi networks...".to_owned()
+    }
+
+    fn call<'a>(
+        &'a self,
+        _arguments: Value,
+        _context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move { tools::scan_wifi() }.boxed()
+    }
+}
+
+pub struct ConnectWifi;
+
+impl ToolHandler for ConnectWifi {
+    fn name(&self) -> &str {
+        "connect_wifi"
+    }
+
+    fn description(&self) -> &str {
+        "Connect to a WiFi network."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "ssid": { "type": "string", "description": "SSID of the network" },
+                "password": { "type": "string", "description": "WiFi password" }
+            },
+            "required": ["ssid", "password"]
+        })
+    }
+
+    fn activity(&self, arguments: &Value) -> String {
+        format!("Connecting to {}...", string(arguments, "ssid"))
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        _context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move {
+            tools::connect_wifi(string(&arguments, "ssid"), string(&arguments, "password"))
+        }
+        .boxed()
+    }
+}
+
+pub struct SearchFiles;
+
+impl SearchFiles {
+    const MAX_RESULTS: u64 = 500;
+    const MAX_CONTEXT: u64 = 10;
+}
+
+impl ToolHandler for SearchFiles {
+    fn name(&self) -> &str {
+        "search_files"
+    }
+
+    fn description(&self) -> &str {
+        "Search for files and directories by name, or for lines inside files. Hidden files, \
+         what `.gitignore` excludes and binary files are skipped. Matches are streamed as \
+         `search/match` notifications."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "Search term" },
+                "base_path": { "type": "string", "description": "Path to search from" },
+                "contents": { "type": "boolean", "default": false, "description": "Search inside files instead of names" },
+                "regex": { "type": "boolean", "default": false, "description": "Treat the query as a regular expression" },
+                "case_sensitive": { "type": "boolean", "default": false },
+                "hidden": { "type": "boolean", "default": false, "description": "Include hidden and ignored files" },
+                "max_depth": { "type": "integer", "description": "Directory levels to descend" },
+                "max_results": { "type": "integer", "default": 100 },
+                "context": { "type": "integer", "default": 0, "description": "Lines around each match" }
+            },
+            "required": ["query", "base_path"]
+        })
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Safe
+    }
+
+    fn paths(&self, arguments: &Value) -> Vec<PathBuf> {
+        path(arguments, "base_path")
+    }
+
+    fn activity(&self, arguments: &Value) -> String {
+        format!("Searching for {}...", string(arguments, "query"))
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move {
+            let flag = |name: &str| arguments[name].as_bool().unwrap_or(false);
+
+            let mut options =
+                search::Options::new(string(&arguments, "base_path"), string(&arguments, "query"));
+            options.contents = flag("contents");
+            options.names = !options.contents;
+            options.is_regex = flag("regex");
+            options.is_case_sensitive = flag("case_sensitive");
+            options.hidden = flag("hidden");
+            options.gitignore = !options.hidden;
+            options.max_depth = arguments["max_depth"].as_u64().map(|depth| depth as usize);
+
+            if let Some(max_results) = arguments["max_results"].as_u64() {
+                options.max_results = max_results.min(Self::MAX_RESULTS) as usize;
+            }
+
+            if let Some(lines) = arguments["context"].as_u64() {
+                options.context = lines.min(Self::MAX_CONTEXT) as usize;
+            }
+
+            // Only `base_path` was authorized; everything below it is checked
+            // before it is opened
+            if let Some(policy) = context.policy.clone() {
+                options.filter = Some(search::Filter::new(move |path| {
+                    policy.permissions().is_path_allowed(path)
+                }));
+            }
+
+            let mut matches = search::stream(options)?;
+            let mut results = Vec::new();
+
+            while let Some(found) = matches.recv().await {
+                let found = serde_json::to_value(found)?;
+
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "search/match",
+                    "params": found,
+                });
+
+                let _ = context.notifications.send(notification.to_string()).await;
+
+                results.push(found);
+            }
+
+            Ok(json!(results))
+        }
+        .boxed()
+    }
+}
+
+pub struct RunCommand;
+
+impl RunCommand {
+    const DEFAULT_TIMEOUT: u64 = 30;
+    const MAX_TIMEOUT: u64 = 300;
+}
+
+impl ToolHandler for RunCommand {
+    fn name(&self) -> &str {
+        "run_command"
+    }
+
+    fn description(&self) -> &str {
+        // Shown when asking for permission, so this has to be honest
+        if tools::is_sandboxed() {
+            "Run a command line in a sandbox, without a shell, and capture its output."
+        } else {
+            "Run a command line without a shell and capture its output. Bubblewrap is not \
+             installed, so the command is NOT sandboxed: it can read and change your files \
+             and use the network."
+        }
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": { "type": "string", "description": "Command line, e.g. `df -h /`" },
+                "timeout": { "type": "integer", "description": "Seconds before the command is killed" }
+            },
+            "required": ["command"]
+        })
+    }
+
+    fn activity(&self, arguments: &Value) -> String {
+        format!("Running `{}`...", string(arguments, "command"))
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        _context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move {
+            let timeout = arguments["timeout"]
+                .as_u64()
+                .unwrap_or(Self::DEFAULT_TIMEOUT)
+                .clamp(1, Self::MAX_TIMEOUT);
+
+            tools::run_command(
+                string(&arguments, "command"),
+                std::time::Duration::from_secs(timeout),
+            )
+            .await
+        }
+        .boxed()
+    }
+}
+
+/// Keeps its own [`Monitor`], so throughputs are measured since the
+/// previous call of the tool.
+#[derive(Default)]
+pub struct SystemTelemetry(std::sync::Mutex<Monitor>);
+
+impl ToolHandler for SystemTelemetry {
+    fn name(&self) -> &str {
+        "system_telemetry"
+    }
+
+    fn description(&self) -> &str {
+        "Get a snapshot of the system: memory, per-core load, CPU temperature, \
+         battery, power draw, disk and network throughput and uptime."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Safe
+    }
+
+    fn activity(&self, _arguments: &Value) -> String {
+        "Checking the system...".to_owned()
+    }
+
+    fn call<'a>(
+        &'a self,
+        _arguments: Value,
+        _context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move { tools::telemetry(&self.0) }.boxed()
+    }
+}
+
+/// Reads an optional integer argument, rejecting it outside of `range`
+/// rather than truncating it.
+fn bounded<T>(value: &Value, name: &str, range: RangeInclusive<T>) -> anyhow::Result<Option<T>>
+where
+    T: TryFrom<u64> + PartialOrd + Display,
+{
+    if value.is_null() {
+        return Ok(None);
+    }
+
+    value
+        .as_u64()
+        .and_then(|value| T::try_from(value).ok())
+        .filter(|value| range.contains(value))
+        .map(Some)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "{name} must be between {} and {}",
+                range.start(),
+                range.end()
+            )
+        })
+}
+
+pub struct SpeechToText;
+
+impl SpeechToText {
+    /// Resampling from far below or above these rates would take more memory
+    /// and time than any recording is worth.
+    const SAMPLE_RATES: RangeInclusive<u32> = 8_000..=192_000;
+    const CHANNELS: RangeInclusive<u16> = 1..=8;
+}
+
+impl ToolHandler for SpeechToText {
+    fn name(&self) -> &str {
+        "intelligence/stt"
+    }
+
+    fn description(&self) -> &str {
+        "Convert PCM audio data (f32, 16kHz mono unless told otherwise) or a WAV file \
+         to text using Whisper. Files are split into utterances, streamed as \
+         `voice/transcript` notifications."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "audio": { "type": "array", "items": { "type": "number" } },
+                "sample_rate": {
+                    "type": "integer",
+                    "minimum": Self::SAMPLE_RATES.start(),
+                    "maximum": Self::SAMPLE_RATES.end(),
+                    "default": 16000
+                },
+                "channels": {
+                    "type": "integer",
+                    "minimum": Self::CHANNELS.start(),
+                    "maximum": Self::CHANNELS.end(),
+                    "default": 1
+                },
+                "path": { "type": "string" }
+            }
+        })
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Safe
+    }
+
+    fn paths(&self, arguments: &Value) -> Vec<PathBuf> {
+        if arguments["path"].is_string() {
+            path(arguments, "path")
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn activity(&self, _arguments: &Value) -> String {
+        "Listening...".to_owned()
+    }
+
+    /// Keeps the format of the audio, but not the samples.
+    fn audit_summary(&self, arguments: &Value) -> Value {
+        audit::summarize(arguments, &["audio"])
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move {
+            // Whisper runs without the lock, so speaking is not held up
+            let mut whisper = {
+                let mut manager = VOICE.lock().await;
+                manager.init_whisper("tiny.en").await?;
+                manager.whisper()?
+            };
+
+            let Some(path) = arguments["path"].as_str() else {
+                let samples: Vec<f32> = arguments["audio"]
+                    .as_array()
+                    .map(|audio| {
+                        audio
+                            .iter()
+                            .filter_map(|v| v.as_f64().map(|f| f as f32))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                let format = audio::Format {
+                    sample_rate: bounded(
+                        &arguments["sample_rate"],
+                        "sample_rate",
+                        Self::SAMPLE_RATES,
+                    )?
+                    .unwrap_or(voice::SAMPLE_RATE),
+                    channels: bounded(&arguments["channels"], "channels", Self::CHANNELS)?
+                        .unwrap_or(1),
+                };
+
+                let samples = audio::normalize(&samples, format);
+
+                if samples.is_empty() {
+                    return Ok(json!(""));
+                }
+
+                let text =
+                    tokio::task::spawn_blocking(move || whisper.transcribe(&samples)).await??;
+
+                return Ok(json!(text));
+            };
+
+            let wav = Wav::open(path)?;
+
+            if !Self::SAMPLE_RATES.contains(&wav.sample_rate) {
+                anyhow::bail!("unsupported sample rate: {} Hz", wav.sample_rate);
+            }
+
+            let audio = wav.resample(voice::SAMPLE_RATE);
+            let notifications = context.notifications.clone();
+
+            let utterances = tokio::task::spawn_blocking(move || {
+                let mut listener = Listener::new(whisper, listener::Settings::default());
+                let mut utterances = Vec::new();
+
+                // Half a second at a time, as a microphone would deliver it
+                for chunk in audio
+                    .chunks(voice::SAMPLE_RATE as usize / 2)
+                    .map(Some)
+                    .chain([None])
+                {
+                    let events = match chunk {
+                        Some(chunk) => listener.push(chunk)?,
+                        None => listener.finish()?,
+                    };
+
+                    for event in events {
+                        let params = match event {
+                            listener::Event::SpeechStarted => json!({ "speaking": true }),
+                            listener::Event::Partial(text) => {
+                                json!({ "text": text, "final": false })
+                            }
+                            listener::Event::Final(text) => {
+                                utterances.push(text.clone());
+
+                                json!({ "text": text, "final": true })
+                            }
+                        };
+
+                        let notification = json!({
+                            "jsonrpc": "2.0",
+                            "method": "voice/transcript",
+                            "params": params,
+                        });
+
+                        let _ = notifications.blocking_send(notification.to_string());
+                    }
+                }
+
+                anyhow::Ok(utterances)
+            })
+            .await??;
+
+            Ok(json!({ "utterances": utterances }))
+        }
+        .boxed()
+    }
+}
+
+pub struct TextToSpeech;
+
+impl ToolHandler for TextToSpeech {
+    fn name(&self) -> &str {
+        "intelligence/tts"
+    }
+
+    fn description(&self) -> &str {
+        "Convert text to speech samples (f32, 22kHz) using Piper."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "text": { "type": "string" },
+                "voice": { "type": "string", "default": "en_US-lessac-medium" }
+            },
+            "required": ["text"]
+        })
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Safe
+    }
+
+    fn activity(&self, _arguments: &Value) -> String {
+        "Speaking...".to_owned()
+    }
+
+    /// Keeps the voice, but not what was said.
+    fn audit_summary(&self, arguments: &Value) -> Value {
+        audit::summarize(arguments, &["text"])
+    }
+
+    fn call<'a>(
+        &'a self,
+        arguments: Value,
+        _context: &'a Context,
+    ) -> BoxFuture<'a, anyhow::Result<Value>> {
+        async move {
+            let text = string(&arguments, "text");
+            let voice = arguments["voice"].as_str().unwrap_or("en_US-lessac-medium");
+
+            let mut manager = VOICE.lock().await;
+            manager
+                .synthesize(text, voice)
+                .await
+                .map(|samples| json!({ "samples": samples }))
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_summaries_identify_the_call() {
+        assert_eq!(
+            RunCommand.audit_summary(&json!({ "command": "rm -rf /tmp/cache" })),
+            json!({ "command": "rm -rf /tmp/cache" })
+        );
+        assert_eq!(
+            KillProcess.audit_summary(&json!({ "pid": "4242" })),
+            json!({ "pid": "4242" })
+        );
+        assert_eq!(
+            ConnectWifi.audit_summary(&json!({ "ssid": "Home", "password": "hunter2" })),
+            json!({ "ssid": "Home", "password": "[redacted]" })
+        );
+        assert_eq!(
+            WriteFile.audit_summary(&json!({ "path": "/tmp/diary.txt", "content": "Dear diary" })),
+            json!({ "path": "/tmp/diary.txt", "content": "<10 bytes>" })
+        );
+    }
+
+    #[test]
+    fn test_speech_to_text_bounds() {
+        let schema = SpeechToText.input_schema();
+
+        assert!(crate::schema::validate(&schema, &json!({ "sample_rate": 1 })).is_err());
+        assert!(crate::schema::validate(&schema, &json!({ "channels": 9 })).is_err());
+        assert!(crate::schema::validate(&schema, &json!({ "sample_rate": 48000 })).is_ok());
+
+        let rates = SpeechToText::SAMPLE_RATES;
+
+        assert_eq!(
+            bounded(&Value::Null, "sample_rate", rates.clone()).unwrap(),
+            None
+        );
+        assert_eq!(
+            bounded(&json!(44100), "sample_rate", rates.clone()).unwrap(),
+            Some(44100)
+        );
+        assert!(bounded(&json!(1), "sample_rate", rates.clone()).is_err());
+        assert!(bounded(&json!(u64::MAX), "sample_rate", rates).is_err());
+        assert!(bounded(&json!(65537), "channels", SpeechToText::CHANNELS).is_err());
+    }
+}
@@ -1,3 +1,8 @@
+#[cfg(feature = "native")]
+pub mod builtin;
+#[cfg(feature = "native")]
+pub mod search;
+
 use anyhow::Result;
 use serde_json::{json, Value};
 
@@ -8,8 +13,6 @@ use std::fs;
 use std::process::Command;
 #[cfg(feature = "native")]
 use sysinfo::{Pid, System};
-#[cfg(feature = "native")]
-use walkdir::WalkDir;
 
 #[cfg(feature = "native")]
 pub fn list_processes() -> Result<Value> {
@@ -179,32 +182,25 @@ pub fn scan_wifi() -> Result<Value> {
     }
 }
 
+/// Files and directories whose name contains `query`, a few levels deep.
 pub fn search_files(query: &str, base_path: &str) -> Result<Value> {
     #[cfg(feature = "native")]
     {
-        let mut results = Vec::new();
-        let max_results = 20;
-        let query_lower = query.to_lowercase();
-
-        for entry in WalkDir::new(base_path)
-            .max_depth(3) // Stay shallow for performance
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            let name = entry.file_name().to_string_lossy();
-            if name.to_lowercase().contains(&query_lower) {
-                let metadata = entry.metadata()?;
-                results.push(json!({
-                    "name": name,
-                    "path": entry.path().to_string_lossy(),
-                    "is_dir": metadata.is_dir(),
-                    "size": metadata.len()
-                }));
-                if results.len() >= max_results {
-                    break;
-                }
-            }
-        }
+        let mut options = search::Options::new(base_path, query);
+        options.max_depth = Some(3); // Stay shallow for performance
+        options.max_results = 20;
+
+        let results: Vec<Value> = search::Search::new(options)?
+            .filter_map(|found| match found {
+                search::Match::Name { path, is_dir, size } => Some(json!({
+                    "name": path.file_name().map(|name| name.to_string_lossy()),
+                    "path": path.to_string_lossy(),
+                    "is_dir": is_dir,
+                    "size": size
+                })),
+                search::Match::Line { .. } => None,
+            })
+            .collect();
 
         Ok(json!(results))
     }
@@ -215,7 +211,7 @@ pub fn search_files(query: &str, base_path: &str) -> Result<Value> {
     }
 }
 
-pub fn connect_wifi(ssid: &str, _password: &str) -> Result<Value> {
+pub fn connect_wifi(ssid: &str, password: &str) -> Result<Value> {
     #[cfg(all(feature = "native", target_os = "linux"))]
     {
         let output = Command::new("nmcli")
@@ -237,6 +233,7 @@ pub fn connect_wifi(ssid: &str, _password: &str) -> Result<Value> {
     #[cfg(feature = "native")]
     {
         // Mock connection
+        let _ = password;
         Ok(json!({ "status": "success", "message": format!("[MOCK] Connected to {}", ssid) }))
     }
     #[cfg(not(feature = "native"))]
@@ -245,3 +242,196 @@ pub fn connect_wifi(ssid: &str, _password: &str) -> Result<Value> {
         Err(anyhow::anyhow!("WiFi management not supported on web"))
     }
 }
+
+/// Longest output kept from each stream of a command.
+#[cfg(feature = "native")]
+const MAX_COMMAND_OUTPUT: usize = 64 * 1024;
+
+/// Runs a command line in a sandbox and captures its output.
+///
+/// The command runs without a shell, with a clean environment, no input
+/// and a scratch working directory, and is killed after `timeout`. When
+/// bubblewrap is installed, it also gets a read-only view of the file
+/// system and no network; see [`is_sandboxed`].
+#[cfg(feature = "native")]
+pub async fn run_command(command_line: &str, timeout: std::time::Duration) -> Result<Value> {
+    use std::process::Stdio;
+    use tokio::process::Command;
+
+    let words = split_command(command_line)?;
+
+    let Some((program, args)) = words.split_first() else {
+        return Err(anyhow::anyhow!("The command is empty"));
+    };
+
+    let scratch = std::env::temp_dir().join("peak-sandbox");
+    fs::create_dir_all(&scratch)?;
+
+    let mut command = match find_program("bwrap") {
+        Some(bwrap) => {
+            let mut command = Command::new(bwrap);
+            command
+                .args(["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"])
+                .args(["--tmpfs", "/tmp", "--unshare-all", "--die-with-parent"])
+                .args(["--chdir", "/tmp", "--", program])
+                .args(args);
+            command
+        }
+        None => {
+            let mut command = Command::new(program);
+            command.args(args).current_dir(&scratch);
+            command
+        }
+    };
+
+    let _ = command
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("LANG", "C.UTF-8")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut child = command.spawn()?;
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+
+    let run = async { tokio::try_join!(read_capped(stdout), read_capped(stderr), child.wait()) };
+
+    // On timeout the child is dropped, and killed with it
+    let Ok(output) = tokio::time::timeout(timeout, run).await else {
+        return Ok(json!({
+            "status": "error",
+            "message": format!("Timed out after {} seconds", timeout.as_secs()),
+        }));
+    };
+
+    let (stdout, stderr, status) = output?;
+
+    Ok(json!({
+        "exit_code": status.code(),
+        "stdout": String::from_utf8_lossy(&stdout),
+        "stderr": String::from_utf8_lossy(&stderr),
+    }))
+}
+
+/// Whether [`run_command`] can sandbox commands on this computer.
+#[cfg(feature = "native")]
+pub fn is_sandboxed() -> bool {
+    find_program("bwrap").is_some()
+}
+
+/// Keeps the first [`MAX_COMMAND_OUTPUT`] bytes of a stream and throws the
+/// rest away, so a chatty command neither fills memory nor blocks on a full
+/// pipe.
+#[cfg(feature = "native")]
+async fn read_capped(mut stream: impl tokio::io::AsyncRead + Unpin) -> std::io::Result<Vec<u8>> {
+    use tokio::io::AsyncReadExt;
+
+    let mut bytes = Vec::new();
+    let _ = (&mut stream)
+        .take(MAX_COMMAND_OUTPUT as u64)
+        .read_to_end(&mut bytes)
+        .await?;
+    let _ = tokio::io::copy(&mut stream, &mut tokio::io::sink()).await?;
+
+    Ok(bytes)
+}
+
+/// Splits a command line into words, honoring single and double quotes
+/// and backslash escapes. Nothing is expanded.
+#[cfg(feature = "native")]
+pub fn split_command(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('"') | None, '\\') => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("Dangling escape in command"))?;
+
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                let _ = word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(anyhow::anyhow!("Unterminated quote in command"));
+    }
+
+    words.extend(word);
+
+    Ok(words)
+}
+
+#[cfg(feature = "native")]
+fn find_program(name: &str) -> Option<std::path::PathBuf> {
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|directory| directory.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(feature = "native")]
+pub fn telemetry(monitor: &std::sync::Mutex<crate::kernel::Monitor>) -> Result<Value> {
+    let snapshot = monitor
+        .lock()
+        .unwrap_or_else(|error| error.into_inner())
+        .snapshot();
+
+    Ok(serde_json::to_value(snapshot)?)
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command(r#"grep -n "two words" 'it''s' a\ b """#).unwrap(),
+            ["grep", "-n", "two words", "its", "a b", ""]
+        );
+
+        assert!(split_command("echo 'open").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_command() {
+        let output = run_command("echo sandboxed", std::time::Duration::from_secs(10))
+            .await
+            .unwrap();
+
+        assert_eq!(output["exit_code"], 0);
+        assert_eq!(output["stdout"], "sandboxed\n");
+
+        let output = run_command("yes", std::time::Duration::from_millis(500))
+            .await
+            .unwrap();
+
+        assert_eq!(output["status"], "error");
+
+        let output = run_command(
+            "head -c 1000000 /dev/zero",
+            std::time::Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(output["exit_code"], 0);
+        assert_eq!(output["stdout"].as_str().unwrap().len(), MAX_COMMAND_OUTPUT);
+    }
+}
@@ -0,0 +1,443 @@
+//! Searching files by name and by content.
+//!
+//! A [`Search`] walks a directory like a developer would expect: hidden
+//! files and whatever `.gitignore` excludes are skipped, and so are binary
+//! files when looking inside them. It is an iterator, so results arrive as
+//! they are found and the walk stops when they are no longer wanted;
+//! [`stream`] runs it in the background for async callers.
+mod gitignore;
+
+pub use gitignore::Gitignore;
+
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Bytes looked at to tell binary files from text.
+const SNIFF: usize = 8 * 1024;
+
+/// Matched lines are cut at this length, in characters.
+const MAX_LINE: usize = 400;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub root: PathBuf,
+    pub query: String,
+    /// Whether `query` is a regular expression rather than text.
+    pub is_regex: bool,
+    pub is_case_sensitive: bool,
+    /// Match file and directory names.
+    pub names: bool,
+    /// Match lines inside files.
+    pub contents: bool,
+    pub hidden: bool,
+    /// Skip what `.gitignore` files exclude.
+    pub gitignore: bool,
+    /// Levels below `root` to look at; `None` for all of them.
+    pub max_depth: Option<usize>,
+    pub max_results: usize,
+    /// Lines around a match to include.
+    pub context: usize,
+    /// Larger files are not searched inside.
+    pub max_file_size: u64,
+    /// Paths it rejects are neither reported, opened nor entered.
+    pub filter: Option<Filter>,
+}
+
+impl Options {
+    pub fn new(root: impl Into<PathBuf>, query: impl Into<String>) -> Self {
+        Self {
+            root: root.into(),
+            query: query.into(),
+            is_regex: false,
+            is_case_sensitive: false,
+            names: true,
+            contents: false,
+            hidden: false,
+            gitignore: true,
+            max_depth: None,
+            max_results: 100,
+            context: 0,
+            max_file_size: 4 * 1024 * 1024,
+            filter: None,
+        }
+    }
+}
+
+/// Decides which paths a [`Search`] may look at, e.g. from the allow and
+/// deny lists of a policy.
+#[derive(Clone)]
+pub struct Filter(Arc<dyn Fn(&Path) -> bool + Send + Sync>);
+
+impl Filter {
+    pub fn new(filter: impl Fn(&Path) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(filter))
+    }
+
+    pub fn allows(&self, path: &Path) -> bool {
+        (self.0)(path)
+    }
+}
+
+impl fmt::Debug for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Filter")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Match {
+    /// A file or directory whose name matches.
+    Name {
+        path: PathBuf,
+        is_dir: bool,
+        size: u64,
+    },
+    /// A line of a file that matches.
+    Line {
+        path: PathBuf,
+        /// Starting at 1.
+        line_number: usize,
+        /// Where the match starts in the line, in bytes.
+        column: usize,
+        line: String,
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+}
+
+pub struct Search {
+    options: Options,
+    pattern: Regex,
+    stack: Vec<Directory>,
+    found: Vec<Match>,
+    count: usize,
+}
+
+struct Directory {
+    entries: std::vec::IntoIter<PathBuf>,
+    depth: usize,
+    gitignores: Vec<Arc<Gitignore>>,
+}
+
+impl Search {
+    pub fn new(options: Options) -> anyhow::Result<Self> {
+        let pattern = if options.is_regex {
+            options.query.clone()
+        } else {
+            regex::escape(&options.query)
+        };
+
+        let pattern = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.is_case_sensitive)
+            .build()?;
+
+        let mut search = Self {
+            pattern,
+            stack: Vec::new(),
+            found: Vec::new(),
+            count: 0,
+            options,
+        };
+
+        let root = search.options.root.clone();
+        search.enter(&root, 0, Vec::new());
+
+        Ok(search)
+    }
+
+    fn enter(&mut self, directory: &Path, depth: usize, mut gitignores: Vec<Arc<Gitignore>>) {
+        let Ok(entries) = fs::read_dir(directory) else {
+            return;
+        };
+
+        if self.options.gitignore {
+            if let Some(gitignore) = Gitignore::open(directory) {
+                gitignores.push(Arc::new(gitignore));
+            }
+        }
+
+        // Sorted, so results come in the same order every time
+        let mut entries: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+        entries.sort();
+
+        self.stack.push(Directory {
+            entries: entries.into_iter(),
+            depth: depth + 1,
+            gitignores,
+        });
+    }
+
+    fn visit(&mut self, path: PathBuf, depth: usize, gitignores: &[Arc<Gitignore>]) {
+        let Some(name) = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+        else {
+            return;
+        };
+
+        if name == ".git" || (!self.options.hidden && name.starts_with('.')) {
+            return;
+        }
+
+        // Links are not followed, so the walk cannot loop
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            return;
+        };
+
+        let is_dir = metadata.is_dir();
+
+        // The deepest `.gitignore` with an opinion decides
+        let is_ignored = gitignores
+            .iter()
+            .rev()
+            .find_map(|gitignore| gitignore.matches(&path, is_dir))
+            .unwrap_or(false);
+
+        if is_ignored {
+            return;
+        }
+
+        if let Some(filter) = &self.options.filter {
+            if !filter.allows(&path) {
+                return;
+            }
+        }
+
+        if self.options.names && self.pattern.is_match(&name) {
+            self.found.push(Match::Name {
+                path: path.clone(),
+                is_dir,
+                size: metadata.len(),
+            });
+        }
+
+        if is_dir {
+            if self
+                .options
+                .max_depth
+                .is_none_or(|max_depth| depth < max_depth)
+            {
+                self.enter(&path, depth, gitignores.to_vec());
+            }
+        } else if self.options.contents
+            && metadata.is_file()
+            && metadata.len() <= self.options.max_file_size
+        {
+            self.search_file(&path);
+        }
+    }
+
+    fn search_file(&mut self, path: &Path) {
+        let Ok(mut file) = fs::File::open(path) else {
+            return;
+        };
+
+        let mut bytes = Vec::new();
+
+        if file.read_to_end(&mut bytes).is_err() || is_binary(&bytes) {
+            return;
+        }
+
+        let text = String::from_utf8_lossy(&bytes);
+        let lines: Vec<&str> = text.lines().collect();
+        let context = self.options.context;
+
+        for (i, line) in lines.iter().enumerate() {
+            let Some(found) = self.pattern.find(line) else {
+                continue;
+            };
+
+            let before = &lines[i.saturating_sub(context)..i];
+            let after = &lines[i + 1..(i + 1 + context).min(lines.len())];
+
+            self.found.push(Match::Line {
+                path: path.to_path_buf(),
+                line_number: i + 1,
+                column: found.start(),
+                line: truncate(line),
+                before: before.iter().map(|line| truncate(line)).collect(),
+                after: after.iter().map(|line| truncate(line)).collect(),
+            });
+
+            // A file with many hits should not hold up the walk
+            if self.count + self.found.len() >= self.options.max_results {
+                return;
+            }
+        }
+    }
+}
+
+impl Iterator for Search {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        loop {
+            if self.count >= self.options.max_results {
+                return None;
+            }
+
+            if !self.found.is_empty() {
+                self.count += 1;
+
+                return Some(self.found.remove(0));
+            }
+
+            let directory = self.stack.last_mut()?;
+
+            let Some(path) = directory.entries.next() else {
+                let _ = self.stack.pop();
+                continue;
+            };
+
+            let depth = directory.depth;
+            let gitignores = directory.gitignores.clone();
+
+            self.visit(path, depth, &gitignores);
+        }
+    }
+}
+
+/// Searches in the background. Dropping the receiver cancels the search.
+pub fn stream(options: Options) -> anyhow::Result<tokio::sync::mpsc::Receiver<Match>> {
+    let search = Search::new(options)?;
+    let (sender, receiver) = tokio::sync::mpsc::channel(64);
+
+    tokio::task::spawn_blocking(move || {
+        for found in search {
+            if sender.blocking_send(found).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(receiver)
+}
+
+/// Text files have no NUL bytes at the start; most binary files do.
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(SNIFF)].contains(&0)
+}
+
+fn truncate(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let root = std::env::temp_dir().join(format!("peak-search-{}", uuid::Uuid::new_v4()));
+
+        for directory in ["src/deep/deeper", "target/debug", ".cache"] {
+            fs::create_dir_all(root.join(directory)).unwrap();
+        }
+
+        let files: &[(&str, &[u8])] = &[
+            (".gitignore", b"target/\n*.log\n"),
+            (
+                "src/main.rs",
+                b"fn main() {\n    // TODO: parse\n    run();\n}\n",
+            ),
+            ("src/todo.txt", b"nothing to see\n"),
+            ("src/deep/deeper/lib.rs", b"// todo: deeper\n"),
+            ("src/app.log", b"TODO in a log\n"),
+            ("target/debug/build.rs", b"// TODO: generated\n"),
+            (".cache/notes.md", b"TODO hidden\n"),
+            ("image.png", b"\x89PNG\0\0TODO"),
+        ];
+
+        for (path, content) in files {
+            fs::write(root.join(path), content).unwrap();
+        }
+
+        let relative = |found: &Match| match found {
+            Match::Name { path, .. } | Match::Line { path, .. } => path
+                .strip_prefix(&root)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned(),
+        };
+
+        // Names only, by default
+        let names: Vec<_> = Search::new(Options::new(&root, "todo"))
+            .unwrap()
+            .map(|found| relative(&found))
+            .collect();
+
+        assert_eq!(names, ["src/todo.txt"]);
+
+        // Contents, with context
+        let mut options = Options::new(&root, "todo");
+        options.names = false;
+        options.contents = true;
+        options.context = 1;
+
+        let lines: Vec<_> = Search::new(options.clone()).unwrap().collect();
+
+        assert_eq!(
+            lines.iter().map(relative).collect::<Vec<_>>(),
+            ["src/deep/deeper/lib.rs", "src/main.rs"]
+        );
+        assert_eq!(
+            lines[1],
+            Match::Line {
+                path: root.join("src/main.rs"),
+                line_number: 2,
+                column: 7,
+                line: "    // TODO: parse".to_owned(),
+                before: vec!["fn main() {".to_owned()],
+                after: vec!["    run();".to_owned()],
+            }
+        );
+
+        // Case, depth and limits
+        options.is_case_sensitive = true;
+        options.is_regex = true;
+        options.query = r"TODO:?\s".to_owned();
+        options.hidden = true;
+        options.gitignore = false;
+        options.max_depth = Some(2);
+
+        let lines: Vec<_> = Search::new(options.clone())
+            .unwrap()
+            .map(|found| relative(&found))
+            .collect();
+
+        assert_eq!(lines, [".cache/notes.md", "src/app.log", "src/main.rs"]);
+
+        // Filtered paths are not looked into
+        let filtered = root.join(".cache");
+        options.filter = Some(Filter::new(move |path| !path.starts_with(&filtered)));
+        options.max_depth = None;
+
+        let lines: Vec<_> = Search::new(options.clone())
+            .unwrap()
+            .map(|found| relative(&found))
+            .collect();
+
+        assert_eq!(
+            lines,
+            ["src/app.log", "src/main.rs", "target/debug/build.rs"]
+        );
+
+        options.max_results = 1;
+        assert_eq!(Search::new(options.clone()).unwrap().count(), 1);
+
+        options.query = "(".to_owned();
+        assert!(Search::new(options).is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
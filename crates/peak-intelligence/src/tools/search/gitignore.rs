@@ -0,0 +1,180 @@
+//! The patterns of a `.gitignore` file.
+//!
+//! Patterns without a slash match a name at any depth; patterns with one
+//! are relative to the directory of the file. The last matching pattern
+//! wins, so `!` can bring back what an earlier one excluded.
+use regex::Regex;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct Gitignore {
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: Regex,
+    is_negated: bool,
+    is_dir_only: bool,
+}
+
+impl Gitignore {
+    /// Reads the `.gitignore` of a directory, if it has one.
+    pub fn open(directory: &Path) -> Option<Self> {
+        let text = fs::read_to_string(directory.join(".gitignore")).ok()?;
+
+        Some(Self::parse(directory, &text))
+    }
+
+    pub fn parse(base: &Path, text: &str) -> Self {
+        Self {
+            base: base.to_path_buf(),
+            rules: text.lines().filter_map(Rule::parse).collect(),
+        }
+    }
+
+    /// Whether a path below the base is ignored, or `None` if no pattern
+    /// says anything about it.
+    pub fn matches(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.base).ok()?;
+        let relative = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.is_dir_only) && rule.pattern.is_match(&relative))
+            .map(|rule| !rule.is_negated)
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Self> {
+        // Trailing spaces do not count, unless escaped
+        let line = if line.ends_with("\\ ") {
+            line
+        } else {
+            line.trim_end()
+        };
+
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (is_negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+
+        let (is_dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+
+        // A slash anywhere but at the end ties the pattern to the base
+        let is_anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+
+        if line.is_empty() {
+            return None;
+        }
+
+        let prefix = if is_anchored { "^" } else { "^(?:.*/)?" };
+        let pattern = Regex::new(&format!("{prefix}{}$", translate(line))).ok()?;
+
+        Some(Self {
+            pattern,
+            is_negated,
+            is_dir_only,
+        })
+    }
+}
+
+/// Turns a glob into a regular expression.
+fn translate(glob: &str) -> String {
+    let mut pattern = String::new();
+    let mut characters = glob.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        match character {
+            '*' if characters.peek() == Some(&'*') => {
+                let _ = characters.next();
+
+                // `**/` matches no directory too
+                if characters.peek() == Some(&'/') {
+                    let _ = characters.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '[' => {
+                let class: String = characters.by_ref().take_while(|c| *c != ']').collect();
+                let class = class
+                    .strip_prefix('!')
+                    .map_or(class.clone(), |class| format!("^{class}"));
+
+                pattern.push('[');
+                pattern.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                pattern.push(']');
+            }
+            '\\' => {
+                if let Some(escaped) = characters.next() {
+                    pattern.push_str(&regex::escape(&escaped.to_string()));
+                }
+            }
+            character => pattern.push_str(&regex::escape(&character.to_string())),
+        }
+    }
+
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gitignore() {
+        let base = Path::new("/project");
+        let gitignore = Gitignore::parse(
+            base,
+            &[
+                "# Build output",
+                "/target",
+                "*.log",
+                "!important.log",
+                "build/",
+                "docs/**/*.pdf",
+                "cache?.[ab]in",
+            ]
+            .join("\n"),
+        );
+
+        let matches = |path: &str, is_dir| gitignore.matches(&base.join(path), is_dir);
+
+        assert_eq!(matches("target", true), Some(true));
+        assert_eq!(matches("crates/target", true), None);
+        assert_eq!(matches("src/debug.log", false), Some(true));
+        assert_eq!(matches("src/important.log", false), Some(false));
+        assert_eq!(matches("web/build", true), Some(true));
+        assert_eq!(matches("web/build", false), None);
+        assert_eq!(matches("docs/manual.pdf", false), Some(true));
+        assert_eq!(matches("docs/a/b/manual.pdf", false), Some(true));
+        assert_eq!(matches("cache1.bin", false), Some(true));
+        assert_eq!(matches("cache1.cin", false), None);
+        assert_eq!(matches("src/main.rs", false), None);
+        assert_eq!(
+            gitignore.matches(Path::new("/elsewhere/x.log"), false),
+            None
+        );
+    }
+}
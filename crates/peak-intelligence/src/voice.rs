@@ -16,6 +16,23 @@ use tts::Tts;
 #[cfg(feature = "voice")]
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
 
+pub mod audio;
+pub mod hands_free;
+pub mod intent;
+pub mod listener;
+pub mod vad;
+pub mod wake;
+pub mod wav;
+
+pub use hands_free::HandsFree;
+pub use intent::Intent;
+pub use listener::{Listener, Transcriber};
+pub use wake::WakeWord;
+pub use wav::Wav;
+
+/// The sample rate Whisper expects.
+pub const SAMPLE_RATE: u32 = 16_000;
+
 #[allow(dead_code)]
 pub struct VoiceManager {
     #[cfg(feature = "voice")]
@@ -93,32 +110,36 @@ impl VoiceManager {
     pub async fn transcribe(&self, _audio_data: &[f32]) -> anyhow::Result<String> {
         #[cfg(feature = "voice")]
         {
-            let ctx = self
-                .whisper_context
-                .as_ref()
-                .ok_or_else(|| anyhow::anyhow!("Whisper not initialized"))?;
-
-            let mut state = ctx.create_state()?;
-            let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-
-            params.set_language(Some("en"));
-            params.set_print_special(false);
-            params.set_print_progress(false);
-            params.set_print_realtime(false);
-            params.set_print_timestamps(false);
+            self.whisper()?.transcribe(_audio_data)
+        }
+        #[cfg(not(feature = "voice"))]
+        {
+            Err(anyhow::anyhow!("Voice feature is disabled."))
+        }
+    }
 
-            state.full(params, _audio_data)?;
+    /// Starts transcribing speech as it comes, with the loaded Whisper model.
+    pub fn listen(&self, settings: listener::Settings) -> anyhow::Result<Listener<Whisper>> {
+        Ok(Listener::new(self.whisper()?, settings))
+    }
 
-            let num_segments = state.full_n_segments()?;
-            let mut result = String::new();
+    /// Starts listening for the wake word and the command after it, with the
+    /// loaded Whisper model.
+    pub fn hands_free(&self, settings: hands_free::Settings) -> anyhow::Result<HandsFree<Whisper>> {
+        Ok(HandsFree::new(self.whisper()?, settings))
+    }
 
-            for i in 0..num_segments {
-                if let Ok(segment) = state.full_get_segment_text(i) {
-                    result.push_str(&segment);
-                }
-            }
+    /// The loaded Whisper model, to transcribe with after letting go of
+    /// [`VOICE`].
+    pub fn whisper(&self) -> anyhow::Result<Whisper> {
+        #[cfg(feature = "voice")]
+        {
+            let context = self
+                .whisper_context
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("Whisper not initialized"))?;
 
-            Ok(result.trim().to_string())
+            Ok(Whisper { context })
         }
         #[cfg(not(feature = "voice"))]
         {
@@ -155,6 +176,59 @@ impl VoiceManager {
     }
 }
 
+impl VoiceManager {
+    /// Waits until speech started with [`VoiceManager::synthesize`] has
+    /// played; native speech plays in the background.
+    pub async fn finish_speaking(&self) {
+        #[cfg(feature = "voice")]
+        if let Some(tts) = &self.tts {
+            while tts.lock().await.is_speaking().unwrap_or(false) {
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+/// A [`Transcriber`] backed by a Whisper model.
+#[derive(Clone)]
+pub struct Whisper {
+    #[cfg(feature = "voice")]
+    context: Arc<WhisperContext>,
+}
+
+impl Transcriber for Whisper {
+    #[cfg(not(feature = "voice"))]
+    fn transcribe(&mut self, _samples: &[f32]) -> anyhow::Result<String> {
+        Err(anyhow::anyhow!("Voice feature is disabled."))
+    }
+
+    #[cfg(feature = "voice")]
+    fn transcribe(&mut self, samples: &[f32]) -> anyhow::Result<String> {
+        let mut state = self.context.create_state()?;
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+
+        params.set_language(Some("en"));
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        params.set_single_segment(true);
+
+        state.full(params, samples)?;
+
+        let num_segments = state.full_n_segments()?;
+        let mut result = String::new();
+
+        for i in 0..num_segments {
+            if let Ok(segment) = state.full_get_segment_text(i) {
+                result.push_str(&segment);
+            }
+        }
+
+        Ok(result.trim().to_string())
+    }
+}
+
 pub static VOICE: Lazy<TokioMutex<VoiceManager>> = Lazy::new(|| {
     #[cfg(not(target_arch = "wasm32"))]
     let home = std::env::var("HOME").unwrap_or_else(|_| "/root".into());
@@ -0,0 +1,268 @@
+//! Bringing recordings to what Whisper expects: 16 kHz mono `f32`.
+//!
+//! Microphones deliver whatever their driver likes, e.g. 48 kHz stereo
+//! `i16`. [`normalize`] mixes the channels down, resamples with a windowed
+//! sinc filter, so nothing above the new Nyquist frequency folds back as
+//! noise, and trims the silence around the speech.
+use crate::voice::SAMPLE_RATE;
+
+/// A sample type microphones deliver.
+pub trait Sample: Copy {
+    /// The sample between -1 and 1.
+    fn to_f32(self) -> f32;
+}
+
+impl Sample for f32 {
+    fn to_f32(self) -> f32 {
+        self
+    }
+}
+
+impl Sample for i16 {
+    fn to_f32(self) -> f32 {
+        f32::from(self) / 32768.0
+    }
+}
+
+impl Sample for u16 {
+    fn to_f32(self) -> f32 {
+        (f32::from(self) - 32768.0) / 32768.0
+    }
+}
+
+/// How interleaved samples are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Samples quieter than this, as an RMS amplitude, are silence.
+pub const SILENCE: f32 = 0.01;
+
+/// Silence kept around speech by [`normalize`], in milliseconds.
+const PADDING: u32 = 200;
+
+/// Zero crossings of the sinc on each side of a sample.
+const TAPS: f64 = 16.0;
+
+/// Converts a recording to 16 kHz mono and trims its silence.
+pub fn normalize<S: Sample>(samples: &[S], format: Format) -> Vec<f32> {
+    let mono = to_mono(samples, format.channels);
+    let resampled = resample(&mono, format.sample_rate, SAMPLE_RATE);
+
+    trim_silence(&resampled, SAMPLE_RATE, SILENCE, PADDING).to_vec()
+}
+
+/// Averages interleaved channels.
+pub fn to_mono<S: Sample>(samples: &[S], channels: u16) -> Vec<f32> {
+    let channels = usize::from(channels.max(1));
+
+    samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().map(|sample| sample.to_f32()).sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// Resamples mono audio with a Hann-windowed sinc filter.
+pub fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || from == 0 || to == 0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let ratio = f64::from(to) / f64::from(from);
+
+    // Downsampling must also cut what the new rate cannot hold
+    let cutoff = ratio.min(1.0) * 0.95;
+    let radius = TAPS / cutoff;
+
+    let length = (samples.len() as f64 * ratio).round() as usize;
+
+    (0..length)
+        .map(|i| {
+            let center = i as f64 / ratio;
+
+            let first = (center - radius).ceil().max(0.0) as usize;
+            let last = ((center + radius).floor() as usize).min(samples.len() - 1);
+
+            let mut sum = 0.0;
+            let mut weights = 0.0;
+
+            for (j, sample) in samples.iter().enumerate().take(last + 1).skip(first) {
+                let distance = j as f64 - center;
+                let weight = cutoff * sinc(cutoff * distance) * hann(distance / radius);
+
+                sum += f64::from(*sample) * weight;
+                weights += weight;
+            }
+
+            // Normalized, so the edges do not fade
+            if weights.abs() > f64::EPSILON {
+                (sum / weights) as f32
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Cuts the silence at both ends, keeping some `padding` in milliseconds.
+pub fn trim_silence(samples: &[f32], sample_rate: u32, threshold: f32, padding: u32) -> &[f32] {
+    // 10 ms frames
+    let frame = (sample_rate as usize / 100).max(1);
+
+    let is_loud = |chunk: &[f32]| {
+        let energy = chunk.iter().map(|sample| sample * sample).sum::<f32>() / chunk.len() as f32;
+
+        energy.sqrt() > threshold
+    };
+
+    let chunks: Vec<_> = samples.chunks(frame).collect();
+
+    let Some(first) = chunks.iter().position(|chunk| is_loud(chunk)) else {
+        return &[];
+    };
+
+    let last = chunks
+        .iter()
+        .rposition(|chunk| is_loud(chunk))
+        .unwrap_or(first);
+    let padding = sample_rate as usize * padding as usize / 1000;
+
+    let start = (first * frame).saturating_sub(padding);
+    let end = ((last + 1) * frame + padding).min(samples.len());
+
+    &samples[start..end]
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        let x = x * std::f64::consts::PI;
+
+        x.sin() / x
+    }
+}
+
+/// The Hann window, for `x` between -1 and 1.
+fn hann(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        0.0
+    } else {
+        0.5 + 0.5 * (x * std::f64::consts::PI).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voice::Wav;
+
+    use std::f32::consts::TAU;
+
+    fn sine(frequency: f32, sample_rate: u32, seconds: f32) -> Vec<f32> {
+        (0..(sample_rate as f32 * seconds) as usize)
+            .map(|i| (i as f32 * frequency * TAU / sample_rate as f32).sin() * 0.5)
+            .collect()
+    }
+
+    /// The amplitude of a frequency, by correlating with it.
+    fn amplitude(samples: &[f32], frequency: f32, sample_rate: u32) -> f32 {
+        let (sin, cos) = samples
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(sin, cos), (i, sample)| {
+                let phase = i as f32 * frequency * TAU / sample_rate as f32;
+
+                (sin + sample * phase.sin(), cos + sample * phase.cos())
+            });
+
+        2.0 * (sin * sin + cos * cos).sqrt() / samples.len() as f32
+    }
+
+    #[test]
+    fn test_resample() {
+        // A tone Whisper hears, and one that is too high for 16 kHz
+        let tone: Vec<_> = sine(440.0, 48_000, 1.0)
+            .iter()
+            .zip(sine(12_000.0, 48_000, 1.0))
+            .map(|(low, high)| low + high)
+            .collect();
+
+        let resampled = resample(&tone, 48_000, SAMPLE_RATE);
+
+        assert_eq!(resampled.len(), 16_000);
+        assert!((amplitude(&resampled, 440.0, SAMPLE_RATE) - 0.5).abs() < 0.01);
+
+        // Folded back, 12 kHz would become 4 kHz
+        assert!(amplitude(&resampled, 4_000.0, SAMPLE_RATE) < 0.01);
+
+        // Upsampling keeps the tone too
+        let upsampled = resample(&sine(440.0, 8_000, 1.0), 8_000, SAMPLE_RATE);
+
+        assert_eq!(upsampled.len(), 16_000);
+        assert!((amplitude(&upsampled, 440.0, SAMPLE_RATE) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_normalize() {
+        // Half a second of silence around a second of tone, in stereo
+        let mut mono = vec![0.0; 22_050];
+        mono.extend(sine(440.0, 44_100, 1.0));
+        mono.extend(vec![0.0; 22_050]);
+
+        let fixture = Wav {
+            sample_rate: 44_100,
+            samples: mono,
+        };
+
+        let mono = Wav::parse(&fixture.encode()).unwrap();
+
+        let stereo: Vec<i16> = mono
+            .samples
+            .iter()
+            .flat_map(|sample| {
+                let sample = (sample * 32767.0) as i16;
+
+                [sample, sample]
+            })
+            .collect();
+
+        let normalized = normalize(
+            &stereo,
+            Format {
+                sample_rate: 44_100,
+                channels: 2,
+            },
+        );
+
+        // The second of tone and 200 ms on each side, give or take a frame
+        assert!((normalized.len() as i64 - 22_400).abs() <= 320);
+        assert!((amplitude(&normalized[3_200..19_200], 440.0, SAMPLE_RATE) - 0.5).abs() < 0.01);
+
+        let unsigned: Vec<u16> = stereo
+            .iter()
+            .map(|sample| (i32::from(*sample) + 32768) as u16)
+            .collect();
+
+        let normalized_unsigned = normalize(
+            &unsigned,
+            Format {
+                sample_rate: 44_100,
+                channels: 2,
+            },
+        );
+
+        assert_eq!(normalized, normalized_unsigned);
+
+        assert!(normalize(
+            &[0.0f32; 4_800],
+            Format {
+                sample_rate: 48_000,
+                channels: 1
+            }
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+//! Listening for the wake word, then for a command.
+//!
+//! [`HandsFree`] sleeps until someone says the [`WakeWord`], and then
+//! turns the next utterance into an [`Intent`]. Asleep, each utterance is
+//! transcribed once, to keep an always-on microphone cheap; awake, partial
+//! results show what is being heard. If nobody speaks for a while after
+//! the wake word, it goes back to sleep.
+use crate::voice::intent::Intent;
+use crate::voice::listener::{self, Event as Heard, Listener, Transcriber};
+use crate::voice::wake::WakeWord;
+use crate::voice::SAMPLE_RATE;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub listener: listener::Settings,
+    pub wake_word: String,
+    /// How long to wait for a command after the wake word, in milliseconds.
+    pub timeout: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            listener: listener::Settings::default(),
+            wake_word: "hey peak".to_owned(),
+            timeout: 5000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The wake word was said.
+    Woke,
+    /// The command so far.
+    Heard(String),
+    Command(Intent),
+    /// Nobody spoke after the wake word.
+    Dozed,
+}
+
+pub struct HandsFree<T> {
+    listener: Listener<T>,
+    wake_word: WakeWord,
+    timeout: usize,
+    /// Samples since the wake word or the last speech, while awake.
+    waited: Option<usize>,
+}
+
+impl<T: Transcriber> HandsFree<T> {
+    pub fn new(transcriber: T, settings: Settings) -> Self {
+        let mut listener = Listener::new(transcriber, settings.listener);
+        listener.set_partials(false);
+
+        Self {
+            listener,
+            wake_word: WakeWord::new(&settings.wake_word),
+            timeout: SAMPLE_RATE as usize * settings.timeout as usize / 1000,
+            waited: None,
+        }
+    }
+
+    pub fn is_awake(&self) -> bool {
+        self.waited.is_some()
+    }
+
+    /// Listens to more 16 kHz mono audio.
+    pub fn push(&mut self, samples: &[f32]) -> anyhow::Result<Vec<Event>> {
+        let heard = self.listener.push(samples)?;
+        let mut events = self.hear(heard);
+
+        if let Some(waited) = &mut self.waited {
+            if !self.listener.is_speaking() {
+                *waited += samples.len();
+
+                if *waited >= self.timeout {
+                    events.push(Event::Dozed);
+                    self.sleep();
+                }
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Ends the utterance in progress, if any, and goes back to sleep.
+    pub fn finish(&mut self) -> anyhow::Result<Vec<Event>> {
+        let heard = self.listener.finish()?;
+        let events = self.hear(heard);
+
+        self.sleep();
+
+        Ok(events)
+    }
+
+    fn hear(&mut self, heard: Vec<Heard>) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        for heard in heard {
+            match (self.waited.is_some(), heard) {
+                (false, Heard::Final(text)) => {
+                    let Some(rest) = self.wake_word.find(spoken(&text)) else {
+                        continue;
+                    };
+
+                    events.push(Event::Woke);
+
+                    // "Hey Peak, open the terminal" in one breath
+                    match Intent::parse(rest) {
+                        Some(intent) => events.push(Event::Command(intent)),
+                        None => self.wake(),
+                    }
+                }
+                (true, Heard::SpeechStarted) => self.waited = Some(0),
+                (true, Heard::Partial(text)) => {
+                    let text = spoken(&text);
+
+                    if !text.is_empty() {
+                        events.push(Event::Heard(text.to_owned()));
+                    }
+                }
+                (true, Heard::Final(text)) => {
+                    // A cough or a door; keep waiting
+                    if let Some(intent) = Intent::parse(spoken(&text)) {
+                        events.push(Event::Command(intent));
+                        self.sleep();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        events
+    }
+
+    fn wake(&mut self) {
+        self.waited = Some(0);
+        self.listener.set_partials(true);
+    }
+
+    fn sleep(&mut self) {
+        self.waited = None;
+        self.listener.set_partials(false);
+    }
+}
+
+/// Drops what Whisper writes for sounds that are not words, e.g.
+/// "[BLANK_AUDIO]" or "(door slams)".
+fn spoken(text: &str) -> &str {
+    let text = text.trim();
+
+    if text.starts_with(['[', '(', '*']) {
+        ""
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::VecDeque;
+
+    /// Answers from a script, whatever it hears.
+    struct Script(VecDeque<&'static str>);
+
+    impl Transcriber for Script {
+        fn transcribe(&mut self, _samples: &[f32]) -> anyhow::Result<String> {
+            Ok(self.0.pop_front().unwrap_or_default().to_owned())
+        }
+    }
+
+    /// Tones for speech and silence in between.
+    fn recording(parts: &[(usize, bool)]) -> Vec<f32> {
+        parts
+            .iter()
+            .flat_map(|(milliseconds, is_speech)| {
+                (0..SAMPLE_RATE as usize * milliseconds / 1000).map(move |i| {
+                    if *is_speech {
+                        (i as f32 * 220.0 * std::f32::consts::TAU / SAMPLE_RATE as f32).sin() * 0.3
+                    } else {
+                        0.001
+                    }
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hands_free() {
+        let script = Script(VecDeque::from([
+            "Hey Peak.",
+            // Awake, with partials
+            "Open",
+            "Open the",
+            "Open the term",
+            "Open the terminal.",
+            "Hey, Pete!",
+            // Asleep again, only the final result
+            "What time is it?",
+            "[BLANK_AUDIO]",
+            "Hey Peak, switch to TV mode.",
+        ]));
+
+        let audio = recording(&[
+            (1000, false),
+            (800, true),
+            (1000, false),
+            (2500, true),
+            (1000, false),
+            (800, true),
+            (6000, false),
+            (1500, true),
+            (1000, false),
+            (200, true),
+            (1000, false),
+            (2000, true),
+            (1000, false),
+        ]);
+
+        let mut hands_free = HandsFree::new(script, Settings::default());
+        let mut events = Vec::new();
+
+        for chunk in audio.chunks(1024) {
+            events.extend(hands_free.push(chunk).unwrap());
+        }
+
+        events.extend(hands_free.finish().unwrap());
+
+        assert_eq!(
+            events,
+            [
+                Event::Woke,
+                Event::Heard("Open".to_owned()),
+                Event::Heard("Open the".to_owned()),
+                Event::Heard("Open the term".to_owned()),
+                Event::Command(Intent::OpenApp("terminal".to_owned())),
+                Event::Woke,
+                Event::Dozed,
+                Event::Woke,
+                Event::Command(Intent::SwitchMode("tv".to_owned())),
+            ]
+        );
+        assert!(!hands_free.is_awake());
+    }
+}
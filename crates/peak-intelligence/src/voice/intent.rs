@@ -0,0 +1,129 @@
+//! What a spoken command asks for.
+//!
+//! Commands are matched by their first words, the way people phrase them
+//! to a speaker: "open the terminal", "switch to TV mode". Anything else is
+//! a question for the assistant.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Intent {
+    /// Open an app, by the name it was called.
+    OpenApp(String),
+    /// Switch the shell to a mode, by the name it was called.
+    SwitchMode(String),
+    /// Anything else, for the assistant to answer.
+    Ask(String),
+}
+
+/// Words that come before the command and mean nothing.
+const POLITE: &[&str] = &[
+    "please",
+    "can you",
+    "could you",
+    "would you",
+    "i want to",
+    "i'd like to",
+];
+
+const OPEN: &[&str] = &["open", "launch", "start", "run", "show me", "show"];
+
+const SWITCH: &[&str] = &["switch to", "change to", "go to", "enter", "turn on", "use"];
+
+impl Intent {
+    /// Reads a transcribed command, e.g. "Please open the terminal.".
+    pub fn parse(command: &str) -> Option<Self> {
+        let command = command
+            .trim()
+            .trim_end_matches(['.', '!', '?'])
+            .trim_start_matches(|character: char| !character.is_alphanumeric())
+            .trim();
+
+        if command.is_empty() {
+            return None;
+        }
+
+        let lowercase = command.to_lowercase();
+
+        let mut words = lowercase.as_str();
+
+        while let Some(rest) = strip_any(words, POLITE) {
+            words = rest;
+        }
+
+        // "TV mode", "switch to the kitchen mode"
+        if let Some(mode) = words
+            .strip_suffix(" mode")
+            .map(|words| strip_any(words, SWITCH).unwrap_or(words))
+            .map(name)
+            .filter(|mode| !mode.is_empty() && mode.split(' ').count() <= 2)
+        {
+            return Some(Self::SwitchMode(mode.to_owned()));
+        }
+
+        if let Some(app) = strip_any(words, OPEN).map(name) {
+            let app = app.strip_suffix(" app").unwrap_or(app);
+
+            // Longer ones are requests, e.g. "start a timer for ten minutes"
+            if !app.is_empty() && app.split(' ').count() <= 3 {
+                return Some(Self::OpenApp(app.to_owned()));
+            }
+        }
+
+        Some(Self::Ask(command.to_owned()))
+    }
+}
+
+/// Strips one of the phrases and the space after it.
+fn strip_any<'a>(words: &'a str, phrases: &[&str]) -> Option<&'a str> {
+    phrases.iter().find_map(|phrase| {
+        words
+            .strip_prefix(phrase)
+            .and_then(|rest| rest.strip_prefix([' ', ',']))
+            .map(str::trim_start)
+    })
+}
+
+/// The name in "the terminal", "my browser".
+fn name(words: &str) -> &str {
+    let words = words.trim_matches([' ', ',']);
+
+    ["the ", "my ", "a "]
+        .iter()
+        .find_map(|article| words.strip_prefix(article))
+        .unwrap_or(words)
+        .trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let open = |app: &str| Some(Intent::OpenApp(app.to_owned()));
+        let switch = |mode: &str| Some(Intent::SwitchMode(mode.to_owned()));
+
+        assert_eq!(Intent::parse("Open the terminal."), open("terminal"));
+        assert_eq!(Intent::parse("please launch my browser"), open("browser"));
+        assert_eq!(
+            Intent::parse("Could you start the Jukebox app?"),
+            open("jukebox")
+        );
+
+        assert_eq!(Intent::parse("Switch to TV mode."), switch("tv"));
+        assert_eq!(Intent::parse("go to the kitchen mode"), switch("kitchen"));
+        assert_eq!(Intent::parse("Smart home mode"), switch("smart home"));
+
+        assert_eq!(
+            Intent::parse("What's the weather like?"),
+            Some(Intent::Ask("What's the weather like".to_owned()))
+        );
+        assert_eq!(
+            Intent::parse("Start a timer for ten minutes in cooking mode"),
+            Some(Intent::Ask(
+                "Start a timer for ten minutes in cooking mode".to_owned()
+            ))
+        );
+
+        assert_eq!(Intent::parse(" ... "), None);
+    }
+}
@@ -0,0 +1,278 @@
+//! Transcribing speech as it comes.
+//!
+//! A [`Listener`] is fed audio in chunks of any size, from a microphone or a
+//! [`Wav`](crate::voice::Wav). It cuts the audio into utterances with a
+//! [`Vad`], transcribes the utterance in progress every now and then for
+//! partial results, and transcribes it once more when the speaker stops.
+use crate::voice::vad::{self, Transition, Vad};
+use crate::voice::SAMPLE_RATE;
+
+use std::collections::VecDeque;
+
+/// Turns speech into text.
+pub trait Transcriber {
+    /// Transcribes 16 kHz mono audio.
+    fn transcribe(&mut self, samples: &[f32]) -> anyhow::Result<String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    pub vad: vad::Settings,
+    /// Audio kept from before speech starts, so the first syllable is not
+    /// cut, in milliseconds.
+    pub preroll: u32,
+    /// How often the utterance in progress is transcribed, in milliseconds.
+    pub partial_interval: u32,
+    /// Utterances are cut at this length, in milliseconds. Whisper looks at
+    /// 30 seconds at most.
+    pub max_utterance: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            vad: vad::Settings::default(),
+            preroll: 300,
+            partial_interval: 1000,
+            max_utterance: 30_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    SpeechStarted,
+    /// The utterance so far; later partials replace earlier ones.
+    Partial(String),
+    /// The whole utterance, once the speaker stopped.
+    Final(String),
+}
+
+pub struct Listener<T> {
+    transcriber: T,
+    settings: Settings,
+    vad: Vad,
+    /// Samples that do not make a whole frame yet.
+    pending: Vec<f32>,
+    preroll: VecDeque<f32>,
+    utterance: Vec<f32>,
+    since_partial: usize,
+    partials: bool,
+}
+
+impl<T: Transcriber> Listener<T> {
+    pub fn new(transcriber: T, settings: Settings) -> Self {
+        Self {
+            transcriber,
+            vad: Vad::new(settings.vad),
+            settings,
+            pending: Vec::new(),
+            preroll: VecDeque::new(),
+            utterance: Vec::new(),
+            since_partial: 0,
+            partials: true,
+        }
+    }
+
+    pub fn is_speaking(&self) -> bool {
+        self.vad.is_speaking()
+    }
+
+    /// Turns partial results on or off; without them, each utterance is
+    /// transcribed once.
+    pub fn set_partials(&mut self, partials: bool) {
+        self.partials = partials;
+    }
+
+    /// Listens to more 16 kHz mono audio.
+    pub fn push(&mut self, samples: &[f32]) -> anyhow::Result<Vec<Event>> {
+        let mut events = Vec::new();
+
+        self.pending.extend_from_slice(samples);
+
+        let frames = self.pending.len() / vad::FRAME;
+        let pending: Vec<_> = self.pending.drain(..frames * vad::FRAME).collect();
+
+        for frame in pending.chunks_exact(vad::FRAME) {
+            self.frame(frame, &mut events)?;
+        }
+
+        Ok(events)
+    }
+
+    /// Ends the utterance in progress, if any; e.g. when the recording stops.
+    pub fn finish(&mut self) -> anyhow::Result<Vec<Event>> {
+        let mut events = Vec::new();
+
+        self.utterance.append(&mut self.pending);
+
+        if self.vad.is_speaking() {
+            self.vad = Vad::new(self.settings.vad);
+            events.push(self.end()?);
+        }
+
+        self.preroll.clear();
+
+        Ok(events)
+    }
+
+    fn frame(&mut self, frame: &[f32], events: &mut Vec<Event>) -> anyhow::Result<()> {
+        match self.vad.push(frame) {
+            Some(Transition::Started) => {
+                self.utterance = self.preroll.drain(..).collect();
+                self.utterance.extend_from_slice(frame);
+                self.since_partial = self.utterance.len();
+
+                events.push(Event::SpeechStarted);
+            }
+            Some(Transition::Ended) => {
+                self.utterance.extend_from_slice(frame);
+
+                events.push(self.end()?);
+            }
+            None if self.vad.is_speaking() => {
+                self.utterance.extend_from_slice(frame);
+                self.since_partial += frame.len();
+
+                if self.utterance.len() >= samples(self.settings.max_utterance) {
+                    events.push(self.end()?);
+                } else if self.partials
+                    && self.since_partial >= samples(self.settings.partial_interval)
+                {
+                    self.since_partial = 0;
+
+                    let text = self.transcriber.transcribe(&self.utterance)?;
+
+                    if !text.is_empty() {
+                        events.push(Event::Partial(text));
+                    }
+                }
+            }
+            None => {
+                self.preroll.extend(frame);
+
+                let excess = self
+                    .preroll
+                    .len()
+                    .saturating_sub(samples(self.settings.preroll));
+
+                let _ = self.preroll.drain(..excess);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn end(&mut self) -> anyhow::Result<Event> {
+        let utterance = std::mem::take(&mut self.utterance);
+        self.since_partial = 0;
+
+        Ok(Event::Final(self.transcriber.transcribe(&utterance)?))
+    }
+}
+
+/// The number of samples in some milliseconds.
+fn samples(milliseconds: u32) -> usize {
+    (SAMPLE_RATE as usize * milliseconds as usize) / 1000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voice::Wav;
+
+    /// Says how long it listened, in tenths of a second.
+    struct Stopwatch;
+
+    impl Transcriber for Stopwatch {
+        fn transcribe(&mut self, samples: &[f32]) -> anyhow::Result<String> {
+            Ok(format!("{}", samples.len() * 10 / SAMPLE_RATE as usize))
+        }
+    }
+
+    /// Some noise, then speech-like tones and pauses.
+    fn recording() -> Wav {
+        let mut seed = 7u32;
+        let mut noise = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+
+            (seed >> 16) as f32 / 65_536.0 * 0.004 - 0.002
+        };
+
+        let mut samples = Vec::new();
+
+        for (milliseconds, is_speech) in [
+            (1000, false),
+            (2500, true),
+            (1000, false),
+            (1200, true),
+            (1000, false),
+        ] {
+            for i in 0..samples_at_44k(milliseconds) {
+                let tone = if is_speech {
+                    (i as f32 * 220.0 * std::f32::consts::TAU / 44_100.0).sin() * 0.3
+                } else {
+                    0.0
+                };
+
+                samples.push(tone + noise());
+            }
+        }
+
+        Wav {
+            sample_rate: 44_100,
+            samples,
+        }
+    }
+
+    fn samples_at_44k(milliseconds: usize) -> usize {
+        44_100 * milliseconds / 1000
+    }
+
+    #[test]
+    fn test_listener() {
+        let wav = Wav::parse(&recording().encode()).unwrap();
+        let audio = wav.resample(SAMPLE_RATE);
+
+        let mut listener = Listener::new(Stopwatch, Settings::default());
+        let mut events = Vec::new();
+
+        // As a microphone would deliver it
+        for chunk in audio.chunks(1024) {
+            events.extend(listener.push(chunk).unwrap());
+        }
+
+        events.extend(listener.finish().unwrap());
+
+        // Utterances keep 300 ms from before and end after 720 ms of silence
+        assert_eq!(
+            events,
+            [
+                Event::SpeechStarted,
+                Event::Partial("10".to_owned()),
+                Event::Partial("20".to_owned()),
+                Event::Partial("30".to_owned()),
+                Event::Final("34".to_owned()),
+                Event::SpeechStarted,
+                Event::Partial("10".to_owned()),
+                Event::Partial("20".to_owned()),
+                Event::Final("21".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recording_stops() {
+        let audio = recording().resample(SAMPLE_RATE);
+        let mut listener = Listener::new(Stopwatch, Settings::default());
+
+        // Stopped in the middle of the first utterance
+        let events = listener.push(&audio[..samples(2000)]).unwrap();
+        assert_eq!(events[0], Event::SpeechStarted);
+        assert!(listener.is_speaking());
+
+        let events = listener.finish().unwrap();
+        assert!(matches!(&events[..], [Event::Final(_)]));
+        assert!(!listener.is_speaking());
+    }
+}
@@ -0,0 +1,124 @@
+//! Telling speech from silence by its energy.
+//!
+//! The [`Vad`] follows the background noise, so a fan or a quiet room both
+//! work, and only calls it speech when a few loud frames come in a row.
+use crate::voice::SAMPLE_RATE;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    /// How much louder than the background speech must be.
+    pub ratio: f32,
+    /// The quietest level that may be speech, as an RMS amplitude.
+    pub floor: f32,
+    /// Loud frames needed to start speech, in milliseconds.
+    pub start: u32,
+    /// Quiet frames needed to end speech, in milliseconds.
+    pub end: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            ratio: 3.0,
+            floor: 0.01,
+            start: 90,
+            end: 700,
+        }
+    }
+}
+
+/// Samples the [`Vad`] looks at together: 30 ms.
+pub const FRAME: usize = SAMPLE_RATE as usize * 30 / 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Started,
+    Ended,
+}
+
+#[derive(Debug, Clone)]
+pub struct Vad {
+    settings: Settings,
+    noise: Option<f32>,
+    is_speaking: bool,
+    /// Frames in a row that disagree with `is_speaking`.
+    streak: u32,
+}
+
+impl Vad {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings,
+            noise: None,
+            is_speaking: false,
+            streak: 0,
+        }
+    }
+
+    pub fn is_speaking(&self) -> bool {
+        self.is_speaking
+    }
+
+    /// Looks at the next [`FRAME`] of audio.
+    pub fn push(&mut self, frame: &[f32]) -> Option<Transition> {
+        let level = rms(frame);
+        let noise = *self.noise.get_or_insert(level);
+
+        let is_loud = level > (noise * self.settings.ratio).max(self.settings.floor);
+
+        // The background only changes while nobody speaks; drops are
+        // followed at once, rises slowly
+        if !self.is_speaking && !is_loud {
+            self.noise = Some(if level < noise {
+                level
+            } else {
+                noise * 0.95 + level * 0.05
+            });
+        }
+
+        if is_loud == self.is_speaking {
+            self.streak = 0;
+            return None;
+        }
+
+        self.streak += 1;
+
+        let needed = if self.is_speaking {
+            self.settings.end
+        } else {
+            self.settings.start
+        };
+
+        if self.streak * frame_duration() < needed {
+            return None;
+        }
+
+        self.streak = 0;
+        self.is_speaking = !self.is_speaking;
+
+        Some(if self.is_speaking {
+            Transition::Started
+        } else {
+            Transition::Ended
+        })
+    }
+}
+
+impl Default for Vad {
+    fn default() -> Self {
+        Self::new(Settings::default())
+    }
+}
+
+/// The duration of a frame, in milliseconds.
+fn frame_duration() -> u32 {
+    (FRAME as u32 * 1000).div_ceil(SAMPLE_RATE)
+}
+
+fn rms(frame: &[f32]) -> f32 {
+    if frame.is_empty() {
+        return 0.0;
+    }
+
+    (frame.iter().map(|sample| sample * sample).sum::<f32>() / frame.len() as f32).sqrt()
+}
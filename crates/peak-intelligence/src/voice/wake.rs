@@ -0,0 +1,147 @@
+//! Spotting the wake word in what a [`Listener`](crate::voice::Listener)
+//! heard.
+//!
+//! Small Whisper models rarely spell a name the same way twice: "Hey Peak"
+//! comes out as "Hey, Pete.", "Hey speak" or "Hay-Peak". A [`WakeWord`]
+//! compares letters rather than words, so all of them match, and only
+//! looks at the start of an utterance, so talking about peaks does not.
+
+/// Words before the wake word that are ignored, e.g. "Oh, hey Peak".
+const LEAD: usize = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WakeWord {
+    /// The phrase, as lowercase letters and digits.
+    letters: String,
+    words: usize,
+    /// Letters that may differ.
+    tolerance: usize,
+}
+
+impl WakeWord {
+    pub fn new(phrase: &str) -> Self {
+        let words = tokenize(phrase);
+        let letters: String = words.iter().map(|(_, word)| word.as_str()).collect();
+
+        Self {
+            tolerance: letters.chars().count() / 3,
+            words: words.len().max(1),
+            letters,
+        }
+    }
+
+    /// Finds the wake word at the start of a transcript and returns what was
+    /// said after it, which is empty when the speaker paused.
+    pub fn find<'a>(&self, transcript: &'a str) -> Option<&'a str> {
+        if self.letters.is_empty() {
+            return None;
+        }
+
+        let words = tokenize(transcript);
+
+        // The closest match of any length around the phrase's
+        let (_, end) = (0..=LEAD.min(words.len()))
+            .flat_map(|start| {
+                (start + 1..=(start + self.words + 1).min(words.len())).map(move |end| (start, end))
+            })
+            .map(|(start, end)| {
+                let heard: String = words[start..end]
+                    .iter()
+                    .map(|(_, word)| word.as_str())
+                    .collect();
+
+                (distance(&heard, &self.letters), end)
+            })
+            .filter(|(distance, _)| *distance <= self.tolerance)
+            .min()?;
+
+        let rest = words
+            .get(end)
+            .map_or("", |(offset, _)| &transcript[*offset..]);
+
+        Some(rest.trim_end_matches(['.', '!', '?', ' ']))
+    }
+}
+
+impl Default for WakeWord {
+    fn default() -> Self {
+        Self::new("hey peak")
+    }
+}
+
+/// The words of a text with their offsets, lowercase and without
+/// punctuation.
+fn tokenize(text: &str) -> Vec<(usize, String)> {
+    let mut words = Vec::new();
+    let mut start = None;
+
+    for (offset, character) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, character.is_alphanumeric() || character == '\'') {
+            (None, true) => start = Some(offset),
+            (Some(first), false) => {
+                let word: String = text[first..offset]
+                    .chars()
+                    .filter(|character| character.is_alphanumeric())
+                    .flat_map(char::to_lowercase)
+                    .collect();
+
+                if !word.is_empty() {
+                    words.push((first, word));
+                }
+
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    words
+}
+
+/// The Levenshtein distance between two words.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, b) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a != *b);
+
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wake_word() {
+        let wake_word = WakeWord::default();
+
+        // What Whisper makes of it
+        assert_eq!(wake_word.find("Hey Peak."), Some(""));
+        assert_eq!(wake_word.find("Hey, Pete!"), Some(""));
+        assert_eq!(wake_word.find("Hey speak?"), Some(""));
+        assert_eq!(wake_word.find("Hay-Peak"), Some(""));
+        assert_eq!(wake_word.find("Oh, hey peek"), Some(""));
+
+        // Said in one breath with the command
+        assert_eq!(
+            wake_word.find("Hey Peak, open the terminal."),
+            Some("open the terminal")
+        );
+
+        assert_eq!(wake_word.find("Hello there"), None);
+        assert_eq!(wake_word.find("We climbed the peak today"), None);
+        assert_eq!(wake_word.find("Peak performance"), None);
+        assert_eq!(wake_word.find(""), None);
+    }
+}
@@ -0,0 +1,188 @@
+//! Reading and writing WAV files, so recordings can stand in for a
+//! microphone.
+use crate::voice::audio;
+
+use std::fs;
+use std::path::Path;
+
+/// Mono audio with samples between -1 and 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wav {
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+}
+
+const PCM: u16 = 1;
+const FLOAT: u16 = 3;
+const EXTENSIBLE: u16 = 0xFFFE;
+
+impl Wav {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    /// Reads integer PCM or 32-bit float audio, mixing channels down to mono.
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            anyhow::bail!("not a WAV file");
+        }
+
+        let mut format = None;
+        let mut data = None;
+        let mut chunks = &bytes[12..];
+
+        while chunks.len() >= 8 {
+            let id = &chunks[0..4];
+            let size = u32::from_le_bytes(chunks[4..8].try_into()?) as usize;
+            let body = &chunks[8..chunks.len().min(8 + size)];
+
+            match id {
+                b"fmt " if body.len() >= 16 => {
+                    let mut tag = u16::from_le_bytes(body[0..2].try_into()?);
+
+                    // The real format hides in the first bytes of the GUID
+                    if tag == EXTENSIBLE && body.len() >= 26 {
+                        tag = u16::from_le_bytes(body[24..26].try_into()?);
+                    }
+
+                    format = Some(Format {
+                        tag,
+                        channels: u16::from_le_bytes(body[2..4].try_into()?),
+                        sample_rate: u32::from_le_bytes(body[4..8].try_into()?),
+                        bits: u16::from_le_bytes(body[14..16].try_into()?),
+                    });
+                }
+                b"data" => data = Some(body),
+                _ => {}
+            }
+
+            // Chunks are padded to an even size
+            chunks = &chunks[(8 + size + size % 2).min(chunks.len())..];
+        }
+
+        let format = format.ok_or_else(|| anyhow::anyhow!("the WAV file has no format"))?;
+        let data = data.ok_or_else(|| anyhow::anyhow!("the WAV file has no data"))?;
+
+        let width = usize::from(format.bits / 8);
+        let channels = usize::from(format.channels);
+
+        if width == 0 || channels == 0 || format.sample_rate == 0 {
+            anyhow::bail!("invalid WAV format");
+        }
+
+        let sample = |bytes: &[u8]| -> Option<f32> {
+            Some(match (format.tag, format.bits) {
+                (PCM, 8) => (f32::from(bytes[0]) - 128.0) / 128.0,
+                (PCM, 16) => f32::from(i16::from_le_bytes(bytes.try_into().ok()?)) / 32768.0,
+                (PCM, 24) => {
+                    let value = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+
+                    value as f32 / 8_388_608.0
+                }
+                (PCM, 32) => i32::from_le_bytes(bytes.try_into().ok()?) as f32 / 2_147_483_648.0,
+                (FLOAT, 32) => f32::from_le_bytes(bytes.try_into().ok()?),
+                _ => return None,
+            })
+        };
+
+        let samples = data
+            .chunks_exact(width * channels)
+            .map(|frame| {
+                let sum = frame
+                    .chunks_exact(width)
+                    .map(|bytes| sample(bytes).ok_or_else(|| unsupported(&format)))
+                    .sum::<anyhow::Result<f32>>()?;
+
+                Ok(sum / channels as f32)
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            sample_rate: format.sample_rate,
+            samples,
+        })
+    }
+
+    /// Writes the audio as 16-bit PCM.
+    pub fn encode(&self) -> Vec<u8> {
+        let data_size = self.samples.len() as u32 * 2;
+
+        let mut bytes = Vec::with_capacity(44 + data_size as usize);
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&PCM.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&self.sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(self.sample_rate * 2).to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_size.to_le_bytes());
+
+        for sample in &self.samples {
+            let sample = (sample.clamp(-1.0, 1.0) * 32767.0).round() as i16;
+
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Converts the audio to another sample rate.
+    pub fn resample(&self, sample_rate: u32) -> Vec<f32> {
+        audio::resample(&self.samples, self.sample_rate, sample_rate)
+    }
+}
+
+struct Format {
+    tag: u16,
+    channels: u16,
+    sample_rate: u32,
+    bits: u16,
+}
+
+fn unsupported(format: &Format) -> anyhow::Error {
+    anyhow::anyhow!(
+        "unsupported WAV format {} with {} bits per sample",
+        format.tag,
+        format.bits
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav() {
+        let wav = Wav {
+            sample_rate: 32_000,
+            samples: vec![0.0, 0.5, -0.5, 1.0],
+        };
+
+        let decoded = Wav::parse(&wav.encode()).unwrap();
+
+        assert_eq!(decoded.sample_rate, 32_000);
+        assert!(decoded
+            .samples
+            .iter()
+            .zip(&wav.samples)
+            .all(|(a, b)| (a - b).abs() < 1e-4));
+        assert_eq!(decoded.resample(16_000).len(), 2);
+
+        // Stereo 8-bit, with a chunk the reader should skip
+        let mut stereo = b"RIFF\0\0\0\0WAVE".to_vec();
+        stereo.extend_from_slice(b"LIST\x03\0\0\0abc\0");
+        stereo.extend_from_slice(b"fmt \x10\0\0\0\x01\0\x02\0\x40\x1f\0\0\x80\x3e\0\0\x02\0\x08\0");
+        stereo.extend_from_slice(b"data\x04\0\0\0\x80\xff\x00\x80");
+
+        let stereo = Wav::parse(&stereo).unwrap();
+
+        assert_eq!(stereo.sample_rate, 8_000);
+        assert_eq!(stereo.samples, vec![127.0 / 256.0, -0.5]);
+
+        assert!(Wav::parse(b"RIFF\0\0\0\0AVI ").is_err());
+    }
+}